@@ -1,131 +1,610 @@
 use crate::audio::*;
 use crate::gltf_loader::*;
+use crate::handle::*;
+use crate::level::*;
 use crate::logger::*;
+use crate::mesh_cache::*;
 use crate::renderer::*;
 use crate::scene::*;
+use crate::vfs::*;
+use crate::worker_pool::*;
 
 use std::collections::HashMap;
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime};
+
+const CANCELLED_ERROR: &str = "Loading was cancelled";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetStatus {
+    Queued,
+    Loading { progress: f32 },
+    Loaded,
+    Failed { error: String },
+    Cancelled,
+}
+
+impl AssetStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            AssetStatus::Loaded | AssetStatus::Failed { .. } | AssetStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetEvent {
+    Queued { path: String },
+    Progress { path: String, progress: f32 },
+    Loaded { path: String },
+    Failed { path: String, error: String },
+    Cancelled { path: String },
+}
+
+pub type AssetReadyCallback = Box<dyn FnOnce(Result<(), String>) + Send>;
+
+#[derive(Default)]
+struct AssetStatusTrackerState {
+    statuses: HashMap<String, AssetStatus>,
+    events: Vec<AssetEvent>,
+    ready_callbacks: HashMap<String, Vec<AssetReadyCallback>>,
+}
+
+// keeps the status of every requested asset path and the events that led to it. shared between
+// the loader threads and the game
+#[derive(Clone, Default)]
+pub struct AssetStatusTracker {
+    state: Arc<Mutex<AssetStatusTrackerState>>,
+}
+
+impl AssetStatusTracker {
+    pub fn get_status(&self, path: &str) -> Option<AssetStatus> {
+        self.state.lock().unwrap().statuses.get(path).cloned()
+    }
+
+    pub fn take_events(&self) -> Vec<AssetEvent> {
+        std::mem::take(&mut self.state.lock().unwrap().events)
+    }
+
+    // the callback is called from the loader thread once the asset is loaded or failed to load,
+    // or right away if that already happened
+    pub fn on_ready(&self, path: &str, callback: impl FnOnce(Result<(), String>) + Send + 'static) {
+        let mut state_guard = self.state.lock().unwrap();
+        let result = match state_guard.statuses.get(path) {
+            Some(AssetStatus::Loaded) => Ok(()),
+            Some(AssetStatus::Failed { error }) => Err(error.clone()),
+            Some(AssetStatus::Cancelled) => Err(CANCELLED_ERROR.to_string()),
+            _ => {
+                state_guard
+                    .ready_callbacks
+                    .entry(path.to_string())
+                    .or_default()
+                    .push(Box::new(callback));
+                return;
+            }
+        };
+        drop(state_guard);
+        callback(result);
+    }
+
+    pub fn wait_until_ready(&self, path: &str) -> AssetReadyFuture {
+        let shared = Arc::new(Mutex::new(AssetReadyFutureState::default()));
+        let shared_clone = shared.clone();
+        self.on_ready(path, move |result| {
+            let mut shared_guard = shared_clone.lock().unwrap();
+            shared_guard.result = Some(result);
+            if let Some(waker) = shared_guard.waker.take() {
+                waker.wake();
+            }
+        });
+        AssetReadyFuture { shared }
+    }
+
+    fn set_status(&self, path: &str, status: AssetStatus) {
+        let event = match &status {
+            AssetStatus::Queued => AssetEvent::Queued {
+                path: path.to_string(),
+            },
+            AssetStatus::Loading { progress } => AssetEvent::Progress {
+                path: path.to_string(),
+                progress: *progress,
+            },
+            AssetStatus::Loaded => AssetEvent::Loaded {
+                path: path.to_string(),
+            },
+            AssetStatus::Failed { error } => AssetEvent::Failed {
+                path: path.to_string(),
+                error: error.clone(),
+            },
+            AssetStatus::Cancelled => AssetEvent::Cancelled {
+                path: path.to_string(),
+            },
+        };
+        let result = match &status {
+            AssetStatus::Loaded => Some(Ok(())),
+            AssetStatus::Failed { error } => Some(Err(error.clone())),
+            AssetStatus::Cancelled => Some(Err(CANCELLED_ERROR.to_string())),
+            _ => None,
+        };
+
+        let callbacks = {
+            let mut state_guard = self.state.lock().unwrap();
+            state_guard.statuses.insert(path.to_string(), status);
+            state_guard.events.push(event);
+            if result.is_some() {
+                state_guard.ready_callbacks.remove(path).unwrap_or_default()
+            } else {
+                vec![]
+            }
+        };
+        // called without holding the lock so the callbacks can query the tracker
+        if let Some(result) = result {
+            for callback in callbacks {
+                callback(result.clone());
+            }
+        }
+    }
+
+    fn set_queued(&self, path: &str) {
+        self.set_status(path, AssetStatus::Queued);
+    }
+
+    fn set_progress(&self, path: &str, progress: f32) {
+        self.set_status(
+            path,
+            AssetStatus::Loading {
+                progress: progress.clamp(0.0, 1.0),
+            },
+        );
+    }
+
+    fn set_loaded(&self, path: &str) {
+        self.set_status(path, AssetStatus::Loaded);
+    }
+
+    fn set_cancelled(&self, path: &str) {
+        self.set_status(path, AssetStatus::Cancelled);
+    }
+
+    fn set_failed(&self, path: &str, err: &anyhow::Error) {
+        self.set_status(
+            path,
+            AssetStatus::Failed {
+                error: format!("{:#}", err),
+            },
+        );
+    }
+}
+
+#[derive(Default)]
+struct AssetReadyFutureState {
+    result: Option<Result<(), String>>,
+    waker: Option<Waker>,
+}
+
+// resolves once the asset is loaded or failed to load
+pub struct AssetReadyFuture {
+    shared: Arc<Mutex<AssetReadyFutureState>>,
+}
+
+impl Future for AssetReadyFuture {
+    type Output = Result<(), String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared_guard = self.shared.lock().unwrap();
+        match shared_guard.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared_guard.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+const HOT_RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct WatchedGltfAsset {
+    priority: JobPriority,
+    // None if the file doesn't exist
+    source_files: Vec<(PathBuf, Option<SystemTime>)>,
+}
 
 pub struct AssetLoader {
     pub renderer_base: Arc<BaseRenderer>,
-    pub pending_gltf_scenes: Arc<Mutex<Vec<String>>>,
-    pub loaded_gltf_scenes: Arc<Mutex<HashMap<String, (Scene, RenderBuffers)>>>,
+    // waiting to be merged into the game's scene on the main thread, see merge_loaded_gltf_scene
+    // and reload_changed_gltf_scenes
+    loaded_gltf_scenes: Arc<Mutex<HashMap<String, (Scene, RenderBuffers)>>>,
+    reloaded_gltf_scenes: Arc<Mutex<HashMap<String, (Scene, RenderBuffers)>>>,
+    pub is_hot_reload_enabled: Arc<AtomicBool>,
+    // keep a cpu-side copy of the pbr meshes and their textures in the scenes loaded from now on,
+    // which is needed to export them with gltf_exporter::export_scene
+    pub keep_mesh_sources: Arc<AtomicBool>,
+    watched_gltf_assets: Arc<Mutex<HashMap<String, WatchedGltfAsset>>>,
+
+    pub loaded_levels: Arc<Mutex<HashMap<String, LevelDesc>>>,
 
     pub audio_manager: Arc<Mutex<AudioManager>>,
-    pub pending_audio: Arc<Mutex<Vec<(String, AudioFileFormat, SoundParams)>>>,
     pub loaded_audio: Arc<Mutex<HashMap<String, usize>>>,
+
+    pub statuses: AssetStatusTracker,
 }
 
 impl AssetLoader {
     pub fn new(renderer_base: Arc<BaseRenderer>, audio_manager: Arc<Mutex<AudioManager>>) -> Self {
         Self {
             renderer_base,
-            pending_gltf_scenes: Arc::new(Mutex::new(Vec::new())),
             loaded_gltf_scenes: Arc::new(Mutex::new(HashMap::new())),
+            reloaded_gltf_scenes: Arc::new(Mutex::new(HashMap::new())),
+            is_hot_reload_enabled: Arc::new(AtomicBool::new(false)),
+            keep_mesh_sources: Arc::new(AtomicBool::new(false)),
+            watched_gltf_assets: Arc::new(Mutex::new(HashMap::new())),
+
+            loaded_levels: Arc::new(Mutex::new(HashMap::new())),
 
             audio_manager,
-            pending_audio: Arc::new(Mutex::new(Vec::new())),
             loaded_audio: Arc::new(Mutex::new(HashMap::new())),
+
+            statuses: AssetStatusTracker::default(),
         }
     }
 
-    pub fn load_gltf_asset(&self, path: &str) {
-        let pending_assets_clone = self.pending_gltf_scenes.clone();
-        let mut pending_assets_clone_guard = pending_assets_clone.lock().unwrap();
-        pending_assets_clone_guard.push(path.to_string());
-
-        if pending_assets_clone_guard.len() == 1 {
-            let pending_assets = self.pending_gltf_scenes.clone();
-            let loaded_assets = self.loaded_gltf_scenes.clone();
-            let renderer_base = self.renderer_base.clone();
-
-            std::thread::spawn(move || {
-                while pending_assets.lock().unwrap().len() > 0 {
-                    let next_scene_path = pending_assets.lock().unwrap().remove(0);
-
-                    let do_load = || {
-                        let (document, buffers, images) = gltf::import(&next_scene_path)?;
-                        let (other_scene, other_render_buffers) = build_scene(
-                            &renderer_base,
-                            (&document, &buffers, &images),
-                            Path::new(&next_scene_path),
-                        )?;
-                        anyhow::Ok((other_scene, other_render_buffers))
-                    };
-                    match do_load() {
-                        Ok(result) => {
-                            let _replaced_ignored = loaded_assets
-                                .lock()
-                                .unwrap()
-                                .insert(next_scene_path, result);
-                        }
-                        Err(err) => {
-                            logger_log(&format!(
-                                "Error loading gltf asset {}: {}\n{}",
-                                next_scene_path,
-                                err,
-                                err.backtrace()
-                            ));
-                        }
+    pub fn get_status(&self, path: &str) -> Option<AssetStatus> {
+        self.statuses.get_status(path)
+    }
+
+    // drains the events of all requests since the last call
+    pub fn take_events(&self) -> Vec<AssetEvent> {
+        self.statuses.take_events()
+    }
+
+    pub fn on_ready(&self, path: &str, callback: impl FnOnce(Result<(), String>) + Send + 'static) {
+        self.statuses.on_ready(path, callback)
+    }
+
+    pub fn wait_until_ready(&self, path: &str) -> AssetReadyFuture {
+        self.statuses.wait_until_ready(path)
+    }
+
+    pub fn load_gltf_asset(&self, path: &str, priority: JobPriority) -> CancellationToken {
+        self.spawn_gltf_asset_load(path, priority, self.loaded_gltf_scenes.clone())
+    }
+
+    // merges the gltf asset into the scene if it finished loading, calling prepare on it first.
+    // the meshes and textures of the asset are freed once the returned handle is dropped and no
+    // node uses them anymore
+    pub fn merge_loaded_gltf_scene(
+        &self,
+        path: &str,
+        scene: &mut Scene,
+        renderer_data: &mut RendererPublicData,
+        prepare: impl FnOnce(&mut Scene),
+    ) -> Option<Handle<GltfScene>> {
+        let (mut other_scene, other_render_buffers) =
+            self.loaded_gltf_scenes.lock().unwrap().remove(path)?;
+        prepare(&mut other_scene);
+        Some(scene.merge_scene(renderer_data, other_scene, other_render_buffers))
+    }
+
+    // swaps the gltf assets that were reloaded since the last call into the scene. get_gltf_scene
+    // returns the handle that was merged from the asset at the path, if any, and gets to prepare
+    // the new version the same way as the first one. returns the paths that were swapped in
+    pub fn reload_changed_gltf_scenes(
+        &self,
+        scene: &mut Scene,
+        renderer_data: &mut RendererPublicData,
+        mut get_gltf_scene: impl FnMut(&str, &mut Scene) -> Option<Handle<GltfScene>>,
+    ) -> Vec<String> {
+        let reloaded_gltf_scenes: Vec<_> =
+            self.reloaded_gltf_scenes.lock().unwrap().drain().collect();
+        let mut reloaded_paths = vec![];
+        for (path, (mut other_scene, other_render_buffers)) in reloaded_gltf_scenes {
+            if let Some(gltf_scene) = get_gltf_scene(&path, &mut other_scene) {
+                scene.reload_scene(
+                    renderer_data,
+                    &gltf_scene,
+                    other_scene,
+                    other_render_buffers,
+                );
+                reloaded_paths.push(path);
+            }
+        }
+        reloaded_paths
+    }
+
+    // the loaded or reloaded scene is put in destination
+    fn spawn_gltf_asset_load(
+        &self,
+        path: &str,
+        priority: JobPriority,
+        destination: Arc<Mutex<HashMap<String, (Scene, RenderBuffers)>>>,
+    ) -> CancellationToken {
+        let path = path.to_string();
+        let renderer_base = self.renderer_base.clone();
+        let statuses = self.statuses.clone();
+        let is_hot_reload_enabled = self.is_hot_reload_enabled.clone();
+        let keep_mesh_sources = self.keep_mesh_sources.load(Ordering::Relaxed);
+        let watched_gltf_assets = self.watched_gltf_assets.clone();
+
+        statuses.set_queued(&path);
+        WORKER_POOL.spawn(priority, move |cancellation_token| {
+            let do_load = || {
+                if cancellation_token.is_cancelled() {
+                    return anyhow::Ok(None);
+                }
+                statuses.set_progress(&path, 0.0);
+                // the cache is checked before reading the buffers so that the vertex buffers
+                // don't have to be read at all when it's fresh
+                let gltf = open_gltf(Path::new(&path))?;
+                let mesh_cache = match load_fresh_mesh_cache(Path::new(&path), &gltf.document) {
+                    Ok(mesh_cache) => mesh_cache,
+                    Err(err) => {
+                        logger_log(&format!(
+                            "Warning: ignoring mesh cache of {}: {}",
+                            path, err
+                        ));
+                        None
+                    }
+                };
+                if cancellation_token.is_cancelled() {
+                    return anyhow::Ok(None);
+                }
+                statuses.set_progress(&path, 0.1);
+                let (document, buffers, images) =
+                    import_gltf_data(Path::new(&path), gltf, mesh_cache.is_some())?;
+                if cancellation_token.is_cancelled() {
+                    return anyhow::Ok(None);
+                }
+                statuses.set_progress(&path, 0.5);
+                let (other_scene, other_render_buffers) = build_scene(
+                    &renderer_base,
+                    (&document, &buffers, &images),
+                    Path::new(&path),
+                    mesh_cache,
+                    keep_mesh_sources,
+                )?;
+                let source_paths = get_gltf_source_paths(Path::new(&path), &document);
+                anyhow::Ok(Some((other_scene, other_render_buffers, source_paths)))
+            };
+            match do_load() {
+                Ok(Some((other_scene, other_render_buffers, source_paths))) => {
+                    if is_hot_reload_enabled.load(Ordering::Relaxed) {
+                        watched_gltf_assets.lock().unwrap().insert(
+                            path.clone(),
+                            WatchedGltfAsset {
+                                priority,
+                                source_files: source_paths
+                                    .into_iter()
+                                    .map(|source_path| {
+                                        let modified = vfs_modified(&source_path);
+                                        (source_path, modified)
+                                    })
+                                    .collect(),
+                            },
+                        );
                     }
+                    let _replaced_ignored = destination
+                        .lock()
+                        .unwrap()
+                        .insert(path.clone(), (other_scene, other_render_buffers));
+                    statuses.set_loaded(&path);
                 }
-            });
+                Ok(None) => {
+                    statuses.set_cancelled(&path);
+                }
+                Err(err) => {
+                    logger_log(&format!(
+                        "Error loading gltf asset {}: {}\n{}",
+                        path,
+                        err,
+                        err.backtrace()
+                    ));
+                    statuses.set_failed(&path, &err);
+                }
+            }
+        })
+    }
+
+    // gltf assets that are loaded from now on are reloaded when one of their files changes. the
+    // game is responsible for swapping them into the scene, see reload_changed_gltf_scenes
+    pub fn enable_hot_reload(self: &Arc<Self>) {
+        if self.is_hot_reload_enabled.swap(true, Ordering::Relaxed) {
+            return;
         }
+        let asset_loader = Arc::downgrade(self);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(HOT_RELOAD_POLL_INTERVAL);
+            match asset_loader.upgrade() {
+                Some(asset_loader) => asset_loader.reload_changed_gltf_assets(),
+                None => break,
+            }
+        });
     }
 
-    pub fn load_audio(&self, path: &str, format: AudioFileFormat, params: SoundParams) {
-        let pending_audio_clone = self.pending_audio.clone();
-        let mut pending_audio_clone_guard = pending_audio_clone.lock().unwrap();
-        pending_audio_clone_guard.push((path.to_string(), format, params));
-
-        if pending_audio_clone_guard.len() == 1 {
-            let pending_audio = self.pending_audio.clone();
-            let loaded_audio = self.loaded_audio.clone();
-            let audio_manager = self.audio_manager.clone();
-
-            std::thread::spawn(move || {
-                while pending_audio.lock().unwrap().len() > 0 {
-                    let (next_audio_path, next_audio_format, next_audio_params) =
-                        pending_audio.lock().unwrap().remove(0);
-
-                    let do_load = || {
-                        let device_sample_rate = audio_manager.lock().unwrap().device_sample_rate();
-                        let sound_data = AudioManager::decode_audio_file(
-                            device_sample_rate,
-                            &next_audio_path,
-                            Some(next_audio_format),
-                        )?;
-                        // let sound = Sound::new(self, sound_data, params);
-                        let signal = AudioManager::get_signal(
-                            &sound_data,
-                            next_audio_params.clone(),
-                            device_sample_rate,
-                        );
-                        let sound_index = audio_manager.lock().unwrap().add_sound(
-                            sound_data,
-                            next_audio_params,
-                            signal,
-                        );
-                        anyhow::Ok(sound_index)
-                    };
-                    match do_load() {
-                        Ok(result) => {
-                            let _replaced_ignored =
-                                loaded_audio.lock().unwrap().insert(next_audio_path, result);
-                        }
-                        Err(err) => {
-                            logger_log(&format!(
-                                "Error loading audio asset {}: {}\n{}",
-                                next_audio_path,
-                                err,
-                                err.backtrace()
-                            ));
-                        }
+    fn reload_changed_gltf_assets(&self) {
+        let changed_assets: Vec<_> = self
+            .watched_gltf_assets
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter_map(|(path, watched_asset)| {
+                let mut is_changed = false;
+                for (source_path, last_modified) in &mut watched_asset.source_files {
+                    let modified = vfs_modified(source_path);
+                    if modified != *last_modified {
+                        *last_modified = modified;
+                        is_changed = true;
                     }
                 }
-            });
+                is_changed.then(|| (path.clone(), watched_asset.priority))
+            })
+            .collect();
+        for (path, priority) in changed_assets {
+            logger_log(&format!("Reloading gltf asset {}", path));
+            self.spawn_gltf_asset_load(&path, priority, self.reloaded_gltf_scenes.clone());
         }
     }
+
+    // only reads the level file, the game is responsible for loading the assets it references
+    pub fn load_level(&self, path: &str, priority: JobPriority) -> CancellationToken {
+        let path = path.to_string();
+        let loaded_levels = self.loaded_levels.clone();
+        let statuses = self.statuses.clone();
+
+        statuses.set_queued(&path);
+        WORKER_POOL.spawn(priority, move |cancellation_token| {
+            if cancellation_token.is_cancelled() {
+                statuses.set_cancelled(&path);
+                return;
+            }
+            statuses.set_progress(&path, 0.0);
+            match LevelDesc::load(&path) {
+                Ok(result) => {
+                    let _replaced_ignored =
+                        loaded_levels.lock().unwrap().insert(path.clone(), result);
+                    statuses.set_loaded(&path);
+                }
+                Err(err) => {
+                    logger_log(&format!(
+                        "Error loading level {}: {}\n{}",
+                        path,
+                        err,
+                        err.backtrace()
+                    ));
+                    statuses.set_failed(&path, &err);
+                }
+            }
+        })
+    }
+
+    pub fn load_audio(
+        &self,
+        path: &str,
+        format: AudioFileFormat,
+        params: SoundParams,
+        priority: JobPriority,
+    ) -> CancellationToken {
+        let path = path.to_string();
+        let loaded_audio = self.loaded_audio.clone();
+        let audio_manager = self.audio_manager.clone();
+        let statuses = self.statuses.clone();
+
+        statuses.set_queued(&path);
+        WORKER_POOL.spawn(priority, move |cancellation_token| {
+            let do_load = || {
+                if cancellation_token.is_cancelled() {
+                    return anyhow::Ok(None);
+                }
+                statuses.set_progress(&path, 0.0);
+                let device_sample_rate = audio_manager.lock().unwrap().device_sample_rate();
+                let sound_data =
+                    AudioManager::decode_audio_file(device_sample_rate, &path, Some(format))?;
+                if cancellation_token.is_cancelled() {
+                    return anyhow::Ok(None);
+                }
+                statuses.set_progress(&path, 0.8);
+                let signal =
+                    AudioManager::get_signal(&sound_data, params.clone(), device_sample_rate);
+                let sound_index = audio_manager
+                    .lock()
+                    .unwrap()
+                    .add_sound(sound_data, params, signal);
+                anyhow::Ok(Some(sound_index))
+            };
+            match do_load() {
+                Ok(Some(result)) => {
+                    let _replaced_ignored =
+                        loaded_audio.lock().unwrap().insert(path.clone(), result);
+                    statuses.set_loaded(&path);
+                }
+                Ok(None) => {
+                    statuses.set_cancelled(&path);
+                }
+                Err(err) => {
+                    logger_log(&format!(
+                        "Error loading audio asset {}: {}\n{}",
+                        path,
+                        err,
+                        err.backtrace()
+                    ));
+                    statuses.set_failed(&path, &err);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::task::Wake;
+
+    struct FlagWaker(Mutex<bool>);
+
+    impl Wake for FlagWaker {
+        fn wake(self: Arc<Self>) {
+            *self.0.lock().unwrap() = true;
+        }
+    }
+
+    #[test]
+    fn status_events_and_ready_notifications() {
+        let statuses = AssetStatusTracker::default();
+        let path = "./model.gltf";
+        assert_eq!(statuses.get_status(path), None);
+
+        let callback_result = Arc::new(Mutex::new(None));
+        let callback_result_clone = callback_result.clone();
+        statuses.on_ready(path, move |result| {
+            *callback_result_clone.lock().unwrap() = Some(result);
+        });
+        let mut future = statuses.wait_until_ready(path);
+        let waker_flag = Arc::new(FlagWaker(Mutex::new(false)));
+        let waker = Waker::from(waker_flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Pending);
+
+        statuses.set_queued(path);
+        statuses.set_progress(path, 0.5);
+        assert_eq!(
+            statuses.get_status(path),
+            Some(AssetStatus::Loading { progress: 0.5 })
+        );
+        assert_eq!(*callback_result.lock().unwrap(), None);
+
+        statuses.set_loaded(path);
+        assert_eq!(*callback_result.lock().unwrap(), Some(Ok(())));
+        assert!(*waker_flag.0.lock().unwrap());
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(
+            statuses.take_events(),
+            vec![
+                AssetEvent::Queued {
+                    path: path.to_string()
+                },
+                AssetEvent::Progress {
+                    path: path.to_string(),
+                    progress: 0.5
+                },
+                AssetEvent::Loaded {
+                    path: path.to_string()
+                },
+            ]
+        );
+        assert!(statuses.take_events().is_empty());
+
+        statuses.set_failed("./missing.gltf", &anyhow::anyhow!("file not found"));
+        let mut failed_future = statuses.wait_until_ready("./missing.gltf");
+        assert_eq!(
+            Pin::new(&mut failed_future).poll(&mut cx),
+            Poll::Ready(Err("file not found".to_string()))
+        );
+    }
 }
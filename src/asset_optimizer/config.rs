@@ -0,0 +1,241 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use glob::Pattern;
+use serde::Deserialize;
+
+use ikari::texture_compression::{DEFAULT_UASTC_QUALITY_LEVEL, MAX_UASTC_QUALITY_LEVEL};
+
+const DEFAULT_COMPRESSION_THREAD_COUNT: usize = 4;
+
+/// Builds the mesh caches and compressed textures that are loaded by ikari
+#[derive(Debug, Parser)]
+#[command(name = "asset_optimizer")]
+pub struct Cli {
+    /// Folder that is searched for gltf files and textures, the outputs are written next to their sources
+    #[arg(long, default_value = "./src")]
+    pub input: PathBuf,
+
+    /// Only process the files that match one of these globs, relative to the input folder
+    #[arg(long)]
+    pub include: Vec<String>,
+
+    /// Skip the files that match one of these globs, relative to the input folder
+    #[arg(long)]
+    pub exclude: Vec<String>,
+
+    /// Number of textures that are compressed at the same time [default: cpu count / compression threads]
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Number of threads used to compress each texture
+    #[arg(long, default_value_t = DEFAULT_COMPRESSION_THREAD_COUNT)]
+    pub compression_threads: usize,
+
+    /// UASTC quality level, higher is slower but looks better
+    #[arg(long, default_value_t = DEFAULT_UASTC_QUALITY_LEVEL, value_parser = clap::value_parser!(u32).range(0..=MAX_UASTC_QUALITY_LEVEL as i64))]
+    pub quality: u32,
+
+    /// Rebuild the outputs even if they're up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Print what would be processed without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// JSON config file with extra include/exclude globs and per-texture overrides
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Write a JSON report of the processed files to this path, or to stdout if it's "-"
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+}
+
+// e.g.
+// {
+//     "exclude": ["models/gltf/TestLevel/**"],
+//     "texture_overrides": [{ "path": "**/*_orm.png", "srgb": false }]
+// }
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // applied in order, so later overrides win
+    pub texture_overrides: Vec<TextureOverride>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureOverride {
+    // glob relative to the input folder
+    pub path: String,
+    pub srgb: Option<bool>,
+    pub normal_map: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub input_root: PathBuf,
+    pub worker_count: usize,
+    pub compression_thread_count: usize,
+    pub quality_level: u32,
+    pub force: bool,
+    pub dry_run: bool,
+    pub report_path: Option<PathBuf>,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    texture_overrides: Vec<(Pattern, TextureOverride)>,
+}
+
+impl Config {
+    pub fn new(cli: Cli) -> Result<Self> {
+        let config_file: ConfigFile = match &cli.config {
+            Some(config_path) => serde_json::from_str(&std::fs::read_to_string(config_path)?)
+                .map_err(|err| anyhow::anyhow!("Invalid config file {:?}: {}", config_path, err))?,
+            None => ConfigFile::default(),
+        };
+
+        let parse_globs = |globs: Vec<String>| -> Result<Vec<Pattern>> {
+            globs
+                .iter()
+                .map(|glob| {
+                    Pattern::new(glob)
+                        .map_err(|err| anyhow::anyhow!("Invalid glob {:?}: {}", glob, err))
+                })
+                .collect()
+        };
+
+        if cli.compression_threads == 0 {
+            bail!("--compression-threads must be at least 1");
+        }
+        let worker_count = cli
+            .jobs
+            .unwrap_or_else(|| num_cpus::get() / cli.compression_threads)
+            .max(1);
+
+        Ok(Self {
+            input_root: cli.input,
+            worker_count,
+            compression_thread_count: cli.compression_threads,
+            quality_level: cli.quality,
+            force: cli.force,
+            dry_run: cli.dry_run,
+            report_path: cli.report,
+            include: parse_globs([cli.include, config_file.include].concat())?,
+            exclude: parse_globs([cli.exclude, config_file.exclude].concat())?,
+            texture_overrides: config_file
+                .texture_overrides
+                .into_iter()
+                .map(|texture_override| {
+                    Ok((
+                        parse_globs(vec![texture_override.path.clone()])?.remove(0),
+                        texture_override,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    // the json report takes over stdout when it's written there
+    pub fn is_report_on_stdout(&self) -> bool {
+        self.report_path.as_deref() == Some(Path::new("-"))
+    }
+
+    pub fn is_included(&self, path: &Path) -> bool {
+        let relative_path = self.get_relative_path(path);
+        let matches = |pattern: &Pattern| pattern.matches(&relative_path);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    // returns (is_srgb, is_normal_map)
+    pub fn classify_texture(
+        &self,
+        path: &Path,
+        is_srgb: bool,
+        is_normal_map: bool,
+    ) -> (bool, bool) {
+        let relative_path = self.get_relative_path(path);
+        self.texture_overrides
+            .iter()
+            .filter(|(pattern, _)| pattern.matches(&relative_path))
+            .fold(
+                (is_srgb, is_normal_map),
+                |(is_srgb, is_normal_map), (_, texture_override)| {
+                    // normal maps are never srgb, so marking a texture as one implies linear
+                    let is_now_normal_map = texture_override.normal_map == Some(true);
+                    (
+                        texture_override
+                            .srgb
+                            .unwrap_or(is_srgb && !is_now_normal_map),
+                        texture_override.normal_map.unwrap_or(is_normal_map),
+                    )
+                },
+            )
+    }
+
+    // forward slashes so the same globs work on every platform
+    pub fn get_relative_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.input_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_and_overrides() {
+        let config_dir = std::env::temp_dir().join("ikari_asset_optimizer_config_test");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        let config_path = config_dir.join("config.json");
+        std::fs::write(
+            &config_path,
+            r#"{
+                "exclude": ["models/ignored/**"],
+                "texture_overrides": [
+                    { "path": "**/*_orm.png", "srgb": false },
+                    { "path": "**/normal.png", "normal_map": true }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::new(Cli::parse_from([
+            "asset_optimizer",
+            "--input",
+            "./assets",
+            "--include",
+            "models/**",
+            "--config",
+            config_path.to_str().unwrap(),
+        ]))
+        .unwrap();
+
+        assert!(config.is_included(Path::new("./assets/models/a/texture.png")));
+        assert!(!config.is_included(Path::new("./assets/models/ignored/texture.png")));
+        assert!(!config.is_included(Path::new("./assets/textures/texture.png")));
+
+        assert_eq!(
+            config.classify_texture(Path::new("./assets/models/a/normal.png"), true, false),
+            (false, true)
+        );
+        assert_eq!(
+            config.classify_texture(Path::new("./assets/models/a/metal_orm.png"), true, false),
+            (false, false)
+        );
+        assert_eq!(
+            config.classify_texture(Path::new("./assets/models/a/albedo.jpg"), true, false),
+            (true, false)
+        );
+
+        assert!(Cli::try_parse_from(["asset_optimizer", "--quality", "9"]).is_err());
+    }
+}
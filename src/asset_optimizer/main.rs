@@ -1,79 +1,400 @@
+mod config;
+mod manifest;
+
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     sync::{mpsc::channel, Arc},
 };
 
+use clap::Parser;
+use serde::Serialize;
 use threadpool::ThreadPool;
 use walkdir::WalkDir;
 
-use ikari::texture_compression::{texture_path_to_compressed_path, TextureCompressionArgs};
+use ikari::gltf_loader::{
+    build_mesh_cache, decode_uri, get_gltf_buffer_paths, import_gltf, is_ktx2_image, open_gltf,
+};
+use ikari::mesh_cache::{gltf_path_to_mesh_cache_path, MESH_CACHE_VERSION};
+use ikari::mesh_optimizer::{
+    get_mesh_stats, optimize_geometry, MeshOptimizationOptions, MeshStats,
+};
+use ikari::mesh_simplifier::{generate_lods, LodOptions};
+use ikari::renderer::PrimitiveMode;
+use ikari::texture_compression::{
+    embedded_texture_path_to_compressed_path, texture_path_to_compressed_path,
+    TextureCompressionArgs,
+};
+
+use config::{Cli, Config};
+use manifest::{Manifest, ManifestEntry};
+
+// the file names of everything that this tool writes, including the legacy .basis textures
+const OUTPUT_FILE_SUFFIXES: [&str; 3] = ["_compressed.ktx2", "_compressed.bin", "_mesh_cache.bin"];
+
+// human readable progress goes to stderr when the json report is written to stdout
+macro_rules! log {
+    ($config:expr, $($arg:tt)*) => {
+        if $config.is_report_on_stdout() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputStatus {
+    Written,
+    UpToDate,
+    WouldWrite,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    dry_run: bool,
+    meshes: Vec<MeshReport>,
+    textures: Vec<TextureReport>,
+    // outputs whose sources no longer exist
+    removed_outputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct MeshReport {
+    source: PathBuf,
+    output: PathBuf,
+    status: OutputStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_before: Option<MeshStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_after: Option<MeshStats>,
+    // summed over all primitives, one entry per lod level
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    lod_triangle_counts: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct TextureReport {
+    source: PathBuf,
+    // set for images that are embedded in the source gltf file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_index: Option<usize>,
+    output: PathBuf,
+    srgb: bool,
+    normal_map: bool,
+    status: OutputStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TextureJob {
+    // the gltf file for embedded images
+    path: PathBuf,
+    embedded_image_index: Option<usize>,
+    is_srgb: bool,
+    is_normal_map: bool,
+}
+
+impl TextureJob {
+    fn get_compressed_path(&self) -> PathBuf {
+        match self.embedded_image_index {
+            Some(image_index) => embedded_texture_path_to_compressed_path(&self.path, image_index),
+            None => texture_path_to_compressed_path(&self.path),
+        }
+    }
+
+    fn get_source_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        match self.embedded_image_index {
+            Some(_) => Ok(get_gltf_buffer_paths(
+                &self.path,
+                &open_gltf(&self.path)?.document,
+            )),
+            None => Ok(vec![self.path.clone()]),
+        }
+    }
+
+    // the encoded png/jpeg file
+    fn read_image_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match self.embedded_image_index {
+            Some(image_index) => read_embedded_image_bytes(&self.path, image_index),
+            None => Ok(std::fs::read(&self.path)?),
+        }
+    }
 
-const DATA_FOLDER: &str = "./src";
-const COMPRESSION_THREAD_COUNT: usize = 4;
+    fn get_display_name(&self) -> String {
+        match self.embedded_image_index {
+            Some(image_index) => format!("{:?} (embedded image {:?})", self.path, image_index),
+            None => format!("{:?}", self.path),
+        }
+    }
+}
 
 fn main() {
-    let mut texture_paths = find_gltf_texture_paths().unwrap();
+    let config = match Config::new(Cli::parse()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(2);
+        }
+    };
 
-    let gltf_exlusion_map: HashSet<PathBuf> = texture_paths
-        .iter()
-        .cloned()
-        .map(|(path, _, _)| path.canonicalize().unwrap())
-        .collect();
+    let mut report = Report {
+        dry_run: config.dry_run,
+        meshes: Vec::new(),
+        textures: Vec::new(),
+        removed_outputs: Vec::new(),
+    };
 
-    // interpret all dangling textures as srgb color maps
-    texture_paths.extend(
-        find_dangling_texture_paths(gltf_exlusion_map)
-            .unwrap()
-            .iter()
-            .cloned()
-            .map(|path| (path, true, false)),
-    );
-
-    // remove all paths that have already been processed
-    texture_paths = texture_paths
-        .iter()
-        .cloned()
-        .filter(|(path, _is_srgb, _is_normal_map)| {
-            !texture_path_to_compressed_path(path).try_exists().unwrap()
-        })
-        .collect();
+    let manifest_path = Manifest::get_path(&config);
+    let mut manifest = Manifest::load(&manifest_path).unwrap_or_else(|err| {
+        log!(
+            config,
+            "Warning: failed to load manifest {:?}, rebuilding everything: {}",
+            manifest_path,
+            err
+        );
+        Manifest::default()
+    });
+
+    for gltf_path in find_gltf_paths(&config) {
+        let mesh_report = process_mesh(&config, &mut manifest, &gltf_path);
+        match mesh_report.status {
+            OutputStatus::Written => {
+                log!(config, "wrote mesh cache for {:?}", gltf_path);
+                if let (Some(stats_before), Some(stats_after)) =
+                    (mesh_report.stats_before, mesh_report.stats_after)
+                {
+                    log!(
+                        config,
+                        "  vertices: {:?} -> {:?}, triangles: {:?}",
+                        stats_before.vertex_count,
+                        stats_after.vertex_count,
+                        stats_after.triangle_count
+                    );
+                    log!(
+                        config,
+                        "  acmr: {:.3} -> {:.3}, atvr: {:.3} -> {:.3}",
+                        stats_before.acmr,
+                        stats_after.acmr,
+                        stats_before.atvr,
+                        stats_after.atvr
+                    );
+                }
+                if !mesh_report.lod_triangle_counts.is_empty() {
+                    log!(
+                        config,
+                        "  lod triangles: {:?}",
+                        mesh_report.lod_triangle_counts
+                    );
+                }
+            }
+            OutputStatus::WouldWrite => {
+                log!(config, "would write mesh cache for {:?}", gltf_path);
+            }
+            OutputStatus::UpToDate => {}
+            OutputStatus::Failed => {
+                log!(
+                    config,
+                    "Warning: failed to write mesh cache for {:?}: {}",
+                    gltf_path,
+                    mesh_report.error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        report.meshes.push(mesh_report);
+    }
+
+    let texture_jobs = match find_texture_jobs(&config) {
+        Ok(texture_jobs) => texture_jobs,
+        Err(err) => {
+            eprintln!("Error: failed to find textures: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let texture_paths = Arc::new(texture_paths);
+    let mut pending_texture_jobs = Vec::new();
+    for texture_job in texture_jobs {
+        let output = texture_job.get_compressed_path();
+        let manifest_entry = texture_job.get_source_paths().and_then(|source_paths| {
+            ManifestEntry::new(
+                &config,
+                &source_paths,
+                serde_json::json!({
+                    "srgb": texture_job.is_srgb,
+                    "normal_map": texture_job.is_normal_map,
+                    "quality_level": config.quality_level,
+                }),
+            )
+        });
+        let (status, error) = match manifest_entry {
+            Err(err) => (OutputStatus::Failed, Some(err.to_string())),
+            Ok(manifest_entry) => {
+                let status =
+                    if !config.force && manifest.is_fresh(&config, &output, &manifest_entry) {
+                        OutputStatus::UpToDate
+                    } else if config.dry_run {
+                        log!(
+                            config,
+                            "would compress {} (srgb={:?}, is_normal_map={:?})",
+                            texture_job.get_display_name(),
+                            texture_job.is_srgb,
+                            texture_job.is_normal_map
+                        );
+                        OutputStatus::WouldWrite
+                    } else {
+                        pending_texture_jobs.push((
+                            report.textures.len(),
+                            texture_job.clone(),
+                            output.clone(),
+                            manifest_entry,
+                        ));
+                        // replaced once the job is done
+                        OutputStatus::Failed
+                    };
+                (status, None)
+            }
+        };
+        report.textures.push(TextureReport {
+            source: texture_job.path,
+            image_index: texture_job.embedded_image_index,
+            output,
+            srgb: texture_job.is_srgb,
+            normal_map: texture_job.is_normal_map,
+            status,
+            error,
+        });
+    }
 
-    let worker_count = num_cpus::get() / COMPRESSION_THREAD_COUNT;
-    let texture_count = texture_paths.len();
+    // the meshes are already done, don't lose them if the texture compression gets interrupted
+    if !config.dry_run {
+        save_manifest(&config, &manifest, &manifest_path);
+    }
 
-    let pool = ThreadPool::new(worker_count);
+    let config = Arc::new(config);
+    let texture_count = pending_texture_jobs.len();
+    let pool = ThreadPool::new(config.worker_count);
 
     let (tx, rx) = channel();
-    for texture_index in 0..texture_count {
+    for (report_index, texture_job, output, manifest_entry) in pending_texture_jobs {
         let tx = tx.clone();
-        let texture_paths = texture_paths.clone();
+        let config = config.clone();
+        log!(
+            config,
+            "start {} (srgb={:?}, is_normal_map={:?})",
+            texture_job.get_display_name(),
+            texture_job.is_srgb,
+            texture_job.is_normal_map
+        );
         pool.execute(move || {
-            let (path, is_srgb, is_normal_map) = &texture_paths[texture_index];
-            println!(
-                "start {:?} (srgb={:?}, is_normal_map={:?})",
-                path, is_srgb, is_normal_map
-            );
-            compress_file(path.as_path(), *is_srgb, *is_normal_map).unwrap();
-            tx.send(texture_index).unwrap();
+            let result =
+                compress_file(&config, &texture_job, &output).map(|_| (output, manifest_entry));
+            tx.send((report_index, result)).unwrap();
         });
     }
-    let mut done_count = 0;
-    for _ in 0..texture_count {
-        let texture_index = rx.recv().unwrap();
-        done_count += 1;
-        println!(
-            "done {:?} ({:?}/{:?})",
-            texture_paths[texture_index].0, done_count, texture_count
+    for done_count in 1..=texture_count {
+        let (report_index, result) = rx.recv().unwrap();
+        let texture_report = &mut report.textures[report_index];
+        match result {
+            Ok((output, manifest_entry)) => {
+                texture_report.status = OutputStatus::Written;
+                manifest.insert(&config, &output, manifest_entry);
+                save_manifest(&config, &manifest, &manifest_path);
+                log!(
+                    config,
+                    "done {:?} ({:?}/{:?})",
+                    texture_report.source,
+                    done_count,
+                    texture_count
+                );
+            }
+            Err(err) => {
+                texture_report.error = Some(err.to_string());
+                log!(
+                    config,
+                    "Warning: failed to compress {:?} ({:?}/{:?}): {}",
+                    texture_report.source,
+                    done_count,
+                    texture_count,
+                    err
+                );
+            }
+        }
+    }
+
+    // the outputs of the current sources, whatever else looks like an output is left over
+    let mut expected_outputs: HashSet<String> = HashSet::new();
+    for mesh_report in &report.meshes {
+        expected_outputs.insert(config.get_relative_path(&mesh_report.output));
+    }
+    for texture_report in &report.textures {
+        expected_outputs.insert(config.get_relative_path(&texture_report.output));
+        // the legacy output is still loaded until the texture gets compressed again
+        expected_outputs
+            .insert(config.get_relative_path(&texture_report.output.with_extension("bin")));
+    }
+    let orphaned_outputs = [
+        manifest.get_orphaned_outputs(&config),
+        find_untracked_orphaned_outputs(&config, &manifest, &expected_outputs),
+    ]
+    .concat();
+    for orphaned_output in orphaned_outputs {
+        if config.dry_run {
+            log!(config, "would remove orphaned output {:?}", orphaned_output);
+        } else {
+            log!(config, "removing orphaned output {:?}", orphaned_output);
+            match std::fs::remove_file(&orphaned_output) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    log!(
+                        config,
+                        "Warning: failed to remove {:?}: {}",
+                        orphaned_output,
+                        err
+                    );
+                    continue;
+                }
+            }
+            manifest.remove(&config, &orphaned_output);
+        }
+        report.removed_outputs.push(orphaned_output);
+    }
+    if !config.dry_run {
+        save_manifest(&config, &manifest, &manifest_path);
+    }
+
+    if let Some(report_path) = &config.report_path {
+        let report_json = serde_json::to_string_pretty(&report).unwrap();
+        if config.is_report_on_stdout() {
+            println!("{}", report_json);
+        } else if let Err(err) = std::fs::write(report_path, report_json) {
+            eprintln!(
+                "Error: failed to write report to {:?}: {}",
+                report_path, err
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn save_manifest(config: &Config, manifest: &Manifest, manifest_path: &Path) {
+    if let Err(err) = manifest.save(manifest_path) {
+        log!(
+            config,
+            "Warning: failed to save manifest {:?}: {}",
+            manifest_path,
+            err
         );
     }
 }
 
-fn find_gltf_texture_paths() -> anyhow::Result<Vec<(PathBuf, bool, bool)>> {
-    let mut result = Vec::new();
-    let gltf_paths: Vec<_> = WalkDir::new(DATA_FOLDER)
+fn find_gltf_paths(config: &Config) -> Vec<PathBuf> {
+    WalkDir::new(&config.input_root)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
@@ -82,13 +403,190 @@ fn find_gltf_texture_paths() -> anyhow::Result<Vec<(PathBuf, bool, bool)>> {
             e.path().extension().unwrap() == "gltf" || e.path().extension().unwrap() == "glb"
         })
         .map(|e| e.path().to_path_buf())
+        .filter(|path| config.is_included(path))
+        .collect()
+}
+
+fn process_mesh(config: &Config, manifest: &mut Manifest, gltf_path: &Path) -> MeshReport {
+    let output = gltf_path_to_mesh_cache_path(gltf_path);
+    let mut mesh_report = MeshReport {
+        source: gltf_path.to_path_buf(),
+        output: output.clone(),
+        status: OutputStatus::Failed,
+        error: None,
+        stats_before: None,
+        stats_after: None,
+        lod_triangle_counts: Vec::new(),
+    };
+    let result = (|| {
+        let options = MeshOptimizationOptions::default();
+        let lod_options = LodOptions::default();
+        let manifest_entry = ManifestEntry::new(
+            config,
+            &get_gltf_buffer_paths(gltf_path, &open_gltf(gltf_path)?.document),
+            serde_json::json!({
+                "mesh_cache_version": MESH_CACHE_VERSION,
+                "optimization": options,
+                "lods": lod_options,
+            }),
+        )?;
+        if !config.force && manifest.is_fresh(config, &output, &manifest_entry) {
+            mesh_report.status = OutputStatus::UpToDate;
+            return Ok(());
+        }
+        if config.dry_run {
+            mesh_report.status = OutputStatus::WouldWrite;
+            return Ok(());
+        }
+        let MeshCacheReport {
+            stats_before,
+            stats_after,
+            lod_triangle_counts,
+        } = write_mesh_cache(gltf_path, &output, &options, &lod_options)?;
+        manifest.insert(config, &output, manifest_entry);
+        mesh_report.status = OutputStatus::Written;
+        mesh_report.stats_before = Some(stats_before);
+        mesh_report.stats_after = Some(stats_after);
+        mesh_report.lod_triangle_counts = lod_triangle_counts;
+        anyhow::Ok(())
+    })();
+    if let Err(err) = result {
+        mesh_report.status = OutputStatus::Failed;
+        mesh_report.error = Some(err.to_string());
+    }
+    mesh_report
+}
+
+struct MeshCacheReport {
+    stats_before: MeshStats,
+    stats_after: MeshStats,
+    // summed over all primitives, one entry per lod level
+    lod_triangle_counts: Vec<usize>,
+}
+
+fn write_mesh_cache(
+    gltf_path: &Path,
+    output_path: &Path,
+    options: &MeshOptimizationOptions,
+    lod_options: &LodOptions,
+) -> anyhow::Result<MeshCacheReport> {
+    let (document, buffers, _images) = import_gltf(gltf_path)?;
+    let mut mesh_cache = build_mesh_cache(&document, &buffers)?;
+
+    let mut stats_before = Vec::with_capacity(mesh_cache.primitives.len());
+    let mut stats_after = Vec::with_capacity(mesh_cache.primitives.len());
+    let mut lod_triangle_counts: Vec<usize> = Vec::new();
+    for primitive in mesh_cache.primitives.iter_mut() {
+        stats_before.push(get_mesh_stats(
+            &primitive.geometry_source,
+            primitive.primitive_mode,
+        ));
+        optimize_geometry(
+            &mut primitive.geometry_source,
+            primitive.primitive_mode,
+            options,
+        );
+        stats_after.push(get_mesh_stats(
+            &primitive.geometry_source,
+            primitive.primitive_mode,
+        ));
+        if primitive.primitive_mode == PrimitiveMode::Triangles {
+            primitive.lods = generate_lods(&primitive.geometry_source, lod_options);
+            for (lod_index, lod) in primitive.lods.iter().enumerate() {
+                if lod_index == lod_triangle_counts.len() {
+                    lod_triangle_counts.push(0);
+                }
+                lod_triangle_counts[lod_index] += lod.indices.len() / 3;
+            }
+        }
+    }
+
+    if let Some(output_folder) = output_path.parent() {
+        std::fs::create_dir_all(output_folder)?;
+    }
+    std::fs::write(output_path, mesh_cache.to_bytes(options.narrow_indices)?)?;
+    Ok(MeshCacheReport {
+        stats_before: MeshStats::combined(&stats_before),
+        stats_after: MeshStats::combined(&stats_after),
+        lod_triangle_counts,
+    })
+}
+
+// all the textures referenced by gltf files plus the dangling ones, each listed once
+fn find_texture_jobs(config: &Config) -> anyhow::Result<Vec<TextureJob>> {
+    // (canonical path, embedded image index) -> job
+    let mut texture_jobs: HashMap<(PathBuf, Option<usize>), TextureJob> = HashMap::new();
+    for job in find_gltf_textures(config)? {
+        let canonical_path = match job.path.canonicalize() {
+            Ok(canonical_path) => canonical_path,
+            Err(err) => {
+                log!(
+                    config,
+                    "Warning: texture {:?} could not be found: {}",
+                    job.path,
+                    err
+                );
+                continue;
+            }
+        };
+        // the same image can be used by several materials or files
+        texture_jobs
+            .entry((canonical_path, job.embedded_image_index))
+            .and_modify(|existing_job| {
+                existing_job.is_srgb |= job.is_srgb;
+                existing_job.is_normal_map |= job.is_normal_map;
+            })
+            .or_insert(job);
+    }
+
+    let gltf_texture_paths: HashSet<PathBuf> = texture_jobs
+        .keys()
+        .filter(|(_, embedded_image_index)| embedded_image_index.is_none())
+        .map(|(path, _)| path.clone())
+        .collect();
+    // interpret all dangling textures as srgb color maps
+    for path in find_dangling_texture_paths(config, &gltf_texture_paths)? {
+        texture_jobs.insert(
+            (path.canonicalize()?, None),
+            TextureJob {
+                path,
+                embedded_image_index: None,
+                is_srgb: true,
+                is_normal_map: false,
+            },
+        );
+    }
+
+    let mut texture_jobs: Vec<TextureJob> = texture_jobs
+        .into_values()
+        .filter(|job| config.is_included(&job.path))
+        .map(|job| {
+            let (is_srgb, is_normal_map) =
+                config.classify_texture(&job.path, job.is_srgb, job.is_normal_map);
+            TextureJob {
+                is_srgb,
+                is_normal_map: !is_srgb && is_normal_map,
+                ..job
+            }
+        })
         .collect();
-    for path in gltf_paths {
-        let gltf = gltf::Gltf::open(&path)?;
+    texture_jobs
+        .sort_by(|a, b| (&a.path, a.embedded_image_index).cmp(&(&b.path, b.embedded_image_index)));
+    Ok(texture_jobs)
+}
+
+fn find_gltf_textures(config: &Config) -> anyhow::Result<Vec<TextureJob>> {
+    let mut result = Vec::new();
+    for path in find_gltf_paths(config) {
+        let gltf = open_gltf(&path)?;
 
-        for texture in gltf.textures() {
+        // KHR_texture_basisu textures are already compressed
+        for texture in gltf
+            .textures()
+            .filter(|texture| !is_ktx2_image(&texture.source()))
+        {
             let is_srgb = gltf.materials().any(|material| {
-                vec![
+                [
                     material.emissive_texture(),
                     material.pbr_metallic_roughness().base_color_texture(),
                 ]
@@ -104,11 +602,20 @@ fn find_gltf_texture_paths() -> anyhow::Result<Vec<(PathBuf, bool, bool)>> {
 
             match texture.source().source() {
                 gltf::image::Source::View { .. } => {
-                    println!("Warning: found inline texture in gltf file {:?}, texture index {:?}. This texture wont be compressed", path, texture.index());
+                    result.push(TextureJob {
+                        path: path.clone(),
+                        embedded_image_index: Some(texture.source().index()),
+                        is_srgb,
+                        is_normal_map,
+                    });
                 }
                 gltf::image::Source::Uri { uri, .. } => {
-                    let path = path.parent().unwrap().join(PathBuf::from(uri));
-                    result.push((path, is_srgb, is_normal_map));
+                    result.push(TextureJob {
+                        path: path.parent().unwrap().join(decode_uri(uri)),
+                        embedded_image_index: None,
+                        is_srgb,
+                        is_normal_map,
+                    });
                 }
             };
         }
@@ -116,23 +623,96 @@ fn find_gltf_texture_paths() -> anyhow::Result<Vec<(PathBuf, bool, bool)>> {
     Ok(result)
 }
 
-// TODO: exclude list doesn't work because the relative path is different between the gltf file and the CWD of this binary
-fn find_dangling_texture_paths(exclude_list: HashSet<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
-    Ok(WalkDir::new(DATA_FOLDER)
+fn read_embedded_image_bytes(gltf_path: &Path, image_index: usize) -> anyhow::Result<Vec<u8>> {
+    let gltf = open_gltf(gltf_path)?;
+    let image = gltf
+        .images()
+        .nth(image_index)
+        .ok_or_else(|| anyhow::anyhow!("Image {:?} not found", image_index))?;
+    let view = match image.source() {
+        gltf::image::Source::View { view, .. } => view,
+        gltf::image::Source::Uri { .. } => anyhow::bail!("Image {:?} is not embedded", image_index),
+    };
+    let buffer_bytes = match view.buffer().source() {
+        gltf::buffer::Source::Bin => gltf
+            .blob
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Missing binary chunk in glb file"))?,
+        gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => std::fs::read(
+            gltf_path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(decode_uri(uri)),
+        )?,
+        // let the gltf crate decode the base64 data
+        gltf::buffer::Source::Uri(_) => {
+            let (_, buffers, _) = import_gltf(gltf_path)?;
+            buffers[view.buffer().index()].0.clone()
+        }
+    };
+    Ok(buffer_bytes
+        .get(view.offset()..view.offset() + view.length())
+        .ok_or_else(|| anyhow::anyhow!("Buffer view of image {:?} is out of bounds", image_index))?
+        .to_vec())
+}
+
+// gltf_texture_paths must be canonicalized since the gltf uris are relative to the gltf file
+// outputs that aren't in the manifest and whose sources are gone, e.g. ones that were written
+// before the manifest existed
+fn find_untracked_orphaned_outputs(
+    config: &Config,
+    manifest: &Manifest,
+    expected_outputs: &HashSet<String>,
+) -> Vec<PathBuf> {
+    WalkDir::new(&config.input_root)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
-        .filter(|e| !exclude_list.contains(&e.path().canonicalize().unwrap()))
-        .filter(|e| e.path().extension().is_some())
         .filter(|e| {
-            e.path().extension().unwrap() == "jpg" || e.path().extension().unwrap() == "png"
+            let file_name = e.file_name().to_string_lossy();
+            OUTPUT_FILE_SUFFIXES
+                .iter()
+                .any(|suffix| file_name.ends_with(suffix))
         })
         .map(|e| e.path().to_path_buf())
-        .collect())
+        .filter(|path| config.is_included(path))
+        .filter(|path| {
+            let relative_path = config.get_relative_path(path);
+            !expected_outputs.contains(&relative_path)
+                && !manifest.outputs.contains_key(&relative_path)
+        })
+        .collect()
+}
+
+fn find_dangling_texture_paths(
+    config: &Config,
+    gltf_texture_paths: &HashSet<PathBuf>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    for entry in WalkDir::new(&config.input_root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|extension| extension == "jpg" || extension == "png")
+                == Some(true)
+        })
+    {
+        if !gltf_texture_paths.contains(&entry.path().canonicalize()?) {
+            result.push(entry.path().to_path_buf());
+        }
+    }
+    Ok(result)
 }
 
-fn compress_file(img_path: &Path, is_srgb: bool, is_normal_map: bool) -> anyhow::Result<()> {
-    let img_bytes = std::fs::read(img_path)?;
+fn compress_file(
+    config: &Config,
+    texture_job: &TextureJob,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let img_bytes = texture_job.read_image_bytes()?;
     let img_decoded = image::load_from_memory(&img_bytes)?.to_rgba8();
     let (img_width, img_height) = img_decoded.dimensions();
     let img_channel_count = 4;
@@ -144,24 +724,86 @@ fn compress_file(img_path: &Path, is_srgb: bool, is_normal_map: bool) -> anyhow:
             img_width,
             img_height,
             img_channel_count,
-            is_srgb,
-            is_normal_map,
-            thread_count: COMPRESSION_THREAD_COUNT as u32,
+            is_srgb: texture_job.is_srgb,
+            is_normal_map: texture_job.is_normal_map,
+            thread_count: config.compression_thread_count as u32,
+            quality_level: config.quality_level,
         })
     }?;
 
-    // println!(
-    //     "path: {:?} jpg: {:?}, decoded: {:?}, compressed: {:?}",
-    //     img_path,
-    //     img_bytes.len(),
-    //     img_decoded.len(),
-    //     compressed_img_bytes.len()
-    // );
-
-    std::fs::write(
-        texture_path_to_compressed_path(img_path),
-        compressed_img_bytes,
-    )?;
+    if let Some(output_folder) = output_path.parent() {
+        std::fs::create_dir_all(output_folder)?;
+    }
+    std::fs::write(output_path, compressed_img_bytes)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_embedded_images() {
+        let glb_path = Path::new(
+            "./src/models/gltf/TextureLinearInterpolationTest/TextureLinearInterpolationTest.glb",
+        );
+        let image_bytes = read_embedded_image_bytes(glb_path, 0).unwrap();
+        let (document, _buffers, images) = gltf::import(glb_path).unwrap();
+        let decoded = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (images[0].width, images[0].height));
+
+        assert!(read_embedded_image_bytes(glb_path, document.images().len()).is_err());
+    }
+
+    #[test]
+    fn finds_untracked_orphaned_outputs() {
+        let input_root = std::env::temp_dir().join(format!(
+            "ikari_asset_optimizer_untracked_outputs_test_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&input_root).unwrap();
+        for file_name in [
+            "texture.png",
+            "texture_compressed.ktx2",
+            "texture_compressed.bin",
+            "removed_compressed.bin",
+            "removed_mesh_cache.bin",
+            "tracked_compressed.ktx2",
+            "notes_compressed.bin.bak",
+        ] {
+            std::fs::write(input_root.join(file_name), b"").unwrap();
+        }
+
+        let config = Config::new(Cli::parse_from([
+            "asset_optimizer",
+            "--input",
+            input_root.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            &config,
+            &input_root.join("tracked_compressed.ktx2"),
+            ManifestEntry::new(&config, &[], serde_json::json!({})).unwrap(),
+        );
+        let expected_outputs: HashSet<String> =
+            ["texture_compressed.ktx2", "texture_compressed.bin"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+
+        let mut orphaned_outputs =
+            find_untracked_orphaned_outputs(&config, &manifest, &expected_outputs);
+        orphaned_outputs.sort();
+        std::fs::remove_dir_all(&input_root).unwrap();
+
+        assert_eq!(
+            orphaned_outputs,
+            vec![
+                input_root.join("removed_compressed.bin"),
+                input_root.join("removed_mesh_cache.bin"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::BTreeMap,
+    hash::Hasher,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+use crate::config::Config;
+
+// lives in the input folder, next to the outputs
+pub const MANIFEST_FILE_NAME: &str = "asset_optimizer_manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+// outputs written by another version of the tool are rebuilt
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// records what each output was built from so that only the stale ones get rebuilt
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    // output path relative to the input folder -> what it was built from
+    pub outputs: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub tool_version: String,
+    // source path relative to the input folder -> xxhash64 of its contents
+    pub sources: BTreeMap<String, String>,
+    // everything besides the sources that affects the output, e.g. the compression settings
+    pub params: serde_json::Value,
+}
+
+impl ManifestEntry {
+    pub fn new(config: &Config, sources: &[PathBuf], params: serde_json::Value) -> Result<Self> {
+        Ok(Self {
+            tool_version: TOOL_VERSION.to_string(),
+            sources: sources
+                .iter()
+                .map(|source| Ok((config.get_relative_path(source), hash_file(source)?)))
+                .collect::<Result<_>>()?,
+            params,
+        })
+    }
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            outputs: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn get_path(config: &Config) -> PathBuf {
+        config.input_root.join(MANIFEST_FILE_NAME)
+    }
+
+    // a missing manifest just means that everything gets rebuilt
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.try_exists()? {
+            return Ok(Self::default());
+        }
+        let manifest: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if manifest.version != MANIFEST_VERSION {
+            bail!(
+                "Manifest version {:?} doesn't match the expected version {:?}",
+                manifest.version,
+                MANIFEST_VERSION
+            );
+        }
+        Ok(manifest)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_fresh(&self, config: &Config, output_path: &Path, entry: &ManifestEntry) -> bool {
+        self.outputs.get(&get_output_key(config, output_path)) == Some(entry)
+            && output_path.try_exists().unwrap_or(false)
+    }
+
+    pub fn insert(&mut self, config: &Config, output_path: &Path, entry: ManifestEntry) {
+        self.outputs
+            .insert(get_output_key(config, output_path), entry);
+    }
+
+    // outputs whose sources were deleted or moved
+    pub fn get_orphaned_outputs(&self, config: &Config) -> Vec<PathBuf> {
+        self.outputs
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .sources
+                    .keys()
+                    .any(|source| !config.input_root.join(source).exists())
+            })
+            .map(|(output_key, _)| config.input_root.join(output_key))
+            .collect()
+    }
+
+    pub fn remove(&mut self, config: &Config, output_path: &Path) {
+        self.outputs.remove(&get_output_key(config, output_path));
+    }
+}
+
+fn get_output_key(config: &Config, output_path: &Path) -> String {
+    config.get_relative_path(output_path)
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = XxHash64::with_seed(0);
+    let mut buffer = vec![0u8; 1 << 16];
+    loop {
+        let read_count = file.read(&mut buffer)?;
+        if read_count == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read_count]);
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Parser;
+
+    use crate::config::Cli;
+
+    #[test]
+    fn stale_and_orphaned_outputs() {
+        let input_root = std::env::temp_dir().join("ikari_asset_optimizer_manifest_test");
+        std::fs::create_dir_all(&input_root).unwrap();
+        let source_path = input_root.join("texture.png");
+        let output_path = input_root.join("texture_compressed.bin");
+        std::fs::write(&source_path, b"original").unwrap();
+        std::fs::write(&output_path, b"compressed").unwrap();
+
+        let config = Config::new(Cli::parse_from([
+            "asset_optimizer",
+            "--input",
+            input_root.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let params = serde_json::json!({ "srgb": true });
+        let get_entry = |params: &serde_json::Value| {
+            ManifestEntry::new(&config, &[source_path.clone()], params.clone()).unwrap()
+        };
+
+        let mut manifest = Manifest::default();
+        assert!(!manifest.is_fresh(&config, &output_path, &get_entry(&params)));
+        manifest.insert(&config, &output_path, get_entry(&params));
+        assert!(manifest.is_fresh(&config, &output_path, &get_entry(&params)));
+
+        let manifest_path = Manifest::get_path(&config);
+        manifest.save(&manifest_path).unwrap();
+        let mut manifest = Manifest::load(&manifest_path).unwrap();
+        assert!(manifest.is_fresh(&config, &output_path, &get_entry(&params)));
+
+        // different settings
+        assert!(!manifest.is_fresh(
+            &config,
+            &output_path,
+            &get_entry(&serde_json::json!({ "srgb": false }))
+        ));
+
+        // different source contents
+        std::fs::write(&source_path, b"modified").unwrap();
+        assert!(!manifest.is_fresh(&config, &output_path, &get_entry(&params)));
+
+        assert!(manifest.get_orphaned_outputs(&config).is_empty());
+        std::fs::remove_file(&source_path).unwrap();
+        assert_eq!(
+            manifest.get_orphaned_outputs(&config),
+            vec![output_path.clone()]
+        );
+        manifest.remove(&config, &output_path);
+        assert!(manifest.outputs.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use glob::Pattern;
+use walkdir::WalkDir;
+
+use ikari::vfs::{normalize_path, PackArchiveWriter};
+
+const DEFAULT_COMPRESSION_LEVEL: i32 = 19;
+
+/// Packs asset folders into a single archive that the game mounts in place of the loose files
+#[derive(Debug, Parser)]
+#[command(name = "asset_packer")]
+struct Cli {
+    /// Folders or files to pack
+    #[arg(default_values = ["./src/models", "./src/textures", "./src/sounds", "./src/levels", "./src/shaders"])]
+    inputs: Vec<PathBuf>,
+
+    /// Folder that the paths inside the archive are relative to, should be the folder that the game mounts the archive in place of
+    #[arg(long, default_value = "./src")]
+    root: PathBuf,
+
+    /// Path of the archive that is written
+    #[arg(long, default_value = "./assets.pak")]
+    output: PathBuf,
+
+    /// Skip the files that match one of these globs, relative to the root folder
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// zstd compression level, higher is slower but makes a smaller archive
+    #[arg(long, default_value_t = DEFAULT_COMPRESSION_LEVEL, value_parser = clap::value_parser!(i32).range(1..=22))]
+    compression_level: i32,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let exclude_patterns = cli
+        .exclude
+        .iter()
+        .map(|glob| Pattern::new(glob))
+        .collect::<Result<Vec<_>, _>>()?;
+    let root = normalize_path(&cli.root);
+    let output = normalize_path(&cli.output);
+
+    let mut pack_paths = vec![];
+    for input in &cli.inputs {
+        for entry in WalkDir::new(input) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = normalize_path(entry.path());
+            if path == output {
+                continue;
+            }
+            let pack_path = match path.strip_prefix(&root) {
+                Ok(pack_path) => pack_path.to_path_buf(),
+                Err(_) => anyhow::bail!(
+                    "{} is not inside the root folder {}",
+                    path.display(),
+                    root.display()
+                ),
+            };
+            if exclude_patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(&pack_path))
+            {
+                continue;
+            }
+            pack_paths.push((pack_path, path));
+        }
+    }
+    pack_paths.sort();
+    pack_paths.dedup();
+
+    let mut writer = PackArchiveWriter::create(&cli.output, cli.compression_level)?;
+    let mut total_size = 0;
+    for (pack_path, path) in &pack_paths {
+        let bytes = std::fs::read(path)?;
+        total_size += bytes.len() as u64;
+        let modified = std::fs::metadata(path)?.modified().ok();
+        writer.add_file(pack_path, &bytes, modified)?;
+        println!("Packed {}", pack_path.display());
+    }
+    writer.finish()?;
+
+    let archive_size = std::fs::metadata(&cli.output)?.len();
+    println!(
+        "Packed {} files ({} bytes) into {} ({} bytes)",
+        pack_paths.len(),
+        total_size,
+        cli.output.display(),
+        archive_size
+    );
+    Ok(())
+}
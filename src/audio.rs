@@ -1,4 +1,4 @@
-use std::fs::File;
+use crate::vfs::*;
 
 use anyhow::Result;
 use cpal::{
@@ -134,12 +134,27 @@ impl AudioManager {
         ))
     }
 
+    // plays into nothing, for tests that need an audio manager but have no output device
+    #[cfg(test)]
+    pub(crate) fn new_silent() -> Self {
+        let (spatial_scene_handle, _) = oddio::split(oddio::SpatialScene::new());
+        let (mixer_handle, _) = oddio::split(oddio::Mixer::new());
+        AudioManager {
+            master_volume: 1.0,
+            device_sample_rate: 44100,
+
+            spatial_scene_handle,
+            mixer_handle,
+            sounds: vec![],
+        }
+    }
+
     pub fn decode_audio_file(
         sample_rate: u32,
         file_path: &str,
         file_format: Option<AudioFileFormat>,
     ) -> Result<SoundData> {
-        let src = File::open(file_path)?;
+        let src = std::io::Cursor::new(vfs_read(file_path)?);
         let mss = MediaSourceStream::new(Box::new(src), Default::default());
 
         let mut hint = Hint::new();
@@ -1,5 +1,7 @@
+use crate::behaviour::*;
 use crate::game::*;
 use crate::math::*;
+use crate::scene::*;
 use crate::transform::*;
 
 use glam::f32::{Vec2, Vec3};
@@ -125,3 +127,67 @@ impl BallComponent {
         }
     }
 }
+
+// the balls are simulated at a fixed timestep and their nodes are placed between the previous and
+// the next state
+#[derive(Clone, Debug)]
+pub struct InterpolatedBallComponent {
+    pub prev: BallComponent,
+    pub next: BallComponent,
+}
+
+impl InterpolatedBallComponent {
+    pub fn new(ball: BallComponent) -> Self {
+        Self {
+            prev: ball.clone(),
+            next: ball,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.prev = self.next.clone();
+        self.next.update(dt);
+    }
+
+    pub fn lerp(&self, alpha: f32) -> BallComponent {
+        self.prev.lerp(&self.next, alpha)
+    }
+}
+
+// steps the InterpolatedBallComponent of its node at the fixed rate and places the node between
+// its last two states every frame
+pub struct InterpolatedBall {
+    node_id: GameNodeId,
+}
+
+impl InterpolatedBall {
+    pub fn new(node_id: GameNodeId) -> Self {
+        Self { node_id }
+    }
+}
+
+impl Behaviour for InterpolatedBall {
+    fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+        let Some(ball) = ctx
+            .scene
+            .components
+            .get::<InterpolatedBallComponent>(self.node_id)
+        else {
+            return;
+        };
+        let transform = ball.lerp(ctx.fixed_update_alpha).transform;
+        if let Some(node) = ctx.scene.get_node_mut(self.node_id) {
+            node.transform = transform;
+        }
+    }
+
+    fn fixed_update(&mut self, dt: f32, ctx: &mut BehaviourContext) {
+        if let Some(ball) = ctx
+            .scene
+            .components
+            .get_mut::<InterpolatedBallComponent>(self.node_id)
+        {
+            ball.update(dt);
+        }
+    }
+}
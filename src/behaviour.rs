@@ -0,0 +1,431 @@
+use crate::audio::*;
+use crate::physics::*;
+use crate::player_controller::*;
+use crate::scene::*;
+
+use std::any::Any;
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::Mutex;
+
+// game logic that's attached to a node. the behaviour is destroyed once its node is removed from
+// the scene. behaviours keep the ids of the nodes they work on themselves
+pub trait Behaviour {
+    // called in the first update after the behaviour was added
+    fn on_spawn(&mut self, _ctx: &mut BehaviourContext) {}
+
+    // called once per frame, before the animations are stepped
+    fn update(&mut self, _dt: f32, _ctx: &mut BehaviourContext) {}
+
+    // called at a fixed rate, possibly many times per frame
+    fn fixed_update(&mut self, _dt: f32, _ctx: &mut BehaviourContext) {}
+
+    // called with every message that's sent, the behaviour should downcast the ones it cares about
+    fn on_message(&mut self, _message: &dyn Any, _ctx: &mut BehaviourContext) {}
+
+    // called after the node was removed from the scene, to clean up anything outside of it
+    fn on_destroy(&mut self, _ctx: &mut BehaviourContext) {}
+}
+
+// the parts of the game that behaviours can access
+pub struct BehaviourContext<'a> {
+    pub scene: &'a mut Scene,
+    pub physics_state: &'a mut PhysicsState,
+    pub audio_manager: &'a Mutex<AudioManager>,
+    // holds the player's input
+    pub player_controller: &'a PlayerController,
+    // how far the frame is past the last fixed update, as a fraction of the fixed timestep. for
+    // interpolating the state of the fixed updates in update, it's 0 in fixed_update
+    pub fixed_update_alpha: f32,
+    added_behaviours: &'a mut Vec<AttachedBehaviour>,
+    node_behaviour_counts: &'a mut HashMap<GameNodeId, usize>,
+    messages: &'a mut Vec<Box<dyn Any>>,
+}
+
+impl<'a> BehaviourContext<'a> {
+    pub fn add_behaviour(&mut self, node_id: GameNodeId, behaviour: impl Behaviour + 'static) {
+        *self.node_behaviour_counts.entry(node_id).or_default() += 1;
+        self.added_behaviours.push(AttachedBehaviour {
+            node_id,
+            behaviour: Box::new(behaviour),
+        });
+    }
+
+    // the message is passed to all the behaviours at the end of the update
+    pub fn send_message(&mut self, message: impl Any) {
+        self.messages.push(Box::new(message));
+    }
+}
+
+struct AttachedBehaviour {
+    node_id: GameNodeId,
+    behaviour: Box<dyn Behaviour>,
+}
+
+#[derive(Default)]
+pub struct BehaviourManager {
+    behaviours: Vec<AttachedBehaviour>,
+    added_behaviours: Vec<AttachedBehaviour>,
+    // how many of the behaviours above are attached to each node
+    node_behaviour_counts: HashMap<GameNodeId, usize>,
+    messages: Vec<Box<dyn Any>>,
+}
+
+impl BehaviourManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, node_id: GameNodeId, behaviour: impl Behaviour + 'static) {
+        *self.node_behaviour_counts.entry(node_id).or_default() += 1;
+        self.added_behaviours.push(AttachedBehaviour {
+            node_id,
+            behaviour: Box::new(behaviour),
+        });
+    }
+
+    pub fn has_behaviour(&self, node_id: GameNodeId) -> bool {
+        self.node_behaviour_counts.contains_key(&node_id)
+    }
+
+    pub fn behaviour_count(&self) -> usize {
+        self.behaviours.len() + self.added_behaviours.len()
+    }
+
+    // the message is passed to all the behaviours in the next update
+    pub fn send_message(&mut self, message: impl Any) {
+        self.messages.push(Box::new(message));
+    }
+
+    #[profiling::function]
+    pub fn update(
+        &mut self,
+        dt: f32,
+        fixed_update_alpha: f32,
+        scene: &mut Scene,
+        physics_state: &mut PhysicsState,
+        audio_manager: &Mutex<AudioManager>,
+        player_controller: &PlayerController,
+    ) {
+        let Self {
+            behaviours,
+            added_behaviours,
+            node_behaviour_counts,
+            messages,
+        } = self;
+        let mut ctx = BehaviourContext {
+            scene,
+            physics_state,
+            audio_manager,
+            player_controller,
+            fixed_update_alpha,
+            added_behaviours,
+            node_behaviour_counts,
+            messages,
+        };
+
+        destroy_behaviours_of_removed_nodes(behaviours, &mut ctx);
+        spawn_added_behaviours(behaviours, &mut ctx);
+        for attached_behaviour in behaviours.iter_mut() {
+            attached_behaviour.behaviour.update(dt, &mut ctx);
+        }
+        // messages can lead to more messages
+        while !ctx.messages.is_empty() {
+            for message in std::mem::take(ctx.messages) {
+                for attached_behaviour in behaviours.iter_mut() {
+                    attached_behaviour
+                        .behaviour
+                        .on_message(message.as_ref(), &mut ctx);
+                }
+            }
+        }
+        destroy_behaviours_of_removed_nodes(behaviours, &mut ctx);
+    }
+
+    pub fn fixed_update(
+        &mut self,
+        dt: f32,
+        scene: &mut Scene,
+        physics_state: &mut PhysicsState,
+        audio_manager: &Mutex<AudioManager>,
+        player_controller: &PlayerController,
+    ) {
+        let Self {
+            behaviours,
+            added_behaviours,
+            node_behaviour_counts,
+            messages,
+        } = self;
+        let mut ctx = BehaviourContext {
+            scene,
+            physics_state,
+            audio_manager,
+            player_controller,
+            fixed_update_alpha: 0.0,
+            added_behaviours,
+            node_behaviour_counts,
+            messages,
+        };
+        for attached_behaviour in behaviours.iter_mut() {
+            attached_behaviour.behaviour.fixed_update(dt, &mut ctx);
+        }
+    }
+}
+
+fn spawn_added_behaviours(behaviours: &mut Vec<AttachedBehaviour>, ctx: &mut BehaviourContext) {
+    // on_spawn can add more behaviours
+    while !ctx.added_behaviours.is_empty() {
+        let mut added_behaviours = std::mem::take(ctx.added_behaviours);
+        for attached_behaviour in added_behaviours.iter_mut() {
+            attached_behaviour.behaviour.on_spawn(ctx);
+        }
+        behaviours.append(&mut added_behaviours);
+    }
+}
+
+fn destroy_behaviours_of_removed_nodes(
+    behaviours: &mut Vec<AttachedBehaviour>,
+    ctx: &mut BehaviourContext,
+) {
+    behaviours.retain_mut(|attached_behaviour| {
+        if ctx.scene.get_node(attached_behaviour.node_id).is_some() {
+            return true;
+        }
+        attached_behaviour.behaviour.on_destroy(ctx);
+        if let Entry::Occupied(mut entry) =
+            ctx.node_behaviour_counts.entry(attached_behaviour.node_id)
+        {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use glam::f32::{Vec2, Vec3};
+
+    type EventLog = Rc<RefCell<Vec<String>>>;
+
+    struct Ping;
+
+    // logs its hooks, sends a ping from its first update and can add another behaviour on spawn
+    struct RecordingBehaviour {
+        name: &'static str,
+        events: EventLog,
+        sends_ping: bool,
+        spawns: Option<(GameNodeId, Box<RecordingBehaviour>)>,
+    }
+
+    impl RecordingBehaviour {
+        fn new(name: &'static str, events: &EventLog) -> Self {
+            Self {
+                name,
+                events: events.clone(),
+                sends_ping: false,
+                spawns: None,
+            }
+        }
+
+        fn log(&self, event: &str) {
+            self.events
+                .borrow_mut()
+                .push(format!("{} {}", self.name, event));
+        }
+    }
+
+    impl Behaviour for RecordingBehaviour {
+        fn on_spawn(&mut self, ctx: &mut BehaviourContext) {
+            self.log("spawn");
+            if let Some((node_id, behaviour)) = self.spawns.take() {
+                ctx.add_behaviour(node_id, *behaviour);
+            }
+        }
+
+        fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+            self.log("update");
+            if std::mem::take(&mut self.sends_ping) {
+                ctx.send_message(Ping);
+            }
+        }
+
+        fn on_message(&mut self, message: &dyn Any, _ctx: &mut BehaviourContext) {
+            if message.is::<Ping>() {
+                self.log("ping");
+            }
+        }
+
+        fn on_destroy(&mut self, _ctx: &mut BehaviourContext) {
+            self.log("destroy");
+        }
+    }
+
+    struct TestGame {
+        scene: Scene,
+        physics_state: PhysicsState,
+        audio_manager: Mutex<AudioManager>,
+        player_controller: PlayerController,
+        behaviours: BehaviourManager,
+        fixed_update_alpha: f32,
+    }
+
+    impl TestGame {
+        fn new() -> Self {
+            let mut physics_state = PhysicsState::new();
+            let player_controller = PlayerController::new(
+                &mut physics_state,
+                1.0,
+                Vec3::ZERO,
+                ControlledViewDirection {
+                    horizontal: 0.0,
+                    vertical: 0.0,
+                },
+            );
+            Self {
+                scene: Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]),
+                physics_state,
+                audio_manager: Mutex::new(AudioManager::new_silent()),
+                player_controller,
+                behaviours: BehaviourManager::new(),
+                fixed_update_alpha: 0.0,
+            }
+        }
+
+        fn update(&mut self) {
+            self.behaviours.update(
+                1.0 / 60.0,
+                self.fixed_update_alpha,
+                &mut self.scene,
+                &mut self.physics_state,
+                &self.audio_manager,
+                &self.player_controller,
+            );
+        }
+
+        fn fixed_update(&mut self) {
+            self.behaviours.fixed_update(
+                1.0 / 60.0,
+                &mut self.scene,
+                &mut self.physics_state,
+                &self.audio_manager,
+                &self.player_controller,
+            );
+        }
+    }
+
+    fn take_events(events: &EventLog) -> Vec<String> {
+        std::mem::take(&mut *events.borrow_mut())
+    }
+
+    #[test]
+    fn behaviours_spawn_on_first_update_and_are_destroyed_once() {
+        let events = EventLog::default();
+        let mut game = TestGame::new();
+        let node_id = game.scene.add_node(GameNodeDesc::default()).id();
+        game.behaviours
+            .add(node_id, RecordingBehaviour::new("a", &events));
+        assert!(game.behaviours.has_behaviour(node_id));
+        assert!(take_events(&events).is_empty());
+
+        game.update();
+        assert_eq!(take_events(&events), vec!["a spawn", "a update"]);
+        game.update();
+        assert_eq!(take_events(&events), vec!["a update"]);
+
+        game.scene.remove_node(node_id);
+        game.update();
+        assert_eq!(take_events(&events), vec!["a destroy"]);
+        assert!(!game.behaviours.has_behaviour(node_id));
+        assert_eq!(game.behaviours.behaviour_count(), 0);
+        game.update();
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn messages_reach_the_other_behaviours_in_the_same_frame() {
+        let events = EventLog::default();
+        let mut game = TestGame::new();
+        let sender_id = game.scene.add_node(GameNodeDesc::default()).id();
+        let receiver_id = game.scene.add_node(GameNodeDesc::default()).id();
+        let mut sender = RecordingBehaviour::new("sender", &events);
+        sender.sends_ping = true;
+        game.behaviours.add(sender_id, sender);
+        game.behaviours
+            .add(receiver_id, RecordingBehaviour::new("receiver", &events));
+
+        game.update();
+        assert_eq!(
+            take_events(&events),
+            vec![
+                "sender spawn",
+                "receiver spawn",
+                "sender update",
+                "receiver update",
+                "sender ping",
+                "receiver ping",
+            ]
+        );
+    }
+
+    #[test]
+    fn behaviours_added_from_on_spawn_are_spawned() {
+        let events = EventLog::default();
+        let mut game = TestGame::new();
+        let parent_id = game.scene.add_node(GameNodeDesc::default()).id();
+        let child_id = game.scene.add_node(GameNodeDesc::default()).id();
+        let mut parent = RecordingBehaviour::new("parent", &events);
+        parent.spawns = Some((
+            child_id,
+            Box::new(RecordingBehaviour::new("child", &events)),
+        ));
+        game.behaviours.add(parent_id, parent);
+        assert!(!game.behaviours.has_behaviour(child_id));
+
+        game.update();
+        assert_eq!(
+            take_events(&events),
+            vec![
+                "parent spawn",
+                "child spawn",
+                "parent update",
+                "child update"
+            ]
+        );
+        assert!(game.behaviours.has_behaviour(child_id));
+        assert_eq!(game.behaviours.behaviour_count(), 2);
+    }
+
+    #[test]
+    fn interpolated_balls_are_placed_between_their_fixed_updates() {
+        use crate::ball::*;
+
+        let mut game = TestGame::new();
+        let ball = BallComponent::new(Vec2::ZERO, Vec2::new(1.0, 0.0), 0.1, 6.0);
+        let node_id = game
+            .scene
+            .add_node(GameNodeDescBuilder::new().transform(ball.transform).build())
+            .id();
+        game.scene
+            .components
+            .insert(node_id, InterpolatedBallComponent::new(ball));
+        game.behaviours.add(node_id, InterpolatedBall::new(node_id));
+        // spawns the behaviour, fixed updates only reach spawned behaviours
+        game.update();
+
+        game.fixed_update();
+        game.fixed_update_alpha = 0.5;
+        game.update();
+
+        // 6 units per second for half of a 60th of a second
+        let position = game.scene.get_node(node_id).unwrap().transform.position();
+        assert!((position.x - 0.05).abs() < 1e-4);
+        assert_eq!(position.z, 0.0);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::math::*;
+use crate::renderer::*;
+use crate::scene::*;
 use crate::transform::*;
 
 use glam::{
@@ -25,6 +27,82 @@ impl Camera {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraProjection {
+    Perspective {
+        fov_y: f32,
+        // None means the aspect ratio of the viewport should be used
+        aspect_ratio: Option<f32>,
+        near_plane_distance: f32,
+        // None means an infinite projection
+        far_plane_distance: Option<f32>,
+    },
+    Orthographic {
+        half_width: f32,
+        half_height: f32,
+        near_plane_distance: f32,
+        far_plane_distance: f32,
+    },
+}
+
+// a camera that looks down the -Z axis of the node it's attached to
+#[derive(Clone, Debug)]
+pub struct CameraComponent {
+    pub node_id: GameNodeId,
+    pub name: Option<String>,
+    pub projection: CameraProjection,
+}
+
+impl CameraComponent {
+    // transform is the global transform of the camera's node
+    pub fn to_shader_camera_data(
+        &self,
+        transform: Mat4,
+        viewport_aspect_ratio: f32,
+        reverse_z: bool,
+    ) -> ShaderCameraData {
+        match self.projection {
+            CameraProjection::Perspective {
+                fov_y,
+                aspect_ratio,
+                near_plane_distance,
+                far_plane_distance,
+            } => ShaderCameraData::from_mat4(
+                transform,
+                aspect_ratio.unwrap_or(viewport_aspect_ratio),
+                near_plane_distance,
+                far_plane_distance.unwrap_or(FAR_PLANE_DISTANCE),
+                fov_y,
+                reverse_z,
+            ),
+            CameraProjection::Orthographic {
+                half_width,
+                half_height,
+                near_plane_distance,
+                far_plane_distance,
+            } => ShaderCameraData::from_transform_and_proj(
+                transform,
+                make_orthographic_proj_matrix(
+                    half_width * 2.0,
+                    half_height * 2.0,
+                    near_plane_distance,
+                    far_plane_distance,
+                    reverse_z,
+                ),
+                near_plane_distance,
+                far_plane_distance,
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexedCameraComponent {
+    pub node_index: usize,
+    pub name: Option<String>,
+    pub projection: CameraProjection,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct ShaderCameraData {
     pub proj: Mat4,
@@ -51,6 +129,23 @@ impl ShaderCameraData {
             aspect_ratio,
             reverse_z,
         );
+        Self::from_transform_and_proj(transform, proj, near_plane_distance, far_plane_distance)
+        // orthographic instead of perspective:
+        // build_directional_light_camera_view(
+        //     Vec3::new(-0.5, -0.5, 0.1).normalize(),
+        //     100.0,
+        //     100.0,
+        //     100.0,
+        // )
+        // .into()
+    }
+
+    fn from_transform_and_proj(
+        transform: Mat4,
+        proj: Mat4,
+        near_plane_distance: f32,
+        far_plane_distance: f32,
+    ) -> Self {
         let rotation_only_matrix = clear_translation_from_matrix(transform);
         let rotation_only_view = rotation_only_matrix.inverse();
         let view = transform.inverse();
@@ -63,14 +158,6 @@ impl ShaderCameraData {
             near_plane_distance,
             far_plane_distance,
         }
-        // orthographic instead of perspective:
-        // build_directional_light_camera_view(
-        //     Vec3::new(-0.5, -0.5, 0.1).normalize(),
-        //     100.0,
-        //     100.0,
-        //     100.0,
-        // )
-        // .into()
     }
 }
 
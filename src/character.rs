@@ -1,17 +1,26 @@
+use crate::behaviour::*;
 use crate::game::*;
+use crate::handle::*;
 use crate::mesh::*;
 use crate::physics::*;
 use crate::renderer::*;
+use crate::revolver::*;
 use crate::scene::*;
 
+use std::any::Any;
+
 use glam::f32::Vec3;
 
+// toggles the display of the characters' collision boxes
+#[derive(Debug, Clone, Copy)]
+pub struct ToggleCollisionBoxDisplayMessage;
+
 pub struct Character {
     root_node_id: GameNodeId,
     skin_index: usize,
     collision_box_nodes: Vec<GameNodeId>,
     collision_box_colliders: Vec<ColliderHandle>,
-    collision_debug_mesh_index: usize,
+    collision_debug_mesh: Handle<BindedUnlitMesh>,
     is_displaying_collision_boxes: bool,
 }
 
@@ -25,21 +34,25 @@ impl Character {
         skin_index: usize,
         cube_mesh: &BasicMesh,
     ) -> Self {
-        let collision_debug_mesh_index =
+        let collision_debug_mesh =
             Renderer::bind_basic_unlit_mesh(renderer_base, renderer_data, cube_mesh);
         let mut result = Self {
             root_node_id,
             skin_index,
             collision_box_nodes: vec![],
             collision_box_colliders: vec![],
-            collision_debug_mesh_index,
+            collision_debug_mesh,
             is_displaying_collision_boxes: false,
         };
-        result.update(scene, physics_state);
+        result.update_collision_boxes(scene, physics_state);
         result
     }
 
-    pub fn update(&mut self, scene: &mut Scene, physics_state: &mut PhysicsState) {
+    pub fn root_node_id(&self) -> GameNodeId {
+        self.root_node_id
+    }
+
+    fn update_collision_boxes(&mut self, scene: &mut Scene, physics_state: &mut PhysicsState) {
         let root_node_global_transform: crate::transform::Transform =
             scene.get_global_transform_for_node(self.root_node_id);
         let should_fill_collision_boxes = self.collision_box_colliders.is_empty();
@@ -130,15 +143,18 @@ impl Character {
                         .mesh
                         .as_mut()
                         .map(|mesh| {
-                            if mesh.mesh_indices.contains(&self.collision_debug_mesh_index) {
+                            if mesh
+                                .mesh_indices
+                                .contains(&self.collision_debug_mesh.index())
+                            {
                                 mesh.mesh_indices.clone()
                             } else {
                                 let mut res = mesh.mesh_indices.clone();
-                                res.push(self.collision_debug_mesh_index);
+                                res.push(self.collision_debug_mesh.index());
                                 res
                             }
                         })
-                        .unwrap_or_else(|| vec![self.collision_debug_mesh_index]),
+                        .unwrap_or_else(|| vec![self.collision_debug_mesh.index()]),
                     mesh_type: GameNodeMeshType::Unlit {
                         color: Vec3::new(1.0, 0.0, 0.0),
                     },
@@ -157,15 +173,18 @@ impl Character {
                         .mesh
                         .as_mut()
                         .map(|mesh| {
-                            if mesh.mesh_indices.contains(&self.collision_debug_mesh_index) {
+                            if mesh
+                                .mesh_indices
+                                .contains(&self.collision_debug_mesh.index())
+                            {
                                 mesh.mesh_indices.clone()
                             } else {
                                 let mut res = mesh.mesh_indices.clone();
-                                res.push(self.collision_debug_mesh_index);
+                                res.push(self.collision_debug_mesh.index());
                                 res
                             }
                         })
-                        .unwrap_or_else(|| vec![self.collision_debug_mesh_index]),
+                        .unwrap_or_else(|| vec![self.collision_debug_mesh.index()]),
                     mesh_type: GameNodeMeshType::Unlit {
                         color: Vec3::new(rand::random(), rand::random(), rand::random()),
                     },
@@ -194,3 +213,32 @@ impl Character {
         }
     }
 }
+
+impl Behaviour for Character {
+    fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+        self.update_collision_boxes(ctx.scene, ctx.physics_state);
+    }
+
+    fn on_message(&mut self, message: &dyn Any, ctx: &mut BehaviourContext) {
+        if let Some(ColliderHitMessage { collider_handle }) = message.downcast_ref() {
+            self.handle_hit(ctx.scene, *collider_handle);
+        }
+        if message.is::<ToggleCollisionBoxDisplayMessage>() {
+            self.toggle_collision_box_display(ctx.scene);
+        }
+    }
+
+    fn on_destroy(&mut self, ctx: &mut BehaviourContext) {
+        for node_id in self.collision_box_nodes.drain(..) {
+            ctx.scene.remove_node(node_id);
+        }
+        for collider_handle in self.collision_box_colliders.drain(..) {
+            ctx.physics_state.collider_set.remove(
+                collider_handle,
+                &mut ctx.physics_state.island_manager,
+                &mut ctx.physics_state.rigid_body_set,
+                false,
+            );
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use crate::scene::*;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+// data of any type that's attached to the nodes of a scene, at most one component of each type per
+// node. the scene removes the components of the nodes that it removes
+#[derive(Default)]
+pub struct ComponentStore {
+    storages: HashMap<TypeId, Box<dyn ComponentStorage>>,
+}
+
+type TypedComponentStorage<T> = HashMap<GameNodeId, T>;
+
+trait ComponentStorage: Send {
+    fn remove_node(&mut self, node_id: GameNodeId);
+    fn convert_node_ids(&mut self, convert_node_id: &dyn Fn(GameNodeId) -> GameNodeId);
+    // other must hold the same type of component
+    fn append(&mut self, other: Box<dyn ComponentStorage>);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Send + 'static> ComponentStorage for TypedComponentStorage<T> {
+    fn remove_node(&mut self, node_id: GameNodeId) {
+        self.remove(&node_id);
+    }
+
+    fn convert_node_ids(&mut self, convert_node_id: &dyn Fn(GameNodeId) -> GameNodeId) {
+        *self = std::mem::take(self)
+            .into_iter()
+            .map(|(node_id, component)| (convert_node_id(node_id), component))
+            .collect();
+    }
+
+    fn append(&mut self, other: Box<dyn ComponentStorage>) {
+        let other = other
+            .into_any()
+            .downcast::<TypedComponentStorage<T>>()
+            .unwrap();
+        self.extend(*other);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl ComponentStore {
+    fn get_storage<T: Send + 'static>(&self) -> Option<&TypedComponentStorage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .map(|storage| storage.as_any().downcast_ref().unwrap())
+    }
+
+    fn get_storage_mut<T: Send + 'static>(&mut self) -> Option<&mut TypedComponentStorage<T>> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .map(|storage| storage.as_any_mut().downcast_mut().unwrap())
+    }
+
+    // returns the component of the same type that the node had before
+    pub fn insert<T: Send + 'static>(&mut self, node_id: GameNodeId, component: T) -> Option<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::<TypedComponentStorage<T>>::default())
+            .as_any_mut()
+            .downcast_mut::<TypedComponentStorage<T>>()
+            .unwrap()
+            .insert(node_id, component)
+    }
+
+    pub fn get<T: Send + 'static>(&self, node_id: GameNodeId) -> Option<&T> {
+        self.get_storage().and_then(|storage| storage.get(&node_id))
+    }
+
+    pub fn get_mut<T: Send + 'static>(&mut self, node_id: GameNodeId) -> Option<&mut T> {
+        self.get_storage_mut()
+            .and_then(|storage| storage.get_mut(&node_id))
+    }
+
+    pub fn contains<T: Send + 'static>(&self, node_id: GameNodeId) -> bool {
+        self.get::<T>(node_id).is_some()
+    }
+
+    pub fn remove<T: Send + 'static>(&mut self, node_id: GameNodeId) -> Option<T> {
+        self.get_storage_mut()
+            .and_then(|storage| storage.remove(&node_id))
+    }
+
+    // removes all the components of the node
+    pub fn remove_node(&mut self, node_id: GameNodeId) {
+        for storage in self.storages.values_mut() {
+            storage.remove_node(node_id);
+        }
+    }
+
+    pub fn count<T: Send + 'static>(&self) -> usize {
+        self.get_storage::<T>()
+            .map(|storage| storage.len())
+            .unwrap_or_default()
+    }
+
+    // in no particular order
+    pub fn iter<T: Send + 'static>(&self) -> impl Iterator<Item = (GameNodeId, &T)> {
+        self.get_storage::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.iter())
+            .map(|(node_id, component)| (*node_id, component))
+    }
+
+    pub fn iter_mut<T: Send + 'static>(&mut self) -> impl Iterator<Item = (GameNodeId, &mut T)> {
+        self.get_storage_mut::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.iter_mut())
+            .map(|(node_id, component)| (*node_id, component))
+    }
+
+    // the nodes that have both types of components
+    pub fn iter2<A: Send + 'static, B: Send + 'static>(
+        &self,
+    ) -> impl Iterator<Item = (GameNodeId, &A, &B)> {
+        self.iter::<A>()
+            .filter_map(|(node_id, a)| self.get::<B>(node_id).map(|b| (node_id, a, b)))
+    }
+
+    // calls f on the nodes that have both types of components, which must be different
+    pub fn for_each2_mut<A: Send + 'static, B: Send + 'static>(
+        &mut self,
+        mut f: impl FnMut(GameNodeId, &mut A, &mut B),
+    ) {
+        assert_ne!(
+            TypeId::of::<A>(),
+            TypeId::of::<B>(),
+            "Can't borrow the same type of component twice"
+        );
+        // the other storage is taken out so both can be borrowed mutably
+        let Some(mut b_storage) = self.storages.remove(&TypeId::of::<B>()) else {
+            return;
+        };
+        let typed_b_storage = b_storage
+            .as_any_mut()
+            .downcast_mut::<TypedComponentStorage<B>>()
+            .unwrap();
+        for (node_id, a) in self.iter_mut::<A>() {
+            if let Some(b) = typed_b_storage.get_mut(&node_id) {
+                f(node_id, a, b);
+            }
+        }
+        self.storages.insert(TypeId::of::<B>(), b_storage);
+    }
+
+    // moves the components of another scene's store over, changing their node ids to the ones they
+    // got in this scene
+    pub fn append(
+        &mut self,
+        other: ComponentStore,
+        convert_node_id: impl Fn(GameNodeId) -> GameNodeId,
+    ) {
+        for (type_id, mut other_storage) in other.storages {
+            other_storage.convert_node_ids(&convert_node_id);
+            match self.storages.get_mut(&type_id) {
+                Some(storage) => storage.append(other_storage),
+                None => {
+                    self.storages.insert(type_id, other_storage);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ComponentStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentStore")
+            .field("component_type_count", &self.storages.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f32);
+
+    #[test]
+    fn components_follow_their_nodes() {
+        let mut scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        let player_id = scene.add_node(GameNodeDesc::default()).id();
+        let ball_id = scene.add_node(GameNodeDesc::default()).id();
+        let wall_id = scene.add_node(GameNodeDesc::default()).id();
+
+        scene.components.insert(player_id, Health(100));
+        scene.components.insert(player_id, Velocity(1.0));
+        scene.components.insert(ball_id, Velocity(5.0));
+        assert_eq!(
+            scene.components.insert(player_id, Health(90)),
+            Some(Health(100))
+        );
+        assert_eq!(scene.components.get::<Health>(wall_id), None);
+        assert_eq!(scene.components.count::<Velocity>(), 2);
+
+        let with_both: Vec<_> = scene.components.iter2::<Health, Velocity>().collect();
+        assert_eq!(with_both, vec![(player_id, &Health(90), &Velocity(1.0))]);
+        scene
+            .components
+            .for_each2_mut::<Velocity, Health>(|_, velocity, health| {
+                velocity.0 *= 2.0;
+                health.0 -= 10;
+            });
+        assert_eq!(
+            scene.components.get::<Velocity>(player_id),
+            Some(&Velocity(2.0))
+        );
+        assert_eq!(
+            scene.components.get::<Velocity>(ball_id),
+            Some(&Velocity(5.0))
+        );
+        assert_eq!(
+            scene.components.remove::<Health>(player_id),
+            Some(Health(80))
+        );
+        assert!(!scene.components.contains::<Health>(player_id));
+
+        scene.remove_node(ball_id);
+        assert_eq!(scene.components.count::<Velocity>(), 1);
+        let new_node_id = scene.add_node(GameNodeDesc::default()).id();
+        assert_eq!(scene.components.get::<Velocity>(new_node_id), None);
+
+        let mut other_scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        let other_node_id = other_scene.add_node(GameNodeDesc::default()).id();
+        other_scene.components.insert(other_node_id, Velocity(3.0));
+        scene.components.append(other_scene.components, |_| wall_id);
+        assert_eq!(
+            scene.components.get::<Velocity>(wall_id),
+            Some(&Velocity(3.0))
+        );
+    }
+}
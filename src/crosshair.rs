@@ -0,0 +1,52 @@
+use crate::behaviour::*;
+use crate::math::*;
+use crate::scene::*;
+use crate::transform::*;
+
+use std::any::Any;
+
+use glam::f32::Vec3;
+
+// sent when the window is resized, with its new size in pixels
+#[derive(Debug, Clone, Copy)]
+pub struct WindowResizedMessage {
+    pub size: winit::dpi::PhysicalSize<u32>,
+}
+
+// keeps its node in front of the player, at the same size on screen whatever the window height
+pub struct Crosshair {
+    node_id: GameNodeId,
+    window_height: u32,
+}
+
+impl Crosshair {
+    pub fn new(node_id: GameNodeId, window_height: u32) -> Self {
+        Self {
+            node_id,
+            window_height,
+        }
+    }
+}
+
+impl Behaviour for Crosshair {
+    fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+        let player_transform = ctx.player_controller.transform(ctx.physics_state);
+        if let Some(node) = ctx.scene.get_node_mut(self.node_id) {
+            node.transform = player_transform
+                * TransformBuilder::new()
+                    .position(Vec3::new(0.0, 0.0, -1.0))
+                    .rotation(make_quat_from_axis_angle(
+                        Vec3::new(0.0, 1.0, 0.0),
+                        deg_to_rad(90.0),
+                    ))
+                    .scale((1080.0 / self.window_height as f32) * 0.06 * Vec3::new(1.0, 1.0, 1.0))
+                    .build();
+        }
+    }
+
+    fn on_message(&mut self, message: &dyn Any, _ctx: &mut BehaviourContext) {
+        if let Some(WindowResizedMessage { size }) = message.downcast_ref() {
+            self.window_height = size.height;
+        }
+    }
+}
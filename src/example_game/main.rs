@@ -33,6 +33,8 @@ async fn start() {
     };
 
     let run_result = async {
+        mount_assets()?;
+
         let game_scene = Scene::default();
 
         let mut renderer = Renderer::new(base_render_state, &window).await?;
@@ -2,8 +2,11 @@ use crate::animation::*;
 use crate::asset_loader::*;
 use crate::audio::*;
 use crate::ball::*;
+use crate::behaviour::*;
 use crate::character::*;
+use crate::crosshair::*;
 use crate::game_state::*;
+use crate::level::*;
 use crate::light::*;
 use crate::logger::*;
 use crate::math::*;
@@ -18,9 +21,11 @@ use crate::scene::*;
 use crate::texture::*;
 use crate::texture_compression::*;
 use crate::transform::*;
+use crate::vfs::*;
+use crate::worker_pool::*;
 
 use std::{
-    collections::hash_map::Entry,
+    collections::{hash_map::Entry, HashMap},
     sync::{Arc, Mutex},
 };
 
@@ -33,7 +38,17 @@ pub const INITIAL_RENDER_SCALE: f32 = 1.0;
 pub const INITIAL_TONE_MAPPING_EXPOSURE: f32 = 1.0;
 pub const INITIAL_BLOOM_THRESHOLD: f32 = 0.8;
 pub const INITIAL_BLOOM_RAMP_SIZE: f32 = 0.2;
+pub const INITIAL_POINT_SIZE: f32 = 4.0;
 pub const ARENA_SIDE_LENGTH: f32 = 500.0;
+pub const LEVEL_PATH: &str = "assets/levels/forest.json";
+pub const REVOLVER_GLTF_PATH: &str = "assets/models/gltf/ColtPython/colt_python.gltf";
+pub const BGM_SOUND_PATH: &str = "assets/sounds/bgm.mp3";
+pub const GUNSHOT_SOUND_PATH: &str = "assets/sounds/gunshot.wav";
+// all the assets are loaded through this mount point so the asset pack can replace the loose files
+pub const ASSETS_MOUNT_POINT: &str = "assets";
+pub const ASSETS_FOLDER: &str = "./src";
+// built by asset_packer from ASSETS_FOLDER, the loose files are used if it doesn't exist
+pub const ASSET_PACK_PATH: &str = "./assets.pak";
 // pub const LIGHT_COLOR_A: Vec3 = Vec3::new(0.996, 0.973, 0.663);
 // pub const LIGHT_COLOR_B: Vec3 = Vec3::new(0.25, 0.973, 0.663);
 
@@ -54,12 +69,12 @@ pub fn get_skybox_path() -> (
     // src: https://github.com/JoeyDeVries/LearnOpenGL/tree/master/resources/textures/skybox
     let _skybox_background = SkyboxBackground::Cube {
         face_image_paths: [
-            "./src/textures/skybox/right.jpg",
-            "./src/textures/skybox/left.jpg",
-            "./src/textures/skybox/top.jpg",
-            "./src/textures/skybox/bottom.jpg",
-            "./src/textures/skybox/front.jpg",
-            "./src/textures/skybox/back.jpg",
+            "assets/textures/skybox/right.jpg",
+            "assets/textures/skybox/left.jpg",
+            "assets/textures/skybox/top.jpg",
+            "assets/textures/skybox/bottom.jpg",
+            "assets/textures/skybox/front.jpg",
+            "assets/textures/skybox/back.jpg",
         ],
     };
     let _skybox_hdr_environment: Option<SkyboxHDREnvironment> = None;
@@ -67,27 +82,27 @@ pub fn get_skybox_path() -> (
     // Newport Loft
     // src: http://www.hdrlabs.com/sibl/archive/
     let _skybox_background = SkyboxBackground::Equirectangular {
-        image_path: "./src/textures/newport_loft/background.jpg",
+        image_path: "assets/textures/newport_loft/background.jpg",
     };
     let _skybox_hdr_environment: Option<SkyboxHDREnvironment> =
         Some(SkyboxHDREnvironment::Equirectangular {
-            image_path: "./src/textures/newport_loft/radiance.hdr",
+            image_path: "assets/textures/newport_loft/radiance.hdr",
         });
 
     // Milkyway
     // src: http://www.hdrlabs.com/sibl/archive/
     let _skybox_background = SkyboxBackground::Equirectangular {
-        image_path: "./src/textures/milkyway/background.jpg",
+        image_path: "assets/textures/milkyway/background.jpg",
     };
     let _skybox_hdr_environment: Option<SkyboxHDREnvironment> =
         Some(SkyboxHDREnvironment::Equirectangular {
-            image_path: "./src/textures/milkyway/radiance.hdr",
+            image_path: "assets/textures/milkyway/radiance.hdr",
         });
 
     // My photosphere pic
     // src: me
     let skybox_background = SkyboxBackground::Equirectangular {
-        image_path: "./src/textures/photosphere_skybox_small.jpg",
+        image_path: "assets/textures/photosphere_skybox_small.jpg",
     };
     let skybox_hdr_environment: Option<SkyboxHDREnvironment> = None;
 
@@ -96,18 +111,18 @@ pub fn get_skybox_path() -> (
 
 fn get_misc_gltf_path() -> &'static str {
     // "/home/david/Downloads/adamHead/adamHead.gltf"
-    // "./src/models/gltf/free_low_poly_forest/scene.gltf"
-    // "./src/models/gltf/TextureCoordinateTest/TextureCoordinateTest.gltf"
-    // "./src/models/gltf/SimpleMeshes/SimpleMeshes.gltf"
-    // "./src/models/gltf/Triangle/Triangle.gltf"
-    // "./src/models/gltf/TriangleWithoutIndices/TriangleWithoutIndices.gltf"
-    // "./src/models/gltf/EnvironmentTest/EnvironmentTest.gltf"
-    // "./src/models/gltf/Arrow/Arrow.gltf"
-    "./src/models/gltf/DamagedHelmet/DamagedHelmet.gltf"
-    // "./src/models/gltf/VertexColorTest/VertexColorTest.gltf"
-    // "./src/models/gltf/Revolver/revolver_low_poly.gltf"
-    // "./src/models/gltf/NormalTangentMirrorTest/NormalTangentMirrorTest.gltf"
-    // "./src/models/gltf/TextureLinearInterpolationTest/TextureLinearInterpolationTest.glb"
+    // "assets/models/gltf/free_low_poly_forest/scene.gltf"
+    // "assets/models/gltf/TextureCoordinateTest/TextureCoordinateTest.gltf"
+    // "assets/models/gltf/SimpleMeshes/SimpleMeshes.gltf"
+    // "assets/models/gltf/Triangle/Triangle.gltf"
+    // "assets/models/gltf/TriangleWithoutIndices/TriangleWithoutIndices.gltf"
+    // "assets/models/gltf/EnvironmentTest/EnvironmentTest.gltf"
+    // "assets/models/gltf/Arrow/Arrow.gltf"
+    "assets/models/gltf/DamagedHelmet/DamagedHelmet.gltf"
+    // "assets/models/gltf/VertexColorTest/VertexColorTest.gltf"
+    // "assets/models/gltf/Revolver/revolver_low_poly.gltf"
+    // "assets/models/gltf/NormalTangentMirrorTest/NormalTangentMirrorTest.gltf"
+    // "assets/models/gltf/TextureLinearInterpolationTest/TextureLinearInterpolationTest.glb"
     // "../glTF-Sample-Models/2.0/RiggedFigure/glTF/RiggedFigure.gltf"
     // "../glTF-Sample-Models/2.0/RiggedSimple/glTF/RiggedSimple.gltf"
     // "../glTF-Sample-Models/2.0/CesiumMan/glTF/CesiumMan.gltf"
@@ -116,9 +131,46 @@ fn get_misc_gltf_path() -> &'static str {
     // "../glTF-Sample-Models/2.0/BrainStem/glTF/BrainStem.gltf"
     // "/home/david/Programming/glTF-Sample-Models/2.0/BoxAnimated/glTF/BoxAnimated.gltf"
     // "/home/david/Programming/glTF-Sample-Models/2.0/Lantern/glTF/Lantern.gltf"
-    // "./src/models/gltf/VC/VC.gltf"
+    // "assets/models/gltf/VC/VC.gltf"
     //  "../glTF-Sample-Models-master/2.0/InterpolationTest/glTF/InterpolationTest.gltf"
-    // "./src/models/gltf/Sponza/Sponza.gltf"
+    // "assets/models/gltf/Sponza/Sponza.gltf"
+}
+
+// the path is pushed to ready_gltf_paths once the gltf asset finished loading,
+// failures are already logged by the asset loader
+fn queue_gltf_asset_when_ready(
+    statuses: &AssetStatusTracker,
+    ready_gltf_paths: &Arc<Mutex<Vec<String>>>,
+    path: &str,
+) {
+    let ready_gltf_paths = ready_gltf_paths.clone();
+    let ready_path = path.to_string();
+    statuses.on_ready(path, move |result| {
+        if result.is_ok() {
+            ready_gltf_paths.lock().unwrap().push(ready_path);
+        }
+    });
+}
+
+// must be called before anything is loaded so all the assets come from the same place
+pub fn mount_assets() -> Result<()> {
+    VFS.write().unwrap().mount(
+        ASSETS_MOUNT_POINT,
+        VfsSource::Directory(ASSETS_FOLDER.into()),
+    );
+    if !std::path::Path::new(ASSET_PACK_PATH).try_exists()? {
+        return Ok(());
+    }
+    let pack_archive = PackArchive::open(ASSET_PACK_PATH)?;
+    logger_log(&format!(
+        "Mounted asset pack {} with {} files",
+        ASSET_PACK_PATH,
+        pack_archive.paths().count()
+    ));
+    VFS.write()
+        .unwrap()
+        .mount(ASSETS_MOUNT_POINT, VfsSource::Pack(pack_archive));
+    Ok(())
 }
 
 pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<GameState> {
@@ -148,6 +200,27 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         audio_manager_mutex.clone(),
     ));
 
+    // lets artists see their changes to the models without restarting
+    if cfg!(debug_assertions) {
+        asset_loader.enable_hot_reload();
+    }
+
+    let ready_gltf_paths = Arc::new(Mutex::new(Vec::new()));
+
+    // the revolver plays the gunshot sound so it's only added after the sound is done loading
+    {
+        let statuses = asset_loader.statuses.clone();
+        let ready_gltf_paths = ready_gltf_paths.clone();
+        asset_loader.on_ready(GUNSHOT_SOUND_PATH, move |_| {
+            queue_gltf_asset_when_ready(&statuses, &ready_gltf_paths, REVOLVER_GLTF_PATH);
+        });
+    }
+    queue_gltf_asset_when_ready(
+        &asset_loader.statuses,
+        &ready_gltf_paths,
+        get_misc_gltf_path(),
+    );
+
     let asset_loader_clone = asset_loader.clone();
 
     std::thread::spawn(move || {
@@ -155,50 +228,50 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         // load in gltf files
 
         // player's revolver
-        asset_loader.load_gltf_asset("./src/models/gltf/ColtPython/colt_python.gltf");
-        // forest
-        asset_loader.load_gltf_asset("./src/models/gltf/free_low_poly_forest/scene.gltf");
-        // legendary robot
+        asset_loader.load_gltf_asset(REVOLVER_GLTF_PATH, JobPriority::High);
+        // forest and legendary robot
         // https://www.cgtrader.com/free-3d-models/character/sci-fi-character/legendary-robot-free-low-poly-3d-model
-        asset_loader.load_gltf_asset("./src/models/gltf/LegendaryRobot/Legendary_Robot.gltf");
-        // maze
-        // asset_loader.load_gltf_asset("./src/models/gltf/TestLevel/test_level.gltf");
+        asset_loader.load_level(LEVEL_PATH, JobPriority::High);
         // other
-        asset_loader.load_gltf_asset(get_misc_gltf_path());
+        asset_loader.load_gltf_asset(get_misc_gltf_path(), JobPriority::Background);
 
         asset_loader.load_audio(
-            "./src/sounds/bgm.mp3",
+            BGM_SOUND_PATH,
             AudioFileFormat::Mp3,
             SoundParams {
                 initial_volume: 0.5,
                 fixed_volume: false,
                 spacial_params: None,
             },
+            JobPriority::Normal,
         );
         asset_loader.load_audio(
-            "./src/sounds/gunshot.wav",
+            GUNSHOT_SOUND_PATH,
             AudioFileFormat::Wav,
             SoundParams {
                 initial_volume: 0.75,
                 fixed_volume: true,
                 spacial_params: None,
             },
+            JobPriority::High,
         );
     });
 
-    let sphere_mesh = BasicMesh::new("./src/models/sphere.obj")?;
-    let plane_mesh = BasicMesh::new("./src/models/plane.obj")?;
-    let cube_mesh = BasicMesh::new("./src/models/cube.obj")?;
+    let sphere_mesh = BasicMesh::new("assets/models/sphere.obj")?;
+    let plane_mesh = BasicMesh::new("assets/models/plane.obj")?;
+    let cube_mesh = BasicMesh::new("assets/models/cube.obj")?;
 
     // add lights to the scene
     let directional_lights = vec![
         DirectionalLightComponent {
+            node_id: None,
             position: Vec3::new(1.0, 5.0, -10.0) * 10.0,
             direction: (-Vec3::new(1.0, 5.0, -10.0)).normalize(),
             color: DIRECTIONAL_LIGHT_COLOR_A,
             intensity: 1.0,
         },
         DirectionalLightComponent {
+            node_id: None,
             position: Vec3::new(-1.0, 10.0, 10.0) * 10.0,
             direction: (-Vec3::new(-1.0, 10.0, 10.0)).normalize(),
             color: DIRECTIONAL_LIGHT_COLOR_B,
@@ -227,9 +300,10 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
     ];
     // let point_lights: Vec<(crate::transform::Transform, Vec3, f32)> = vec![];
 
+    // the nodes keep the mesh alive
     let point_light_unlit_mesh_index =
-        Renderer::bind_basic_unlit_mesh(&renderer.base, &mut renderer_data_guard, &sphere_mesh);
-    let mut point_light_node_ids: Vec<GameNodeId> = Vec::new();
+        Renderer::bind_basic_unlit_mesh(&renderer.base, &mut renderer_data_guard, &sphere_mesh)
+            .index();
     let mut point_light_components: Vec<PointLightComponent> = Vec::new();
     for (transform, color, intensity) in point_lights {
         let node_id = scene
@@ -246,7 +320,7 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
                     .build(),
             )
             .id();
-        point_light_node_ids.push(node_id);
+        scene.components.insert(node_id, PointLightMeshComponent);
         point_light_components.push(PointLightComponent {
             node_id,
             color: POINT_LIGHT_COLOR,
@@ -254,8 +328,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         });
     }
 
-    // let simple_normal_map_path = "./src/textures/simple_normal_map.jpg";
-    // let simple_normal_map_bytes = std::fs::read(simple_normal_map_path)?;
+    // let simple_normal_map_path = "assets/textures/simple_normal_map.jpg";
+    // let simple_normal_map_bytes = vfs_read(simple_normal_map_path)?;
     // let simple_normal_map = Texture::from_encoded_image(
     //     &renderer.base.device,
     //     &renderer.base.queue,
@@ -266,27 +340,44 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
     //     &Default::default(),
     // )?;
 
-    let texture_compressor = TextureCompressor::new();
-    let rainbow_texture_path = "./src/textures/rainbow_gradient_vertical_compressed.bin";
-    let rainbow_texture_bytes = std::fs::read(rainbow_texture_path)?;
-    let rainbow_texture_decompressed =
-        texture_compressor.transcode_image(&rainbow_texture_bytes, false)?;
-    let rainbow_texture = Texture::from_decoded_image(
-        &renderer.base,
-        &rainbow_texture_decompressed.raw,
-        (
-            rainbow_texture_decompressed.width,
-            rainbow_texture_decompressed.height,
-        ),
-        rainbow_texture_decompressed.mip_count,
-        Some(rainbow_texture_path),
-        Some(wgpu::TextureFormat::Bc7RgbaUnormSrgb),
-        false,
-        &Default::default(),
-    )?;
+    let rainbow_texture_path = "assets/textures/rainbow_gradient_vertical.jpg";
+    let rainbow_texture = match find_compressed_texture_path(&texture_path_to_compressed_path(
+        std::path::Path::new(rainbow_texture_path),
+    ))? {
+        Some(rainbow_compressed_texture_path) => {
+            let rainbow_texture_bytes = vfs_read(&rainbow_compressed_texture_path)?;
+            let rainbow_texture_decompressed = TextureCompressor::new().transcode_image(
+                &rainbow_texture_bytes,
+                false,
+                TranscodeTarget::from_features(renderer.base.device.features()),
+            )?;
+            Texture::from_decoded_image(
+                &renderer.base,
+                &rainbow_texture_decompressed.raw,
+                (
+                    rainbow_texture_decompressed.width,
+                    rainbow_texture_decompressed.height,
+                ),
+                rainbow_texture_decompressed.mip_count,
+                rainbow_compressed_texture_path.to_str(),
+                Some(rainbow_texture_decompressed.format.get_wgpu_format(true)),
+                false,
+                &Default::default(),
+            )?
+        }
+        // the asset optimizer hasn't been run
+        None => Texture::from_encoded_image(
+            &renderer.base,
+            &vfs_read(rainbow_texture_path)?,
+            rainbow_texture_path,
+            None,
+            true,
+            &Default::default(),
+        )?,
+    };
 
-    let brick_normal_map_path = "./src/textures/brick_normal_map.jpg";
-    let brick_normal_map_bytes = std::fs::read(brick_normal_map_path)?;
+    let brick_normal_map_path = "assets/textures/brick_normal_map.jpg";
+    let brick_normal_map_bytes = vfs_read(brick_normal_map_path)?;
     let brick_normal_map = Texture::from_encoded_image(
         &renderer.base,
         &brick_normal_map_bytes,
@@ -297,8 +388,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
     )?;
 
     // add test object to scene
-    /* let earth_texture_path = "./src/textures/8k_earth.jpg";
-    let earth_texture_bytes = std::fs::read(earth_texture_path)?;
+    /* let earth_texture_path = "assets/textures/8k_earth.jpg";
+    let earth_texture_bytes = vfs_read(earth_texture_path)?;
     let earth_texture = Texture::from_encoded_image(
         &renderer.base,
         &earth_texture_bytes,
@@ -316,8 +407,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         },
     )?; */
 
-    /* let earth_normal_map_path = "./src/textures/8k_earth_normal_map.jpg";
-    let earth_normal_map_bytes = std::fs::read(earth_normal_map_path)?;
+    /* let earth_normal_map_path = "assets/textures/8k_earth_normal_map.jpg";
+    let earth_normal_map_bytes = vfs_read(earth_normal_map_path)?;
     let earth_normal_map = Texture::from_encoded_image(
         &renderer.base,
         &earth_normal_map_bytes,
@@ -377,8 +468,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
     // add balls to scene
 
     // source: https://www.solarsystemscope.com/textures/
-    /* let mars_texture_path = "./src/textures/8k_mars.jpg";
-    let mars_texture_bytes = std::fs::read(mars_texture_path)?;
+    /* let mars_texture_path = "assets/textures/8k_mars.jpg";
+    let mars_texture_bytes = vfs_read(mars_texture_path)?;
     let mars_texture = Texture::from_encoded_image(
         &renderer.base,
         &mars_texture_bytes,
@@ -417,7 +508,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
             ..Default::default()
         },
         Default::default(),
-    )?;
+    )?
+    .index();
     let test_object_node_id = scene
         .add_node(
             GameNodeDescBuilder::new()
@@ -440,7 +532,7 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
     let ball_count = 0;
     let balls: Vec<_> = (0..ball_count).map(|_| BallComponent::rand()).collect();
 
-    let ball_pbr_mesh_index = Renderer::bind_basic_pbr_mesh(
+    let ball_pbr_mesh = Renderer::bind_basic_pbr_mesh(
         &renderer.base,
         &mut renderer_data_guard,
         &sphere_mesh,
@@ -451,27 +543,34 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         Default::default(),
     )?;
 
-    let mut ball_node_ids: Vec<GameNodeId> = Vec::new();
-    for ball in &balls {
-        let node = scene.add_node(
-            GameNodeDescBuilder::new()
-                .mesh(Some(GameNodeMesh::from_pbr_mesh_index(ball_pbr_mesh_index)))
-                .transform(ball.transform)
-                .build(),
-        );
-        ball_node_ids.push(node.id());
+    let mut behaviours = BehaviourManager::new();
+
+    for ball in balls {
+        let node_id = scene
+            .add_node(
+                GameNodeDescBuilder::new()
+                    .mesh(Some(GameNodeMesh::from_pbr_mesh_index(
+                        ball_pbr_mesh.index(),
+                    )))
+                    .transform(ball.transform)
+                    .build(),
+            )
+            .id();
+        scene
+            .components
+            .insert(node_id, InterpolatedBallComponent::new(ball));
+        behaviours.add(node_id, InterpolatedBall::new(node_id));
     }
 
     let physics_ball_count = 500;
-    let physics_balls: Vec<_> = (0..physics_ball_count)
-        .map(|_| {
-            PhysicsBall::new_random(
-                &mut scene,
-                &mut physics_state,
-                GameNodeMesh::from_pbr_mesh_index(ball_pbr_mesh_index),
-            )
-        })
-        .collect();
+    for _ in 0..physics_ball_count {
+        let physics_ball = PhysicsBall::new_random(
+            &mut scene,
+            &mut physics_state,
+            GameNodeMesh::from_pbr_mesh_index(ball_pbr_mesh.index()),
+        );
+        behaviours.add(physics_ball.node_id(), physics_ball);
+    }
 
     // let box_pbr_mesh_index = renderer.bind_basic_pbr_mesh(
     //     &cube_mesh,
@@ -506,7 +605,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
             ..Default::default()
         },
         Default::default(),
-    )?;
+    )?
+    .index();
     let floor_transform = TransformBuilder::new()
         .position(Vec3::new(0.0, -0.01, 0.0))
         .scale(Vec3::new(ARENA_SIDE_LENGTH, 1.0, ARENA_SIDE_LENGTH))
@@ -549,7 +649,8 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
                 ..Default::default()
             },
             Default::default(),
-        )?;
+        )?
+        .index();
         let bouncing_ball_radius = 0.5;
         let bouncing_ball_node = scene.add_node(
             GameNodeDescBuilder::new()
@@ -591,6 +692,10 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         );
         (bouncing_ball_node.id(), bouncing_ball_body_handle)
     };
+    behaviours.add(
+        bouncing_ball_node_id,
+        BouncingBall::new(bouncing_ball_node_id, bouncing_ball_body_handle),
+    );
     scene.remove_node(bouncing_ball_node_id);
 
     // add crosshair to scene
@@ -623,8 +728,6 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         }
         img
     };
-    #[allow(unused_assignments)]
-    let mut crosshair_node_id: Option<GameNodeId> = None;
     let crosshair_texture = Texture::from_decoded_image(
         &renderer.base,
         &crosshair_texture_img,
@@ -673,28 +776,34 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
             ..Default::default()
         },
         Default::default(),
-    )?;
+    )?
+    .index();
     let crosshair_color = Vec3::new(1.0, 0.0, 0.0);
-    crosshair_node_id = Some(
-        scene
-            .add_node(
-                GameNodeDescBuilder::new()
-                    .mesh(Some(GameNodeMesh {
-                        mesh_indices: vec![crosshair_mesh_index],
-                        mesh_type: GameNodeMeshType::Pbr {
-                            material_override: Some(DynamicPbrParams {
-                                emissive_factor: crosshair_color,
-                                base_color_factor: Vec4::new(0.0, 0.0, 0.0, 1.0),
-                                alpha_cutoff: 0.5,
-                                ..Default::default()
-                            }),
-                        },
-                        wireframe: false,
-                        ..Default::default()
-                    }))
-                    .build(),
-            )
-            .id(),
+    let crosshair_node_id = scene
+        .add_node(
+            GameNodeDescBuilder::new()
+                .mesh(Some(GameNodeMesh {
+                    mesh_indices: vec![crosshair_mesh_index],
+                    mesh_type: GameNodeMeshType::Pbr {
+                        material_override: Some(DynamicPbrParams {
+                            emissive_factor: crosshair_color,
+                            base_color_factor: Vec4::new(0.0, 0.0, 0.0, 1.0),
+                            alpha_cutoff: 0.5,
+                            ..Default::default()
+                        }),
+                    },
+                    wireframe: false,
+                    ..Default::default()
+                }))
+                .build(),
+        )
+        .id();
+    behaviours.add(
+        crosshair_node_id,
+        Crosshair::new(
+            crosshair_node_id,
+            renderer.base.window_size.lock().unwrap().height,
+        ),
     );
 
     // logger_log(&format!("{:?}", &revolver));
@@ -713,36 +822,28 @@ pub fn init_game_state(mut scene: Scene, renderer: &mut Renderer) -> Result<Game
         gunshot_sound_index: None,
         // gunshot_sound_data,
         player_node_id,
+        scene_camera_node_id: None,
 
         point_lights: point_light_components,
-        point_light_node_ids,
         directional_lights,
 
-        next_balls: balls.clone(),
-        prev_balls: balls.clone(),
-        actual_balls: balls,
-        ball_node_ids,
-        ball_pbr_mesh_index,
+        ball_pbr_mesh,
 
         ball_spawner_acc: 0.0,
 
         test_object_node_id,
-        crosshair_node_id,
-        revolver: None,
-
-        bouncing_ball_node_id,
-        bouncing_ball_body_handle,
-
         physics_state,
 
-        physics_balls,
-
-        character: None,
+        behaviours,
         player_controller,
 
         cube_mesh,
 
         asset_loader: asset_loader_clone,
+        gltf_scenes: HashMap::new(),
+        ready_gltf_paths,
+
+        level: None,
     })
 }
 
@@ -854,9 +955,46 @@ pub fn process_window_input(
                     render_data_guard.draw_node_bounding_spheres =
                         !render_data_guard.draw_node_bounding_spheres;
                 }
+                VirtualKeyCode::V => {
+                    // cycle through the cameras of the loaded scenes, then back to the player
+                    let cameras = &game_state.scene.cameras;
+                    let next_camera_index = match game_state.scene_camera_node_id {
+                        Some(node_id) => cameras
+                            .iter()
+                            .position(|camera| camera.node_id == node_id)
+                            .map(|camera_index| camera_index + 1),
+                        None => Some(0),
+                    };
+                    game_state.scene_camera_node_id = next_camera_index
+                        .and_then(|camera_index| cameras.get(camera_index))
+                        .map(|camera| camera.node_id);
+                }
                 VirtualKeyCode::C => {
-                    if let Some(character) = game_state.character.as_mut() {
-                        character.toggle_collision_box_display(&mut game_state.scene);
+                    game_state
+                        .behaviours
+                        .send_message(ToggleCollisionBoxDisplayMessage);
+                }
+                VirtualKeyCode::F9 => {
+                    // replaces the current level once it's loaded, see update_game_state
+                    game_state
+                        .asset_loader
+                        .load_level(LEVEL_PATH, JobPriority::High);
+                }
+                VirtualKeyCode::F5 => {
+                    if let Some(level) = game_state.level.as_ref() {
+                        let save_result = level
+                            .to_desc(
+                                &game_state.scene,
+                                &game_state.point_lights,
+                                &game_state.directional_lights,
+                            )
+                            .save(&level.path);
+                        match save_result {
+                            Ok(()) => logger_log(&format!("Saved level to {}", level.path)),
+                            Err(err) => {
+                                logger_log(&format!("Error saving level {}: {}", level.path, err))
+                            }
+                        }
                     }
                 }
                 _ => {}
@@ -874,28 +1012,111 @@ pub fn update_game_state(
     renderer_base: &BaseRenderer,
     renderer_data: Arc<Mutex<RendererPublicData>>,
 ) {
+    // failures are already logged by the asset loader
+    for event in game_state.asset_loader.take_events() {
+        if let AssetEvent::Loaded { path } = event {
+            logger_log(&format!("Loaded asset {}", path));
+        }
+    }
+
+    let loaded_level = game_state
+        .asset_loader
+        .loaded_levels
+        .lock()
+        .unwrap()
+        .remove(LEVEL_PATH);
+    if let Some(level_desc) = loaded_level {
+        // the level was loaded again, e.g. after editing it
+        if let Some(old_level) = game_state.level.take() {
+            old_level.unload(
+                &mut game_state.scene,
+                &renderer_data.lock().unwrap(),
+                &mut game_state.physics_state,
+                &mut game_state.point_lights,
+                &mut game_state.directional_lights,
+            );
+        }
+        let level = LevelState::new(
+            LEVEL_PATH.to_string(),
+            level_desc,
+            &mut game_state.scene,
+            &mut game_state.physics_state,
+            &mut game_state.point_lights,
+            &mut game_state.directional_lights,
+        );
+        for asset_path in level.asset_paths() {
+            game_state
+                .asset_loader
+                .load_gltf_asset(asset_path, JobPriority::Normal);
+            queue_gltf_asset_when_ready(
+                &game_state.asset_loader.statuses,
+                &game_state.ready_gltf_paths,
+                asset_path,
+            );
+        }
+        if let Some(spawn_point) = level.spawn_point("player") {
+            if let Some(rigid_body) = game_state
+                .physics_state
+                .rigid_body_set
+                .get_mut(game_state.player_controller.rigid_body_handle)
+            {
+                let [x, y, z] = spawn_point.position;
+                rigid_body.set_translation(vector![x, y, z], true);
+                rigid_body.set_linvel(vector![0.0, 0.0, 0.0], true);
+            }
+            game_state.player_controller.view_direction = ControlledViewDirection {
+                horizontal: deg_to_rad(spawn_point.yaw_degrees),
+                vertical: 0.0,
+            };
+        }
+        game_state.level = Some(level);
+    }
+
+    {
+        let mut loaded_audio_guard = game_state.asset_loader.loaded_audio.lock().unwrap();
+        let mut audio_manager_guard = game_state.audio_manager.lock().unwrap();
+
+        if let Entry::Occupied(entry) = loaded_audio_guard.entry(BGM_SOUND_PATH.to_string()) {
+            let (_, bgm_sound_index) = entry.remove_entry();
+            audio_manager_guard.play_sound(bgm_sound_index);
+            game_state.bgm_sound_index = Some(bgm_sound_index);
+            // logger_log("loaded bgm sound");
+        }
+
+        if let Entry::Occupied(entry) = loaded_audio_guard.entry(GUNSHOT_SOUND_PATH.to_string()) {
+            let (_, gunshot_sound_index) = entry.remove_entry();
+            game_state.gunshot_sound_index = Some(gunshot_sound_index);
+            // logger_log("loaded gunshot sound");
+            // audio_manager_guard.set_sound_volume(gunshot_sound_index, 0.001);
+        }
+    }
+
+    let ready_gltf_paths = std::mem::take(&mut *game_state.ready_gltf_paths.lock().unwrap());
     {
-        let mut loaded_assets_guard = game_state.asset_loader.loaded_gltf_scenes.lock().unwrap();
         let mut renderer_data_guard = renderer_data.lock().unwrap();
-        if game_state.gunshot_sound_index.is_some() {
-            if let Entry::Occupied(entry) = loaded_assets_guard
-                .entry("./src/models/gltf/ColtPython/colt_python.gltf".to_string())
-            {
-                let (_, (other_scene, other_render_buffers)) = entry.remove_entry();
-                game_state.scene.merge_scene(
+        for path in ready_gltf_paths {
+            if path == REVOLVER_GLTF_PATH {
+                let Some(gltf_scene) = game_state.asset_loader.merge_loaded_gltf_scene(
+                    REVOLVER_GLTF_PATH,
+                    &mut game_state.scene,
                     &mut renderer_data_guard,
-                    other_scene,
-                    other_render_buffers,
-                );
+                    |_| {},
+                ) else {
+                    continue;
+                };
+                game_state
+                    .gltf_scenes
+                    .insert(REVOLVER_GLTF_PATH.to_string(), gltf_scene);
 
-                let node_id = game_state.scene.nodes().last().unwrap().id();
-                let animation_index = game_state.scene.animations.len() - 1;
+                let node_id = game_state.scene.find_node_by_name("Colt Python").unwrap();
+                let animation_index = game_state.scene.find_animation("BAction").unwrap();
                 // revolver_indices = Some((revolver_model_node_id, animation_index));
-                game_state.revolver = Some(Revolver::new(
+                let revolver = Revolver::new(
                     &mut game_state.scene,
                     game_state.player_node_id,
                     node_id,
                     animation_index,
+                    game_state.gunshot_sound_index,
                     // revolver model
                     // TransformBuilder::new()
                     //     .position(Vec3::new(0.21, -0.09, -1.0))
@@ -914,133 +1135,89 @@ pub fn update_game_state(
                         )
                         .scale(2.0f32 * Vec3::new(1.0, 1.0, 1.0))
                         .build(),
-                ));
-            }
-        }
-
-        if let Entry::Occupied(entry) = loaded_assets_guard
-            .entry("./src/models/gltf/free_low_poly_forest/scene.gltf".to_string())
-        {
-            let (_, (mut other_scene, other_render_buffers)) = entry.remove_entry();
-            // hack to get the terrain to be at the same height as the ground.
-            let node_has_parent: Vec<_> = other_scene
-                .nodes()
-                .map(|node| node.parent_id.is_some())
-                .collect();
-            for (i, node) in other_scene.nodes_mut().enumerate() {
-                if node_has_parent[i] {
-                    continue;
+                );
+                game_state.behaviours.add(revolver.node_id, revolver);
+            } else if path == get_misc_gltf_path() {
+                if let Some(gltf_scene) = game_state.asset_loader.merge_loaded_gltf_scene(
+                    get_misc_gltf_path(),
+                    &mut game_state.scene,
+                    &mut renderer_data_guard,
+                    |other_scene| {
+                        for animation in other_scene.animations.iter_mut() {
+                            animation.state.is_playing = true;
+                            animation.state.loop_type = LoopType::Wrap;
+                        }
+                    },
+                ) {
+                    game_state
+                        .gltf_scenes
+                        .insert(get_misc_gltf_path().to_string(), gltf_scene);
                 }
-                node.transform
-                    .set_position(node.transform.position() + Vec3::new(0.0, 29.0, 0.0));
-            }
-            game_state.scene.merge_scene(
-                &mut renderer_data_guard,
-                other_scene,
-                other_render_buffers,
-            );
-        }
-
-        if let Entry::Occupied(entry) = loaded_assets_guard
-            .entry("./src/models/gltf/LegendaryRobot/Legendary_Robot.gltf".to_string())
-        {
-            let (_, (mut other_scene, other_render_buffers)) = entry.remove_entry();
-            if let Some(jump_up_animation) = other_scene
-                .animations
-                .iter_mut()
-                .find(|animation| animation.name == Some(String::from("jump_up_root_motion")))
-            {
-                jump_up_animation.speed = 0.25;
-                jump_up_animation.state.is_playing = true;
-                jump_up_animation.state.loop_type = LoopType::Wrap;
+            } else if let Some(level) = game_state.level.as_mut() {
+                level.instantiate_loaded_assets(
+                    &mut game_state.scene,
+                    &mut renderer_data_guard,
+                    &mut game_state.physics_state,
+                    &game_state.asset_loader,
+                );
             }
-            game_state.scene.merge_scene(
-                &mut renderer_data_guard,
-                other_scene,
-                other_render_buffers,
-            );
         }
 
-        if let Entry::Occupied(entry) =
-            loaded_assets_guard.entry("./src/models/gltf/TestLevel/test_level.gltf".to_string())
-        {
-            let (_, (other_scene, other_render_buffers)) = entry.remove_entry();
-            let skip_nodes = game_state.scene.node_count();
-            game_state.scene.merge_scene(
-                &mut renderer_data_guard,
-                other_scene,
-                other_render_buffers,
-            );
-
-            let test_level_node_ids: Vec<_> = game_state
-                .scene
-                .nodes()
-                .skip(skip_nodes)
-                .map(|node| node.id())
-                .collect();
-            for node_id in test_level_node_ids {
-                if let Some(_mesh) = game_state
-                    .scene
-                    .get_node_mut(node_id)
-                    .unwrap()
-                    .mesh
-                    .as_mut()
-                {
-                    // _mesh.wireframe = true;
-                }
-                game_state.physics_state.add_static_box(
-                    &game_state.scene,
+        let reloaded_paths = game_state.asset_loader.reload_changed_gltf_scenes(
+            &mut game_state.scene,
+            &mut renderer_data_guard,
+            |path, other_scene| {
+                game_state.gltf_scenes.get(path).cloned().or_else(|| {
+                    game_state
+                        .level
+                        .as_ref()?
+                        .prepare_reloaded_asset(path, other_scene)
+                })
+            },
+        );
+        if let Some(level) = game_state.level.as_mut() {
+            for path in reloaded_paths {
+                level.on_asset_reloaded(
+                    &mut game_state.scene,
                     &renderer_data_guard,
-                    node_id,
+                    &mut game_state.physics_state,
+                    &path,
                 );
             }
         }
 
-        if let Entry::Occupied(entry) = loaded_assets_guard.entry(get_misc_gltf_path().to_string())
-        {
-            let (_, (mut other_scene, other_render_buffers)) = entry.remove_entry();
-            for animation in other_scene.animations.iter_mut() {
-                animation.state.is_playing = true;
-                animation.state.loop_type = LoopType::Wrap;
+        // hand the lights that were authored in the loaded scenes over to the renderer
+        for point_light in game_state.scene.point_lights.drain(..) {
+            if game_state.point_lights.len() >= MAX_SHADOW_CASTING_POINT_LIGHT_COUNT as usize {
+                logger_log(&format!(
+                    "Warning: too many point lights in the scene, skipping light attached to node {:?}",
+                    point_light.node_id
+                ));
+                continue;
             }
-            game_state.scene.merge_scene(
-                &mut renderer_data_guard,
-                other_scene,
-                other_render_buffers,
-            );
-        }
-    }
-
-    {
-        let mut loaded_audio_guard = game_state.asset_loader.loaded_audio.lock().unwrap();
-        let mut audio_manager_guard = game_state.audio_manager.lock().unwrap();
-
-        if let Entry::Occupied(entry) = loaded_audio_guard.entry("./src/sounds/bgm.mp3".to_string())
-        {
-            let (_, bgm_sound_index) = entry.remove_entry();
-            audio_manager_guard.play_sound(bgm_sound_index);
-            game_state.bgm_sound_index = Some(bgm_sound_index);
-            // logger_log("loaded bgm sound");
+            game_state.point_lights.push(point_light);
         }
-
-        if let Entry::Occupied(entry) =
-            loaded_audio_guard.entry("./src/sounds/gunshot.wav".to_string())
-        {
-            let (_, gunshot_sound_index) = entry.remove_entry();
-            game_state.gunshot_sound_index = Some(gunshot_sound_index);
-            // logger_log("loaded gunshot sound");
-            // audio_manager_guard.set_sound_volume(gunshot_sound_index, 0.001);
+        for directional_light in game_state.scene.directional_lights.drain(..) {
+            if game_state.directional_lights.len()
+                >= MAX_SHADOW_CASTING_DIRECTIONAL_LIGHT_COUNT as usize
+            {
+                logger_log("Warning: too many directional lights in the scene, skipping light");
+                continue;
+            }
+            game_state.directional_lights.push(directional_light);
         }
     }
+    update_light_nodes(
+        &game_state.scene,
+        &mut game_state.point_lights,
+        &mut game_state.directional_lights,
+    );
 
-    if game_state.character.is_none() {
-        let legendary_robot_root_node_id = game_state
-            .scene
-            .nodes()
-            .find(|node| node.name == Some(String::from("robot")))
-            .map(|legendary_robot_root_node| legendary_robot_root_node.id());
-
-        game_state.character = legendary_robot_root_node_id.map(|legendary_robot_root_node_id| {
+    if let Some(legendary_robot_root_node_id) = game_state.scene.find_node_by_name("robot") {
+        if !game_state
+            .behaviours
+            .has_behaviour(legendary_robot_root_node_id)
+        {
             game_state
                 .scene
                 .get_node_mut(legendary_robot_root_node_id)
@@ -1050,7 +1227,7 @@ pub fn update_game_state(
 
             let legendary_robot_skin_index = 0;
 
-            Character::new(
+            let character = Character::new(
                 &mut game_state.scene,
                 &mut game_state.physics_state,
                 renderer_base,
@@ -1058,11 +1235,14 @@ pub fn update_game_state(
                 legendary_robot_root_node_id,
                 legendary_robot_skin_index,
                 &game_state.cube_mesh,
-            )
-        });
+            );
+            game_state
+                .behaviours
+                .add(legendary_robot_root_node_id, character);
+        }
     }
 
-    // "./src/models/gltf/free_low_poly_forest/scene.gltf"
+    // "assets/models/gltf/free_low_poly_forest/scene.gltf"
 
     let time_tracker = game_state.time();
     let global_time_seconds = time_tracker.global_time_seconds();
@@ -1095,34 +1275,17 @@ pub fn update_game_state(
         player_transform.transform = new_player_transform;
     }
 
-    // update ball positions
     while game_state.state_update_time_accumulator >= min_update_timestep_seconds {
-        if game_state.state_update_time_accumulator < min_update_timestep_seconds * 2.0 {
-            game_state.prev_balls = game_state.next_balls.clone();
-        }
-        game_state.prev_balls = game_state.next_balls.clone();
-        game_state
-            .next_balls
-            .iter_mut()
-            .for_each(|ball| ball.update(min_update_timestep_seconds));
+        game_state.behaviours.fixed_update(
+            min_update_timestep_seconds,
+            &mut game_state.scene,
+            &mut game_state.physics_state,
+            &game_state.audio_manager,
+            &game_state.player_controller,
+        );
         game_state.state_update_time_accumulator -= min_update_timestep_seconds;
     }
-    let alpha = game_state.state_update_time_accumulator / min_update_timestep_seconds;
-    game_state.actual_balls = game_state
-        .prev_balls
-        .iter()
-        .zip(game_state.next_balls.iter())
-        .map(|(prev_ball, next_ball)| prev_ball.lerp(next_ball, alpha))
-        .collect();
-    game_state
-        .ball_node_ids
-        .iter()
-        .zip(game_state.actual_balls.iter())
-        .for_each(|(node_id, ball)| {
-            if let Some(node) = game_state.scene.get_node_mut(*node_id) {
-                node.transform = ball.transform;
-            }
-        });
+    let fixed_update_alpha = game_state.state_update_time_accumulator / min_update_timestep_seconds;
 
     if let Some(point_light_0) = game_state.point_lights.get_mut(0) {
         // point_light_0.color = lerp_vec(
@@ -1148,22 +1311,25 @@ pub fn update_game_state(
     }
 
     // sync unlit mesh config with point light component
-    game_state
-        .point_light_node_ids
-        .iter()
-        .zip(game_state.point_lights.iter())
-        .for_each(|(node_id, point_light)| {
-            if let Some(GameNodeMesh {
-                mesh_type: GameNodeMeshType::Unlit { ref mut color },
-                ..
-            }) = game_state
-                .scene
-                .get_node_mut(*node_id)
-                .and_then(|node| node.mesh.as_mut())
-            {
-                *color = point_light.color * point_light.intensity;
-            }
-        });
+    for point_light in &game_state.point_lights {
+        if !game_state
+            .scene
+            .components
+            .contains::<PointLightMeshComponent>(point_light.node_id)
+        {
+            continue;
+        }
+        if let Some(GameNodeMesh {
+            mesh_type: GameNodeMeshType::Unlit { ref mut color },
+            ..
+        }) = game_state
+            .scene
+            .get_node_mut(point_light.node_id)
+            .and_then(|node| node.mesh.as_mut())
+        {
+            *color = point_light.color * point_light.intensity;
+        }
+    }
 
     let directional_light_0 = game_state
         .directional_lights
@@ -1206,7 +1372,6 @@ pub fn update_game_state(
     // remove physics balls over time
     game_state.ball_spawner_acc += frame_time_seconds;
     let rate = 0.1; // lower value spawns balls more quickly
-    let prev_ball_count = game_state.physics_balls.len();
     while game_state.ball_spawner_acc > rate {
         // let new_ball = BallComponent::rand();
         // let new_ball_transform = new_ball.transform;
@@ -1214,7 +1379,7 @@ pub fn update_game_state(
         // game_state.scene.nodes.push(
         //     GameNodeBuilder::new()
         //         .mesh(Some(GameNodeMesh::Pbr {
-        //             mesh_indices: vec![game_state.ball_pbr_mesh_index],
+        //             mesh_indices: vec![game_state.ball_pbr_mesh.index()],
         //             material_override: None,
         //         }))
         //         .transform(new_ball_transform)
@@ -1229,145 +1394,28 @@ pub fn update_game_state(
         // game_state.physics_balls.push(PhysicsBall::new_random(
         //     &mut game_state.scene,
         //     &mut game_state.physics_state,
-        //     GameNodeMesh::from_pbr_mesh_index(game_state.ball_pbr_mesh_index),
+        //     GameNodeMesh::from_pbr_mesh_index(game_state.ball_pbr_mesh.index()),
         // ));
         game_state.ball_spawner_acc -= rate;
     }
-    let new_ball_count = game_state.physics_balls.len();
-    if prev_ball_count != new_ball_count {
-        // logger_log(&format!("Ball count: {:?}", new_ball_count));
-    }
 
     // let physics_time_step_start = Instant::now();
 
     // logger_log(&format!("Physics step time: {:?}", physics_time_step_start.elapsed()));
-    let physics_state = &mut game_state.physics_state;
-    let ball_body = &physics_state.rigid_body_set[game_state.bouncing_ball_body_handle];
-    if let Some(node) = game_state
-        .scene
-        .get_node_mut(game_state.bouncing_ball_node_id)
-    {
-        node.transform.apply_isometry(*ball_body.position());
-    }
-
-    physics_state.integration_parameters.dt = frame_time_seconds;
-    game_state
-        .physics_balls
-        .iter()
-        .for_each(|physics_ball| physics_ball.update(&mut game_state.scene, physics_state));
-
-    if let Some(crosshair_node) = game_state
-        .crosshair_node_id
-        .and_then(|crosshair_node_id| game_state.scene.get_node_mut(crosshair_node_id))
-    {
-        crosshair_node.transform = new_player_transform
-            * TransformBuilder::new()
-                .position(Vec3::new(0.0, 0.0, -1.0))
-                .rotation(make_quat_from_axis_angle(
-                    Vec3::new(0.0, 1.0, 0.0),
-                    deg_to_rad(90.0),
-                ))
-                .scale(
-                    (1080.0 / renderer_base.window_size.lock().unwrap().height as f32)
-                        * 0.06
-                        * Vec3::new(1.0, 1.0, 1.0),
-                )
-                .build();
-    }
-
-    if let Some(revolver) = game_state.revolver.as_mut() {
-        revolver.update(
-            game_state.player_controller.view_direction,
-            &mut game_state.scene,
-        );
-
-        if game_state.player_controller.mouse_button_pressed && revolver.fire(&mut game_state.scene)
-        {
-            if let Some(gunshot_sound_index) = game_state.gunshot_sound_index {
-                {
-                    let mut audio_manager_guard = game_state.audio_manager.lock().unwrap();
-                    audio_manager_guard.play_sound(gunshot_sound_index);
-                    audio_manager_guard.reload_sound(
-                        gunshot_sound_index,
-                        SoundParams {
-                            initial_volume: 0.75,
-                            fixed_volume: true,
-                            spacial_params: None,
-                        },
-                    )
-                }
-            }
-
-            // logger_log("Fired!");
-            let player_position = game_state
-                .player_controller
-                .position(&game_state.physics_state);
-            let direction_vec = game_state
-                .player_controller
-                .view_direction
-                .to_direction_vector();
-            let ray = Ray::new(
-                point![player_position.x, player_position.y, player_position.z],
-                vector![direction_vec.x, direction_vec.y, direction_vec.z],
-            );
-            let max_distance = ARENA_SIDE_LENGTH * 10.0;
-            let solid = true;
-            if let Some((collider_handle, collision_point_distance)) =
-                game_state.physics_state.query_pipeline.cast_ray(
-                    &game_state.physics_state.rigid_body_set,
-                    &game_state.physics_state.collider_set,
-                    &ray,
-                    max_distance,
-                    solid,
-                    QueryFilter::from(
-                        InteractionGroups::all().with_filter(!COLLISION_GROUP_PLAYER_UNSHOOTABLE),
-                    ),
-                )
-            {
-                // The first collider hit has the handle `handle` and it hit after
-                // the ray travelled a distance equal to `ray.dir * toi`.
-                let _hit_point = ray.point_at(collision_point_distance); // Same as: `ray.origin + ray.dir * toi`
-
-                // logger_log(&format!(
-                //     "Collider {:?} hit at point {}",
-                //     collider_handle, _hit_point
-                // ));
-                if let Some(rigid_body_handle) = game_state
-                    .physics_state
-                    .collider_set
-                    .get(collider_handle)
-                    .unwrap()
-                    .parent()
-                {
-                    if let Some((ball_index, ball)) = game_state
-                        .physics_balls
-                        .iter()
-                        .enumerate()
-                        .find(|(_, ball)| ball.rigid_body_handle() == rigid_body_handle)
-                    {
-                        // logger_log(&format!(
-                        //     "Hit physics ball {:?} hit at point {}",
-                        //     ball_index, hit_point
-                        // ));
-                        // ball.toggle_wireframe(&mut game_state.scene);
-                        ball.destroy(&mut game_state.scene, &mut game_state.physics_state);
-                        game_state.physics_balls.remove(ball_index);
-                    }
-                }
-                if let Some(character) = game_state.character.as_mut() {
-                    character.handle_hit(&mut game_state.scene, collider_handle);
-                }
-            }
-        }
-    }
+    game_state.physics_state.integration_parameters.dt = frame_time_seconds;
+
+    game_state.behaviours.update(
+        frame_time_seconds,
+        fixed_update_alpha,
+        &mut game_state.scene,
+        &mut game_state.physics_state,
+        &game_state.audio_manager,
+        &game_state.player_controller,
+    );
 
     // step animatons
     let scene = &mut game_state.scene;
     if game_state.is_playing_animations {
         step_animations(scene, frame_time_seconds)
     }
-
-    if let Some(character) = game_state.character.as_mut() {
-        character.update(scene, &mut game_state.physics_state);
-    }
 }
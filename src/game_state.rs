@@ -1,16 +1,17 @@
 use crate::asset_loader::*;
 use crate::audio::*;
-use crate::ball::*;
-use crate::character::*;
+use crate::behaviour::*;
+use crate::handle::*;
+use crate::level::*;
 use crate::light::*;
 use crate::mesh::*;
 use crate::physics::*;
-use crate::physics_ball::*;
 use crate::player_controller::*;
-use crate::revolver::*;
+use crate::renderer::*;
 use crate::scene::*;
 use crate::time_tracker::*;
 
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 pub struct GameState {
@@ -25,37 +26,33 @@ pub struct GameState {
     pub gunshot_sound_index: Option<usize>,
     // pub gunshot_sound_data: SoundData,
     pub player_node_id: GameNodeId,
+    // the imported scene camera that is rendered from instead of the player's, if any
+    pub scene_camera_node_id: Option<GameNodeId>,
 
     pub point_lights: Vec<PointLightComponent>,
-    pub point_light_node_ids: Vec<GameNodeId>,
     pub directional_lights: Vec<DirectionalLightComponent>,
 
-    // store the previous state and next state and interpolate between them
-    pub next_balls: Vec<BallComponent>,
-    pub prev_balls: Vec<BallComponent>,
-    pub actual_balls: Vec<BallComponent>,
-    pub ball_node_ids: Vec<GameNodeId>,
-    pub ball_pbr_mesh_index: usize,
+    // the balls are the nodes with an InterpolatedBallComponent
+    pub ball_pbr_mesh: Handle<BindedPbrMesh>,
 
     pub ball_spawner_acc: f32,
 
     pub test_object_node_id: GameNodeId,
-    pub crosshair_node_id: Option<GameNodeId>,
-    pub revolver: Option<Revolver>,
-
-    pub bouncing_ball_node_id: GameNodeId,
-    pub bouncing_ball_body_handle: RigidBodyHandle,
 
     pub physics_state: PhysicsState,
 
-    pub physics_balls: Vec<PhysicsBall>,
-
-    pub character: Option<Character>,
+    pub behaviours: BehaviourManager,
     pub player_controller: PlayerController,
 
     pub cube_mesh: BasicMesh,
 
     pub asset_loader: Arc<AssetLoader>,
+    // assets that were added to the scene directly instead of through the level, by path
+    pub gltf_scenes: HashMap<String, Handle<GltfScene>>,
+    // gltf assets that finished loading, pushed from the asset loader's on_ready callbacks
+    pub ready_gltf_paths: Arc<Mutex<Vec<String>>>,
+
+    pub level: Option<LevelState>,
 }
 
 impl GameState {
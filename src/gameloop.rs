@@ -1,3 +1,4 @@
+use crate::crosshair::*;
 use crate::game::*;
 use crate::game_state::*;
 use crate::logger::*;
@@ -89,11 +90,17 @@ pub fn run(
                     WindowEvent::Resized(size) => {
                         if size.width > 0 && size.height > 0 {
                             renderer.resize(*size, window.scale_factor());
+                            game_state
+                                .behaviours
+                                .send_message(WindowResizedMessage { size: *size });
                         }
                     }
                     WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                         if new_inner_size.width > 0 && new_inner_size.height > 0 {
                             renderer.resize(**new_inner_size, window.scale_factor());
+                            game_state.behaviours.send_message(WindowResizedMessage {
+                                size: **new_inner_size,
+                            });
                         }
                     }
                     WindowEvent::CloseRequested
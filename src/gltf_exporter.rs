@@ -0,0 +1,886 @@
+use crate::logger::*;
+use crate::mesh::*;
+use crate::renderer::*;
+use crate::scene::*;
+
+use std::borrow::Cow;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use glam::f32::Vec3;
+use gltf::json;
+use gltf::json::validation::Checked::Valid;
+
+// the parts of a binded pbr mesh that are needed to export it
+pub struct ExportablePbrMesh<'a> {
+    pub geometry_source: &'a GeometrySource,
+    pub texture_sources: &'a PbrTextureSources,
+    pub dynamic_pbr_params: DynamicPbrParams,
+}
+
+impl<'a> ExportablePbrMesh<'a> {
+    // None if the mesh was loaded without its cpu-side copies
+    pub fn from_binded(binded_pbr_mesh: &'a BindedPbrMesh) -> Option<Self> {
+        binded_pbr_mesh.sources.as_ref().map(|sources| Self {
+            geometry_source: &sources.geometry,
+            texture_sources: &sources.textures,
+            dynamic_pbr_params: binded_pbr_mesh.dynamic_pbr_params,
+        })
+    }
+}
+
+/// Writes the scene to a .gltf or .glb file, depending on the extension of the path.
+/// A .gltf file gets a .bin file with the same name written next to it.
+///
+/// Only pbr meshes are exported since the unlit ones don't keep their data on the cpu. The pbr
+/// meshes are only kept on the cpu if the scene was loaded with AssetLoader::keep_mesh_sources,
+/// the nodes of the other ones are exported without a mesh.
+/// Material overrides, lights and cameras aren't exported yet.
+pub fn export_scene(scene: &Scene, binded_pbr_meshes: &[BindedPbrMesh], path: &Path) -> Result<()> {
+    let pbr_meshes: Vec<_> = binded_pbr_meshes
+        .iter()
+        .map(ExportablePbrMesh::from_binded)
+        .collect();
+    write_gltf(scene, &pbr_meshes, path)
+}
+
+pub fn write_gltf(
+    scene: &Scene,
+    pbr_meshes: &[Option<ExportablePbrMesh>],
+    path: &Path,
+) -> Result<()> {
+    let is_binary = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("glb") => true,
+        Some("gltf") => false,
+        _ => bail!("Expected a .gltf or .glb file path but found: {:?}", path),
+    };
+
+    let (mut root, buffer) = build_gltf(scene, pbr_meshes)?;
+
+    if is_binary {
+        let glb = gltf::binary::Glb {
+            header: gltf::binary::Header {
+                magic: *b"glTF",
+                version: 2,
+                // computed by the writer
+                length: 0,
+            },
+            json: Cow::Owned(root.to_vec()?),
+            bin: (!buffer.is_empty()).then_some(Cow::Owned(buffer)),
+        };
+        glb.to_writer(std::fs::File::create(path)?)?;
+    } else {
+        if !buffer.is_empty() {
+            let buffer_path = path.with_extension("bin");
+            root.buffers[0].uri = buffer_path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .map(String::from);
+            std::fs::write(&buffer_path, &buffer)?;
+        }
+        root.to_writer_pretty(std::fs::File::create(path)?)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct GltfBuilder {
+    root: json::Root,
+    buffer: Vec<u8>,
+}
+
+impl GltfBuilder {
+    fn push_buffer_view(
+        &mut self,
+        bytes: &[u8],
+        target: Option<json::buffer::Target>,
+    ) -> json::Index<json::buffer::View> {
+        // accessor data must be aligned to the size of its components
+        while self.buffer.len() % 4 != 0 {
+            self.buffer.push(0);
+        }
+        self.root.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: bytes.len() as u32,
+            byte_offset: Some(self.buffer.len() as u32),
+            byte_stride: None,
+            name: None,
+            target: target.map(Valid),
+            extensions: None,
+            extras: Default::default(),
+        });
+        self.buffer.extend_from_slice(bytes);
+        json::Index::new(self.root.buffer_views.len() as u32 - 1)
+    }
+
+    fn push_accessor<T: bytemuck::Pod>(
+        &mut self,
+        elements: &[T],
+        component_type: json::accessor::ComponentType,
+        type_: json::accessor::Type,
+        target: Option<json::buffer::Target>,
+    ) -> json::Index<json::Accessor> {
+        let buffer_view = self.push_buffer_view(bytemuck::cast_slice(elements), target);
+        self.root.accessors.push(json::Accessor {
+            buffer_view: Some(buffer_view),
+            byte_offset: 0,
+            count: elements.len() as u32,
+            component_type: Valid(json::accessor::GenericComponentType(component_type)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(type_),
+            min: None,
+            max: None,
+            name: None,
+            normalized: false,
+            sparse: None,
+        });
+        json::Index::new(self.root.accessors.len() as u32 - 1)
+    }
+
+    // the spec requires min and max for vertex positions and animation inputs
+    fn set_accessor_bounds(
+        &mut self,
+        accessor: json::Index<json::Accessor>,
+        min: Vec<f32>,
+        max: Vec<f32>,
+    ) {
+        let accessor = &mut self.root.accessors[accessor.value()];
+        accessor.min = Some(json::Value::from(min));
+        accessor.max = Some(json::Value::from(max));
+    }
+
+    fn push_texture(
+        &mut self,
+        texture_source: &TextureSource,
+    ) -> Result<json::Index<json::Texture>> {
+        let png_bytes = encode_png(texture_source)?;
+        let buffer_view = self.push_buffer_view(&png_bytes, None);
+        self.root.images.push(json::Image {
+            buffer_view: Some(buffer_view),
+            mime_type: Some(json::image::MimeType(String::from("image/png"))),
+            name: texture_source.name.clone(),
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+        self.root.textures.push(json::Texture {
+            name: texture_source.name.clone(),
+            sampler: None,
+            source: json::Index::new(self.root.images.len() as u32 - 1),
+            extensions: None,
+            extras: Default::default(),
+        });
+        Ok(json::Index::new(self.root.textures.len() as u32 - 1))
+    }
+}
+
+fn encode_png(texture_source: &TextureSource) -> Result<Vec<u8>> {
+    let (color_type, bit_depth) = match texture_source.format {
+        gltf::image::Format::R8 => (png::ColorType::Grayscale, png::BitDepth::Eight),
+        gltf::image::Format::R8G8 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight),
+        gltf::image::Format::R8G8B8 => (png::ColorType::Rgb, png::BitDepth::Eight),
+        gltf::image::Format::R8G8B8A8 => (png::ColorType::Rgba, png::BitDepth::Eight),
+        gltf::image::Format::R16 => (png::ColorType::Grayscale, png::BitDepth::Sixteen),
+        gltf::image::Format::R16G16 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Sixteen),
+        gltf::image::Format::R16G16B16 => (png::ColorType::Rgb, png::BitDepth::Sixteen),
+        gltf::image::Format::R16G16B16A16 => (png::ColorType::Rgba, png::BitDepth::Sixteen),
+        format => bail!("Can't write an image with format {:?} to png", format),
+    };
+
+    // png stores 16 bit samples as big endian
+    let pixels: Cow<[u8]> = match bit_depth {
+        png::BitDepth::Sixteen => Cow::Owned(
+            texture_source
+                .pixels
+                .chunks_exact(2)
+                .flat_map(|sample| u16::from_ne_bytes([sample[0], sample[1]]).to_be_bytes())
+                .collect(),
+        ),
+        _ => Cow::Borrowed(&texture_source.pixels),
+    };
+
+    let mut png_bytes = Vec::new();
+    {
+        let mut encoder =
+            png::Encoder::new(&mut png_bytes, texture_source.width, texture_source.height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        encoder.write_header()?.write_image_data(&pixels)?;
+    }
+    Ok(png_bytes)
+}
+
+fn build_gltf(
+    scene: &Scene,
+    pbr_meshes: &[Option<ExportablePbrMesh>],
+) -> Result<(json::Root, Vec<u8>)> {
+    let mut builder = GltfBuilder::default();
+
+    let nodes: Vec<_> = scene.nodes().collect();
+    let node_indices: HashMap<GameNodeId, u32> = nodes
+        .iter()
+        .enumerate()
+        .map(|(node_index, node)| (node.id(), node_index as u32))
+        .collect();
+    let get_node_index = |node_id: GameNodeId| {
+        node_indices
+            .get(&node_id)
+            .copied()
+            .map(json::Index::<json::Node>::new)
+            .ok_or_else(|| anyhow::anyhow!("Node {:?} is no longer in the scene", node_id))
+    };
+
+    // the bone indices and weights are only written for meshes that are actually skinned
+    let skinned_pbr_mesh_indices: HashSet<usize> = nodes
+        .iter()
+        .filter(|node| node.skin_index.is_some())
+        .filter_map(|node| node.mesh.as_ref())
+        .flat_map(|mesh| mesh.mesh_indices.iter().copied())
+        .collect();
+
+    // pbr mesh index -> gltf primitive
+    let mut primitives: HashMap<usize, json::mesh::Primitive> = HashMap::new();
+    // pbr mesh indices of a game node -> gltf mesh
+    let mut meshes: HashMap<Vec<usize>, json::Index<json::Mesh>> = HashMap::new();
+    let mut textures: HashMap<*const TextureSource, json::Index<json::Texture>> = HashMap::new();
+
+    let mut json_nodes = Vec::with_capacity(nodes.len());
+    for node in &nodes {
+        let mesh = match &node.mesh {
+            Some(GameNodeMesh {
+                mesh_type: GameNodeMeshType::Pbr { .. },
+                mesh_indices,
+                ..
+            }) if mesh_indices
+                .iter()
+                .any(|pbr_mesh_index| matches!(pbr_meshes.get(*pbr_mesh_index), Some(None))) =>
+            {
+                logger_log(&format!(
+                    "Warning: exporting node {:?} without its mesh since it wasn't kept on the cpu",
+                    node.id()
+                ));
+                None
+            }
+            Some(GameNodeMesh {
+                mesh_type: GameNodeMeshType::Pbr { .. },
+                mesh_indices,
+                ..
+            }) => Some(match meshes.entry(mesh_indices.clone()) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(vacant_entry) => {
+                    let mut mesh_primitives = Vec::with_capacity(mesh_indices.len());
+                    for pbr_mesh_index in mesh_indices.iter().copied() {
+                        let primitive = match primitives.get(&pbr_mesh_index) {
+                            Some(primitive) => primitive.clone(),
+                            None => {
+                                let primitive = build_primitive(
+                                    &mut builder,
+                                    &mut textures,
+                                    pbr_meshes
+                                        .get(pbr_mesh_index)
+                                        .and_then(Option::as_ref)
+                                        .ok_or_else(|| {
+                                            anyhow::anyhow!("Missing pbr mesh {:?}", pbr_mesh_index)
+                                        })?,
+                                    skinned_pbr_mesh_indices.contains(&pbr_mesh_index),
+                                )?;
+                                primitives.insert(pbr_mesh_index, primitive.clone());
+                                primitive
+                            }
+                        };
+                        mesh_primitives.push(primitive);
+                    }
+                    builder.root.meshes.push(json::Mesh {
+                        extensions: None,
+                        extras: Default::default(),
+                        name: None,
+                        primitives: mesh_primitives,
+                        weights: None,
+                    });
+                    let mesh_index = json::Index::new(builder.root.meshes.len() as u32 - 1);
+                    vacant_entry.insert(mesh_index);
+                    mesh_index
+                }
+            }),
+            _ => None,
+        };
+
+        let children: Vec<_> = scene
+            .children(node.id())
+            .map(get_node_index)
+            .collect::<Result<_>>()?;
+
+        let transform = node.transform;
+        json_nodes.push(json::Node {
+            camera: None,
+            children: (!children.is_empty()).then_some(children),
+            extensions: None,
+            extras: Default::default(),
+            matrix: None,
+            mesh,
+            name: node.name().map(String::from),
+            rotation: Some(json::scene::UnitQuaternion(transform.rotation().to_array())),
+            scale: Some(transform.scale().to_array()),
+            translation: Some(transform.position().to_array()),
+            skin: node
+                .skin_index
+                .map(|skin_index| json::Index::new(skin_index as u32)),
+            weights: None,
+        });
+    }
+    builder.root.nodes = json_nodes;
+
+    builder.root.scenes.push(json::Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        nodes: nodes
+            .iter()
+            .filter(|node| {
+                node.parent_id()
+                    .map(|parent_id| !node_indices.contains_key(&parent_id))
+                    .unwrap_or(true)
+            })
+            .map(|node| get_node_index(node.id()))
+            .collect::<Result<_>>()?,
+    });
+    builder.root.scene = Some(json::Index::new(0));
+
+    for skin in &scene.skins {
+        let inverse_bind_matrices: Vec<_> = skin
+            .bone_inverse_bind_matrices
+            .iter()
+            .map(|matrix| matrix.to_cols_array())
+            .collect();
+        let inverse_bind_matrices = builder.push_accessor(
+            &inverse_bind_matrices,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Mat4,
+            None,
+        );
+        let skin = json::Skin {
+            extensions: None,
+            extras: Default::default(),
+            inverse_bind_matrices: Some(inverse_bind_matrices),
+            joints: skin
+                .bone_node_ids
+                .iter()
+                .copied()
+                .map(get_node_index)
+                .collect::<Result<_>>()?,
+            name: None,
+            skeleton: None,
+        };
+        builder.root.skins.push(skin);
+    }
+
+    for animation in &scene.animations {
+        let mut channels = Vec::with_capacity(animation.channels.len());
+        let mut samplers = Vec::with_capacity(animation.channels.len());
+        for channel in &animation.channels {
+            let input = builder.push_accessor(
+                &channel.keyframe_timings,
+                json::accessor::ComponentType::F32,
+                json::accessor::Type::Scalar,
+                None,
+            );
+            builder.set_accessor_bounds(
+                input,
+                vec![channel
+                    .keyframe_timings
+                    .iter()
+                    .copied()
+                    .fold(f32::MAX, f32::min)],
+                vec![channel
+                    .keyframe_timings
+                    .iter()
+                    .copied()
+                    .fold(f32::MIN, f32::max)],
+            );
+            let output = match channel.property {
+                gltf::animation::Property::Translation | gltf::animation::Property::Scale => {
+                    builder.push_accessor(
+                        bytemuck::cast_slice::<_, [f32; 3]>(&channel.keyframe_values_u8),
+                        json::accessor::ComponentType::F32,
+                        json::accessor::Type::Vec3,
+                        None,
+                    )
+                }
+                gltf::animation::Property::Rotation => builder.push_accessor(
+                    bytemuck::cast_slice::<_, [f32; 4]>(&channel.keyframe_values_u8),
+                    json::accessor::ComponentType::F32,
+                    json::accessor::Type::Vec4,
+                    None,
+                ),
+                gltf::animation::Property::MorphTargetWeights => {
+                    bail!("MorphTargetWeights not supported")
+                }
+            };
+            samplers.push(json::animation::Sampler {
+                extensions: None,
+                extras: Default::default(),
+                input,
+                interpolation: Valid(channel.interpolation_type),
+                output,
+            });
+            channels.push(json::animation::Channel {
+                sampler: json::Index::new(samplers.len() as u32 - 1),
+                target: json::animation::Target {
+                    extensions: None,
+                    extras: Default::default(),
+                    node: get_node_index(channel.node_id)?,
+                    path: Valid(channel.property),
+                },
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+        builder.root.animations.push(json::Animation {
+            extensions: None,
+            extras: Default::default(),
+            channels,
+            name: animation.name.clone(),
+            samplers,
+        });
+    }
+
+    builder.root.asset = json::Asset {
+        copyright: None,
+        extensions: None,
+        extras: Default::default(),
+        generator: Some(String::from("ikari")),
+        min_version: None,
+        version: String::from("2.0"),
+    };
+
+    if !builder.buffer.is_empty() {
+        builder.root.buffers.push(json::Buffer {
+            byte_length: builder.buffer.len() as u32,
+            name: None,
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+
+    Ok((builder.root, builder.buffer))
+}
+
+fn build_primitive(
+    builder: &mut GltfBuilder,
+    textures: &mut HashMap<*const TextureSource, json::Index<json::Texture>>,
+    pbr_mesh: &ExportablePbrMesh,
+    is_skinned: bool,
+) -> Result<json::mesh::Primitive> {
+    let vertices = &pbr_mesh.geometry_source.vertices;
+    let mut attributes = HashMap::new();
+
+    let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+    let position_accessor = builder.push_accessor(
+        &positions,
+        json::accessor::ComponentType::F32,
+        json::accessor::Type::Vec3,
+        Some(json::buffer::Target::ArrayBuffer),
+    );
+    let (min, max) = positions.iter().copied().map(Vec3::from).fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), position| (min.min(position), max.max(position)),
+    );
+    builder.set_accessor_bounds(
+        position_accessor,
+        min.to_array().to_vec(),
+        max.to_array().to_vec(),
+    );
+    attributes.insert(Valid(json::mesh::Semantic::Positions), position_accessor);
+
+    let normals: Vec<_> = vertices.iter().map(|vertex| vertex.normal).collect();
+    attributes.insert(
+        Valid(json::mesh::Semantic::Normals),
+        builder.push_accessor(
+            &normals,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec3,
+            Some(json::buffer::Target::ArrayBuffer),
+        ),
+    );
+
+    // the loader derives the bitangent from the sign of w, see get_vertex_tangents in gltf_loader.rs
+    let tangents: Vec<_> = vertices
+        .iter()
+        .map(|vertex| {
+            let normal = Vec3::from(vertex.normal);
+            let tangent = Vec3::from(vertex.tangent);
+            let bitangent = Vec3::from(vertex.bitangent);
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                1.0
+            } else {
+                -1.0
+            };
+            [tangent.x, tangent.y, tangent.z, handedness]
+        })
+        .collect();
+    attributes.insert(
+        Valid(json::mesh::Semantic::Tangents),
+        builder.push_accessor(
+            &tangents,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec4,
+            Some(json::buffer::Target::ArrayBuffer),
+        ),
+    );
+
+    let tex_coords: Vec<_> = vertices.iter().map(|vertex| vertex.tex_coords).collect();
+    attributes.insert(
+        Valid(json::mesh::Semantic::TexCoords(0)),
+        builder.push_accessor(
+            &tex_coords,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec2,
+            Some(json::buffer::Target::ArrayBuffer),
+        ),
+    );
+
+    let colors: Vec<_> = vertices.iter().map(|vertex| vertex.color).collect();
+    attributes.insert(
+        Valid(json::mesh::Semantic::Colors(0)),
+        builder.push_accessor(
+            &colors,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec4,
+            Some(json::buffer::Target::ArrayBuffer),
+        ),
+    );
+
+    if is_skinned {
+        let bone_indices = vertices
+            .iter()
+            .map(|vertex| {
+                let mut bone_indices = [0u16; 4];
+                for (i, bone_index) in vertex.bone_indices.iter().enumerate() {
+                    bone_indices[i] = u16::try_from(*bone_index)?;
+                }
+                anyhow::Ok(bone_indices)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        attributes.insert(
+            Valid(json::mesh::Semantic::Joints(0)),
+            builder.push_accessor(
+                &bone_indices,
+                json::accessor::ComponentType::U16,
+                json::accessor::Type::Vec4,
+                Some(json::buffer::Target::ArrayBuffer),
+            ),
+        );
+
+        let bone_weights: Vec<_> = vertices.iter().map(|vertex| vertex.bone_weights).collect();
+        attributes.insert(
+            Valid(json::mesh::Semantic::Weights(0)),
+            builder.push_accessor(
+                &bone_weights,
+                json::accessor::ComponentType::F32,
+                json::accessor::Type::Vec4,
+                Some(json::buffer::Target::ArrayBuffer),
+            ),
+        );
+    }
+
+    let indices = builder.push_accessor(
+        &pbr_mesh.geometry_source.indices,
+        json::accessor::ComponentType::U32,
+        json::accessor::Type::Scalar,
+        Some(json::buffer::Target::ElementArrayBuffer),
+    );
+
+    let mut get_texture = |texture_source: &Option<Arc<TextureSource>>| {
+        texture_source
+            .as_ref()
+            .map(|texture_source| {
+                anyhow::Ok(match textures.entry(Arc::as_ptr(texture_source)) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(vacant_entry) => {
+                        *vacant_entry.insert(builder.push_texture(texture_source)?)
+                    }
+                })
+            })
+            .transpose()
+    };
+    let make_texture_info = |index| json::texture::Info {
+        index,
+        tex_coord: 0,
+        extensions: None,
+        extras: Default::default(),
+    };
+
+    let PbrTextureSources {
+        base_color,
+        normal,
+        metallic_roughness,
+        emissive,
+        ambient_occlusion,
+    } = pbr_mesh.texture_sources;
+    let DynamicPbrParams {
+        base_color_factor,
+        emissive_factor,
+        metallic_factor,
+        roughness_factor,
+        normal_scale,
+        occlusion_strength,
+        alpha_cutoff,
+    } = pbr_mesh.dynamic_pbr_params;
+
+    // a negative alpha cutoff means alpha masking is disabled, see GpuPbrMeshInstance
+    let is_alpha_masked = alpha_cutoff >= 0.0;
+
+    let material = json::Material {
+        alpha_cutoff: is_alpha_masked.then_some(json::material::AlphaCutoff(alpha_cutoff)),
+        alpha_mode: Valid(if is_alpha_masked {
+            json::material::AlphaMode::Mask
+        } else {
+            json::material::AlphaMode::Opaque
+        }),
+        double_sided: false,
+        name: None,
+        pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+            base_color_factor: json::material::PbrBaseColorFactor(base_color_factor.to_array()),
+            base_color_texture: get_texture(base_color)?.map(make_texture_info),
+            metallic_factor: json::material::StrengthFactor(metallic_factor),
+            roughness_factor: json::material::StrengthFactor(roughness_factor),
+            metallic_roughness_texture: get_texture(metallic_roughness)?.map(make_texture_info),
+            extensions: None,
+            extras: Default::default(),
+        },
+        normal_texture: get_texture(normal)?.map(|index| json::material::NormalTexture {
+            index,
+            scale: normal_scale,
+            tex_coord: 0,
+            extensions: None,
+            extras: Default::default(),
+        }),
+        occlusion_texture: get_texture(ambient_occlusion)?.map(|index| {
+            json::material::OcclusionTexture {
+                index,
+                strength: json::material::StrengthFactor(occlusion_strength),
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            }
+        }),
+        emissive_texture: get_texture(emissive)?.map(make_texture_info),
+        emissive_factor: json::material::EmissiveFactor(emissive_factor.to_array()),
+        extensions: None,
+        extras: Default::default(),
+    };
+    builder.root.materials.push(material);
+
+    Ok(json::mesh::Primitive {
+        attributes,
+        extensions: None,
+        extras: Default::default(),
+        indices: Some(indices),
+        material: Some(json::Index::new(builder.root.materials.len() as u32 - 1)),
+        mode: Valid(json::mesh::Mode::Triangles),
+        targets: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::transform::*;
+
+    use glam::f32::{Mat4, Vec4};
+
+    #[test]
+    fn exported_scene_can_be_reloaded() {
+        let vertices: Vec<_> = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, position)| Vertex {
+                position,
+                normal: [0.0, 0.0, 1.0],
+                tex_coords: position[0..2].try_into().unwrap(),
+                tangent: [1.0, 0.0, 0.0],
+                bitangent: [0.0, 1.0, 0.0],
+                color: [1.0, 1.0, 1.0, 1.0],
+                bone_indices: [i as u32 % 2, 0, 0, 0],
+                bone_weights: [1.0, 0.0, 0.0, 0.0],
+            })
+            .collect();
+        let geometry_source = GeometrySource {
+            vertices,
+            indices: vec![0, 1, 2],
+        };
+        let base_color_texture = Arc::new(TextureSource {
+            name: Some(String::from("checker")),
+            width: 2,
+            height: 2,
+            format: gltf::image::Format::R8G8B8A8,
+            pixels: vec![
+                255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 128,
+            ],
+        });
+        let texture_sources = PbrTextureSources {
+            base_color: Some(base_color_texture.clone()),
+            ..Default::default()
+        };
+        let dynamic_pbr_params = DynamicPbrParams {
+            base_color_factor: Vec4::new(0.5, 0.25, 1.0, 1.0),
+            alpha_cutoff: 0.3,
+            ..Default::default()
+        };
+
+        let bone_translation_keyframes = [[0.0f32, 0.0, 0.0], [0.0, 2.0, 0.0]];
+        let scene = Scene::new(
+            vec![
+                IndexedGameNodeDesc {
+                    transform: TransformBuilder::new()
+                        .position(Vec3::new(1.0, 2.0, 3.0))
+                        .build(),
+                    skin_index: None,
+                    mesh: None,
+                    name: Some(String::from("root")),
+                    parent_index: None,
+                },
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: Some(0),
+                    mesh: Some(GameNodeMesh::from_pbr_mesh_index(0)),
+                    name: Some(String::from("skinned_mesh")),
+                    parent_index: Some(0),
+                },
+                IndexedGameNodeDesc {
+                    transform: TransformBuilder::new()
+                        .scale(Vec3::new(2.0, 2.0, 2.0))
+                        .build(),
+                    skin_index: None,
+                    mesh: None,
+                    name: Some(String::from("bone")),
+                    parent_index: Some(0),
+                },
+            ],
+            vec![IndexedSkin {
+                bone_node_indices: vec![0, 2],
+                bone_inverse_bind_matrices: vec![
+                    Mat4::IDENTITY,
+                    Mat4::from_translation(Vec3::new(0.0, -1.0, 0.0)),
+                ],
+                bone_bounding_box_transforms: vec![],
+            }],
+            vec![IndexedAnimation {
+                name: Some(String::from("wiggle")),
+                length_seconds: 1.0,
+                channels: vec![IndexedChannel {
+                    node_index: 2,
+                    property: gltf::animation::Property::Translation,
+                    interpolation_type: gltf::animation::Interpolation::Linear,
+                    keyframe_timings: vec![0.0, 1.0],
+                    keyframe_values_u8: bytemuck::cast_slice(&bone_translation_keyframes).to_vec(),
+                }],
+            }],
+            vec![],
+            vec![],
+            vec![],
+        );
+        let pbr_meshes = [Some(ExportablePbrMesh {
+            geometry_source: &geometry_source,
+            texture_sources: &texture_sources,
+            dynamic_pbr_params,
+        })];
+
+        for extension in ["glb", "gltf"] {
+            let path = std::env::temp_dir().join(format!(
+                "ikari_gltf_exporter_test_{}.{extension}",
+                std::process::id()
+            ));
+            write_gltf(&scene, &pbr_meshes, &path).unwrap();
+            let (document, buffers, images) = gltf::import(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            if extension == "gltf" {
+                std::fs::remove_file(path.with_extension("bin")).unwrap();
+            }
+
+            let nodes: Vec<_> = document.nodes().collect();
+            let node_names: Vec<_> = nodes.iter().map(|node| node.name()).collect();
+            assert_eq!(
+                node_names,
+                vec![Some("root"), Some("skinned_mesh"), Some("bone")]
+            );
+            let child_indices: Vec<_> = nodes[0].children().map(|child| child.index()).collect();
+            assert_eq!(child_indices, vec![1, 2]);
+            let root_scene_nodes: Vec<_> = document
+                .default_scene()
+                .unwrap()
+                .nodes()
+                .map(|node| node.index())
+                .collect();
+            assert_eq!(root_scene_nodes, vec![0]);
+            let (translation, _, _) = nodes[0].transform().decomposed();
+            assert_eq!(translation, [1.0, 2.0, 3.0]);
+            let (_, _, scale) = nodes[2].transform().decomposed();
+            assert_eq!(scale, [2.0, 2.0, 2.0]);
+
+            let primitive = nodes[1].mesh().unwrap().primitives().next().unwrap();
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions: Vec<_> = reader.read_positions().unwrap().collect();
+            let expected_positions: Vec<_> = geometry_source
+                .vertices
+                .iter()
+                .map(|vertex| vertex.position)
+                .collect();
+            assert_eq!(positions, expected_positions);
+            let indices: Vec<_> = reader.read_indices().unwrap().into_u32().collect();
+            assert_eq!(indices, geometry_source.indices);
+            let bone_indices: Vec<_> = reader.read_joints(0).unwrap().into_u16().collect();
+            assert_eq!(bone_indices, vec![[0, 0, 0, 0], [1, 0, 0, 0], [0, 0, 0, 0]]);
+
+            let material = primitive.material();
+            assert_eq!(
+                material.pbr_metallic_roughness().base_color_factor(),
+                [0.5, 0.25, 1.0, 1.0]
+            );
+            assert_eq!(material.alpha_mode(), gltf::material::AlphaMode::Mask);
+            assert_eq!(material.alpha_cutoff(), Some(0.3));
+            let base_color_image_index = material
+                .pbr_metallic_roughness()
+                .base_color_texture()
+                .unwrap()
+                .texture()
+                .source()
+                .index();
+            assert_eq!(
+                images[base_color_image_index].pixels,
+                base_color_texture.pixels
+            );
+
+            let skin = nodes[1].skin().unwrap();
+            let joint_indices: Vec<_> = skin.joints().map(|joint| joint.index()).collect();
+            assert_eq!(joint_indices, vec![0, 2]);
+            let inverse_bind_matrices: Vec<_> = skin
+                .reader(|buffer| Some(&buffers[buffer.index()]))
+                .read_inverse_bind_matrices()
+                .unwrap()
+                .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                .collect();
+            assert_eq!(
+                inverse_bind_matrices,
+                scene.skins[0].bone_inverse_bind_matrices
+            );
+
+            let animations = crate::gltf_loader::get_animations(&document, &buffers).unwrap();
+            assert_eq!(animations.len(), 1);
+            assert_eq!(animations[0].name.as_deref(), Some("wiggle"));
+            assert_eq!(animations[0].channels[0].node_index, 2);
+            assert_eq!(animations[0].channels[0].keyframe_timings, vec![0.0, 1.0]);
+            assert_eq!(
+                animations[0].channels[0].keyframe_values_u8,
+                scene.animations[0].channels[0].keyframe_values_u8
+            );
+        }
+    }
+}
@@ -1,15 +1,31 @@
 use crate::buffer::*;
+use crate::camera::*;
+use crate::ktx2::check_uastc_ktx2;
+use crate::light::*;
+use crate::logger::*;
 use crate::mesh::*;
+use crate::mesh_cache::*;
+use crate::mesh_simplifier::*;
 use crate::renderer::*;
 use crate::sampler_cache::*;
 use crate::scene::*;
 use crate::texture::*;
+use crate::texture_compression::embedded_texture_path_to_compressed_path;
+use crate::texture_compression::find_compressed_texture_path;
 use crate::texture_compression::texture_path_to_compressed_path;
 use crate::texture_compression::CompressedTexture;
 use crate::texture_compression::TextureCompressor;
+use crate::texture_compression::TranscodeTarget;
+use crate::texture_compression::TranscodedFormat;
+use crate::texture_streaming::get_initial_base_mip;
+use crate::texture_streaming::get_rgba8_mips;
+use crate::texture_streaming::StreamedTexture;
+use crate::texture_streaming::StreamedTextureSource;
 use crate::transform::*;
+use crate::vfs::*;
+use crate::worker_pool::*;
 
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -18,7 +34,7 @@ use anyhow::{bail, Result};
 use approx::abs_diff_eq;
 use glam::f32::{Mat4, Vec2, Vec3, Vec4};
 
-const USE_TEXTURE_COMPRESSION: bool = true;
+const KHR_TEXTURE_BASISU: &str = "KHR_texture_basisu";
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub struct ChannelPropertyStr<'a>(&'a str);
@@ -34,6 +50,287 @@ impl From<gltf::animation::Property> for ChannelPropertyStr<'_> {
     }
 }
 
+// drop-in replacement for gltf::import that also supports KHR_texture_basisu and decodes the
+// images in parallel. the ktx2 images aren't decoded here, they're transcoded straight to the gpu
+// format in get_textures
+pub fn import_gltf<P: AsRef<Path>>(
+    path: P,
+) -> Result<(
+    gltf::Document,
+    Vec<gltf::buffer::Data>,
+    Vec<gltf::image::Data>,
+)> {
+    let path = path.as_ref();
+    import_gltf_data(path, open_gltf(path)?, false)
+}
+
+// reads the buffers and decodes the images of a gltf file opened with open_gltf.
+// if skip_vertex_buffers is set, the buffers that only hold mesh data are left empty, which is
+// used when the geometry comes from a fresh mesh cache instead
+pub fn import_gltf_data(
+    path: &Path,
+    gltf: gltf::Gltf,
+    skip_vertex_buffers: bool,
+) -> Result<(
+    gltf::Document,
+    Vec<gltf::buffer::Data>,
+    Vec<gltf::image::Data>,
+)> {
+    let base_folder = path.parent().unwrap_or_else(|| Path::new("./"));
+    let gltf::Gltf { document, mut blob } = gltf;
+    let non_vertex_buffer_indices = get_non_vertex_buffer_indices(&document);
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| {
+            let mut data = match buffer.source() {
+                gltf::buffer::Source::Bin => blob
+                    .take()
+                    .ok_or_else(|| anyhow::anyhow!("Missing binary chunk in glb file"))?,
+                gltf::buffer::Source::Uri(_)
+                    if skip_vertex_buffers
+                        && !non_vertex_buffer_indices.contains(&buffer.index()) =>
+                {
+                    return Ok(gltf::buffer::Data(vec![]));
+                }
+                gltf::buffer::Source::Uri(uri) => read_uri(base_folder, uri)?,
+            };
+            if data.len() < buffer.length() {
+                bail!(
+                    "Buffer {:?} is {:?} bytes long, expected {:?}",
+                    buffer.index(),
+                    data.len(),
+                    buffer.length()
+                );
+            }
+            // same padding as gltf::import
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+            Ok(gltf::buffer::Data(data))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let encoded_images = document
+        .images()
+        .map(|image| {
+            (!is_ktx2_image(&image))
+                .then(|| read_image_bytes(&image, base_folder, &buffers))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let images = WORKER_POOL
+        .map(encoded_images, |image_bytes| match image_bytes {
+            Some(image_bytes) => decode_image(&image_bytes),
+            None => Ok(gltf::image::Data {
+                pixels: vec![],
+                format: gltf::image::Format::R8G8B8A8,
+                width: 0,
+                height: 0,
+            }),
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((document, buffers, images))
+}
+
+// the buffers that are still read by build_scene when the meshes come from the mesh cache
+fn get_non_vertex_buffer_indices(document: &gltf::Document) -> HashSet<usize> {
+    let mut accessors: Vec<gltf::Accessor> = vec![];
+    for animation in document.animations() {
+        for sampler in animation.samplers() {
+            accessors.push(sampler.input());
+            accessors.push(sampler.output());
+        }
+    }
+    for skin in document.skins() {
+        accessors.extend(skin.inverse_bind_matrices());
+    }
+
+    let mut buffer_indices = HashSet::new();
+    for accessor in accessors {
+        buffer_indices.extend(accessor.view().map(|view| view.buffer().index()));
+        if let Some(sparse) = accessor.sparse() {
+            buffer_indices.insert(sparse.indices().view().buffer().index());
+            buffer_indices.insert(sparse.values().view().buffer().index());
+        }
+    }
+    for image in document.images() {
+        if let gltf::image::Source::View { view, .. } = image.source() {
+            buffer_indices.insert(view.buffer().index());
+        }
+    }
+    buffer_indices
+}
+
+// keeps the same pixel formats as gltf::import
+fn decode_image(image_bytes: &[u8]) -> Result<gltf::image::Data> {
+    use image::GenericImageView;
+
+    let decoded_image = image::load_from_memory(image_bytes)?;
+    let (width, height) = decoded_image.dimensions();
+    let format = match &decoded_image {
+        image::DynamicImage::ImageLuma8(_) => Some(gltf::image::Format::R8),
+        image::DynamicImage::ImageLumaA8(_) => Some(gltf::image::Format::R8G8),
+        image::DynamicImage::ImageRgb8(_) => Some(gltf::image::Format::R8G8B8),
+        image::DynamicImage::ImageRgba8(_) => Some(gltf::image::Format::R8G8B8A8),
+        image::DynamicImage::ImageLuma16(_) => Some(gltf::image::Format::R16),
+        image::DynamicImage::ImageLumaA16(_) => Some(gltf::image::Format::R16G16),
+        image::DynamicImage::ImageRgb16(_) => Some(gltf::image::Format::R16G16B16),
+        image::DynamicImage::ImageRgba16(_) => Some(gltf::image::Format::R16G16B16A16),
+        _ => None,
+    };
+    let (format, pixels) = match format {
+        Some(format) => (format, decoded_image.into_bytes()),
+        // bgr images
+        None => (
+            gltf::image::Format::R8G8B8A8,
+            decoded_image.to_rgba8().into_raw(),
+        ),
+    };
+    Ok(gltf::image::Data {
+        format,
+        width,
+        height,
+        pixels,
+    })
+}
+
+// replacement for gltf::Gltf::open that uses the KHR_texture_basisu image as the texture source,
+// since the gltf crate doesn't know about the extension and would use the fallback image instead
+pub fn open_gltf(path: &Path) -> Result<gltf::Gltf> {
+    let bytes = vfs_read(path)?;
+    let (json_bytes, blob) = if bytes.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(&bytes)?;
+        (glb.json.into_owned(), glb.bin.map(|bin| bin.into_owned()))
+    } else {
+        (bytes, None)
+    };
+    let mut json: serde_json::Value = serde_json::from_slice(&json_bytes)?;
+    if let Some(textures) = json
+        .get_mut("textures")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        for texture in textures {
+            if let Some(basisu_source) = texture
+                .pointer(&format!("/extensions/{}/source", KHR_TEXTURE_BASISU))
+                .cloned()
+            {
+                texture["source"] = basisu_source;
+            }
+        }
+    }
+    Ok(gltf::Gltf {
+        document: gltf::Document::from_json(serde_json::from_value(json)?)?,
+        blob,
+    })
+}
+
+pub fn is_ktx2_image(image: &gltf::Image) -> bool {
+    match image.source() {
+        gltf::image::Source::Uri { uri, mime_type } => {
+            mime_type == Some("image/ktx2") || uri.ends_with(".ktx2")
+        }
+        gltf::image::Source::View { mime_type, .. } => mime_type == "image/ktx2",
+    }
+}
+
+// the encoded bytes of the image, as stored in the file
+pub fn read_image_bytes(
+    image: &gltf::Image,
+    base_folder: &Path,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<u8>> {
+    match image.source() {
+        gltf::image::Source::Uri { uri, .. } => read_uri(base_folder, uri),
+        gltf::image::Source::View { view, .. } => Ok(buffers[view.buffer().index()]
+            .get(view.offset()..view.offset() + view.length())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Buffer view of image {:?} is out of bounds", image.index())
+            })?
+            .to_vec()),
+    }
+}
+
+fn read_uri(base_folder: &Path, uri: &str) -> Result<Vec<u8>> {
+    match uri.strip_prefix("data:") {
+        Some(data_uri) => match data_uri.split_once(";base64,") {
+            Some((_mime_type, base64_data)) => Ok(base64::decode(base64_data)?),
+            None => bail!("Only base64 data uris are supported"),
+        },
+        None => vfs_read(base_folder.join(decode_uri(uri))),
+    }
+}
+
+// gltf uris are percent-encoded, e.g. spaces are written as %20
+pub fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped_byte = (bytes[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped_byte {
+            Some(escaped_byte) => {
+                decoded.push(escaped_byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// the gltf file and the buffer files it references, which is all the mesh cache is built from
+pub fn get_gltf_buffer_paths(gltf_path: &Path, document: &gltf::Document) -> Vec<PathBuf> {
+    let gltf_folder = gltf_path.parent().unwrap_or_else(|| Path::new("./"));
+    let mut buffer_paths = vec![gltf_path.to_path_buf()];
+    for buffer in document.buffers() {
+        if let gltf::buffer::Source::Uri(uri) = buffer.source() {
+            if !uri.starts_with("data:") {
+                buffer_paths.push(gltf_folder.join(decode_uri(uri)));
+            }
+        }
+    }
+    buffer_paths
+}
+
+// the files that a scene is built from, including the compressed textures and mesh cache that
+// may not exist yet
+pub fn get_gltf_source_paths(gltf_path: &Path, document: &gltf::Document) -> Vec<PathBuf> {
+    let gltf_folder = gltf_path.parent().unwrap_or_else(|| Path::new("./"));
+    let mut source_paths = get_gltf_buffer_paths(gltf_path, document);
+    source_paths.push(gltf_path_to_mesh_cache_path(gltf_path));
+    for image in document.images() {
+        let compressed_texture_path = match image.source() {
+            gltf::image::Source::Uri { uri, .. } => {
+                if uri.starts_with("data:") {
+                    continue;
+                }
+                let source_path = gltf_folder.join(decode_uri(uri));
+                let compressed_texture_path = texture_path_to_compressed_path(&source_path);
+                source_paths.push(source_path);
+                compressed_texture_path
+            }
+            gltf::image::Source::View { .. } => {
+                embedded_texture_path_to_compressed_path(gltf_path, image.index())
+            }
+        };
+        if !is_ktx2_image(&image) {
+            // legacy compressed textures have the .bin extension
+            source_paths.push(compressed_texture_path.with_extension("bin"));
+            source_paths.push(compressed_texture_path);
+        }
+    }
+    source_paths
+}
+
 pub fn build_scene(
     base_renderer: &BaseRenderer,
     (document, buffers, images): (
@@ -42,6 +339,8 @@ pub fn build_scene(
         &Vec<gltf::image::Data>,
     ),
     gltf_path: &Path,
+    mesh_cache: Option<MeshCache>,
+    keep_mesh_sources: bool,
 ) -> Result<(Scene, RenderBuffers)> {
     let scene_index = document
         .default_scene()
@@ -50,18 +349,30 @@ pub fn build_scene(
 
     let materials: Vec<_> = document.materials().collect();
 
-    let textures = get_textures(document, images, materials, gltf_path, base_renderer)?;
+    let (textures, streamed_textures) = get_textures(
+        document,
+        buffers,
+        images,
+        materials,
+        gltf_path,
+        base_renderer,
+    )?;
+    let texture_sources = if keep_mesh_sources {
+        get_texture_sources(document, images)
+    } else {
+        vec![]
+    };
 
-    // node index -> parent node index
-    let parent_index_map: HashMap<usize, usize> = document
-        .nodes()
-        .flat_map(|parent_node| {
-            let parent_node_index = parent_node.index();
-            parent_node
-                .children()
-                .map(move |child_node| (child_node.index(), parent_node_index))
-        })
-        .collect();
+    let (cached_nodes, mut cached_primitives) = match mesh_cache {
+        Some(MeshCache { nodes, primitives }) => (
+            nodes,
+            primitives
+                .into_iter()
+                .map(|primitive| ((primitive.mesh_index, primitive.primitive_index), primitive))
+                .collect(),
+        ),
+        None => (get_cached_nodes(document), HashMap::new()),
+    };
 
     let scene_nodes: Vec<_> = get_full_node_list(
         document
@@ -94,7 +405,7 @@ pub fn build_scene(
             .iter()
             .flat_map(|mesh| mesh.primitives().map(|prim| (&meshes[mesh.index()], prim)))
             .filter(|(_, prim)| {
-                prim.mode() == gltf::mesh::Mode::Triangles
+                get_primitive_mode(prim).is_ok()
                     && (prim.material().alpha_mode() == gltf::material::AlphaMode::Opaque
                         || prim.material().alpha_mode() == gltf::material::AlphaMode::Mask)
             })
@@ -102,45 +413,58 @@ pub fn build_scene(
 
     let supported_mesh_count = make_supported_mesh_iterator().count();
 
+    let skinned_mesh_indices: HashSet<usize> = scene_nodes
+        .iter()
+        .filter(|node| node.skin().is_some())
+        .filter_map(|node| node.mesh().map(|mesh| mesh.index()))
+        .collect();
+    // gltf mesh index -> vertices of its first primitive, used to fit the bone bounding boxes
+    let mut skinned_mesh_vertices: HashMap<usize, Vec<Vertex>> = HashMap::new();
+
     let mut binded_pbr_meshes: Vec<BindedPbrMesh> = Vec::with_capacity(supported_mesh_count);
+    let mut binded_unlit_meshes: Vec<BindedUnlitMesh> = Vec::new();
     let mut binded_wireframe_meshes: Vec<BindedWireframeMesh> =
         Vec::with_capacity(supported_mesh_count);
-    let mut pbr_mesh_vertices: Vec<Vec<Vertex>> = Vec::with_capacity(supported_mesh_count);
     // gltf node index -> game node
     let mut node_mesh_links: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut node_unlit_mesh_links: HashMap<usize, Vec<usize>> = HashMap::new();
 
     // IndexedPbrMaterial
     let mut textures_bind_group_cache: HashMap<IndexedPbrMaterial, Arc<wgpu::BindGroup>> =
         HashMap::new();
+    // base color texture index -> unlit textures bind group
+    let mut unlit_textures_bind_group_cache: HashMap<Option<usize>, Arc<wgpu::BindGroup>> =
+        HashMap::new();
 
-    for (binded_pbr_mesh_index, (mesh, primitive_group)) in
-        make_supported_mesh_iterator().enumerate()
-    {
-        let dynamic_pbr_params = get_dynamic_pbr_params(&primitive_group.material());
+    for (mesh, primitive_group) in make_supported_mesh_iterator() {
+        let material = primitive_group.material();
 
-        let indexed_pbr_material = get_indexed_pbr_material(&primitive_group.material());
-        let textures_bind_group = match textures_bind_group_cache.entry(indexed_pbr_material) {
-            Entry::Occupied(entry) => entry.get().clone(),
-            Entry::Vacant(vacant_entry) => {
-                let pbr_material = get_pbr_material(vacant_entry.key(), &textures);
-                let textures_bind_group =
-                    Arc::new(base_renderer.make_pbr_textures_bind_group(&pbr_material, true)?);
-                vacant_entry.insert(textures_bind_group.clone());
-                textures_bind_group
-            }
-        };
+        let primitive_mode = get_primitive_mode(&primitive_group)?;
 
-        let (vertices, geometry_buffers, wireframe_index_buffer, wireframe_index_buffer_format) =
-            build_geometry_buffers(
-                &base_renderer.device,
-                &base_renderer.limits,
-                &primitive_group,
-                buffers,
-            )?;
+        let (geometry_source, bounding_box, lods) =
+            match cached_primitives.remove(&(mesh.index(), primitive_group.index())) {
+                Some(cached_primitive) => (
+                    cached_primitive.geometry_source,
+                    cached_primitive.bounding_box,
+                    cached_primitive.lods,
+                ),
+                None => {
+                    let (geometry_source, bounding_box) =
+                        build_geometry_source(&primitive_group, buffers)?;
+                    (geometry_source, bounding_box, vec![])
+                }
+            };
 
-        let primitive_mode = crate::renderer::PrimitiveMode::Triangles;
+        let (geometry_buffers, wireframe_index_buffer) = upload_geometry_buffers(
+            &base_renderer.device,
+            &base_renderer.limits,
+            primitive_mode,
+            &geometry_source,
+            bounding_box,
+            &lods,
+        )?;
 
-        let alpha_mode = match primitive_group.material().alpha_mode() {
+        let alpha_mode = match material.alpha_mode() {
             gltf::material::AlphaMode::Opaque => crate::renderer::AlphaMode::Opaque,
             gltf::material::AlphaMode::Mask => crate::renderer::AlphaMode::Mask,
             gltf::material::AlphaMode::Blend => {
@@ -148,55 +472,162 @@ pub fn build_scene(
             }
         };
 
+        if skinned_mesh_indices.contains(&mesh.index()) {
+            skinned_mesh_vertices
+                .entry(mesh.index())
+                .or_insert_with(|| geometry_source.vertices.clone());
+        }
+
+        let (source_mesh_type, source_mesh_index) =
+            if get_mesh_type(&primitive_group)? == MeshType::Unlit {
+                let DynamicPbrParams {
+                    base_color_factor,
+                    alpha_cutoff,
+                    ..
+                } = get_dynamic_pbr_params(&material);
+
+                let base_color_texture_index = get_indexed_pbr_material(&material).base_color;
+                let textures_bind_group =
+                    match unlit_textures_bind_group_cache.entry(base_color_texture_index) {
+                        Entry::Occupied(entry) => entry.get().clone(),
+                        Entry::Vacant(vacant_entry) => {
+                            let textures_bind_group = Arc::new(
+                                base_renderer.make_unlit_textures_bind_group(
+                                    vacant_entry
+                                        .key()
+                                        .map(|texture_index| &textures[texture_index]),
+                                )?,
+                            );
+                            vacant_entry.insert(textures_bind_group.clone());
+                            textures_bind_group
+                        }
+                    };
+
+                binded_unlit_meshes.push(BindedUnlitMesh {
+                    geometry_buffers,
+                    textures_bind_group,
+                    base_color_texture_index,
+                    base_color_factor,
+                    alpha_cutoff,
+                    primitive_mode,
+                    alpha_mode,
+                });
+
+                (MeshType::Unlit, binded_unlit_meshes.len() - 1)
+            } else {
+                let dynamic_pbr_params = get_dynamic_pbr_params(&material);
+
+                let indexed_pbr_material = get_indexed_pbr_material(&material);
+                let sources = keep_mesh_sources.then(|| PbrMeshSources {
+                    geometry: geometry_source,
+                    textures: get_pbr_texture_sources(&indexed_pbr_material, &texture_sources),
+                });
+                let textures_bind_group =
+                    match textures_bind_group_cache.entry(indexed_pbr_material.clone()) {
+                        Entry::Occupied(entry) => entry.get().clone(),
+                        Entry::Vacant(vacant_entry) => {
+                            let pbr_material = vacant_entry.key().get_pbr_material(&textures);
+                            let textures_bind_group = Arc::new(
+                                base_renderer.make_pbr_textures_bind_group(&pbr_material, true)?,
+                            );
+                            vacant_entry.insert(textures_bind_group.clone());
+                            textures_bind_group
+                        }
+                    };
+
+                binded_pbr_meshes.push(BindedPbrMesh {
+                    geometry_buffers,
+                    dynamic_pbr_params,
+                    textures_bind_group,
+                    texture_indices: Some(indexed_pbr_material),
+                    sources,
+                    primitive_mode,
+                    alpha_mode,
+                });
+
+                (MeshType::Pbr, binded_pbr_meshes.len() - 1)
+            };
+
         if let Some(gltf_node_indices) = mesh_node_map.get(&mesh.index()) {
+            let links = match source_mesh_type {
+                MeshType::Pbr => &mut node_mesh_links,
+                MeshType::Unlit => &mut node_unlit_mesh_links,
+            };
             for gltf_node_index in gltf_node_indices {
-                let binded_pbr_mesh_indices =
-                    node_mesh_links.entry(*gltf_node_index).or_insert(vec![]);
-                binded_pbr_mesh_indices.push(binded_pbr_mesh_index);
+                let binded_mesh_indices = links.entry(*gltf_node_index).or_insert(vec![]);
+                binded_mesh_indices.push(source_mesh_index);
             }
         }
 
-        binded_pbr_meshes.push(BindedPbrMesh {
-            geometry_buffers,
-            dynamic_pbr_params,
-            textures_bind_group,
-            primitive_mode,
-            alpha_mode,
-        });
-
-        binded_wireframe_meshes.push(BindedWireframeMesh {
-            source_mesh_type: MeshType::Pbr,
-            source_mesh_index: binded_pbr_mesh_index,
-            index_buffer: wireframe_index_buffer,
-            index_buffer_format: wireframe_index_buffer_format,
-        });
-
-        pbr_mesh_vertices.push(vertices);
+        if let Some((wireframe_index_buffer, wireframe_index_buffer_format)) =
+            wireframe_index_buffer
+        {
+            binded_wireframe_meshes.push(BindedWireframeMesh {
+                source_mesh_type,
+                source_mesh_index,
+                index_buffer: wireframe_index_buffer,
+                index_buffer_format: wireframe_index_buffer_format,
+            });
+        }
     }
 
+    let make_unlit_mesh = |mesh_indices: &Vec<usize>| GameNodeMesh {
+        mesh_indices: mesh_indices.clone(),
+        // the color comes from the base color factor of the binded unlit mesh
+        mesh_type: GameNodeMeshType::Unlit { color: Vec3::ONE },
+        ..Default::default()
+    };
+
     // it is important that the node indices from the gltf document are preserved
     // for any of the other stuff that refers to the nodes by index such as the animations
-    let nodes: Vec<_> = document
-        .nodes()
-        .map(|node| IndexedGameNodeDesc {
-            transform: crate::transform::Transform::from(node.transform()),
-            skin_index: node.skin().map(|skin| skin.index()),
-            mesh: node_mesh_links
-                .get(&node.index())
-                .map(|mesh_indices| GameNodeMesh {
+    let mut nodes: Vec<_> = cached_nodes
+        .into_iter()
+        .enumerate()
+        .map(|(node_index, node)| IndexedGameNodeDesc {
+            transform: node.transform,
+            skin_index: node.skin_index,
+            mesh: match (
+                node_mesh_links.get(&node_index),
+                node_unlit_mesh_links.get(&node_index),
+            ) {
+                (Some(mesh_indices), _) => Some(GameNodeMesh {
                     mesh_indices: mesh_indices.clone(),
                     mesh_type: GameNodeMeshType::Pbr {
                         material_override: None,
                     },
                     ..Default::default()
                 }),
-            name: node.name().map(|name| name.to_string()),
-            parent_index: parent_index_map.get(&node.index()).copied(),
+                (None, Some(unlit_mesh_indices)) => Some(make_unlit_mesh(unlit_mesh_indices)),
+                (None, None) => None,
+            },
+            name: node.name,
+            parent_index: node.parent_index,
         })
         .collect();
 
+    // a game node can only have one mesh type, so if a gltf mesh mixes lit and unlit primitives
+    // the unlit ones are moved to an extra child node. these go after the gltf nodes so their indices are preserved
+    for node in document.nodes() {
+        if let (Some(_), Some(unlit_mesh_indices)) = (
+            node_mesh_links.get(&node.index()),
+            node_unlit_mesh_links.get(&node.index()),
+        ) {
+            nodes.push(IndexedGameNodeDesc {
+                transform: crate::transform::Transform::IDENTITY,
+                skin_index: None,
+                mesh: Some(make_unlit_mesh(unlit_mesh_indices)),
+                name: node.name().map(|name| format!("{name}_unlit")),
+                parent_index: Some(node.index()),
+            });
+        }
+    }
+
     let animations = get_animations(document, buffers)?;
 
+    let (point_lights, directional_lights) = get_lights(document);
+
+    let cameras = get_cameras(document);
+
     let skins: Vec<_> = document
         .skins()
         .enumerate()
@@ -227,12 +658,13 @@ pub fn build_scene(
                         .collect()
                 });
 
-            let skeleton_skin_node = nodes
+            let skeleton_mesh_vertices = scene_nodes
                 .iter()
-                .find(|node| node.skin_index == Some(skin_index))
-                .unwrap();
-            let skeleton_mesh_index = skeleton_skin_node.mesh.as_ref().unwrap().mesh_indices[0];
-            let skeleton_mesh_vertices = &pbr_mesh_vertices[skeleton_mesh_index];
+                .find(|node| node.skin().map(|skin| skin.index()) == Some(skin_index))
+                .and_then(|node| node.mesh())
+                .and_then(|mesh| skinned_mesh_vertices.get(&mesh.index()))
+                .map(|vertices| vertices.as_slice())
+                .unwrap_or_default();
 
             let bone_bounding_box_transforms: Vec<_> = (0..bone_inverse_bind_matrices.len())
                 .map(|bone_index| {
@@ -296,9 +728,10 @@ pub fn build_scene(
 
     let render_buffers = RenderBuffers {
         binded_pbr_meshes,
-        binded_unlit_meshes: vec![],
+        binded_unlit_meshes,
         binded_wireframe_meshes,
         textures,
+        streamed_textures,
     };
 
     /* logger_log("Scene loaded:");
@@ -324,23 +757,174 @@ pub fn build_scene(
         render_buffers.textures.len()
     )); */
 
-    let scene = Scene::new(nodes, skins, animations);
+    let scene = Scene::new(
+        nodes,
+        skins,
+        animations,
+        point_lights,
+        directional_lights,
+        cameras,
+    );
 
     Ok((scene, render_buffers))
 }
 
+// the parts of the node hierarchy that are stored in the mesh cache
+fn get_cached_nodes(document: &gltf::Document) -> Vec<CachedNode> {
+    // node index -> parent node index
+    let parent_index_map: HashMap<usize, usize> = document
+        .nodes()
+        .flat_map(|parent_node| {
+            let parent_node_index = parent_node.index();
+            parent_node
+                .children()
+                .map(move |child_node| (child_node.index(), parent_node_index))
+        })
+        .collect();
+
+    document
+        .nodes()
+        .map(|node| CachedNode {
+            name: node.name().map(|name| name.to_string()),
+            transform: crate::transform::Transform::from(node.transform()),
+            parent_index: parent_index_map.get(&node.index()).copied(),
+            skin_index: node.skin().map(|skin| skin.index()),
+        })
+        .collect()
+}
+
+pub fn build_mesh_cache(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+) -> Result<MeshCache> {
+    let mut primitives = Vec::new();
+    for mesh in document.meshes() {
+        for primitive_group in mesh.primitives() {
+            let primitive_mode = match get_primitive_mode(&primitive_group) {
+                Ok(primitive_mode) => primitive_mode,
+                Err(_) => continue,
+            };
+            let (geometry_source, bounding_box) = build_geometry_source(&primitive_group, buffers)?;
+            primitives.push(CachedPrimitive {
+                mesh_index: mesh.index(),
+                primitive_index: primitive_group.index(),
+                primitive_mode,
+                geometry_source,
+                bounding_box,
+                lods: vec![],
+            });
+        }
+    }
+    Ok(MeshCache {
+        nodes: get_cached_nodes(document),
+        primitives,
+    })
+}
+
+fn get_lights(
+    document: &gltf::Document,
+) -> (
+    Vec<IndexedPointLightComponent>,
+    Vec<IndexedDirectionalLightComponent>,
+) {
+    let mut point_lights = vec![];
+    let mut directional_lights = vec![];
+    for node in document.nodes() {
+        let light = match node.light() {
+            Some(light) => light,
+            None => continue,
+        };
+        let color = Vec3::from(light.color());
+        match light.kind() {
+            gltf::khr_lights_punctual::Kind::Directional => {
+                directional_lights.push(IndexedDirectionalLightComponent {
+                    node_index: node.index(),
+                    color,
+                    intensity: directional_light_intensity_from_lux(light.intensity()),
+                });
+            }
+            gltf::khr_lights_punctual::Kind::Point
+            | gltf::khr_lights_punctual::Kind::Spot { .. } => {
+                // TODO: the renderer doesn't support spot lights yet, so they light up the whole
+                // sphere around them instead of just the cone
+                if matches!(light.kind(), gltf::khr_lights_punctual::Kind::Spot { .. }) {
+                    logger_log(&format!(
+                        "Warning: spot lights aren't supported yet, importing the light of node {:?} as a point light",
+                        node.index()
+                    ));
+                }
+                point_lights.push(IndexedPointLightComponent {
+                    node_index: node.index(),
+                    color,
+                    intensity: point_light_intensity_from_candela(light.intensity()),
+                });
+            }
+        }
+    }
+    (point_lights, directional_lights)
+}
+
+fn get_cameras(document: &gltf::Document) -> Vec<IndexedCameraComponent> {
+    document
+        .nodes()
+        .filter_map(|node| {
+            node.camera().map(|camera| {
+                let projection = match camera.projection() {
+                    gltf::camera::Projection::Perspective(perspective) => {
+                        CameraProjection::Perspective {
+                            fov_y: perspective.yfov(),
+                            aspect_ratio: perspective.aspect_ratio(),
+                            near_plane_distance: perspective.znear(),
+                            far_plane_distance: perspective.zfar(),
+                        }
+                    }
+                    gltf::camera::Projection::Orthographic(orthographic) => {
+                        CameraProjection::Orthographic {
+                            half_width: orthographic.xmag(),
+                            half_height: orthographic.ymag(),
+                            near_plane_distance: orthographic.znear(),
+                            far_plane_distance: orthographic.zfar(),
+                        }
+                    }
+                };
+                IndexedCameraComponent {
+                    node_index: node.index(),
+                    name: camera.name().map(|name| name.to_string()),
+                    projection,
+                }
+            })
+        })
+        .collect()
+}
+
+// where the asset optimizer writes the compressed version of the image
+fn get_compressed_texture_path(gltf_path: &Path, image: &gltf::Image) -> PathBuf {
+    match image.source() {
+        gltf::image::Source::Uri { uri, .. } => {
+            texture_path_to_compressed_path(&gltf_path.parent().unwrap().join(decode_uri(uri)))
+        }
+        gltf::image::Source::View { .. } => {
+            embedded_texture_path_to_compressed_path(gltf_path, image.index())
+        }
+    }
+}
+
 fn get_textures(
     document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
     images: &[gltf::image::Data],
     materials: Vec<gltf::Material>,
     gltf_path: &Path,
     base_renderer: &BaseRenderer,
-) -> Result<Vec<Texture>, anyhow::Error> {
-    let textures = document
+) -> Result<(Vec<Texture>, Vec<StreamedTexture>), anyhow::Error> {
+    // transcoding is the slow part, so it's done for all the textures in parallel before uploading
+    let transcode_target = TranscodeTarget::from_features(base_renderer.device.features());
+    // the compressed sidecar files would have to be decoded on the cpu if the adapter doesn't
+    // support any compressed format, so the source images are used as is instead
+    let use_compressed_textures = transcode_target != TranscodeTarget::Uncompressed;
+    let transcode_inputs = document
         .textures()
         .map(|texture| {
-            let source_image_index = texture.source().index();
-
             let is_srgb = materials.iter().any(|material| {
                 vec![
                     material.emissive_texture(),
@@ -357,41 +941,175 @@ fn get_textures(
                         && material.normal_texture().unwrap().texture().index() == texture.index()
                 });
 
-            let compressed_image_data = match texture.source().source() {
-                gltf::image::Source::Uri { uri, .. } => {
-                    let compressed_texture_path = texture_path_to_compressed_path(
-                        &gltf_path.parent().unwrap().join(PathBuf::from(uri)),
-                    );
-                    if USE_TEXTURE_COMPRESSION && compressed_texture_path.try_exists()? {
-                        let texture_compressor = TextureCompressor::new();
-                        let texture_bytes = std::fs::read(compressed_texture_path)?;
-                        Some(texture_compressor.transcode_image(&texture_bytes, is_normal_map)?)
-                    } else {
-                        None
-                    }
+            let compressed_texture_path = get_compressed_texture_path(gltf_path, &texture.source());
+            // KHR_texture_basisu images are already compressed
+            let compressed_texture_bytes = if is_ktx2_image(&texture.source()) {
+                let ktx2_bytes = read_image_bytes(
+                    &texture.source(),
+                    gltf_path.parent().unwrap_or_else(|| Path::new("./")),
+                    buffers,
+                )?;
+                // fail the import before anything gets transcoded
+                check_uastc_ktx2(&ktx2_bytes).map_err(|err| {
+                    anyhow::anyhow!(
+                        "Unsupported KHR_texture_basisu image {:?}: {}",
+                        texture.source().index(),
+                        err
+                    )
+                })?;
+                Some(ktx2_bytes)
+            } else if use_compressed_textures {
+                find_compressed_texture_path(&compressed_texture_path)?
+                    .map(vfs_read)
+                    .transpose()?
+            } else {
+                None
+            };
+            // the source images only come with mip 0
+            let source_image = match compressed_texture_bytes {
+                Some(_) => None,
+                None => {
+                    let image_data = &images[texture.source().index()];
+                    let (pixels, format) = get_image_pixels(image_data, is_srgb)?;
+                    Some((pixels, format, image_data.width, image_data.height))
                 }
-                gltf::image::Source::View { .. } => None,
             };
+            anyhow::Ok((
+                texture.index(),
+                texture.name().map(String::from),
+                is_srgb,
+                is_normal_map,
+                compressed_texture_bytes,
+                source_image,
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-            let baked_mip_levels = compressed_image_data
-                .as_ref()
-                .map(|data| data.mip_count)
-                .unwrap_or(1);
-            let image_dimensions = compressed_image_data
-                .as_ref()
-                .map(|data| (data.width, data.height))
-                .unwrap_or((
-                    images[source_image_index].width,
-                    images[source_image_index].height,
+    let initial_textures = WORKER_POOL.map(
+        transcode_inputs,
+        move |(
+            texture_index,
+            label,
+            is_srgb,
+            is_normal_map,
+            compressed_texture_bytes,
+            source_image,
+        )| {
+            // only the small mips are uploaded now, the texture streamer adds the rest later
+            if let Some(texture_bytes) = compressed_texture_bytes {
+                let texture_compressor = TextureCompressor::new();
+                let image = texture_compressor.parse_image(&texture_bytes)?;
+                let info = image.info;
+                let format =
+                    TranscodedFormat::new(transcode_target, is_normal_map).get_wgpu_format(is_srgb);
+                let initial_base_mip =
+                    get_initial_base_mip(format, info.width, info.height, info.mip_count);
+                let compressed_image_data = texture_compressor.transcode_image_levels(
+                    &image,
+                    is_normal_map,
+                    transcode_target,
+                    initial_base_mip..u32::MAX,
+                )?;
+                let (width, height, mip_count) = (
+                    compressed_image_data.width,
+                    compressed_image_data.height,
+                    compressed_image_data.mip_count,
+                );
+                let (pixels, format) = get_compressed_image_pixels(compressed_image_data, is_srgb)?;
+                let streamed_texture = (initial_base_mip > 0).then(|| StreamedTexture {
+                    texture_index,
+                    source: StreamedTextureSource::Compressed {
+                        image: Arc::new(image),
+                        is_normal_map,
+                        target: transcode_target,
+                    },
+                    format,
+                    label,
+                    width: info.width,
+                    height: info.height,
+                    mip_count: info.mip_count,
+                    resident_base_mip: initial_base_mip,
+                });
+                return anyhow::Ok((
+                    InitialTextureMips {
+                        pixels,
+                        format,
+                        dimensions: (width, height),
+                        mip_count,
+                        generate_mipmaps: false,
+                    },
+                    streamed_texture,
                 ));
-            let generate_mipmaps = compressed_image_data.is_none();
+            }
 
-            let (image_pixels, texture_format) = match compressed_image_data {
-                Some(compressed_image_data) => {
-                    get_compressed_image_pixels(compressed_image_data, is_srgb)?
-                }
-                None => get_image_pixels(&images[source_image_index], is_srgb)?,
+            let (pixels, format, width, height) =
+                source_image.ok_or_else(|| anyhow::anyhow!("Texture has no image data"))?;
+            let full_mip_count = wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            }
+            .max_mips(wgpu::TextureDimension::D2);
+            let initial_base_mip = get_initial_base_mip(format, width, height, full_mip_count);
+            let is_rgba8 = matches!(
+                format,
+                wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+            );
+            if !is_rgba8 || initial_base_mip == 0 {
+                return anyhow::Ok((
+                    InitialTextureMips {
+                        pixels,
+                        format,
+                        dimensions: (width, height),
+                        mip_count: 1,
+                        generate_mipmaps: true,
+                    },
+                    None,
+                ));
+            }
+
+            // large pngs and jpgs are streamed like the compressed textures
+            let initial_pixels = get_rgba8_mips(
+                &pixels,
+                width,
+                height,
+                is_srgb,
+                initial_base_mip..full_mip_count,
+            );
+            let streamed_texture = StreamedTexture {
+                texture_index,
+                source: StreamedTextureSource::Rgba8 {
+                    pixels: Arc::new(pixels),
+                    is_srgb,
+                },
+                format,
+                label,
+                width,
+                height,
+                mip_count: full_mip_count,
+                resident_base_mip: initial_base_mip,
             };
+            anyhow::Ok((
+                InitialTextureMips {
+                    pixels: initial_pixels,
+                    format,
+                    dimensions: (
+                        (width >> initial_base_mip).max(1),
+                        (height >> initial_base_mip).max(1),
+                    ),
+                    mip_count: full_mip_count - initial_base_mip,
+                    generate_mipmaps: false,
+                },
+                Some(streamed_texture),
+            ))
+        },
+    );
+
+    let textures = document
+        .textures()
+        .zip(initial_textures)
+        .map(|(texture, initial_texture)| {
+            let (initial_mips, streamed_texture) = initial_texture?;
 
             let gltf_sampler = texture.sampler();
             let default_sampler = SamplerDescriptor {
@@ -433,14 +1151,14 @@ fn get_textures(
                 })
                 .unwrap_or((default_sampler.min_filter, default_sampler.mipmap_filter));
 
-            Texture::from_decoded_image(
+            let texture = Texture::from_decoded_image(
                 base_renderer,
-                &image_pixels,
-                image_dimensions,
-                baked_mip_levels,
+                &initial_mips.pixels,
+                initial_mips.dimensions,
+                initial_mips.mip_count,
                 texture.name(),
-                texture_format.into(),
-                generate_mipmaps,
+                initial_mips.format.into(),
+                initial_mips.generate_mipmaps,
                 &SamplerDescriptor {
                     address_mode_u,
                     address_mode_v,
@@ -449,10 +1167,72 @@ fn get_textures(
                     mipmap_filter,
                     ..Default::default()
                 },
-            )
+            )?;
+            anyhow::Ok((texture, streamed_texture))
         })
         .collect::<Result<Vec<_>, _>>()?;
-    Ok(textures)
+    let streamed_textures = textures
+        .iter()
+        .filter_map(|(_, streamed_texture)| streamed_texture.clone())
+        .collect();
+    let textures = textures.into_iter().map(|(texture, _)| texture).collect();
+    Ok((textures, streamed_textures))
+}
+
+// the mips of a texture that are uploaded when it's loaded
+struct InitialTextureMips {
+    pixels: Vec<u8>,
+    format: wgpu::TextureFormat,
+    // size of the largest mip in pixels
+    dimensions: (u32, u32),
+    mip_count: u32,
+    generate_mipmaps: bool,
+}
+
+// keeps a copy of the decoded images so they can be exported again
+fn get_texture_sources(
+    document: &gltf::Document,
+    images: &[gltf::image::Data],
+) -> Vec<Option<Arc<TextureSource>>> {
+    document
+        .textures()
+        .map(|texture| {
+            let image_data = &images[texture.source().index()];
+            // ktx2 images aren't decoded
+            if image_data.pixels.is_empty() {
+                return None;
+            }
+            match image_data.format {
+                // TODO: float images can't be written to png
+                gltf::image::Format::R32G32B32FLOAT | gltf::image::Format::R32G32B32A32FLOAT => {
+                    None
+                }
+                _ => Some(Arc::new(TextureSource {
+                    name: texture.name().map(String::from),
+                    width: image_data.width,
+                    height: image_data.height,
+                    format: image_data.format,
+                    pixels: image_data.pixels.clone(),
+                })),
+            }
+        })
+        .collect()
+}
+
+fn get_pbr_texture_sources(
+    indexed_pbr_material: &IndexedPbrMaterial,
+    texture_sources: &[Option<Arc<TextureSource>>],
+) -> PbrTextureSources {
+    let get_texture_source = |texture_index: Option<usize>| {
+        texture_index.and_then(|texture_index| texture_sources[texture_index].clone())
+    };
+    PbrTextureSources {
+        base_color: get_texture_source(indexed_pbr_material.base_color),
+        normal: get_texture_source(indexed_pbr_material.normal),
+        metallic_roughness: get_texture_source(indexed_pbr_material.metallic_roughness),
+        emissive: get_texture_source(indexed_pbr_material.emissive),
+        ambient_occlusion: get_texture_source(indexed_pbr_material.ambient_occlusion),
+    }
 }
 
 pub fn get_animations(
@@ -550,30 +1330,8 @@ fn get_compressed_image_pixels(
     image_data: CompressedTexture,
     is_srgb: bool,
 ) -> Result<(Vec<u8>, wgpu::TextureFormat)> {
-    let CompressedTexture {
-        raw: image_pixels,
-        format,
-        ..
-    } = image_data;
-    let texture_format = match format {
-        basis_universal::transcoding::TranscoderTextureFormat::BC7_RGBA => {
-            if is_srgb {
-                wgpu::TextureFormat::Bc7RgbaUnormSrgb
-            } else {
-                wgpu::TextureFormat::Bc7RgbaUnorm
-            }
-        }
-        basis_universal::transcoding::TranscoderTextureFormat::BC5_RG => {
-            wgpu::TextureFormat::Bc5RgUnorm
-        }
-        _ => {
-            anyhow::bail!(
-                "Passed unsupported compressed texture format to gltf loader: {:?}",
-                format
-            );
-        }
-    };
-    Ok((image_pixels, texture_format))
+    let texture_format = image_data.format.get_wgpu_format(is_srgb);
+    Ok((image_data.raw, texture_format))
 }
 
 fn get_image_pixels(
@@ -688,19 +1446,6 @@ fn get_indexed_pbr_material(material: &gltf::material::Material) -> IndexedPbrMa
     }
 }
 
-fn get_pbr_material<'a>(material: &IndexedPbrMaterial, textures: &'a [Texture]) -> PbrMaterial<'a> {
-    let get_texture =
-        |texture_index: Option<usize>| texture_index.map(|texture_index| &textures[texture_index]);
-
-    PbrMaterial {
-        base_color: get_texture(material.base_color),
-        normal: get_texture(material.normal),
-        emissive: get_texture(material.emissive),
-        ambient_occlusion: get_texture(material.ambient_occlusion),
-        metallic_roughness: get_texture(material.metallic_roughness),
-    }
-}
-
 fn get_dynamic_pbr_params(material: &gltf::material::Material) -> DynamicPbrParams {
     let pbr_info = material.pbr_metallic_roughness();
 
@@ -735,12 +1480,69 @@ pub fn get_buffer_slice_from_accessor<'a>(
     &buffer[first_byte_offset..last_byte_offset]
 }
 
-pub fn build_geometry_buffers(
-    device: &wgpu::Device,
-    limits: &wgpu::Limits,
+pub fn get_primitive_mode(primitive_group: &gltf::mesh::Primitive) -> Result<PrimitiveMode> {
+    match primitive_group.mode() {
+        gltf::mesh::Mode::Triangles => Ok(PrimitiveMode::Triangles),
+        gltf::mesh::Mode::Lines | gltf::mesh::Mode::LineStrip | gltf::mesh::Mode::LineLoop => {
+            Ok(PrimitiveMode::Lines)
+        }
+        gltf::mesh::Mode::Points => Ok(PrimitiveMode::Points),
+        mode => bail!("Primitive mode {:?} isn't yet supported", mode),
+    }
+}
+
+// lines and points can only be drawn unlit
+fn get_mesh_type(primitive_group: &gltf::mesh::Primitive) -> Result<MeshType> {
+    Ok(
+        if get_primitive_mode(primitive_group)? != PrimitiveMode::Triangles
+            || primitive_group.material().unlit()
+        {
+            MeshType::Unlit
+        } else {
+            MeshType::Pbr
+        },
+    )
+}
+
+/// converts the indices of line strips and line loops into a line list
+fn get_line_list_indices(mode: gltf::mesh::Mode, indices: Vec<u32>) -> Vec<u32> {
+    let is_loop = mode == gltf::mesh::Mode::LineLoop;
+    if mode != gltf::mesh::Mode::LineStrip && !is_loop {
+        return indices;
+    }
+    let mut line_list_indices = Vec::with_capacity(indices.len() * 2);
+    for segment in indices.windows(2) {
+        line_list_indices.extend([segment[0], segment[1]]);
+    }
+    if is_loop && indices.len() > 2 {
+        line_list_indices.extend([indices[indices.len() - 1], indices[0]]);
+    }
+    line_list_indices
+}
+
+/// wgpu can't draw points bigger than 1 pixel, so each point is copied into the 4 corners of a quad
+/// which gets expanded in screen space by the unlit point pipeline
+fn get_point_quads(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut quad_vertices = Vec::with_capacity(indices.len() * 4);
+    let mut quad_indices = Vec::with_capacity(indices.len() * 6);
+    for (point_index, vertex_index) in indices.iter().enumerate() {
+        let vertex = vertices[*vertex_index as usize];
+        quad_vertices.extend([vertex; 4]);
+        let first_corner = point_index as u32 * 4;
+        quad_indices.extend([0, 1, 2, 2, 1, 3].map(|corner| first_corner + corner));
+    }
+    (quad_vertices, quad_indices)
+}
+
+// index buffer and its format
+type WireframeIndexBuffer = (GpuBuffer, wgpu::IndexFormat);
+
+// does all of the cpu-side vertex processing, the result can be stored in the mesh cache
+pub fn build_geometry_source(
     primitive_group: &gltf::mesh::Primitive,
     buffers: &[gltf::buffer::Data],
-) -> Result<(Vec<Vertex>, GeometryBuffers, GpuBuffer, wgpu::IndexFormat)> {
+) -> Result<(GeometrySource, crate::collisions::Aabb)> {
+    let primitive_mode = get_primitive_mode(primitive_group)?;
     let vertex_positions = get_vertex_positions(primitive_group, buffers)?;
     let vertex_position_count = vertex_positions.len();
     let bounding_box = {
@@ -760,9 +1562,15 @@ pub fn build_geometry_buffers(
         }
     };
 
-    let indices = get_indices(primitive_group, buffers, vertex_position_count)?;
+    let indices = get_line_list_indices(
+        primitive_group.mode(),
+        get_indices(primitive_group, buffers, vertex_position_count)?,
+    );
 
-    let triangle_count = indices.len() / 3;
+    let triangle_count = match primitive_mode {
+        PrimitiveMode::Triangles => indices.len() / 3,
+        PrimitiveMode::Lines | PrimitiveMode::Points => 0,
+    };
 
     let mut triangles_as_index_tuples = Vec::with_capacity(triangle_count);
     for triangle_index in 0..triangle_count {
@@ -833,14 +1641,20 @@ pub fn build_geometry_buffers(
       );
     }
 
-    let vertex_tangents_and_bitangents = get_vertex_tangents(
-        primitive_group,
-        buffers,
-        &vertex_normals,
-        triangles_as_index_tuples,
-        &vertex_positions,
-        &vertex_tex_coords,
-    )?;
+    let vertex_tangents_and_bitangents = match primitive_mode {
+        PrimitiveMode::Triangles => get_vertex_tangents(
+            primitive_group,
+            buffers,
+            &vertex_normals,
+            triangles_as_index_tuples,
+            &vertex_positions,
+            &vertex_tex_coords,
+        )?,
+        // tangents are only needed for normal mapping, which lines and points don't do
+        PrimitiveMode::Lines | PrimitiveMode::Points => {
+            vec![(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)); vertex_position_count]
+        }
+    };
 
     let mut vertices_with_all_data = Vec::with_capacity(vertex_position_count);
     for index in 0..vertex_position_count {
@@ -859,7 +1673,31 @@ pub fn build_geometry_buffers(
         });
     }
 
-    let vertex_buffer_bytes = bytemuck::cast_slice(&vertices_with_all_data);
+    let (vertices_with_all_data, indices) = match primitive_mode {
+        PrimitiveMode::Points => get_point_quads(&vertices_with_all_data, &indices),
+        PrimitiveMode::Triangles | PrimitiveMode::Lines => (vertices_with_all_data, indices),
+    };
+
+    Ok((
+        GeometrySource {
+            vertices: vertices_with_all_data,
+            indices,
+        },
+        bounding_box,
+    ))
+}
+
+pub fn upload_geometry_buffers(
+    device: &wgpu::Device,
+    limits: &wgpu::Limits,
+    primitive_mode: PrimitiveMode,
+    geometry_source: &GeometrySource,
+    bounding_box: crate::collisions::Aabb,
+    lods: &[MeshLod],
+) -> Result<(GeometryBuffers, Option<WireframeIndexBuffer>)> {
+    let GeometrySource { vertices, indices } = geometry_source;
+
+    let vertex_buffer_bytes = bytemuck::cast_slice(vertices);
 
     if vertex_buffer_bytes.len() as u64 > limits.max_buffer_size {
         bail!("Tried to upload a vertex buffer of size {:?} which is larger than the max buffer size of {:?}", vertex_buffer_bytes.len(), limits.max_buffer_size);
@@ -914,32 +1752,49 @@ pub fn build_geometry_buffers(
         ))
     };
 
-    let (index_buffer, index_buffer_format) = into_index_buffer(&indices)?;
-
-    let mut wireframe_indices = Vec::with_capacity(indices.len() * 2);
-    for triangle in indices.chunks(3) {
-        wireframe_indices.extend([
-            triangle[0],
-            triangle[1],
-            triangle[1],
-            triangle[2],
-            triangle[2],
-            triangle[0],
-        ]);
-    }
-    let (wireframe_index_buffer, wireframe_index_buffer_format) =
-        into_index_buffer(&wireframe_indices)?;
+    let (index_buffer, index_buffer_format) = into_index_buffer(indices)?;
+
+    let lods = lods
+        .iter()
+        .map(|lod| {
+            let (index_buffer, index_buffer_format) = into_index_buffer(&lod.indices)?;
+            Ok(LodIndexBuffer {
+                index_buffer,
+                index_buffer_format,
+                error: lod.error,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // points don't have a wireframe, they're always drawn as quads
+    let wireframe_index_buffer = match primitive_mode {
+        PrimitiveMode::Triangles => {
+            let mut wireframe_indices = Vec::with_capacity(indices.len() * 2);
+            for triangle in indices.chunks(3) {
+                wireframe_indices.extend([
+                    triangle[0],
+                    triangle[1],
+                    triangle[1],
+                    triangle[2],
+                    triangle[2],
+                    triangle[0],
+                ]);
+            }
+            Some(into_index_buffer(&wireframe_indices)?)
+        }
+        PrimitiveMode::Lines => Some(into_index_buffer(indices)?),
+        PrimitiveMode::Points => None,
+    };
 
     Ok((
-        vertices_with_all_data,
         GeometryBuffers {
             vertex_buffer,
             index_buffer,
             index_buffer_format,
             bounding_box,
+            lods,
         },
         wireframe_index_buffer,
-        wireframe_index_buffer_format,
     ))
 }
 
@@ -1157,9 +2012,11 @@ fn get_vertex_normals(
                 });
             (0..vertex_position_count)
                 .map(|vertex_index| {
-                    let (accumulated_normal, count) =
-                        vertex_normal_accumulators.get(&vertex_index).unwrap();
-                    *accumulated_normal / (*count as f32)
+                    // lines and points aren't part of any triangle so they get an arbitrary normal
+                    vertex_normal_accumulators
+                        .get(&vertex_index)
+                        .map(|(accumulated_normal, count)| *accumulated_normal / (*count as f32))
+                        .unwrap_or(Vec3::new(0.0, 1.0, 0.0))
                 })
                 .collect()
         });
@@ -1302,3 +2159,513 @@ fn get_indices(
         })?;
     Ok(indices)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ktx2::{UastcChannels, UastcKtx2};
+    use crate::skinning::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn imports_basisu_textures() {
+        let folder = std::env::temp_dir().join(format!(
+            "ikari_gltf_loader_basisu_test_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let ktx2_bytes = UastcKtx2 {
+            width: 4,
+            height: 4,
+            is_srgb: true,
+            channels: UastcChannels::Rgb,
+            levels: vec![vec![0; 16]],
+        }
+        .to_bytes(Some(0))
+        .unwrap();
+        std::fs::write(folder.join("texture.ktx2"), &ktx2_bytes).unwrap();
+        image::RgbaImage::new(2, 2)
+            .save(folder.join("fallback.png"))
+            .unwrap();
+
+        let gltf_path = folder.join("scene.gltf");
+        std::fs::write(
+            &gltf_path,
+            r#"{
+                "asset": { "version": "2.0" },
+                "extensionsUsed": ["KHR_texture_basisu"],
+                "images": [{ "uri": "fallback.png" }, { "uri": "texture.ktx2" }],
+                "textures": [
+                    { "source": 0, "extensions": { "KHR_texture_basisu": { "source": 1 } } },
+                    { "extensions": { "KHR_texture_basisu": { "source": 1 } } },
+                    { "source": 0 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let (document, buffers, images) = import_gltf(&gltf_path).unwrap();
+        let ktx2_image_bytes =
+            read_image_bytes(&document.images().nth(1).unwrap(), &folder, &buffers).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        let texture_sources: Vec<_> = document
+            .textures()
+            .map(|texture| texture.source().index())
+            .collect();
+        assert_eq!(texture_sources, vec![1, 1, 0]);
+
+        assert_eq!((images[0].width, images[0].height), (2, 2));
+        assert!(images[1].pixels.is_empty());
+
+        assert!(is_ktx2_image(&document.images().nth(1).unwrap()));
+        assert_eq!(ktx2_image_bytes, ktx2_bytes);
+    }
+
+    #[test]
+    fn compressed_textures_are_found_for_encoded_uris() {
+        let gltf::Gltf { document, .. } = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "images": [{ "uri": "bark%20color.png" }]
+            }"#,
+        )
+        .unwrap();
+        let gltf_path = Path::new("./forest/scene.gltf");
+
+        assert_eq!(
+            get_compressed_texture_path(gltf_path, &document.images().next().unwrap()),
+            texture_path_to_compressed_path(Path::new("./forest/bark color.png"))
+        );
+    }
+
+    #[test]
+    fn source_paths_of_encoded_uris_are_decoded() {
+        let gltf::Gltf { document, .. } = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "images": [{ "uri": "bark%20color.png" }]
+            }"#,
+        )
+        .unwrap();
+        let source_paths = get_gltf_source_paths(Path::new("./forest/scene.gltf"), &document);
+
+        let compressed_texture_path =
+            texture_path_to_compressed_path(Path::new("./forest/bark color.png"));
+        assert!(source_paths.contains(&PathBuf::from("./forest/bark color.png")));
+        assert!(source_paths.contains(&compressed_texture_path));
+        assert!(source_paths.contains(&compressed_texture_path.with_extension("bin")));
+    }
+
+    #[test]
+    fn imports_lights_and_cameras_attached_to_nodes() {
+        let gltf::Gltf { document, .. } = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "extensionsUsed": ["KHR_lights_punctual"],
+                "extensions": {
+                    "KHR_lights_punctual": {
+                        "lights": [
+                            { "type": "directional", "color": [1.0, 0.5, 0.25], "intensity": 2.0 },
+                            { "type": "point", "intensity": 54351.41 },
+                            { "type": "spot", "intensity": 54351.41, "spot": {} }
+                        ]
+                    }
+                },
+                "cameras": [
+                    { "type": "perspective", "perspective": { "yfov": 0.8, "znear": 0.1 } },
+                    {
+                        "type": "orthographic",
+                        "orthographic": { "xmag": 2.0, "ymag": 1.0, "znear": 0.1, "zfar": 10.0 }
+                    }
+                ],
+                "nodes": [
+                    { "name": "pivot", "translation": [0.0, 5.0, 0.0], "children": [1] },
+                    {
+                        "name": "sun",
+                        "rotation": [-0.70710678, 0.0, 0.0, 0.70710678],
+                        "extensions": { "KHR_lights_punctual": { "light": 0 } }
+                    },
+                    { "name": "lamp", "extensions": { "KHR_lights_punctual": { "light": 1 } } },
+                    { "name": "spot", "extensions": { "KHR_lights_punctual": { "light": 2 } } },
+                    { "name": "camera", "camera": 0 },
+                    { "name": "map camera", "camera": 1 }
+                ],
+                "scenes": [{ "nodes": [0, 2, 3, 4, 5] }]
+            }"#,
+        )
+        .unwrap();
+
+        let (point_lights, directional_lights) = get_lights(&document);
+        let cameras = get_cameras(&document);
+        let nodes = get_cached_nodes(&document)
+            .into_iter()
+            .map(|node| IndexedGameNodeDesc {
+                transform: node.transform,
+                skin_index: node.skin_index,
+                mesh: None,
+                name: node.name,
+                parent_index: node.parent_index,
+            })
+            .collect();
+        let mut scene = Scene::new(
+            nodes,
+            vec![],
+            vec![],
+            point_lights,
+            directional_lights,
+            cameras,
+        );
+        let pivot_id = scene.find_node_by_name("pivot").unwrap();
+        let sun_id = scene.find_node_by_name("sun").unwrap();
+
+        // spot lights are imported as point lights
+        let point_light_node_ids: Vec<_> = scene
+            .point_lights
+            .iter()
+            .map(|point_light| point_light.node_id)
+            .collect();
+        assert_eq!(
+            point_light_node_ids,
+            vec![
+                scene.find_node_by_name("lamp").unwrap(),
+                scene.find_node_by_name("spot").unwrap()
+            ]
+        );
+        assert_abs_diff_eq!(scene.point_lights[0].intensity, 1.0, epsilon = 0.001);
+
+        assert_eq!(scene.directional_lights.len(), 1);
+        let sun = &scene.directional_lights[0];
+        assert_eq!(sun.node_id, Some(sun_id));
+        assert_eq!(sun.color, Vec3::new(1.0, 0.5, 0.25));
+        assert_eq!(sun.intensity, 2.0);
+        assert_abs_diff_eq!(sun.position, Vec3::new(0.0, 5.0, 0.0), epsilon = 0.0001);
+        assert_abs_diff_eq!(sun.direction, Vec3::new(0.0, -1.0, 0.0), epsilon = 0.0001);
+
+        assert_eq!(scene.cameras.len(), 2);
+        assert_eq!(
+            scene.cameras[0].projection,
+            CameraProjection::Perspective {
+                fov_y: 0.8,
+                aspect_ratio: None,
+                near_plane_distance: 0.1,
+                far_plane_distance: None,
+            }
+        );
+        assert_eq!(
+            scene.cameras[1].projection,
+            CameraProjection::Orthographic {
+                half_width: 2.0,
+                half_height: 1.0,
+                near_plane_distance: 0.1,
+                far_plane_distance: 10.0,
+            }
+        );
+        assert_eq!(
+            scene.cameras[1].node_id,
+            scene.find_node_by_name("map camera").unwrap()
+        );
+
+        // the directional light follows its node
+        let mut point_lights = std::mem::take(&mut scene.point_lights);
+        let mut directional_lights = std::mem::take(&mut scene.directional_lights);
+        scene
+            .get_node_mut(pivot_id)
+            .unwrap()
+            .transform
+            .set_position(Vec3::new(1.0, 2.0, 3.0));
+        update_light_nodes(&scene, &mut point_lights, &mut directional_lights);
+        assert_abs_diff_eq!(
+            directional_lights[0].position,
+            Vec3::new(1.0, 2.0, 3.0),
+            epsilon = 0.0001
+        );
+
+        // and goes away with it
+        scene.remove_node_recursive(pivot_id);
+        update_light_nodes(&scene, &mut point_lights, &mut directional_lights);
+        assert!(directional_lights.is_empty());
+        assert_eq!(point_lights.len(), 2);
+
+        scene.remove_node(scene.find_node_by_name("map camera").unwrap());
+        assert_eq!(scene.cameras.len(), 1);
+    }
+
+    #[test]
+    fn unlit_materials_and_skinned_unlit_meshes_are_imported_unlit() {
+        let gltf::Gltf { document, .. } = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "extensionsUsed": ["KHR_materials_unlit"],
+                "materials": [
+                    {
+                        "pbrMetallicRoughness": {
+                            "baseColorFactor": [1.0, 0.0, 0.0, 0.5],
+                            "baseColorTexture": { "index": 0 }
+                        },
+                        "alphaMode": "MASK",
+                        "alphaCutoff": 0.25,
+                        "extensions": { "KHR_materials_unlit": {} }
+                    },
+                    {}
+                ],
+                "meshes": [
+                    {
+                        "primitives": [
+                            { "attributes": { "POSITION": 0 }, "material": 0 },
+                            { "attributes": { "POSITION": 0 }, "material": 1 },
+                            { "attributes": { "POSITION": 0 }, "material": 1, "mode": 1 },
+                            { "attributes": { "POSITION": 0 }, "material": 1, "mode": 4 }
+                        ]
+                    }
+                ],
+                "buffers": [{ "byteLength": 36 }],
+                "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+                "accessors": [
+                    {
+                        "bufferView": 0,
+                        "componentType": 5126,
+                        "count": 3,
+                        "type": "VEC3",
+                        "min": [0.0, 0.0, 0.0],
+                        "max": [1.0, 1.0, 0.0]
+                    }
+                ],
+                "textures": [{ "source": 0 }],
+                "images": [{ "uri": "unused.png" }]
+            }"#,
+        )
+        .unwrap();
+        let primitives: Vec<_> = document.meshes().next().unwrap().primitives().collect();
+        let mesh_types: Vec<_> = primitives
+            .iter()
+            .map(|primitive| get_mesh_type(primitive).unwrap())
+            .collect();
+        assert_eq!(
+            mesh_types,
+            vec![
+                MeshType::Unlit,
+                MeshType::Pbr,
+                MeshType::Unlit,
+                MeshType::Pbr
+            ]
+        );
+
+        let unlit_material = primitives[0].material();
+        let DynamicPbrParams {
+            base_color_factor,
+            alpha_cutoff,
+            ..
+        } = get_dynamic_pbr_params(&unlit_material);
+        assert_eq!(base_color_factor, Vec4::new(1.0, 0.0, 0.0, 0.5));
+        assert_eq!(alpha_cutoff, 0.25);
+        assert_eq!(
+            get_indexed_pbr_material(&unlit_material).base_color,
+            Some(0)
+        );
+
+        // skinned unlit meshes get their bones like the pbr ones
+        let mut scene = Scene::new(
+            vec![
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: Some(0),
+                    mesh: Some(GameNodeMesh {
+                        mesh_indices: vec![3],
+                        mesh_type: GameNodeMeshType::Unlit { color: Vec3::ONE },
+                        ..Default::default()
+                    }),
+                    name: None,
+                    parent_index: None,
+                },
+                IndexedGameNodeDesc {
+                    transform: TransformBuilder::new()
+                        .position(Vec3::new(0.0, 1.0, 0.0))
+                        .build(),
+                    skin_index: None,
+                    mesh: None,
+                    name: Some("bone".to_string()),
+                    parent_index: Some(0),
+                },
+            ],
+            vec![IndexedSkin {
+                bone_inverse_bind_matrices: vec![Mat4::IDENTITY],
+                bone_node_indices: vec![1],
+                bone_bounding_box_transforms: vec![crate::transform::Transform::IDENTITY],
+            }],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        scene.recompute_global_node_transforms();
+        let all_bone_transforms = get_all_bone_data(&scene, 256);
+        let bones_start_index = all_bone_transforms.get_start_index(MeshType::Unlit, 3);
+        assert_ne!(bones_start_index, 0);
+        assert_eq!(all_bone_transforms.get_start_index(MeshType::Pbr, 3), 0);
+        let bone_transform: &[Mat4] = bytemuck::cast_slice(
+            &all_bone_transforms.buffer[bones_start_index as usize
+                ..bones_start_index as usize + std::mem::size_of::<Mat4>()],
+        );
+        assert_eq!(
+            bone_transform[0],
+            Mat4::from_translation(Vec3::new(0.0, 1.0, 0.0))
+        );
+    }
+
+    fn make_test_vertex(x: f32) -> Vertex {
+        Vertex {
+            position: [x, 0.0, 0.0],
+            ..bytemuck::Zeroable::zeroed()
+        }
+    }
+
+    #[test]
+    fn line_strips_and_loops_become_line_lists() {
+        use gltf::mesh::Mode;
+
+        assert_eq!(
+            get_line_list_indices(Mode::Lines, vec![0, 1, 2, 3]),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(
+            get_line_list_indices(Mode::LineStrip, vec![0, 1, 2, 3]),
+            vec![0, 1, 1, 2, 2, 3]
+        );
+        assert_eq!(
+            get_line_list_indices(Mode::LineLoop, vec![3, 1, 2]),
+            vec![3, 1, 1, 2, 2, 3]
+        );
+        // a loop of two points is just one line
+        assert_eq!(
+            get_line_list_indices(Mode::LineLoop, vec![0, 1]),
+            vec![0, 1]
+        );
+        assert!(get_line_list_indices(Mode::LineStrip, vec![0]).is_empty());
+        assert!(get_line_list_indices(Mode::LineStrip, vec![]).is_empty());
+        assert!(get_line_list_indices(Mode::LineLoop, vec![]).is_empty());
+    }
+
+    #[test]
+    fn points_are_expanded_into_quads() {
+        let vertices: Vec<_> = (0..3).map(|x| make_test_vertex(x as f32)).collect();
+        let (quad_vertices, quad_indices) = get_point_quads(&vertices, &[2, 0]);
+        let quad_positions: Vec<_> = quad_vertices
+            .iter()
+            .map(|vertex| vertex.position[0])
+            .collect();
+        assert_eq!(quad_positions, vec![2.0, 2.0, 2.0, 2.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(quad_indices, vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7]);
+
+        let (quad_vertices, quad_indices) = get_point_quads(&vertices, &[]);
+        assert!(quad_vertices.is_empty());
+        assert!(quad_indices.is_empty());
+    }
+
+    #[test]
+    fn non_indexed_lines_and_points_use_every_vertex() {
+        let gltf::Gltf { document, .. } = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "meshes": [
+                    {
+                        "primitives": [
+                            { "attributes": { "POSITION": 0 }, "mode": 3 },
+                            { "attributes": { "POSITION": 0 }, "mode": 2 },
+                            { "attributes": { "POSITION": 0 }, "mode": 0 }
+                        ]
+                    }
+                ],
+                "buffers": [{ "byteLength": 36 }],
+                "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+                "accessors": [
+                    {
+                        "bufferView": 0,
+                        "componentType": 5126,
+                        "count": 3,
+                        "type": "VEC3",
+                        "min": [0.0, 0.0, 0.0],
+                        "max": [2.0, 0.0, 0.0]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 2.0, 0.0, 0.0];
+        let buffers = vec![gltf::buffer::Data(
+            bytemuck::cast_slice(&positions).to_vec(),
+        )];
+        let primitives: Vec<_> = document.meshes().next().unwrap().primitives().collect();
+
+        let (line_strip, _) = build_geometry_source(&primitives[0], &buffers).unwrap();
+        assert_eq!(line_strip.vertices.len(), 3);
+        assert_eq!(line_strip.indices, vec![0, 1, 1, 2]);
+
+        let (line_loop, _) = build_geometry_source(&primitives[1], &buffers).unwrap();
+        assert_eq!(line_loop.indices, vec![0, 1, 1, 2, 2, 0]);
+
+        let (points, bounding_box) = build_geometry_source(&primitives[2], &buffers).unwrap();
+        assert_eq!(points.vertices.len(), 12);
+        assert_eq!(points.indices.len(), 18);
+        assert_eq!(points.vertices[8].position, [2.0, 0.0, 0.0]);
+        assert_eq!(bounding_box.max, Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn vertex_buffers_are_skipped_when_requested() {
+        let folder = std::env::temp_dir().join(format!(
+            "ikari_gltf_loader_skip_buffers_test_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("mesh.bin"), [0u8; 36]).unwrap();
+        std::fs::write(
+            folder.join("skin.bin"),
+            bytemuck::cast_slice(&Mat4::IDENTITY.to_cols_array()),
+        )
+        .unwrap();
+
+        let gltf_path = folder.join("scene.gltf");
+        std::fs::write(
+            &gltf_path,
+            r#"{
+                "asset": { "version": "2.0" },
+                "nodes": [{ "mesh": 0, "skin": 0 }, {}],
+                "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+                "skins": [{ "joints": [1], "inverseBindMatrices": 1 }],
+                "buffers": [
+                    { "uri": "mesh.bin", "byteLength": 36 },
+                    { "uri": "skin.bin", "byteLength": 64 }
+                ],
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 36 },
+                    { "buffer": 1, "byteLength": 64 }
+                ],
+                "accessors": [
+                    {
+                        "bufferView": 0,
+                        "componentType": 5126,
+                        "count": 3,
+                        "type": "VEC3",
+                        "min": [0.0, 0.0, 0.0],
+                        "max": [0.0, 0.0, 0.0]
+                    },
+                    { "bufferView": 1, "componentType": 5126, "count": 1, "type": "MAT4" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let (_, buffers, _) =
+            import_gltf_data(&gltf_path, open_gltf(&gltf_path).unwrap(), true).unwrap();
+        let (_, all_buffers, _) = import_gltf(&gltf_path).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert!(buffers[0].is_empty());
+        assert_eq!(buffers[1].len(), 64);
+        assert_eq!(all_buffers[0].len(), 36);
+    }
+}
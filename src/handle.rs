@@ -0,0 +1,240 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// a reference counted index into one of the renderer's resource lists. the index is updated when
+// the list is compacted, so it must be read through the handle each time it's used
+pub struct Handle<T> {
+    slot: Arc<HandleSlot>,
+    // shared with the pool, see HandlePool::take_dropped
+    dropped: Arc<AtomicBool>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+struct HandleSlot {
+    index: AtomicUsize,
+    // number of handles to the resource. it's counted separately from the arc so that exactly
+    // one of several handles that are dropped at the same time sees the last one go away
+    ref_count: AtomicUsize,
+}
+
+impl<T> Handle<T> {
+    fn new(slot: Arc<HandleSlot>, dropped: Arc<AtomicBool>) -> Self {
+        slot.ref_count.fetch_add(1, Ordering::Relaxed);
+        Self {
+            slot,
+            dropped,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.slot.index.load(Ordering::Relaxed)
+    }
+
+    // number of handles to the resource
+    pub fn ref_count(&self) -> usize {
+        self.slot.ref_count.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.slot.clone(), self.dropped.clone())
+    }
+}
+
+impl<T> Drop for Handle<T> {
+    fn drop(&mut self) {
+        if self.slot.ref_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.dropped.store(true, Ordering::Release);
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.slot).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.index()).finish()
+    }
+}
+
+// keeps one handle slot per element of a resource list, the two must be pushed to together
+pub struct HandlePool<T> {
+    slots: Vec<Arc<HandleSlot>>,
+    dropped: Arc<AtomicBool>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> HandlePool<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            dropped: Arc::new(AtomicBool::new(false)),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // adds the slot of the resource that was just pushed to the list
+    pub fn push(&mut self) -> Handle<T> {
+        let slot = Arc::new(HandleSlot {
+            index: AtomicUsize::new(self.slots.len()),
+            ref_count: AtomicUsize::new(0),
+        });
+        self.slots.push(slot.clone());
+        Handle::new(slot, self.dropped.clone())
+    }
+
+    pub fn get(&self, index: usize) -> Option<Handle<T>> {
+        self.slots
+            .get(index)
+            .map(|slot| Handle::new(slot.clone(), self.dropped.clone()))
+    }
+
+    // whether the last handle to any of the resources was dropped since the last call
+    pub fn take_dropped(&self) -> bool {
+        self.dropped.swap(false, Ordering::Acquire)
+    }
+
+    pub fn is_referenced(&self, index: usize) -> bool {
+        self.slots[index].ref_count.load(Ordering::Acquire) > 0
+    }
+
+    // removes the resources that have no handles left and aren't used according to is_used,
+    // keeping the order of the rest. returns the new index of each old index, or None if
+    // nothing was removed
+    pub fn remove_unused<R>(
+        &mut self,
+        resources: &mut Vec<R>,
+        mut is_used: impl FnMut(usize) -> bool,
+    ) -> Option<Vec<Option<usize>>> {
+        assert_eq!(
+            self.slots.len(),
+            resources.len(),
+            "Handle pool is out of sync with its resources"
+        );
+
+        let keep: Vec<bool> = (0..self.slots.len())
+            .map(|index| self.is_referenced(index) || is_used(index))
+            .collect();
+        if keep.iter().all(|keep| *keep) {
+            return None;
+        }
+
+        let mut new_index = 0;
+        let index_map: Vec<Option<usize>> = keep
+            .iter()
+            .map(|keep| {
+                keep.then(|| {
+                    new_index += 1;
+                    new_index - 1
+                })
+            })
+            .collect();
+
+        let mut old_index = 0;
+        resources.retain(|_| {
+            old_index += 1;
+            keep[old_index - 1]
+        });
+        let mut old_index = 0;
+        self.slots.retain(|_| {
+            old_index += 1;
+            keep[old_index - 1]
+        });
+        for (slot_index, slot) in self.slots.iter().enumerate() {
+            slot.index.store(slot_index, Ordering::Relaxed);
+        }
+
+        Some(index_map)
+    }
+}
+
+impl<T> Default for HandlePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused_resources_are_removed_and_handles_follow() {
+        let mut resources = vec!["a", "b", "c", "d"];
+        let mut pool: HandlePool<&str> = HandlePool::new();
+        let handles: Vec<_> = resources.iter().map(|_| pool.push()).collect();
+        let [a, b, c, d]: [Handle<&str>; 4] = handles.try_into().unwrap();
+
+        assert_eq!(pool.remove_unused(&mut resources, |_| false), None);
+        assert!(!pool.take_dropped());
+
+        drop(a);
+        drop(c);
+        assert!(pool.take_dropped());
+        assert!(!pool.take_dropped());
+        let c_is_used = |index| index == 2;
+        let index_map = pool.remove_unused(&mut resources, c_is_used);
+        assert_eq!(index_map, Some(vec![None, Some(0), Some(1), Some(2)]));
+        assert_eq!(resources, vec!["b", "c", "d"]);
+        assert_eq!(resources[b.index()], "b");
+        assert_eq!(resources[d.index()], "d");
+
+        let b_clone = b.clone();
+        assert_eq!(b_clone, b);
+        assert_eq!(b.ref_count(), 2);
+        assert_ne!(b, d);
+
+        drop(b);
+        assert!(!pool.take_dropped());
+        drop(b_clone);
+        assert!(pool.take_dropped());
+        pool.remove_unused(&mut resources, |_| false);
+        assert_eq!(resources, vec!["d"]);
+        assert_eq!(d.index(), 0);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn drops_on_different_threads_are_noticed() {
+        let mut pool: HandlePool<()> = HandlePool::new();
+        for _ in 0..100 {
+            let handle = pool.push();
+            let clones: Vec<_> = (0..4).map(|_| handle.clone()).collect();
+            drop(handle);
+            let threads: Vec<_> = clones
+                .into_iter()
+                .map(|clone| std::thread::spawn(move || drop(clone)))
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert!(pool.take_dropped());
+            assert!(!pool.is_referenced(pool.len() - 1));
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use anyhow::{bail, Result};
+
+// see https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+
+// «KTX 20»\r\n\x1A\n
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+// basis universal payloads don't have a vulkan format
+const VK_FORMAT_UNDEFINED: u32 = 0;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+// khronos data format descriptor values
+const DFD_VERSION_1_3: u32 = 2;
+// basic block with a single sample
+const DFD_BASIC_BLOCK_SIZE: usize = 24 + 16;
+const DFD_MODEL_ETC1S: u8 = 163;
+const DFD_MODEL_UASTC: u8 = 166;
+const DFD_PRIMARIES_BT709: u8 = 1;
+const DFD_TRANSFER_LINEAR: u8 = 1;
+const DFD_TRANSFER_SRGB: u8 = 2;
+
+const UASTC_BLOCK_SIZE: usize = 16;
+const UASTC_BLOCK_DIMENSION: u32 = 4;
+
+const WRITER_ID: &str = "ikari";
+
+// the channels that are stored in the uastc blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UastcChannels {
+    Rgb,
+    Rgba,
+    Rrr,
+    // normal maps, x in red and y in alpha
+    Rrrg,
+    Rg,
+}
+
+impl UastcChannels {
+    fn from_id(id: u8) -> Result<Self> {
+        Ok(match id {
+            0 => Self::Rgb,
+            3 => Self::Rgba,
+            4 => Self::Rrr,
+            5 => Self::Rrrg,
+            6 => Self::Rg,
+            _ => bail!("Unknown uastc channel id {:?}", id),
+        })
+    }
+
+    fn get_id(self) -> u8 {
+        match self {
+            Self::Rgb => 0,
+            Self::Rgba => 3,
+            Self::Rrr => 4,
+            Self::Rrrg => 5,
+            Self::Rg => 6,
+        }
+    }
+
+    pub fn has_alpha(self) -> bool {
+        matches!(self, Self::Rgba | Self::Rrrg)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UastcKtx2 {
+    pub width: u32,
+    pub height: u32,
+    pub is_srgb: bool,
+    pub channels: UastcChannels,
+    // uastc blocks of each mip level, largest first
+    pub levels: Vec<Vec<u8>>,
+}
+
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(&KTX2_IDENTIFIER)
+}
+
+pub fn get_level_dimensions(width: u32, height: u32, level: usize) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+pub fn get_level_block_counts(width: u32, height: u32, level: usize) -> (u32, u32) {
+    let (level_width, level_height) = get_level_dimensions(width, height, level);
+    (
+        (level_width + UASTC_BLOCK_DIMENSION - 1) / UASTC_BLOCK_DIMENSION,
+        (level_height + UASTC_BLOCK_DIMENSION - 1) / UASTC_BLOCK_DIMENSION,
+    )
+}
+
+// the parts of the header that are needed to read the levels
+struct Ktx2Header {
+    width: u32,
+    height: u32,
+    level_count: usize,
+    supercompression_scheme: u32,
+    transfer_function: u8,
+    channel_id: u8,
+}
+
+// only checks the header, so unsupported files can be rejected before reading their levels
+pub fn check_uastc_ktx2(bytes: &[u8]) -> Result<()> {
+    read_header(bytes).map(|_| ())
+}
+
+fn read_header(bytes: &[u8]) -> Result<Ktx2Header> {
+    if !is_ktx2(bytes) {
+        bail!("Missing ktx2 identifier");
+    }
+    if bytes.len() < HEADER_SIZE {
+        bail!("Ktx2 header is truncated");
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    // 0 means that the mips should be generated by the loader
+    let level_count = read_u32(bytes, 40)?.max(1) as usize;
+    let supercompression_scheme = read_u32(bytes, 44)?;
+    let dfd_offset = read_u32(bytes, 48)? as usize;
+
+    if vk_format != VK_FORMAT_UNDEFINED {
+        bail!(
+            "Only basis universal ktx2 files are supported, found vkFormat {:?}",
+            vk_format
+        );
+    }
+    if depth > 1 || layer_count > 1 || face_count != 1 {
+        bail!("Only 2d ktx2 textures are supported");
+    }
+
+    let color_model = read_u8(bytes, dfd_offset + 4 + 8)?;
+    let transfer_function = read_u8(bytes, dfd_offset + 4 + 10)?;
+    // the channel id is in the lower 4 bits, the upper ones are qualifier flags
+    let channel_id = read_u8(bytes, dfd_offset + 4 + 24 + 3)? & 0xF;
+    match color_model {
+        DFD_MODEL_UASTC => {}
+        DFD_MODEL_ETC1S => {
+            bail!(
+                "ETC1S/BasisLZ ktx2 files are not supported, re-encode them as UASTC, e.g. with toktx --encode uastc"
+            )
+        }
+        _ => bail!("Unsupported ktx2 color model {:?}", color_model),
+    }
+
+    Ok(Ktx2Header {
+        width,
+        height,
+        level_count,
+        supercompression_scheme,
+        transfer_function,
+        channel_id,
+    })
+}
+
+impl UastcKtx2 {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Ktx2Header {
+            width,
+            height,
+            level_count,
+            supercompression_scheme,
+            transfer_function,
+            channel_id,
+        } = read_header(bytes)?;
+
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry_offset = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+                let offset = read_u64(bytes, entry_offset)? as usize;
+                let length = read_u64(bytes, entry_offset + 8)? as usize;
+                let level_bytes = offset
+                    .checked_add(length)
+                    .and_then(|end| bytes.get(offset..end))
+                    .ok_or_else(|| anyhow::anyhow!("Ktx2 level {:?} is out of bounds", level))?;
+                let level_bytes = match supercompression_scheme {
+                    SUPERCOMPRESSION_NONE => level_bytes.to_vec(),
+                    SUPERCOMPRESSION_ZSTD => zstd::stream::decode_all(level_bytes)?,
+                    SUPERCOMPRESSION_BASIS_LZ => {
+                        bail!("BasisLZ supercompression is not supported")
+                    }
+                    _ => bail!(
+                        "Unknown ktx2 supercompression scheme {:?}",
+                        supercompression_scheme
+                    ),
+                };
+
+                let (blocks_x, blocks_y) = get_level_block_counts(width, height, level);
+                let expected_length = (blocks_x * blocks_y) as usize * UASTC_BLOCK_SIZE;
+                if level_bytes.len() != expected_length {
+                    bail!(
+                        "Ktx2 level {:?} has {:?} bytes, expected {:?}",
+                        level,
+                        level_bytes.len(),
+                        expected_length
+                    );
+                }
+                Ok(level_bytes)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            width,
+            height,
+            is_srgb: transfer_function == DFD_TRANSFER_SRGB,
+            channels: UastcChannels::from_id(channel_id)?,
+            levels,
+        })
+    }
+
+    // zstd_level: None writes the levels without supercompression
+    pub fn to_bytes(&self, zstd_level: Option<i32>) -> Result<Vec<u8>> {
+        let level_count = self.levels.len();
+        let (supercompression_scheme, level_alignment) = match zstd_level {
+            Some(_) => (SUPERCOMPRESSION_ZSTD, 1),
+            None => (SUPERCOMPRESSION_NONE, UASTC_BLOCK_SIZE),
+        };
+        let stored_levels: Vec<Vec<u8>> = match zstd_level {
+            Some(zstd_level) => self
+                .levels
+                .iter()
+                .map(|level| Ok(zstd::stream::encode_all(&level[..], zstd_level)?))
+                .collect::<Result<_>>()?,
+            None => self.levels.clone(),
+        };
+
+        let dfd = self.get_dfd();
+        let kvd = get_key_value_data(&[("KTXwriter", WRITER_ID)]);
+
+        let dfd_offset = HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE;
+        let kvd_offset = dfd_offset + dfd.len();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&KTX2_IDENTIFIER);
+        for value in [
+            VK_FORMAT_UNDEFINED,
+            1, // type size
+            self.width,
+            self.height,
+            0, // depth
+            0, // layer count
+            1, // face count
+            level_count as u32,
+            supercompression_scheme,
+            dfd_offset as u32,
+            dfd.len() as u32,
+            kvd_offset as u32,
+            kvd.len() as u32,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        // no supercompression global data
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+
+        // the level index is filled in once the level offsets are known
+        let level_index_offset = bytes.len();
+        bytes.resize(dfd_offset, 0);
+        bytes.extend_from_slice(&dfd);
+        bytes.extend_from_slice(&kvd);
+
+        // levels are stored smallest first
+        for level in (0..level_count).rev() {
+            while bytes.len() % level_alignment != 0 {
+                bytes.push(0);
+            }
+            let entry_offset = level_index_offset + level * LEVEL_INDEX_ENTRY_SIZE;
+            let entry = [
+                bytes.len() as u64,
+                stored_levels[level].len() as u64,
+                self.levels[level].len() as u64,
+            ];
+            for (i, value) in entry.iter().enumerate() {
+                bytes[entry_offset + i * 8..entry_offset + (i + 1) * 8]
+                    .copy_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&stored_levels[level]);
+        }
+
+        Ok(bytes)
+    }
+
+    fn get_dfd(&self) -> Vec<u8> {
+        let mut dfd = Vec::with_capacity(4 + DFD_BASIC_BLOCK_SIZE);
+        dfd.extend_from_slice(&((4 + DFD_BASIC_BLOCK_SIZE) as u32).to_le_bytes());
+        // vendor id and descriptor type are both 0 for the basic block
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(
+            &(DFD_VERSION_1_3 | ((DFD_BASIC_BLOCK_SIZE as u32) << 16)).to_le_bytes(),
+        );
+        dfd.extend_from_slice(&[
+            DFD_MODEL_UASTC,
+            DFD_PRIMARIES_BT709,
+            if self.is_srgb {
+                DFD_TRANSFER_SRGB
+            } else {
+                DFD_TRANSFER_LINEAR
+            },
+            0, // straight alpha
+        ]);
+        // texel block dimensions minus one
+        dfd.extend_from_slice(&[3, 3, 0, 0]);
+        // bytes per plane
+        dfd.extend_from_slice(&[UASTC_BLOCK_SIZE as u8, 0, 0, 0, 0, 0, 0, 0]);
+        // sample: bit offset, bit length - 1, channel id, position, lower, upper
+        dfd.extend_from_slice(&0u16.to_le_bytes());
+        dfd.push(127);
+        dfd.push(self.channels.get_id());
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&u32::MAX.to_le_bytes());
+        dfd
+    }
+}
+
+fn get_key_value_data(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut kvd = Vec::new();
+    for (key, value) in entries {
+        let key_and_value = [key.as_bytes(), &[0], value.as_bytes(), &[0]].concat();
+        kvd.extend_from_slice(&(key_and_value.len() as u32).to_le_bytes());
+        kvd.extend_from_slice(&key_and_value);
+        while kvd.len() % 4 != 0 {
+            kvd.push(0);
+        }
+    }
+    kvd
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Result<u8> {
+    bytes
+        .get(offset)
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("Ktx2 file is truncated"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(
+        bytes
+            .get(offset..offset + 4)
+            .ok_or_else(|| anyhow::anyhow!("Ktx2 file is truncated"))?
+            .try_into()?,
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(
+        bytes
+            .get(offset..offset + 8)
+            .ok_or_else(|| anyhow::anyhow!("Ktx2 file is truncated"))?
+            .try_into()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_texture(width: u32, height: u32, level_count: usize) -> UastcKtx2 {
+        UastcKtx2 {
+            width,
+            height,
+            is_srgb: true,
+            channels: UastcChannels::Rgba,
+            levels: (0..level_count)
+                .map(|level| {
+                    let (blocks_x, blocks_y) = get_level_block_counts(width, height, level);
+                    (0..blocks_x * blocks_y * UASTC_BLOCK_SIZE as u32)
+                        .map(|i| (i * 7 + level as u32) as u8)
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rejects_etc1s_and_out_of_bounds_levels() {
+        let bytes = make_texture(8, 8, 1).to_bytes(None).unwrap();
+        assert!(check_uastc_ktx2(&bytes).is_ok());
+
+        let mut etc1s_bytes = bytes.clone();
+        let dfd_offset = read_u32(&bytes, 48).unwrap() as usize;
+        etc1s_bytes[dfd_offset + 4 + 8] = DFD_MODEL_ETC1S;
+        let err = check_uastc_ktx2(&etc1s_bytes).unwrap_err();
+        assert!(err.to_string().contains("ETC1S"));
+
+        // the level length would overflow when added to the offset
+        let mut out_of_bounds_bytes = bytes;
+        out_of_bounds_bytes[HEADER_SIZE..HEADER_SIZE + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        out_of_bounds_bytes[HEADER_SIZE + 8..HEADER_SIZE + 16].copy_from_slice(&2u64.to_le_bytes());
+        assert!(UastcKtx2::from_bytes(&out_of_bounds_bytes).is_err());
+    }
+
+    #[test]
+    fn ktx2_round_trip() {
+        let texture = make_texture(37, 9, 6);
+        for zstd_level in [None, Some(0)] {
+            let bytes = texture.to_bytes(zstd_level).unwrap();
+            assert!(is_ktx2(&bytes));
+            assert_eq!(UastcKtx2::from_bytes(&bytes).unwrap(), texture);
+        }
+
+        let normal_map = UastcKtx2 {
+            is_srgb: false,
+            channels: UastcChannels::Rrrg,
+            ..make_texture(4, 4, 1)
+        };
+        assert_eq!(
+            UastcKtx2::from_bytes(&normal_map.to_bytes(Some(0)).unwrap()).unwrap(),
+            normal_map
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let bytes = make_texture(8, 8, 2).to_bytes(None).unwrap();
+
+        assert!(UastcKtx2::from_bytes(&bytes[..HEADER_SIZE - 1]).is_err());
+        assert!(UastcKtx2::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(UastcKtx2::from_bytes(&bytes[1..]).is_err());
+
+        let mut etc1s_bytes = bytes.clone();
+        let dfd_offset = read_u32(&bytes, 48).unwrap() as usize;
+        etc1s_bytes[dfd_offset + 4 + 8] = DFD_MODEL_ETC1S;
+        assert!(UastcKtx2::from_bytes(&etc1s_bytes).is_err());
+
+        let mut bc7_bytes = bytes;
+        // VK_FORMAT_BC7_UNORM_BLOCK
+        bc7_bytes[12..16].copy_from_slice(&145u32.to_le_bytes());
+        assert!(UastcKtx2::from_bytes(&bc7_bytes).is_err());
+    }
+}
@@ -0,0 +1,808 @@
+use crate::animation::*;
+use crate::asset_loader::*;
+use crate::game::*;
+use crate::handle::*;
+use crate::light::*;
+use crate::logger::*;
+use crate::physics::*;
+use crate::renderer::*;
+use crate::scene::*;
+use crate::transform::*;
+use crate::vfs::*;
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use glam::f32::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+// bump this whenever the schema changes in a way that older builds can't read, and add a
+// matching step to upgrade_level_json so that level files saved by older builds keep loading.
+// adding a field with a serde default doesn't need a new version
+pub const LEVEL_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelDesc {
+    pub version: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<LevelAssetDesc>,
+    #[serde(default)]
+    pub point_lights: Vec<LevelPointLightDesc>,
+    #[serde(default)]
+    pub directional_lights: Vec<LevelDirectionalLightDesc>,
+    #[serde(default)]
+    pub colliders: Vec<LevelColliderDesc>,
+    #[serde(default)]
+    pub spawn_points: Vec<LevelSpawnPointDesc>,
+}
+
+// a glTF file placed in the level. the root nodes of the file get parented to a node
+// holding the instance transform
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelAssetDesc {
+    pub path: String,
+    #[serde(default)]
+    pub transform: LevelTransform,
+    #[serde(default)]
+    pub overrides: LevelAssetOverrides,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelTransform {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelAssetOverrides {
+    pub animations: Vec<LevelAnimationOverride>,
+    pub wireframe: bool,
+    // add a static box collider around every mesh of the asset
+    pub static_colliders: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelAnimationOverride {
+    // applies to every animation of the asset if not set
+    pub name: Option<String>,
+    pub speed: Option<f32>,
+    pub playing: Option<bool>,
+    pub loop_type: Option<LevelLoopType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelLoopType {
+    Once,
+    Wrap,
+    PingPong,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelPointLightDesc {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelDirectionalLightDesc {
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+// scale is ignored for colliders
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelColliderDesc {
+    #[serde(default)]
+    pub transform: LevelTransform,
+    pub shape: LevelColliderShape,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelColliderShape {
+    Cuboid { half_extents: [f32; 3] },
+    Ball { radius: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelSpawnPointDesc {
+    pub name: String,
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub yaw_degrees: f32,
+}
+
+// runtime state of a level that was loaded into the game
+pub struct LevelState {
+    pub path: String,
+    pub desc: LevelDesc,
+    // one entry per asset of desc, set once the asset has been loaded and added to the scene
+    pub asset_node_ids: Vec<Option<GameNodeId>>,
+    // one entry per asset of desc, the gpu resources and nodes of the asset once it's been added.
+    // only set for the first placement of each file, the others are copies of its nodes
+    pub asset_gltf_scenes: Vec<Option<Handle<GltfScene>>>,
+    pub point_light_node_ids: Vec<GameNodeId>,
+    pub directional_light_node_ids: Vec<GameNodeId>,
+    pub collider_handles: Vec<ColliderHandle>,
+}
+
+// parents the asset's root nodes to an instance node that has the level transform and applies the
+// animation overrides
+fn prepare_asset_scene(asset: &LevelAssetDesc, other_scene: &mut Scene) {
+    let root_node_ids = other_scene.get_instance_root_node_ids();
+    let instance_node_id = other_scene
+        .add_node(
+            GameNodeDescBuilder::new()
+                .name(Some(asset.path.clone()))
+                .transform(asset.transform.into())
+                .build(),
+        )
+        .id();
+    for root_node_id in root_node_ids {
+        other_scene
+            .set_parent(root_node_id, Some(instance_node_id), false)
+            .unwrap();
+    }
+
+    for animation in other_scene.animations.iter_mut() {
+        apply_animation_overrides(asset, animation);
+    }
+}
+
+fn apply_animation_overrides(asset: &LevelAssetDesc, animation: &mut Animation) {
+    for animation_override in &asset.overrides.animations {
+        if animation_override.name.is_some() && animation_override.name != animation.name {
+            continue;
+        }
+        if let Some(speed) = animation_override.speed {
+            animation.speed = speed;
+        }
+        if let Some(playing) = animation_override.playing {
+            animation.state.is_playing = playing;
+        }
+        if let Some(loop_type) = animation_override.loop_type {
+            animation.state.loop_type = loop_type.into();
+        }
+    }
+}
+
+fn apply_node_overrides(
+    asset: &LevelAssetDesc,
+    node_ids: &[GameNodeId],
+    scene: &mut Scene,
+    renderer_data: &RendererPublicData,
+    physics_state: &mut PhysicsState,
+) {
+    for node_id in node_ids.iter().copied() {
+        if asset.overrides.wireframe {
+            if let Some(mesh) = scene.get_node_mut(node_id).unwrap().mesh.as_mut() {
+                mesh.wireframe = true;
+            }
+        }
+        if asset.overrides.static_colliders {
+            physics_state.add_static_box(scene, renderer_data, node_id);
+        }
+    }
+}
+
+// places another copy of an asset whose file was already merged for an earlier placement, leaving
+// out the instance node of that placement. the copy starts out with the animation state of the
+// earlier placement. returns the nodes of the copy, starting with its instance node
+fn add_asset_copy(
+    asset: &LevelAssetDesc,
+    transform: crate::transform::Transform,
+    merged_node_id: GameNodeId,
+    merged_gltf_scene: &Handle<GltfScene>,
+    scene: &mut Scene,
+    renderer_data: &RendererPublicData,
+) -> Vec<GameNodeId> {
+    let instance = scene.instantiate_merged_scene(
+        &renderer_data.gltf_scenes[merged_gltf_scene.index()],
+        &[merged_node_id],
+        None,
+        transform,
+    );
+    scene.set_node_name(instance.node_id, Some(asset.path.clone()));
+    for animation_index in instance.animations {
+        apply_animation_overrides(asset, &mut scene.animations[animation_index]);
+    }
+    std::iter::once(instance.node_id)
+        .chain(scene.descendants(instance.node_id))
+        .collect()
+}
+
+fn remove_static_boxes(physics_state: &mut PhysicsState, node_id: GameNodeId) {
+    if let Some(collider_handles) = physics_state.static_box_set.remove(&node_id) {
+        for collider_handle in collider_handles {
+            physics_state.collider_set.remove(
+                collider_handle,
+                &mut physics_state.island_manager,
+                &mut physics_state.rigid_body_set,
+                false,
+            );
+        }
+    }
+}
+
+impl Default for LevelTransform {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            rotation: Quat::IDENTITY.to_array(),
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl From<LevelTransform> for crate::transform::Transform {
+    fn from(transform: LevelTransform) -> Self {
+        TransformBuilder::new()
+            .position(Vec3::from(transform.position))
+            .rotation(Quat::from_array(transform.rotation))
+            .scale(Vec3::from(transform.scale))
+            .build()
+    }
+}
+
+impl From<crate::transform::Transform> for LevelTransform {
+    fn from(transform: crate::transform::Transform) -> Self {
+        Self {
+            position: transform.position().to_array(),
+            rotation: transform.rotation().to_array(),
+            scale: transform.scale().to_array(),
+        }
+    }
+}
+
+impl From<LevelLoopType> for LoopType {
+    fn from(loop_type: LevelLoopType) -> Self {
+        match loop_type {
+            LevelLoopType::Once => LoopType::Once,
+            LevelLoopType::Wrap => LoopType::Wrap,
+            LevelLoopType::PingPong => LoopType::PingPong,
+        }
+    }
+}
+
+impl LevelDesc {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let version = value
+            .get("version")
+            .and_then(|version| version.as_u64())
+            .ok_or_else(|| anyhow!("Level is missing its format version"))?;
+        let version: u32 = version.try_into()?;
+        if version > LEVEL_FORMAT_VERSION {
+            bail!(
+                "Level format version {} is newer than the latest supported version {}",
+                version,
+                LEVEL_FORMAT_VERSION
+            );
+        }
+        let mut level: Self = serde_json::from_value(upgrade_level_json(value, version)?)?;
+        level.version = LEVEL_FORMAT_VERSION;
+        Ok(level)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&vfs_read_to_string(path)?)
+    }
+
+    // writes to the directory that the path is mounted from, see Vfs::directory_path
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(vfs_directory_path(path)?, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+fn upgrade_level_json(value: serde_json::Value, version: u32) -> Result<serde_json::Value> {
+    match version {
+        LEVEL_FORMAT_VERSION => Ok(value),
+        _ => bail!("Unsupported level format version {}", version),
+    }
+}
+
+impl LevelState {
+    // adds the lights and colliders of the level. the assets are added as they finish loading,
+    // see instantiate_loaded_assets
+    pub fn new(
+        path: String,
+        desc: LevelDesc,
+        scene: &mut Scene,
+        physics_state: &mut PhysicsState,
+        point_lights: &mut Vec<PointLightComponent>,
+        directional_lights: &mut Vec<DirectionalLightComponent>,
+    ) -> Self {
+        let mut point_light_node_ids = Vec::new();
+        for point_light in &desc.point_lights {
+            let node_id = scene
+                .add_node(
+                    GameNodeDescBuilder::new()
+                        .transform(
+                            TransformBuilder::new()
+                                .position(Vec3::from(point_light.position))
+                                .build(),
+                        )
+                        .build(),
+                )
+                .id();
+            point_light_node_ids.push(node_id);
+            if point_lights.len() >= MAX_SHADOW_CASTING_POINT_LIGHT_COUNT as usize {
+                logger_log(&format!(
+                    "Warning: too many point lights in the scene, skipping level light at {:?}",
+                    point_light.position
+                ));
+                continue;
+            }
+            point_lights.push(PointLightComponent {
+                node_id,
+                color: Vec3::from(point_light.color),
+                intensity: point_light.intensity,
+            });
+        }
+
+        let mut directional_light_node_ids = Vec::new();
+        for directional_light in &desc.directional_lights {
+            let position = Vec3::from(directional_light.position);
+            let direction = Vec3::from(directional_light.direction).normalize();
+            let node_id = scene
+                .add_node(
+                    GameNodeDescBuilder::new()
+                        .transform(
+                            TransformBuilder::new()
+                                .position(position)
+                                .rotation(Quat::from_rotation_arc(Vec3::NEG_Z, direction))
+                                .build(),
+                        )
+                        .build(),
+                )
+                .id();
+            directional_light_node_ids.push(node_id);
+            if directional_lights.len() >= MAX_SHADOW_CASTING_DIRECTIONAL_LIGHT_COUNT as usize {
+                logger_log("Warning: too many directional lights in the scene, skipping light");
+                continue;
+            }
+            directional_lights.push(DirectionalLightComponent {
+                node_id: Some(node_id),
+                position,
+                direction,
+                color: Vec3::from(directional_light.color),
+                intensity: directional_light.intensity,
+            });
+        }
+
+        let collider_handles = desc
+            .colliders
+            .iter()
+            .map(|collider| {
+                let shape = match collider.shape {
+                    LevelColliderShape::Cuboid {
+                        half_extents: [x, y, z],
+                    } => ColliderBuilder::cuboid(x, y, z),
+                    LevelColliderShape::Ball { radius } => ColliderBuilder::ball(radius),
+                };
+                let [x, y, z] = collider.transform.position;
+                let [rx, ry, rz, rw] = collider.transform.rotation;
+                let collider = shape
+                    .collision_groups(
+                        InteractionGroups::all()
+                            .with_memberships(!COLLISION_GROUP_PLAYER_UNSHOOTABLE),
+                    )
+                    .friction(1.0)
+                    .restitution(1.0)
+                    .position(Isometry::from_parts(
+                        nalgebra::Translation3::new(x, y, z),
+                        nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(
+                            rw, rx, ry, rz,
+                        )),
+                    ))
+                    .build();
+                physics_state.collider_set.insert(collider)
+            })
+            .collect();
+
+        Self {
+            path,
+            asset_node_ids: vec![None; desc.assets.len()],
+            asset_gltf_scenes: vec![None; desc.assets.len()],
+            desc,
+            point_light_node_ids,
+            directional_light_node_ids,
+            collider_handles,
+        }
+    }
+
+    // each file once, even if it's placed more than once
+    pub fn asset_paths(&self) -> impl Iterator<Item = &str> {
+        self.desc
+            .assets
+            .iter()
+            .enumerate()
+            .filter(|(asset_index, asset)| {
+                self.first_asset_index(&asset.path) == Some(*asset_index)
+            })
+            .map(|(_, asset)| asset.path.as_str())
+    }
+
+    fn first_asset_index(&self, path: &str) -> Option<usize> {
+        self.desc.assets.iter().position(|asset| asset.path == path)
+    }
+
+    pub fn spawn_point(&self, name: &str) -> Option<&LevelSpawnPointDesc> {
+        self.desc
+            .spawn_points
+            .iter()
+            .find(|spawn_point| spawn_point.name == name)
+    }
+
+    // adds the level's assets that finished loading to the scene, called when one of
+    // them becomes ready
+    pub fn instantiate_loaded_assets(
+        &mut self,
+        scene: &mut Scene,
+        renderer_data: &mut RendererPublicData,
+        physics_state: &mut PhysicsState,
+        asset_loader: &AssetLoader,
+    ) {
+        for asset_index in 0..self.desc.assets.len() {
+            if self.asset_node_ids[asset_index].is_some() {
+                continue;
+            }
+            let asset = &self.desc.assets[asset_index];
+            // the file is merged for its first placement and the others copy its nodes, which
+            // comes earlier in the loop
+            let first_asset_index = self.first_asset_index(&asset.path).unwrap();
+            let node_ids = if first_asset_index == asset_index {
+                let skip_nodes = scene.node_count();
+                let Some(gltf_scene) = asset_loader.merge_loaded_gltf_scene(
+                    &asset.path,
+                    scene,
+                    renderer_data,
+                    |other_scene| prepare_asset_scene(asset, other_scene),
+                ) else {
+                    continue;
+                };
+                self.asset_gltf_scenes[asset_index] = Some(gltf_scene);
+
+                let node_ids: Vec<_> = scene
+                    .nodes()
+                    .skip(skip_nodes)
+                    .map(|node| node.id())
+                    .collect();
+                // the instance node was added last so it's also the last one to be merged
+                self.asset_node_ids[asset_index] = node_ids.last().copied();
+                node_ids
+            } else {
+                let (Some(merged_node_id), Some(merged_gltf_scene)) = (
+                    self.asset_node_ids[first_asset_index],
+                    self.asset_gltf_scenes[first_asset_index].as_ref(),
+                ) else {
+                    continue;
+                };
+                let node_ids = add_asset_copy(
+                    asset,
+                    asset.transform.into(),
+                    merged_node_id,
+                    merged_gltf_scene,
+                    scene,
+                    renderer_data,
+                );
+                self.asset_node_ids[asset_index] = node_ids.first().copied();
+                node_ids
+            };
+            apply_node_overrides(asset, &node_ids, scene, renderer_data, physics_state);
+        }
+    }
+
+    // prepares a reloaded version of one of the level's assets like the first one, returning the
+    // handle that it replaces. see AssetLoader::reload_changed_gltf_scenes
+    pub fn prepare_reloaded_asset(
+        &self,
+        path: &str,
+        other_scene: &mut Scene,
+    ) -> Option<Handle<GltfScene>> {
+        let asset_index = self.first_asset_index(path)?;
+        let gltf_scene = self.asset_gltf_scenes[asset_index].clone()?;
+        prepare_asset_scene(&self.desc.assets[asset_index], other_scene);
+        Some(gltf_scene)
+    }
+
+    // applies the overrides again after the asset was reloaded and copies it again for its other
+    // placements. the static colliders of the first placement aren't rebuilt
+    pub fn on_asset_reloaded(
+        &mut self,
+        scene: &mut Scene,
+        renderer_data: &RendererPublicData,
+        physics_state: &mut PhysicsState,
+        path: &str,
+    ) {
+        let Some(first_asset_index) = self.first_asset_index(path) else {
+            return;
+        };
+        let (Some(merged_node_id), Some(merged_gltf_scene)) = (
+            self.asset_node_ids[first_asset_index],
+            self.asset_gltf_scenes[first_asset_index].as_ref(),
+        ) else {
+            return;
+        };
+        if self.desc.assets[first_asset_index].overrides.wireframe {
+            for (_, node_id) in &renderer_data.gltf_scenes[merged_gltf_scene.index()].nodes {
+                if let Some(mesh) = scene
+                    .get_node_mut(*node_id)
+                    .and_then(|node| node.mesh.as_mut())
+                {
+                    mesh.wireframe = true;
+                }
+            }
+        }
+
+        for (asset, asset_node_id) in self
+            .desc
+            .assets
+            .iter()
+            .zip(self.asset_node_ids.iter_mut())
+            .skip(first_asset_index + 1)
+        {
+            let Some(copy_node_id) = asset_node_id.filter(|_| asset.path == path) else {
+                continue;
+            };
+            // keep the copy where it was moved to
+            let transform = scene.get_node(copy_node_id).unwrap().transform;
+            let old_node_ids: Vec<_> = std::iter::once(copy_node_id)
+                .chain(scene.descendants(copy_node_id))
+                .collect();
+            for node_id in old_node_ids {
+                remove_static_boxes(physics_state, node_id);
+            }
+            scene.remove_node_recursive(copy_node_id);
+
+            let node_ids = add_asset_copy(
+                asset,
+                transform,
+                merged_node_id,
+                merged_gltf_scene,
+                scene,
+                renderer_data,
+            );
+            apply_node_overrides(asset, &node_ids, scene, renderer_data, physics_state);
+            *asset_node_id = node_ids.first().copied();
+        }
+    }
+
+    // removes everything that the level added to the game so that another one can be loaded.
+    // the meshes and textures of its assets are freed by the renderer once nothing uses them
+    pub fn unload(
+        self,
+        scene: &mut Scene,
+        renderer_data: &RendererPublicData,
+        physics_state: &mut PhysicsState,
+        point_lights: &mut Vec<PointLightComponent>,
+        directional_lights: &mut Vec<DirectionalLightComponent>,
+    ) {
+        // the current nodes of each asset, which changes when it's reloaded
+        let mut asset_scene_node_ids: Vec<_> = self
+            .asset_gltf_scenes
+            .iter()
+            .flatten()
+            .flat_map(|gltf_scene| &renderer_data.gltf_scenes[gltf_scene.index()].nodes)
+            .map(|(_, node_id)| *node_id)
+            .collect();
+        // and the nodes of the copies
+        for (asset_node_id, asset_gltf_scene) in self
+            .asset_node_ids
+            .iter()
+            .zip(self.asset_gltf_scenes.iter())
+        {
+            if let (Some(copy_node_id), None) = (asset_node_id, asset_gltf_scene) {
+                asset_scene_node_ids.push(*copy_node_id);
+                asset_scene_node_ids.extend(scene.descendants(*copy_node_id));
+            }
+        }
+        for node_id in asset_scene_node_ids
+            .iter()
+            .chain(self.point_light_node_ids.iter())
+            .chain(self.directional_light_node_ids.iter())
+        {
+            remove_static_boxes(physics_state, *node_id);
+            scene.remove_node(*node_id);
+        }
+        for collider_handle in self.collider_handles {
+            physics_state.collider_set.remove(
+                collider_handle,
+                &mut physics_state.island_manager,
+                &mut physics_state.rigid_body_set,
+                false,
+            );
+        }
+
+        point_lights
+            .retain(|point_light| !self.point_light_node_ids.contains(&point_light.node_id));
+        directional_lights.retain(|directional_light| {
+            !directional_light.node_id.map_or(false, |node_id| {
+                self.directional_light_node_ids.contains(&node_id)
+            })
+        });
+    }
+
+    // describes the level as it currently is in the game, e.g. to save it back to disk
+    pub fn to_desc(
+        &self,
+        scene: &Scene,
+        point_lights: &[PointLightComponent],
+        directional_lights: &[DirectionalLightComponent],
+    ) -> LevelDesc {
+        let mut desc = self.desc.clone();
+        desc.version = LEVEL_FORMAT_VERSION;
+        for (asset, asset_node_id) in desc.assets.iter_mut().zip(self.asset_node_ids.iter()) {
+            if let Some(node) = asset_node_id.and_then(|node_id| scene.get_node(node_id)) {
+                asset.transform = node.transform.into();
+            }
+        }
+        for (point_light_desc, node_id) in desc
+            .point_lights
+            .iter_mut()
+            .zip(self.point_light_node_ids.iter())
+        {
+            if let Some(node) = scene.get_node(*node_id) {
+                point_light_desc.position = node.transform.position().to_array();
+            }
+            if let Some(point_light) = point_lights
+                .iter()
+                .find(|point_light| point_light.node_id == *node_id)
+            {
+                point_light_desc.color = point_light.color.to_array();
+                point_light_desc.intensity = point_light.intensity;
+            }
+        }
+        for (directional_light_desc, node_id) in desc
+            .directional_lights
+            .iter_mut()
+            .zip(self.directional_light_node_ids.iter())
+        {
+            if let Some(directional_light) = directional_lights
+                .iter()
+                .find(|directional_light| directional_light.node_id == Some(*node_id))
+            {
+                directional_light_desc.position = directional_light.position.to_array();
+                directional_light_desc.direction = directional_light.direction.to_array();
+                directional_light_desc.color = directional_light.color.to_array();
+                directional_light_desc.intensity = directional_light.intensity;
+            }
+        }
+        desc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_json_round_trip() {
+        let level = LevelDesc::from_json(
+            r#"{
+                "version": 1,
+                "assets": [
+                    {
+                        "path": "./forest.gltf",
+                        "transform": { "position": [0.0, 29.0, 0.0] },
+                        "overrides": {
+                            "animations": [{ "speed": 0.25, "loop_type": "wrap" }]
+                        }
+                    }
+                ],
+                "colliders": [
+                    { "shape": { "ball": { "radius": 2.0 } } }
+                ],
+                "spawn_points": [{ "name": "player", "position": [1.0, 2.0, 3.0] }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(level.assets[0].transform.position, [0.0, 29.0, 0.0]);
+        assert_eq!(level.assets[0].transform.scale, [1.0, 1.0, 1.0]);
+        assert_eq!(
+            level.assets[0].overrides.animations[0].loop_type,
+            Some(LevelLoopType::Wrap)
+        );
+        assert_eq!(
+            level.colliders[0].shape,
+            LevelColliderShape::Ball { radius: 2.0 }
+        );
+        assert_eq!(level.spawn_points[0].yaw_degrees, 0.0);
+        assert!(level.point_lights.is_empty());
+
+        assert_eq!(
+            LevelDesc::from_json(&level.to_json().unwrap()).unwrap(),
+            level
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_level_versions() {
+        assert!(LevelDesc::from_json(r#"{ "version": 2 }"#).is_err());
+        assert!(LevelDesc::from_json(r#"{ "assets": [] }"#).is_err());
+    }
+
+    #[test]
+    fn levels_are_saved_through_the_mount_point() {
+        let folder =
+            std::env::temp_dir().join(format!("ikari_level_save_test_{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("levels")).unwrap();
+        let mount_point = folder.file_name().unwrap().to_str().unwrap().to_string();
+        let level_path = Path::new(&mount_point).join("levels/test.json");
+        let level = LevelDesc::from_json(
+            r#"{ "version": 1, "name": "test", "assets": [{ "path": "a.gltf" }] }"#,
+        )
+        .unwrap();
+
+        VFS.write()
+            .unwrap()
+            .mount(&mount_point, VfsSource::Directory(folder.clone()));
+        let save_result = level.save(&level_path);
+        let loaded_level = LevelDesc::load(&level_path);
+
+        // the packed version is what gets loaded, so saving over the loose file would be lost
+        let pack_path = folder.join("assets.pak");
+        let mut pack_writer = PackArchiveWriter::create(&pack_path, 0).unwrap();
+        pack_writer
+            .add_file(
+                "levels/test.json",
+                level.to_json().unwrap().as_bytes(),
+                None,
+            )
+            .unwrap();
+        pack_writer.finish().unwrap();
+        VFS.write().unwrap().mount(
+            &mount_point,
+            VfsSource::Pack(PackArchive::open(&pack_path).unwrap()),
+        );
+        let packed_save_result = level.save(&level_path);
+        VFS.write().unwrap().unmount(&mount_point);
+        let saved_json = std::fs::read_to_string(folder.join("levels/test.json"));
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        save_result.unwrap();
+        assert_eq!(loaded_level.unwrap(), level);
+        assert_eq!(saved_json.unwrap(), level.to_json().unwrap());
+        assert!(packed_save_result.is_err());
+    }
+
+    #[test]
+    fn files_placed_more_than_once_are_loaded_once() {
+        let desc = LevelDesc::from_json(
+            r#"{
+                "version": 1,
+                "assets": [{ "path": "a.gltf" }, { "path": "b.gltf" }, { "path": "a.gltf" }]
+            }"#,
+        )
+        .unwrap();
+        let level = LevelState {
+            path: "level.json".into(),
+            asset_node_ids: vec![None; desc.assets.len()],
+            asset_gltf_scenes: vec![None; desc.assets.len()],
+            desc,
+            point_light_node_ids: vec![],
+            directional_light_node_ids: vec![],
+            collider_handles: vec![],
+        };
+        assert_eq!(
+            level.asset_paths().collect::<Vec<_>>(),
+            vec!["a.gltf", "b.gltf"]
+        );
+    }
+}
@@ -2,18 +2,28 @@ pub mod animation;
 pub mod asset_loader;
 pub mod audio;
 pub mod ball;
+pub mod behaviour;
 pub mod buffer;
 pub mod camera;
 pub mod character;
 pub mod collisions;
+pub mod component;
+pub mod crosshair;
 pub mod game;
 pub mod game_state;
 pub mod gameloop;
+pub mod gltf_exporter;
 pub mod gltf_loader;
+pub mod handle;
+pub mod ktx2;
+pub mod level;
 pub mod light;
 pub mod logger;
 pub mod math;
 pub mod mesh;
+pub mod mesh_cache;
+pub mod mesh_optimizer;
+pub mod mesh_simplifier;
 pub mod physics;
 pub mod physics_ball;
 pub mod player_controller;
@@ -25,6 +35,9 @@ pub mod scene_tree;
 pub mod skinning;
 pub mod texture;
 pub mod texture_compression;
+pub mod texture_streaming;
 pub mod time_tracker;
 pub mod transform;
 pub mod ui_overlay;
+pub mod vfs;
+pub mod worker_pool;
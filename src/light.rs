@@ -2,6 +2,19 @@ use crate::scene::*;
 
 use glam::f32::Vec3;
 
+// the renderer has no exposure control so its light intensities are unitless, 1.0 being about as
+// bright as the hardcoded lights in game.rs. glTF uses photometric units instead: candela for
+// point and spot lights and lux for directional lights.
+//
+// an isotropic light with a radiant power of P watts gives off P * 683 lumens spread over 4π
+// steradians, so I = P * 683 / 4π candela. 683 lm/W is the luminous efficacy that the glTF spec and
+// blender's exporter use. we undo that conversion and count a 1000W light as an intensity of 1.0
+const LUMENS_PER_WATT: f32 = 683.0;
+const REFERENCE_POINT_LIGHT_WATTS: f32 = 1000.0;
+// blender exports the strength of a sun (in W/m²) as lux without converting it, and a strength of
+// 1.0 already looks like our hardcoded directional lights
+const REFERENCE_DIRECTIONAL_LIGHT_LUX: f32 = 1.0;
+
 #[derive(Clone, Debug)]
 pub struct PointLightComponent {
     pub node_id: GameNodeId,
@@ -9,10 +22,88 @@ pub struct PointLightComponent {
     pub intensity: f32,
 }
 
+// marks a node whose unlit mesh takes the color of the point light attached to the node
+#[derive(Clone, Copy, Debug)]
+pub struct PointLightMeshComponent;
+
 #[derive(Clone, Debug)]
 pub struct DirectionalLightComponent {
+    // if set, the position and direction follow the node's global transform with the light
+    // pointing down the node's -Z axis, and the light is removed along with the node
+    pub node_id: Option<GameNodeId>,
     pub position: Vec3,
     pub direction: Vec3,
     pub color: Vec3,
     pub intensity: f32,
 }
+
+#[derive(Clone, Debug)]
+pub struct IndexedPointLightComponent {
+    pub node_index: usize,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexedDirectionalLightComponent {
+    pub node_index: usize,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+pub fn point_light_intensity_from_candela(candela: f32) -> f32 {
+    let watts = candela * 4.0 * std::f32::consts::PI / LUMENS_PER_WATT;
+    watts / REFERENCE_POINT_LIGHT_WATTS
+}
+
+pub fn directional_light_intensity_from_lux(lux: f32) -> f32 {
+    lux / REFERENCE_DIRECTIONAL_LIGHT_LUX
+}
+
+// moves the directional lights that are attached to nodes along with them and drops the lights
+// whose nodes were removed from the scene
+pub fn update_light_nodes(
+    scene: &Scene,
+    point_lights: &mut Vec<PointLightComponent>,
+    directional_lights: &mut Vec<DirectionalLightComponent>,
+) {
+    point_lights.retain(|point_light| scene.get_node(point_light.node_id).is_some());
+    directional_lights.retain_mut(|directional_light| {
+        let Some(node_id) = directional_light.node_id else {
+            return true;
+        };
+        if scene.get_node(node_id).is_none() {
+            return false;
+        }
+        let global_transform = scene.get_global_transform_for_node(node_id);
+        directional_light.position = global_transform.position();
+        directional_light.direction =
+            (global_transform.rotation() * Vec3::new(0.0, 0.0, -1.0)).normalize();
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn photometric_units_map_to_reference_intensities() {
+        // what blender writes for a 1000W point light and a sun of strength 1
+        let reference_candela =
+            REFERENCE_POINT_LIGHT_WATTS * LUMENS_PER_WATT / (4.0 * std::f32::consts::PI);
+        assert_abs_diff_eq!(
+            point_light_intensity_from_candela(reference_candela),
+            1.0,
+            epsilon = 0.0001
+        );
+        assert_abs_diff_eq!(
+            point_light_intensity_from_candela(reference_candela * 0.5),
+            0.5,
+            epsilon = 0.0001
+        );
+        assert_eq!(directional_light_intensity_from_lux(1.0), 1.0);
+    }
+}
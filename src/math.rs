@@ -1,10 +1,10 @@
 use glam::f32::Vec3;
 
-pub fn _to_srgb(val: f32) -> f32 {
+pub fn srgb_to_linear(val: f32) -> f32 {
     val.powf(2.2)
 }
 
-pub fn _from_srgb(val: f32) -> f32 {
+pub fn linear_to_srgb(val: f32) -> f32 {
     val.powf(1.0 / 2.2)
 }
 
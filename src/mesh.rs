@@ -1,6 +1,8 @@
 use crate::texture::*;
+use crate::vfs::*;
 
 use std::collections::{hash_map, HashMap};
+use std::sync::Arc;
 
 use anyhow::Result;
 use glam::{
@@ -88,8 +90,30 @@ impl GpuPbrMeshInstance {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuUnlitMeshInstance {
-    pub model_transform: Mat4,
-    pub color: [f32; 4],
+    model_transform: Mat4,
+    color: [f32; 4],
+    alpha_cutoff: f32,
+    padding: f32,
+    point_size: [f32; 2],
+}
+
+impl GpuUnlitMeshInstance {
+    /// pass a negative alpha_cutoff to disable alpha masking
+    pub fn new(transform: Mat4, color: [f32; 4], alpha_cutoff: f32) -> Self {
+        Self {
+            model_transform: transform,
+            color,
+            alpha_cutoff,
+            padding: 0.0,
+            point_size: [0.0, 0.0],
+        }
+    }
+
+    /// width and height of the quads drawn for point meshes, in clip space units
+    pub fn with_point_size(mut self, point_size: [f32; 2]) -> Self {
+        self.point_size = point_size;
+        self
+    }
 }
 
 pub type GpuWireframeMeshInstance = GpuUnlitMeshInstance;
@@ -119,7 +143,7 @@ impl Default for DynamicPbrParams {
     }
 }
 
-#[derive(Default, Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone)]
 pub struct IndexedPbrMaterial {
     pub base_color: Option<usize>,
     pub normal: Option<usize>,
@@ -128,6 +152,49 @@ pub struct IndexedPbrMaterial {
     pub ambient_occlusion: Option<usize>,
 }
 
+impl IndexedPbrMaterial {
+    pub fn get_pbr_material<'a>(&self, textures: &'a [Texture]) -> PbrMaterial<'a> {
+        let get_texture = |texture_index: Option<usize>| {
+            texture_index.map(|texture_index| &textures[texture_index])
+        };
+
+        PbrMaterial {
+            base_color: get_texture(self.base_color),
+            normal: get_texture(self.normal),
+            emissive: get_texture(self.emissive),
+            ambient_occlusion: get_texture(self.ambient_occlusion),
+            metallic_roughness: get_texture(self.metallic_roughness),
+        }
+    }
+
+    pub fn texture_indices(&self) -> impl Iterator<Item = usize> {
+        [
+            self.base_color,
+            self.normal,
+            self.metallic_roughness,
+            self.emissive,
+            self.ambient_occlusion,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    pub fn remap_texture_indices(&mut self, mut remap: impl FnMut(&mut usize)) {
+        for texture_index in [
+            &mut self.base_color,
+            &mut self.normal,
+            &mut self.metallic_roughness,
+            &mut self.emissive,
+            &mut self.ambient_occlusion,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            remap(texture_index);
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PbrMaterial<'a> {
     pub base_color: Option<&'a Texture>,
@@ -137,6 +204,38 @@ pub struct PbrMaterial<'a> {
     pub ambient_occlusion: Option<&'a Texture>,
 }
 
+// cpu-side copy of the data that was uploaded to the gpu
+#[derive(Debug, Clone, Default)]
+pub struct GeometrySource {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureSource {
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub format: gltf::image::Format,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PbrTextureSources {
+    pub base_color: Option<Arc<TextureSource>>,
+    pub normal: Option<Arc<TextureSource>>,
+    pub metallic_roughness: Option<Arc<TextureSource>>,
+    pub emissive: Option<Arc<TextureSource>>,
+    pub ambient_occlusion: Option<Arc<TextureSource>>,
+}
+
+// only kept around when the scene may be exported, see AssetLoader::keep_mesh_sources
+#[derive(Debug, Clone, Default)]
+pub struct PbrMeshSources {
+    pub geometry: GeometrySource,
+    pub textures: PbrTextureSources,
+}
+
 pub struct BasicMesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u16>,
@@ -144,7 +243,7 @@ pub struct BasicMesh {
 
 impl BasicMesh {
     pub fn new(obj_file_path: &str) -> Result<Self> {
-        let obj_file_string = std::fs::read_to_string(obj_file_path)?;
+        let obj_file_string = vfs_read_to_string(obj_file_path)?;
 
         let obj = wavefront_obj::obj::parse(obj_file_string)?
             .objects
@@ -0,0 +1,510 @@
+use crate::collisions::Aabb;
+use crate::gltf_loader::*;
+use crate::mesh::*;
+use crate::mesh_simplifier::*;
+use crate::renderer::*;
+use crate::vfs::*;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use glam::{f32::Vec3, Affine3A};
+
+// bump this whenever the layout of the cache or the way the geometry is computed changes,
+// caches written with another version are ignored
+pub const MESH_CACHE_VERSION: u32 = 4;
+const MESH_CACHE_MAGIC: &[u8; 4] = b"IKMC";
+const NONE_MARKER: u32 = u32::MAX;
+
+// precomputed geometry and hierarchy of a gltf file, written by the asset optimizer so that
+// the loader can skip the vertex processing done in build_geometry_source
+#[derive(Debug, Clone, Default)]
+pub struct MeshCache {
+    // one entry per gltf node, in the same order as the document
+    pub nodes: Vec<CachedNode>,
+    pub primitives: Vec<CachedPrimitive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedNode {
+    pub name: Option<String>,
+    pub transform: crate::transform::Transform,
+    pub parent_index: Option<usize>,
+    pub skin_index: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedPrimitive {
+    pub mesh_index: usize,
+    pub primitive_index: usize,
+    pub primitive_mode: PrimitiveMode,
+    pub geometry_source: GeometrySource,
+    pub bounding_box: Aabb,
+    // simplified index buffers, most detailed first
+    pub lods: Vec<MeshLod>,
+}
+
+pub fn gltf_path_to_mesh_cache_path(path: &Path) -> PathBuf {
+    let mut out_path = path.to_path_buf();
+    out_path.set_file_name(format!(
+        "{:}_mesh_cache.bin",
+        out_path.file_stem().unwrap().to_str().unwrap()
+    ));
+    out_path
+}
+
+// the cache is stale if the gltf file or any of the buffer files it references
+// were modified after it was written
+pub fn is_mesh_cache_fresh(gltf_path: &Path, document: &gltf::Document) -> Result<bool> {
+    let cache_path = gltf_path_to_mesh_cache_path(gltf_path);
+    let Some(cache_modified) = vfs_modified(&cache_path) else {
+        return Ok(false);
+    };
+
+    for source_path in get_gltf_buffer_paths(gltf_path, document) {
+        let source_modified = vfs_modified(&source_path)
+            .ok_or_else(|| anyhow::anyhow!("File not found: {}", source_path.display()))?;
+        if source_modified >= cache_modified {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+pub fn load_fresh_mesh_cache(
+    gltf_path: &Path,
+    document: &gltf::Document,
+) -> Result<Option<MeshCache>> {
+    if !is_mesh_cache_fresh(gltf_path, document)? {
+        return Ok(None);
+    }
+    let mesh_cache = MeshCache::from_bytes(&vfs_read(gltf_path_to_mesh_cache_path(gltf_path))?)?;
+    if mesh_cache.nodes.len() != document.nodes().len() {
+        bail!(
+            "Mesh cache has {:?} nodes but the gltf file has {:?}",
+            mesh_cache.nodes.len(),
+            document.nodes().len()
+        );
+    }
+    // the vertex buffers aren't read when the cache is used so it must cover every primitive
+    for mesh in document.meshes() {
+        for primitive_group in mesh.primitives() {
+            let is_cached = mesh_cache.primitives.iter().any(|primitive| {
+                primitive.mesh_index == mesh.index()
+                    && primitive.primitive_index == primitive_group.index()
+            });
+            if !is_cached && get_primitive_mode(&primitive_group).is_ok() {
+                bail!(
+                    "Mesh cache is missing primitive {:?} of mesh {:?}",
+                    primitive_group.index(),
+                    mesh.index()
+                );
+            }
+        }
+    }
+    Ok(Some(mesh_cache))
+}
+
+impl MeshCache {
+    // narrow_indices stores the indices of a primitive as u16 if all of them fit
+    pub fn to_bytes(&self, narrow_indices: bool) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MESH_CACHE_MAGIC);
+        write_u32(&mut bytes, MESH_CACHE_VERSION);
+        write_u32(&mut bytes, std::mem::size_of::<Vertex>().try_into()?);
+
+        write_len(&mut bytes, self.nodes.len())?;
+        for node in &self.nodes {
+            write_optional_string(&mut bytes, node.name.as_deref())?;
+            bytes.extend_from_slice(bytemuck::cast_slice(&node.transform.to_cols_array()));
+            write_optional_index(&mut bytes, node.parent_index)?;
+            write_optional_index(&mut bytes, node.skin_index)?;
+        }
+
+        write_len(&mut bytes, self.primitives.len())?;
+        for primitive in &self.primitives {
+            write_len(&mut bytes, primitive.mesh_index)?;
+            write_len(&mut bytes, primitive.primitive_index)?;
+            write_u32(
+                &mut bytes,
+                match primitive.primitive_mode {
+                    PrimitiveMode::Triangles => 0,
+                    PrimitiveMode::Lines => 1,
+                    PrimitiveMode::Points => 2,
+                },
+            );
+            bytes.extend_from_slice(bytemuck::cast_slice(&primitive.bounding_box.min.to_array()));
+            bytes.extend_from_slice(bytemuck::cast_slice(&primitive.bounding_box.max.to_array()));
+            write_len(&mut bytes, primitive.geometry_source.vertices.len())?;
+            bytes.extend_from_slice(bytemuck::cast_slice(&primitive.geometry_source.vertices));
+            write_indices(
+                &mut bytes,
+                &primitive.geometry_source.indices,
+                narrow_indices,
+            )?;
+            write_len(&mut bytes, primitive.lods.len())?;
+            for lod in &primitive.lods {
+                bytes.extend_from_slice(&lod.error.to_le_bytes());
+                write_indices(&mut bytes, &lod.indices, narrow_indices)?;
+            }
+        }
+
+        // 0 = default compression level
+        Ok(zstd::stream::encode_all(bytes.as_slice(), 0)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes = zstd::stream::decode_all(bytes)?;
+        let mut reader = ByteReader {
+            bytes: &bytes,
+            position: 0,
+        };
+
+        if reader.read_bytes(MESH_CACHE_MAGIC.len())? != MESH_CACHE_MAGIC {
+            bail!("Data is not a mesh cache");
+        }
+        let version = reader.read_u32()?;
+        if version != MESH_CACHE_VERSION {
+            bail!(
+                "Mesh cache version {:?} doesn't match the expected version {:?}",
+                version,
+                MESH_CACHE_VERSION
+            );
+        }
+        let vertex_size = reader.read_u32()? as usize;
+        if vertex_size != std::mem::size_of::<Vertex>() {
+            bail!(
+                "Mesh cache vertex size {:?} doesn't match the expected size {:?}",
+                vertex_size,
+                std::mem::size_of::<Vertex>()
+            );
+        }
+
+        let node_count = reader.read_u32()? as usize;
+        let mut nodes = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            let name = reader.read_optional_string()?;
+            let transform_cols: [f32; 12] = reader.read_pod_vec::<f32>(12)?.try_into().unwrap();
+            nodes.push(CachedNode {
+                name,
+                transform: Affine3A::from_cols_array(&transform_cols).into(),
+                parent_index: reader.read_optional_index()?,
+                skin_index: reader.read_optional_index()?,
+            });
+        }
+
+        let primitive_count = reader.read_u32()? as usize;
+        let mut primitives = Vec::with_capacity(primitive_count);
+        for _ in 0..primitive_count {
+            let mesh_index = reader.read_u32()? as usize;
+            let primitive_index = reader.read_u32()? as usize;
+            let primitive_mode = match reader.read_u32()? {
+                0 => PrimitiveMode::Triangles,
+                1 => PrimitiveMode::Lines,
+                2 => PrimitiveMode::Points,
+                mode => bail!("Unknown primitive mode {:?} in mesh cache", mode),
+            };
+            let bounding_box_min: Vec<f32> = reader.read_pod_vec(3)?;
+            let bounding_box_max: Vec<f32> = reader.read_pod_vec(3)?;
+            let vertex_count = reader.read_u32()? as usize;
+            let vertices = reader.read_pod_vec(vertex_count)?;
+            let indices = reader.read_indices()?;
+            let lod_count = reader.read_u32()? as usize;
+            let mut lods = Vec::with_capacity(lod_count);
+            for _ in 0..lod_count {
+                let error = f32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap());
+                lods.push(MeshLod {
+                    indices: reader.read_indices()?,
+                    error,
+                });
+            }
+            primitives.push(CachedPrimitive {
+                mesh_index,
+                primitive_index,
+                primitive_mode,
+                geometry_source: GeometrySource { vertices, indices },
+                bounding_box: Aabb {
+                    min: Vec3::from_slice(&bounding_box_min),
+                    max: Vec3::from_slice(&bounding_box_max),
+                },
+                lods,
+            });
+        }
+
+        Ok(Self { nodes, primitives })
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_len(bytes: &mut Vec<u8>, len: usize) -> Result<()> {
+    let len: u32 = len.try_into()?;
+    if len == NONE_MARKER {
+        bail!("Length {:?} is too large for the mesh cache", len);
+    }
+    write_u32(bytes, len);
+    Ok(())
+}
+
+fn write_optional_index(bytes: &mut Vec<u8>, index: Option<usize>) -> Result<()> {
+    match index {
+        Some(index) => write_len(bytes, index),
+        None => {
+            write_u32(bytes, NONE_MARKER);
+            Ok(())
+        }
+    }
+}
+
+fn write_optional_string(bytes: &mut Vec<u8>, string: Option<&str>) -> Result<()> {
+    write_optional_index(bytes, string.map(|string| string.len()))?;
+    if let Some(string) = string {
+        bytes.extend_from_slice(string.as_bytes());
+    }
+    Ok(())
+}
+
+// narrow stores the indices as u16 if all of them fit
+fn write_indices(bytes: &mut Vec<u8>, indices: &[u32], narrow: bool) -> Result<()> {
+    write_len(bytes, indices.len())?;
+    let indices_u16: Option<Vec<u16>> = if narrow {
+        indices
+            .iter()
+            .map(|index| u16::try_from(*index).ok())
+            .collect()
+    } else {
+        None
+    };
+    match indices_u16 {
+        Some(indices_u16) => {
+            write_u32(bytes, 2);
+            bytes.extend_from_slice(bytemuck::cast_slice(&indices_u16));
+        }
+        None => {
+            write_u32(bytes, 4);
+            bytes.extend_from_slice(bytemuck::cast_slice(indices));
+        }
+    }
+    Ok(())
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.position + len;
+        if end > self.bytes.len() {
+            bail!("Unexpected end of mesh cache data");
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_optional_index(&mut self) -> Result<Option<usize>> {
+        let value = self.read_u32()?;
+        Ok((value != NONE_MARKER).then_some(value as usize))
+    }
+
+    fn read_optional_string(&mut self) -> Result<Option<String>> {
+        self.read_optional_index()?
+            .map(|len| Ok(String::from_utf8(self.read_bytes(len)?.to_vec())?))
+            .transpose()
+    }
+
+    fn read_indices(&mut self) -> Result<Vec<u32>> {
+        let index_count = self.read_u32()? as usize;
+        match self.read_u32()? {
+            2 => Ok(self
+                .read_pod_vec::<u16>(index_count)?
+                .into_iter()
+                .map(u32::from)
+                .collect()),
+            4 => self.read_pod_vec(index_count),
+            index_size => bail!("Unknown index size {:?} in mesh cache", index_size),
+        }
+    }
+
+    // copies the data out since it isn't necessarily aligned for T
+    fn read_pod_vec<T: bytemuck::Pod>(&mut self, count: usize) -> Result<Vec<T>> {
+        let bytes = self.read_bytes(count * std::mem::size_of::<T>())?;
+        Ok(bytemuck::pod_collect_to_vec(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::transform::TransformBuilder;
+
+    #[test]
+    fn packed_gltf_uses_its_packed_mesh_cache() {
+        let folder =
+            std::env::temp_dir().join(format!("ikari_mesh_cache_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let gltf_json = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "triangle.bin", "byteLength": 36 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 3,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [1.0, 1.0, 0.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let positions: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let document = gltf::Gltf::from_slice(gltf_json).unwrap().document;
+        let mesh_cache_bytes =
+            build_mesh_cache(&document, &[gltf::buffer::Data(positions.clone())])
+                .unwrap()
+                .to_bytes(true)
+                .unwrap();
+
+        // the archive itself is newer than all of its files
+        let sources_modified =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        let cache_modified = sources_modified + std::time::Duration::from_secs(60);
+        let pack_path = folder.join("assets.pak");
+        let mut pack_writer = PackArchiveWriter::create(&pack_path, 0).unwrap();
+        pack_writer
+            .add_file("triangle.gltf", gltf_json, Some(sources_modified))
+            .unwrap();
+        pack_writer
+            .add_file("triangle.bin", &positions, Some(sources_modified))
+            .unwrap();
+        pack_writer
+            .add_file(
+                gltf_path_to_mesh_cache_path(Path::new("triangle.gltf")),
+                &mesh_cache_bytes,
+                Some(cache_modified),
+            )
+            .unwrap();
+        pack_writer.finish().unwrap();
+
+        let mount_point = folder.file_name().unwrap().to_str().unwrap().to_string();
+        VFS.write().unwrap().mount(
+            &mount_point,
+            VfsSource::Pack(PackArchive::open(&pack_path).unwrap()),
+        );
+        let gltf_path = Path::new(&mount_point).join("triangle.gltf");
+        let gltf = open_gltf(&gltf_path).unwrap();
+        let mesh_cache = load_fresh_mesh_cache(&gltf_path, &gltf.document).unwrap();
+        VFS.write().unwrap().unmount(&mount_point);
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        let mesh_cache = mesh_cache.expect("the packed mesh cache should be fresh");
+        assert_eq!(mesh_cache.nodes.len(), 1);
+        assert_eq!(mesh_cache.primitives.len(), 1);
+    }
+
+    #[test]
+    fn mesh_cache_round_trip() {
+        let vertices: Vec<_> = (0..3)
+            .map(|i| Vertex {
+                position: [i as f32, 1.0, 2.0],
+                normal: [0.0, 1.0, 0.0],
+                tex_coords: [0.5, i as f32],
+                tangent: [1.0, 0.0, 0.0],
+                bitangent: [0.0, 0.0, 1.0],
+                color: [1.0, 1.0, 1.0, 1.0],
+                bone_indices: [i, 0, 0, 0],
+                bone_weights: [1.0, 0.0, 0.0, 0.0],
+            })
+            .collect();
+        let mesh_cache = MeshCache {
+            nodes: vec![
+                CachedNode {
+                    name: Some(String::from("root")),
+                    transform: TransformBuilder::new()
+                        .position(Vec3::new(1.0, 2.0, 3.0))
+                        .scale(Vec3::new(2.0, 2.0, 2.0))
+                        .build(),
+                    parent_index: None,
+                    skin_index: Some(0),
+                },
+                CachedNode {
+                    name: None,
+                    transform: crate::transform::Transform::IDENTITY,
+                    parent_index: Some(0),
+                    skin_index: None,
+                },
+            ],
+            primitives: vec![CachedPrimitive {
+                mesh_index: 1,
+                primitive_index: 2,
+                primitive_mode: PrimitiveMode::Lines,
+                geometry_source: GeometrySource {
+                    vertices: vertices.clone(),
+                    indices: vec![0, 1, 2],
+                },
+                bounding_box: Aabb {
+                    min: Vec3::new(0.0, 1.0, 2.0),
+                    max: Vec3::new(2.0, 1.0, 2.0),
+                },
+                lods: vec![MeshLod {
+                    indices: vec![2, 1, 0],
+                    error: 0.25,
+                }],
+            }],
+        };
+
+        let bytes = mesh_cache.to_bytes(false).unwrap();
+        let loaded = MeshCache::from_bytes(&bytes).unwrap();
+        let narrowed_bytes = mesh_cache.to_bytes(true).unwrap();
+        let narrowed = MeshCache::from_bytes(&narrowed_bytes).unwrap();
+        assert_eq!(
+            narrowed.primitives[0].geometry_source.indices,
+            vec![0, 1, 2]
+        );
+        assert!(
+            zstd::stream::decode_all(narrowed_bytes.as_slice())
+                .unwrap()
+                .len()
+                < zstd::stream::decode_all(bytes.as_slice()).unwrap().len()
+        );
+
+        assert_eq!(loaded.nodes.len(), 2);
+        assert_eq!(loaded.nodes[0].name.as_deref(), Some("root"));
+        assert_eq!(loaded.nodes[0].transform, mesh_cache.nodes[0].transform);
+        assert_eq!(loaded.nodes[0].skin_index, Some(0));
+        assert_eq!(loaded.nodes[1].name, None);
+        assert_eq!(loaded.nodes[1].parent_index, Some(0));
+
+        let primitive = &loaded.primitives[0];
+        assert_eq!((primitive.mesh_index, primitive.primitive_index), (1, 2));
+        assert_eq!(primitive.primitive_mode, PrimitiveMode::Lines);
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&primitive.geometry_source.vertices),
+            bytemuck::cast_slice::<_, u8>(&vertices)
+        );
+        assert_eq!(primitive.geometry_source.indices, vec![0, 1, 2]);
+        assert_eq!(primitive.bounding_box.max, Vec3::new(2.0, 1.0, 2.0));
+        assert_eq!(primitive.lods, mesh_cache.primitives[0].lods);
+        assert_eq!(narrowed.primitives[0].lods, mesh_cache.primitives[0].lods);
+
+        // truncated data shouldn't panic
+        let mut truncated = zstd::stream::decode_all(bytes.as_slice()).unwrap();
+        truncated.truncate(truncated.len() - 1);
+        let truncated = zstd::stream::encode_all(truncated.as_slice(), 0).unwrap();
+        assert!(MeshCache::from_bytes(&truncated).is_err());
+    }
+}
@@ -0,0 +1,524 @@
+use crate::mesh::*;
+use crate::renderer::*;
+
+use std::collections::HashMap;
+
+use glam::f32::Vec3;
+use serde::Serialize;
+
+// size of the fifo cache used to compute the stats, roughly matches the post-transform cache of desktop gpus
+const STATS_CACHE_SIZE: usize = 16;
+// size of the lru cache that the vertex cache optimization models
+const VERTEX_CACHE_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MeshOptimizationOptions {
+    pub deduplicate_vertices: bool,
+    pub optimize_vertex_cache: bool,
+    pub optimize_overdraw: bool,
+    // how much the overdraw optimization is allowed to degrade the vertex cache efficiency,
+    // 1.05 means the acmr can get up to 5% worse
+    pub overdraw_threshold: f32,
+    pub optimize_vertex_fetch: bool,
+    // store the indices as u16 in the mesh cache when all of them fit
+    pub narrow_indices: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MeshStats {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    // average cache miss ratio: transformed vertices per triangle, 0.5 is the best case for a regular grid
+    pub acmr: f32,
+    // average transformed vertex ratio: transformed vertices per vertex, 1.0 is optimal
+    pub atvr: f32,
+}
+
+impl MeshStats {
+    // stats of all the given meshes as if they were a single mesh
+    pub fn combined(stats: &[MeshStats]) -> Self {
+        let vertex_count = stats.iter().map(|stats| stats.vertex_count).sum();
+        let triangle_count = stats.iter().map(|stats| stats.triangle_count).sum();
+        let transformed_vertex_count: f32 = stats
+            .iter()
+            .map(|stats| stats.atvr * stats.vertex_count as f32)
+            .sum();
+        Self {
+            vertex_count,
+            triangle_count,
+            acmr: if triangle_count > 0 {
+                transformed_vertex_count / triangle_count as f32
+            } else {
+                0.0
+            },
+            atvr: if vertex_count > 0 {
+                transformed_vertex_count / vertex_count as f32
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl Default for MeshOptimizationOptions {
+    fn default() -> Self {
+        Self {
+            deduplicate_vertices: true,
+            optimize_vertex_cache: true,
+            optimize_overdraw: true,
+            overdraw_threshold: 1.05,
+            optimize_vertex_fetch: true,
+            narrow_indices: true,
+        }
+    }
+}
+
+// reorders the geometry without changing how it looks. points are left alone since
+// their vertices are expanded quads whose order matters to the vertex shader
+pub fn optimize_geometry(
+    geometry_source: &mut GeometrySource,
+    primitive_mode: PrimitiveMode,
+    options: &MeshOptimizationOptions,
+) {
+    if primitive_mode == PrimitiveMode::Points {
+        return;
+    }
+    if options.deduplicate_vertices {
+        deduplicate_vertices(geometry_source);
+    }
+    if primitive_mode == PrimitiveMode::Triangles {
+        if options.optimize_vertex_cache {
+            optimize_vertex_cache(&mut geometry_source.indices, geometry_source.vertices.len());
+        }
+        if options.optimize_overdraw {
+            optimize_overdraw(
+                &mut geometry_source.indices,
+                &geometry_source.vertices,
+                options.overdraw_threshold,
+            );
+        }
+    }
+    if options.optimize_vertex_fetch {
+        optimize_vertex_fetch(geometry_source);
+    }
+}
+
+pub fn get_mesh_stats(
+    geometry_source: &GeometrySource,
+    primitive_mode: PrimitiveMode,
+) -> MeshStats {
+    let vertex_count = geometry_source.vertices.len();
+    let triangle_count = match primitive_mode {
+        PrimitiveMode::Triangles => geometry_source.indices.len() / 3,
+        PrimitiveMode::Lines | PrimitiveMode::Points => 0,
+    };
+    let transformed_vertex_count = count_fifo_cache_misses(&geometry_source.indices, vertex_count);
+    MeshStats {
+        vertex_count,
+        triangle_count,
+        acmr: if triangle_count > 0 {
+            transformed_vertex_count as f32 / triangle_count as f32
+        } else {
+            0.0
+        },
+        atvr: if vertex_count > 0 {
+            transformed_vertex_count as f32 / vertex_count as f32
+        } else {
+            0.0
+        },
+    }
+}
+
+fn count_fifo_cache_misses(indices: &[u32], vertex_count: usize) -> usize {
+    // the timestamp of each vertex tells when it was put in the cache
+    let mut cache_timestamps = vec![0usize; vertex_count];
+    let mut timestamp = STATS_CACHE_SIZE + 1;
+    let mut miss_count = 0;
+    for index in indices {
+        let cache_timestamp = &mut cache_timestamps[*index as usize];
+        if timestamp - *cache_timestamp > STATS_CACHE_SIZE {
+            *cache_timestamp = timestamp;
+            timestamp += 1;
+            miss_count += 1;
+        }
+    }
+    miss_count
+}
+
+// merges vertices whose data is bitwise identical
+pub fn deduplicate_vertices(geometry_source: &mut GeometrySource) {
+    let mut unique_vertices: Vec<Vertex> = Vec::with_capacity(geometry_source.vertices.len());
+    let mut remap: Vec<u32> = Vec::with_capacity(geometry_source.vertices.len());
+    {
+        let mut unique_vertex_indices: HashMap<&[u8], u32> =
+            HashMap::with_capacity(geometry_source.vertices.len());
+        for vertex in &geometry_source.vertices {
+            let new_index = *unique_vertex_indices
+                .entry(bytemuck::bytes_of(vertex))
+                .or_insert_with(|| {
+                    unique_vertices.push(*vertex);
+                    (unique_vertices.len() - 1) as u32
+                });
+            remap.push(new_index);
+        }
+    }
+    for index in geometry_source.indices.iter_mut() {
+        *index = remap[*index as usize];
+    }
+    geometry_source.vertices = unique_vertices;
+}
+
+// tom forsyth's linear-speed vertex cache optimization:
+// https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html
+pub fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // vertex index -> triangles that use it
+    let mut vertex_triangle_offsets = vec![0usize; vertex_count + 1];
+    for index in indices.iter() {
+        vertex_triangle_offsets[*index as usize + 1] += 1;
+    }
+    for vertex_index in 0..vertex_count {
+        vertex_triangle_offsets[vertex_index + 1] += vertex_triangle_offsets[vertex_index];
+    }
+    let mut vertex_triangles = vec![0usize; indices.len()];
+    let mut vertex_fill_counts = vec![0usize; vertex_count];
+    for (index_index, index) in indices.iter().enumerate() {
+        let vertex_index = *index as usize;
+        vertex_triangles
+            [vertex_triangle_offsets[vertex_index] + vertex_fill_counts[vertex_index]] =
+            index_index / 3;
+        vertex_fill_counts[vertex_index] += 1;
+    }
+    // number of triangles that haven't been emitted yet for each vertex
+    let mut live_triangle_counts = vertex_fill_counts;
+
+    let mut cache_positions: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count)
+        .map(|vertex_index| get_vertex_score(None, live_triangle_counts[vertex_index]))
+        .collect();
+    let mut triangle_scores: Vec<f32> = indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            triangle
+                .iter()
+                .map(|index| vertex_scores[*index as usize])
+                .sum()
+        })
+        .collect();
+    let mut emitted_triangles = vec![false; triangle_count];
+
+    let mut output = Vec::with_capacity(indices.len());
+    let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+    let mut best_triangle = None;
+    // triangles before this one have all been emitted
+    let mut emitted_cursor = 0;
+
+    while output.len() < indices.len() {
+        let triangle_index = match best_triangle {
+            Some(triangle_index) => triangle_index,
+            None => {
+                // nothing in the cache is connected to a live triangle, start a new strip
+                while emitted_triangles[emitted_cursor] {
+                    emitted_cursor += 1;
+                }
+                emitted_cursor
+            }
+        };
+        let triangle = [
+            indices[triangle_index * 3],
+            indices[triangle_index * 3 + 1],
+            indices[triangle_index * 3 + 2],
+        ];
+        output.extend_from_slice(&triangle);
+        emitted_triangles[triangle_index] = true;
+
+        for vertex_index in triangle {
+            live_triangle_counts[vertex_index as usize] -= 1;
+        }
+
+        let mut new_cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+        new_cache.extend_from_slice(&triangle);
+        for vertex_index in cache.iter() {
+            if !triangle.contains(vertex_index) {
+                new_cache.push(*vertex_index);
+            }
+        }
+        for evicted_vertex_index in new_cache.iter().skip(VERTEX_CACHE_SIZE) {
+            cache_positions[*evicted_vertex_index as usize] = None;
+        }
+        let touched_vertices = new_cache.clone();
+        new_cache.truncate(VERTEX_CACHE_SIZE);
+        cache = new_cache;
+
+        for (cache_position, vertex_index) in cache.iter().enumerate() {
+            cache_positions[*vertex_index as usize] = Some(cache_position);
+        }
+
+        best_triangle = None;
+        let mut best_triangle_score = f32::MIN;
+        for vertex_index in touched_vertices {
+            let vertex_index = vertex_index as usize;
+            let new_score = get_vertex_score(
+                cache_positions[vertex_index],
+                live_triangle_counts[vertex_index],
+            );
+            let score_delta = new_score - vertex_scores[vertex_index];
+            vertex_scores[vertex_index] = new_score;
+
+            let vertex_triangles = &vertex_triangles
+                [vertex_triangle_offsets[vertex_index]..vertex_triangle_offsets[vertex_index + 1]];
+            for adjacent_triangle_index in vertex_triangles {
+                if emitted_triangles[*adjacent_triangle_index] {
+                    continue;
+                }
+                triangle_scores[*adjacent_triangle_index] += score_delta;
+                if cache_positions[vertex_index].is_some()
+                    && triangle_scores[*adjacent_triangle_index] > best_triangle_score
+                {
+                    best_triangle_score = triangle_scores[*adjacent_triangle_index];
+                    best_triangle = Some(*adjacent_triangle_index);
+                }
+            }
+        }
+    }
+
+    indices[..output.len()].copy_from_slice(&output);
+}
+
+fn get_vertex_score(cache_position: Option<usize>, live_triangle_count: usize) -> f32 {
+    if live_triangle_count == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        // the last triangle's vertices get a fixed score so that strips aren't favored too much
+        Some(cache_position) if cache_position < 3 => 0.75,
+        Some(cache_position) => {
+            let scaler = 1.0 / (VERTEX_CACHE_SIZE - 3) as f32;
+            (1.0 - (cache_position - 3) as f32 * scaler).powf(1.5)
+        }
+        None => 0.0,
+    };
+    // boost vertices with few triangles left so that lone triangles don't get stranded
+    cache_score + 2.0 * (live_triangle_count as f32).powf(-0.5)
+}
+
+// splits the triangles into clusters wherever the vertex cache would be cold and
+// sorts the clusters so that the ones facing outwards get drawn first, which lets
+// the depth test reject more of the fragments behind them.
+// same idea as https://gfx.cs.princeton.edu/pubs/Sander_2007_%3ETR/tipsy.pdf
+pub fn optimize_overdraw(indices: &mut [u32], vertices: &[Vertex], threshold: f32) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    let original_misses = count_fifo_cache_misses(indices, vertices.len());
+
+    let mut cluster_starts = vec![0];
+    {
+        let mut cache_timestamps = vec![0usize; vertices.len()];
+        let mut timestamp = STATS_CACHE_SIZE + 1;
+        for (triangle_index, triangle) in indices.chunks_exact(3).enumerate() {
+            let mut triangle_misses = 0;
+            for index in triangle {
+                let cache_timestamp = &mut cache_timestamps[*index as usize];
+                if timestamp - *cache_timestamp > STATS_CACHE_SIZE {
+                    *cache_timestamp = timestamp;
+                    timestamp += 1;
+                    triangle_misses += 1;
+                }
+            }
+            if triangle_misses == 3 && triangle_index > 0 {
+                cluster_starts.push(triangle_index);
+            }
+        }
+    }
+    if cluster_starts.len() == 1 {
+        return;
+    }
+
+    let position = |index: u32| Vec3::from(vertices[index as usize].position);
+    let mut mesh_centroid = Vec3::ZERO;
+    let mut mesh_area = 0.0;
+    // (area weighted centroid, area weighted normal, area)
+    let mut cluster_data: Vec<(Vec3, Vec3, f32)> = Vec::with_capacity(cluster_starts.len());
+    for (cluster_index, cluster_start) in cluster_starts.iter().enumerate() {
+        let cluster_end = cluster_starts
+            .get(cluster_index + 1)
+            .copied()
+            .unwrap_or(triangle_count);
+        let mut cluster_centroid = Vec3::ZERO;
+        let mut cluster_normal = Vec3::ZERO;
+        let mut cluster_area = 0.0;
+        for triangle in indices[cluster_start * 3..cluster_end * 3].chunks_exact(3) {
+            let (p0, p1, p2) = (
+                position(triangle[0]),
+                position(triangle[1]),
+                position(triangle[2]),
+            );
+            let normal = (p1 - p0).cross(p2 - p0);
+            let area = normal.length();
+            cluster_centroid += (p0 + p1 + p2) / 3.0 * area;
+            cluster_normal += normal;
+            cluster_area += area;
+        }
+        mesh_centroid += cluster_centroid;
+        mesh_area += cluster_area;
+        cluster_data.push((cluster_centroid, cluster_normal, cluster_area));
+    }
+    if mesh_area > 0.0 {
+        mesh_centroid /= mesh_area;
+    }
+
+    let cluster_sort_keys: Vec<f32> = cluster_data
+        .iter()
+        .map(|(centroid, normal, area)| {
+            if *area == 0.0 {
+                return 0.0;
+            }
+            (*centroid / *area - mesh_centroid).dot(normal.normalize_or_zero())
+        })
+        .collect();
+    let mut cluster_order: Vec<usize> = (0..cluster_starts.len()).collect();
+    cluster_order.sort_by(|a, b| cluster_sort_keys[*b].total_cmp(&cluster_sort_keys[*a]));
+
+    let mut output = Vec::with_capacity(indices.len());
+    for cluster_index in cluster_order {
+        let cluster_start = cluster_starts[cluster_index];
+        let cluster_end = cluster_starts
+            .get(cluster_index + 1)
+            .copied()
+            .unwrap_or(triangle_count);
+        output.extend_from_slice(&indices[cluster_start * 3..cluster_end * 3]);
+    }
+
+    let new_misses = count_fifo_cache_misses(&output, vertices.len());
+    if new_misses as f32 <= original_misses as f32 * threshold {
+        indices[..output.len()].copy_from_slice(&output);
+    }
+}
+
+// renumbers the vertices in the order they're first used by the index buffer so the
+// vertex fetches are as sequential as possible. unused vertices are dropped
+pub fn optimize_vertex_fetch(geometry_source: &mut GeometrySource) {
+    let mut remap: Vec<Option<u32>> = vec![None; geometry_source.vertices.len()];
+    let mut new_vertices = Vec::with_capacity(geometry_source.vertices.len());
+    for index in geometry_source.indices.iter_mut() {
+        let new_index = *remap[*index as usize].get_or_insert_with(|| {
+            new_vertices.push(geometry_source.vertices[*index as usize]);
+            (new_vertices.len() - 1) as u32
+        });
+        *index = new_index;
+    }
+    geometry_source.vertices = new_vertices;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_vertex(x: f32, z: f32) -> Vertex {
+        Vertex {
+            position: [x, (x * 0.3).sin() + (z * 0.2).cos(), z],
+            normal: [0.0, 1.0, 0.0],
+            tex_coords: [x / 16.0, z / 16.0],
+            tangent: [1.0, 0.0, 0.0],
+            bitangent: [0.0, 0.0, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            bone_indices: [0, 0, 0, 0],
+            bone_weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    // an unindexed triangle soup of a bumpy grid, so every vertex is duplicated a few times
+    fn make_grid(size: usize) -> GeometrySource {
+        let mut vertices = Vec::new();
+        for x in 0..size {
+            for z in 0..size {
+                let (x0, x1, z0, z1) = (x as f32, x as f32 + 1.0, z as f32, z as f32 + 1.0);
+                vertices.extend([
+                    make_vertex(x0, z0),
+                    make_vertex(x0, z1),
+                    make_vertex(x1, z0),
+                    make_vertex(x1, z0),
+                    make_vertex(x0, z1),
+                    make_vertex(x1, z1),
+                ]);
+            }
+        }
+        GeometrySource {
+            indices: (0..vertices.len() as u32).collect(),
+            vertices,
+        }
+    }
+
+    // each triangle as the bytes of its vertices, rotated so that the winding is preserved
+    fn get_triangle_set(geometry_source: &GeometrySource) -> Vec<Vec<u8>> {
+        let mut triangles: Vec<_> = geometry_source
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let vertex_bytes: Vec<&[u8]> = triangle
+                    .iter()
+                    .map(|index| bytemuck::bytes_of(&geometry_source.vertices[*index as usize]))
+                    .collect();
+                (0..3)
+                    .map(|rotation| {
+                        (0..3)
+                            .flat_map(|i| vertex_bytes[(i + rotation) % 3].to_vec())
+                            .collect::<Vec<u8>>()
+                    })
+                    .min()
+                    .unwrap()
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn optimization_preserves_triangles() {
+        let mut geometry_source = make_grid(24);
+        let original_triangles = get_triangle_set(&geometry_source);
+        let stats_before = get_mesh_stats(&geometry_source, PrimitiveMode::Triangles);
+
+        optimize_geometry(
+            &mut geometry_source,
+            PrimitiveMode::Triangles,
+            &MeshOptimizationOptions::default(),
+        );
+        let stats_after = get_mesh_stats(&geometry_source, PrimitiveMode::Triangles);
+
+        assert_eq!(get_triangle_set(&geometry_source), original_triangles);
+        assert_eq!(stats_after.triangle_count, stats_before.triangle_count);
+        assert_eq!(stats_after.vertex_count, 25 * 25);
+        assert!(stats_after.acmr < stats_before.acmr);
+        assert!(stats_after.atvr >= 1.0);
+
+        // the vertices are in the order they're first used
+        let mut next_new_index = 0;
+        for index in &geometry_source.indices {
+            assert!(*index <= next_new_index);
+            if *index == next_new_index {
+                next_new_index += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn points_are_left_untouched() {
+        let mut geometry_source = make_grid(2);
+        let original_indices = geometry_source.indices.clone();
+        optimize_geometry(
+            &mut geometry_source,
+            PrimitiveMode::Points,
+            &MeshOptimizationOptions::default(),
+        );
+        assert_eq!(geometry_source.indices, original_indices);
+        assert_eq!(geometry_source.vertices.len(), original_indices.len());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::mesh::*;
+use crate::mesh_optimizer::*;
+
+use std::collections::{HashMap, HashSet};
+
+use glam::f32::Vec3;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LodOptions {
+    // fraction of the triangles of the original mesh to keep in each lod, most detailed first
+    pub target_ratios: Vec<f32>,
+    // relative to the diameter of the bounding sphere of the mesh, which is what the renderer
+    // measures on screen. 0.01 means that vertices can move by up to 1% of the diameter
+    pub max_error: f32,
+}
+
+// a simplified version of a mesh that reuses the vertices of the original
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshLod {
+    pub indices: Vec<u32>,
+    // relative to the diameter of the bounding sphere of the mesh, see LodOptions::max_error
+    pub error: f32,
+}
+
+impl Default for LodOptions {
+    fn default() -> Self {
+        Self {
+            target_ratios: vec![0.5, 0.25, 0.125],
+            max_error: 0.02,
+        }
+    }
+}
+
+// symmetric 4x4 matrix of the plane equations, stored as xx, xy, xz, xw, yy, yz, yw, zz, zw, ww
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: Vec3, distance: f32, weight: f32) -> Self {
+        let (a, b, c, d) = (
+            normal.x as f64,
+            normal.y as f64,
+            normal.z as f64,
+            distance as f64,
+        );
+        let weight = weight as f64;
+        Self([
+            a * a * weight,
+            a * b * weight,
+            a * c * weight,
+            a * d * weight,
+            b * b * weight,
+            b * c * weight,
+            b * d * weight,
+            c * c * weight,
+            c * d * weight,
+            d * d * weight,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other_value) in self.0.iter_mut().zip(other.0.iter()) {
+            *value += other_value;
+        }
+    }
+
+    // sum of the squared distances from the point to the planes
+    fn error(&self, point: Vec3) -> f64 {
+        let [xx, xy, xz, xw, yy, yz, yw, zz, zw, ww] = self.0;
+        let (x, y, z) = (point.x as f64, point.y as f64, point.z as f64);
+        (x * x * xx
+            + y * y * yy
+            + z * z * zz
+            + ww
+            + 2.0 * (x * y * xy + x * z * xz + y * z * yz + x * xw + y * yw + z * zw))
+            .abs()
+    }
+}
+
+// generates a lod for each target ratio, stopping early once the error limit is reached
+pub fn generate_lods(geometry_source: &GeometrySource, options: &LodOptions) -> Vec<MeshLod> {
+    let mut lods: Vec<MeshLod> = Vec::new();
+    let original_index_count = geometry_source.indices.len();
+    for target_ratio in &options.target_ratios {
+        let target_index_count = (original_index_count as f32 * target_ratio) as usize / 3 * 3;
+        let mut lod = simplify_mesh(
+            &geometry_source.vertices,
+            &geometry_source.indices,
+            target_index_count,
+            options.max_error,
+        );
+        let previous_index_count = lods
+            .last()
+            .map(|lod| lod.indices.len())
+            .unwrap_or(original_index_count);
+        // not worth keeping if it barely removed anything compared to the previous level
+        if lod.indices.is_empty() || lod.indices.len() as f32 > previous_index_count as f32 * 0.9 {
+            break;
+        }
+        optimize_vertex_cache(&mut lod.indices, geometry_source.vertices.len());
+        lods.push(lod);
+    }
+    lods
+}
+
+// quadric error metric simplification using half-edge collapses, see
+// https://www.cs.cmu.edu/~./garland/Papers/quadrics.pdf
+// vertices are only ever collapsed onto their neighbors so the attributes don't need to be interpolated.
+// vertices on borders, non-manifold edges and attribute seams (same position, different uv/normal/etc.)
+// are never moved, which keeps the silhouette and the texture mapping intact
+pub fn simplify_mesh(
+    vertices: &[Vertex],
+    indices: &[u32],
+    target_index_count: usize,
+    max_error: f32,
+) -> MeshLod {
+    let vertex_count = vertices.len();
+    if vertex_count == 0 || indices.len() <= target_index_count {
+        return MeshLod {
+            indices: indices.to_vec(),
+            error: 0.0,
+        };
+    }
+
+    // scale the mesh to fit in a unit cube so the error doesn't depend on its units
+    let mut min_point = Vec3::from(vertices[0].position);
+    let mut max_point = min_point;
+    for vertex in vertices {
+        min_point = min_point.min(Vec3::from(vertex.position));
+        max_point = max_point.max(Vec3::from(vertex.position));
+    }
+    let extent = (max_point - min_point).max_element().max(f32::EPSILON);
+    // the diagonal of the bounding box, which is the diameter of the bounding sphere
+    let diameter = (max_point - min_point).length().max(f32::EPSILON);
+    let unit_cube_diameter = (diameter / extent) as f64;
+    let positions: Vec<Vec3> = vertices
+        .iter()
+        .map(|vertex| (Vec3::from(vertex.position) - min_point) / extent)
+        .collect();
+
+    // vertex index -> id shared by all the vertices with the same position
+    let mut position_id_map: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut position_vertex_counts: Vec<usize> = Vec::new();
+    let position_ids: Vec<u32> = vertices
+        .iter()
+        .map(|vertex| {
+            let key = vertex.position.map(f32::to_bits);
+            let position_id = *position_id_map.entry(key).or_insert_with(|| {
+                position_vertex_counts.push(0);
+                (position_vertex_counts.len() - 1) as u32
+            });
+            position_vertex_counts[position_id as usize] += 1;
+            position_id
+        })
+        .collect();
+    let position_count = position_vertex_counts.len();
+
+    let mut directed_edge_counts: HashMap<(u32, u32), usize> = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [
+            (triangle[0], triangle[1]),
+            (triangle[1], triangle[2]),
+            (triangle[2], triangle[0]),
+        ] {
+            *directed_edge_counts
+                .entry((position_ids[a as usize], position_ids[b as usize]))
+                .or_default() += 1;
+        }
+    }
+    let mut is_position_locked: Vec<bool> = position_vertex_counts
+        .iter()
+        .map(|vertex_count| *vertex_count > 1)
+        .collect();
+    for ((a, b), count) in &directed_edge_counts {
+        let is_border = !directed_edge_counts.contains_key(&(*b, *a));
+        if is_border || *count > 1 {
+            is_position_locked[*a as usize] = true;
+            is_position_locked[*b as usize] = true;
+        }
+    }
+
+    let mut quadrics = vec![Quadric::default(); position_count];
+    for triangle in indices.chunks_exact(3) {
+        let (p0, p1, p2) = (
+            positions[triangle[0] as usize],
+            positions[triangle[1] as usize],
+            positions[triangle[2] as usize],
+        );
+        let normal = (p1 - p0).cross(p2 - p0);
+        let area = normal.length();
+        if area == 0.0 {
+            continue;
+        }
+        let normal = normal / area;
+        let quadric = Quadric::from_plane(normal, -normal.dot(p0), area);
+        for index in triangle {
+            quadrics[position_ids[*index as usize] as usize].add(&quadric);
+        }
+    }
+
+    // the errors are computed in the unit cube
+    let max_error_squared = (max_error as f64 * unit_cube_diameter).powi(2);
+    let mut result_error_squared = 0.0f64;
+    let mut indices = indices.to_vec();
+    let mut remap: Vec<u32> = (0..vertex_count as u32).collect();
+
+    while indices.len() > target_index_count {
+        let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for (triangle_index, triangle) in indices.chunks_exact(3).enumerate() {
+            for index in triangle {
+                vertex_triangles[*index as usize].push(triangle_index);
+            }
+        }
+
+        // (cost, from vertex, to vertex)
+        let mut collapses: Vec<(f64, u32, u32)> = Vec::with_capacity(indices.len() * 2);
+        for triangle in indices.chunks_exact(3) {
+            for (from, to) in [
+                (triangle[0], triangle[1]),
+                (triangle[1], triangle[2]),
+                (triangle[2], triangle[0]),
+                (triangle[1], triangle[0]),
+                (triangle[2], triangle[1]),
+                (triangle[0], triangle[2]),
+            ] {
+                let from_position_id = position_ids[from as usize] as usize;
+                if is_position_locked[from_position_id] {
+                    continue;
+                }
+                let cost = quadrics[from_position_id].error(positions[to as usize]);
+                collapses.push((cost, from, to));
+            }
+        }
+        collapses.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // each collapse removes two triangles
+        let collapses_needed = (indices.len() - target_index_count) / 6 + 1;
+        let mut collapse_count = 0;
+        let mut is_vertex_touched = vec![false; vertex_count];
+        for (cost, from, to) in collapses {
+            if collapse_count >= collapses_needed || cost > max_error_squared {
+                break;
+            }
+            let (from, to) = (from as usize, to as usize);
+            if is_vertex_touched[from] || is_vertex_touched[to] {
+                continue;
+            }
+            if !is_collapse_valid(
+                &indices,
+                &vertex_triangles,
+                &positions,
+                &position_ids,
+                from,
+                to,
+            ) {
+                continue;
+            }
+
+            remap[from] = to as u32;
+            for triangle_index in &vertex_triangles[from] {
+                for index in &indices[triangle_index * 3..triangle_index * 3 + 3] {
+                    is_vertex_touched[*index as usize] = true;
+                }
+            }
+            let from_quadric = quadrics[position_ids[from] as usize];
+            quadrics[position_ids[to] as usize].add(&from_quadric);
+            result_error_squared = result_error_squared.max(cost);
+            collapse_count += 1;
+        }
+
+        if collapse_count == 0 {
+            break;
+        }
+
+        let mut new_indices = Vec::with_capacity(indices.len());
+        for triangle in indices.chunks_exact(3) {
+            let triangle =
+                [triangle[0], triangle[1], triangle[2]].map(|index| remap[index as usize]);
+            let [a, b, c] = triangle.map(|index| position_ids[index as usize]);
+            if a != b && b != c && c != a {
+                new_indices.extend_from_slice(&triangle);
+            }
+        }
+        indices = new_indices;
+    }
+
+    MeshLod {
+        indices,
+        error: (result_error_squared.sqrt() / unit_cube_diameter) as f32,
+    }
+}
+
+fn is_collapse_valid(
+    indices: &[u32],
+    vertex_triangles: &[Vec<usize>],
+    positions: &[Vec3],
+    position_ids: &[u32],
+    from: usize,
+    to: usize,
+) -> bool {
+    let get_neighbor_positions = |vertex_index: usize| -> HashSet<u32> {
+        vertex_triangles[vertex_index]
+            .iter()
+            .flat_map(|triangle_index| &indices[triangle_index * 3..triangle_index * 3 + 3])
+            .map(|index| position_ids[*index as usize])
+            .filter(|position_id| *position_id != position_ids[vertex_index])
+            .collect()
+    };
+
+    // link condition: the edge must only have the two triangles on either side of it in common,
+    // otherwise the collapse would pinch the surface into a non-manifold shape
+    let from_neighbors = get_neighbor_positions(from);
+    let to_neighbors = get_neighbor_positions(to);
+    if from_neighbors.intersection(&to_neighbors).count() > 2 {
+        return false;
+    }
+
+    // the triangles that get stretched by the collapse must not flip over
+    for triangle_index in &vertex_triangles[from] {
+        let triangle = &indices[triangle_index * 3..triangle_index * 3 + 3];
+        if triangle
+            .iter()
+            .any(|index| position_ids[*index as usize] == position_ids[to])
+        {
+            continue;
+        }
+        let get_normal = |moved_position: Vec3| {
+            let [p0, p1, p2] = [triangle[0], triangle[1], triangle[2]].map(|index| {
+                if index as usize == from {
+                    moved_position
+                } else {
+                    positions[index as usize]
+                }
+            });
+            (p1 - p0).cross(p2 - p0)
+        };
+        let old_normal = get_normal(positions[from]);
+        let new_normal = get_normal(positions[to]);
+        if old_normal.dot(new_normal) <= 0.0 {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_vertex(position: [f32; 3], tex_coords: [f32; 2]) -> Vertex {
+        Vertex {
+            position,
+            normal: [0.0, 1.0, 0.0],
+            tex_coords,
+            tangent: [1.0, 0.0, 0.0],
+            bitangent: [0.0, 0.0, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            bone_indices: [0, 0, 0, 0],
+            bone_weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    // a flat grid in the xz plane, split in two uv islands along x = size / 2
+    fn make_grid(size: u32, height: impl Fn(f32, f32) -> f32) -> GeometrySource {
+        let mut vertices = Vec::new();
+        let mut vertex_indices = HashMap::new();
+        let mut indices = Vec::new();
+        let seam_x = size / 2;
+        let mut get_vertex_index = |x: u32, z: u32, island: u32| {
+            // vertices off the seam are shared by both islands
+            let island = if x == seam_x { island } else { 0 };
+            *vertex_indices.entry((x, z, island)).or_insert_with(|| {
+                let (x, z) = (x as f32, z as f32);
+                vertices.push(make_vertex(
+                    [x, height(x, z), z],
+                    [x / size as f32 + island as f32, z / size as f32],
+                ));
+                (vertices.len() - 1) as u32
+            })
+        };
+        for x in 0..size {
+            for z in 0..size {
+                let island = if x < seam_x { 0 } else { 1 };
+                let (a, b, c, d) = (
+                    get_vertex_index(x, z, island),
+                    get_vertex_index(x, z + 1, island),
+                    get_vertex_index(x + 1, z, island),
+                    get_vertex_index(x + 1, z + 1, island),
+                );
+                indices.extend([a, b, c, c, b, d]);
+            }
+        }
+        GeometrySource { vertices, indices }
+    }
+
+    fn get_area(geometry_source: &GeometrySource, indices: &[u32]) -> f32 {
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [p0, p1, p2] = [triangle[0], triangle[1], triangle[2]]
+                    .map(|index| Vec3::from(geometry_source.vertices[index as usize].position));
+                // the grid faces up
+                (p1 - p0).cross(p2 - p0).y / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn flat_grid_simplifies_without_error() {
+        let grid = make_grid(16, |_, _| 0.0);
+        let target_index_count = grid.indices.len() / 4;
+        let lod = simplify_mesh(&grid.vertices, &grid.indices, target_index_count, 0.01);
+
+        assert!(lod.indices.len() <= target_index_count);
+        assert!(lod.error < 1e-4);
+        // no triangles got flipped or folded over each other
+        assert!((get_area(&grid, &lod.indices) - 16.0 * 16.0).abs() < 1e-3);
+
+        // the borders and both sides of the seam are kept
+        let used_vertices: HashSet<u32> = lod.indices.iter().copied().collect();
+        for (vertex_index, vertex) in grid.vertices.iter().enumerate() {
+            let [x, _, z] = vertex.position;
+            if x == 0.0 || z == 0.0 || x == 16.0 || z == 16.0 || x == 8.0 {
+                assert!(used_vertices.contains(&(vertex_index as u32)));
+            }
+        }
+    }
+
+    #[test]
+    fn error_limit_stops_simplification() {
+        let bumpy_grid = make_grid(16, |x, z| ((x * 1.7).sin() + (z * 2.3).cos()) * 0.5);
+        let target_index_count = bumpy_grid.indices.len() / 4;
+
+        let strict_lod = simplify_mesh(
+            &bumpy_grid.vertices,
+            &bumpy_grid.indices,
+            target_index_count,
+            0.001,
+        );
+        let loose_lod = simplify_mesh(
+            &bumpy_grid.vertices,
+            &bumpy_grid.indices,
+            target_index_count,
+            1.0,
+        );
+
+        assert!(strict_lod.error <= 0.001);
+        assert!(strict_lod.indices.len() > loose_lod.indices.len());
+        assert!(loose_lod.indices.len() <= target_index_count);
+    }
+
+    #[test]
+    fn error_is_relative_to_the_bounding_sphere_diameter() {
+        let bumpy_grid = make_grid(16, |x, z| ((x * 1.7).sin() + (z * 2.3).cos()) * 0.5);
+        let mut scaled_grid = bumpy_grid.clone();
+        for vertex in &mut scaled_grid.vertices {
+            vertex.position = vertex.position.map(|coord| coord * 4.0);
+        }
+        let target_index_count = bumpy_grid.indices.len() / 4;
+
+        let lod = simplify_mesh(
+            &bumpy_grid.vertices,
+            &bumpy_grid.indices,
+            target_index_count,
+            0.01,
+        );
+        let scaled_lod = simplify_mesh(
+            &scaled_grid.vertices,
+            &scaled_grid.indices,
+            target_index_count,
+            0.01,
+        );
+
+        assert!(lod.error > 0.0 && lod.error <= 0.01);
+        assert_eq!(lod.indices, scaled_lod.indices);
+        assert!((lod.error - scaled_lod.error).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lods_get_smaller() {
+        let grid = make_grid(16, |_, _| 0.0);
+        let lods = generate_lods(&grid, &LodOptions::default());
+
+        assert!(!lods.is_empty());
+        let mut previous_index_count = grid.indices.len();
+        for lod in &lods {
+            assert!(lod.indices.len() < previous_index_count);
+            previous_index_count = lod.indices.len();
+        }
+    }
+}
@@ -92,7 +92,9 @@ impl PhysicsState {
                                 .bounding_box
                         }
                         GameNodeMeshType::Unlit { .. } => {
-                            renderer_data.binded_unlit_meshes[*mesh_index].bounding_box
+                            renderer_data.binded_unlit_meshes[*mesh_index]
+                                .geometry_buffers
+                                .bounding_box
                         }
                     };
                     let base_scale = (bounding_box.max - bounding_box.min) / 2.0;
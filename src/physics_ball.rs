@@ -1,8 +1,12 @@
+use crate::behaviour::*;
 use crate::game::*;
 use crate::physics::*;
+use crate::revolver::*;
 use crate::scene::*;
 use crate::transform::*;
 
+use std::any::Any;
+
 use glam::f32::Vec3;
 
 const RESTITUTION: f32 = 0.1;
@@ -73,19 +77,8 @@ impl PhysicsBall {
         Self::new(scene, physics_state, mesh, position, radius)
     }
 
-    pub fn update(&self, scene: &mut Scene, physics_state: &mut PhysicsState) {
-        if let Some(node) = scene.get_node_mut(self.node_id) {
-            let rigid_body = &mut physics_state.rigid_body_set[self.rigid_body_handle];
-            node.transform.apply_isometry(*rigid_body.position());
-            if node.transform.decompose().position.y < -1.0 {
-                self.destroy(scene, physics_state);
-            }
-        }
-    }
-
-    pub fn destroy(&self, scene: &mut Scene, physics_state: &mut PhysicsState) {
-        scene.remove_node(self.node_id);
-        physics_state.remove_rigid_body(self.rigid_body_handle);
+    pub fn node_id(&self) -> GameNodeId {
+        self.node_id
     }
 
     pub fn _toggle_wireframe(&self, scene: &mut Scene) {
@@ -100,3 +93,57 @@ impl PhysicsBall {
         self.rigid_body_handle
     }
 }
+
+// the rigid body is removed along with the node
+impl Behaviour for PhysicsBall {
+    fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+        if let Some(node) = ctx.scene.get_node_mut(self.node_id) {
+            let rigid_body = &ctx.physics_state.rigid_body_set[self.rigid_body_handle];
+            node.transform.apply_isometry(*rigid_body.position());
+            if node.transform.decompose().position.y < -1.0 {
+                ctx.scene.remove_node(self.node_id);
+            }
+        }
+    }
+
+    fn on_message(&mut self, message: &dyn Any, ctx: &mut BehaviourContext) {
+        if let Some(ColliderHitMessage { collider_handle }) = message.downcast_ref() {
+            let hit_rigid_body_handle = ctx
+                .physics_state
+                .collider_set
+                .get(*collider_handle)
+                .and_then(|collider| collider.parent());
+            if hit_rigid_body_handle == Some(self.rigid_body_handle) {
+                ctx.scene.remove_node(self.node_id);
+            }
+        }
+    }
+
+    fn on_destroy(&mut self, ctx: &mut BehaviourContext) {
+        ctx.physics_state.remove_rigid_body(self.rigid_body_handle);
+    }
+}
+
+// keeps its node on a rigid body that's left alone otherwise, unlike PhysicsBall
+pub struct BouncingBall {
+    node_id: GameNodeId,
+    rigid_body_handle: RigidBodyHandle,
+}
+
+impl BouncingBall {
+    pub fn new(node_id: GameNodeId, rigid_body_handle: RigidBodyHandle) -> Self {
+        Self {
+            node_id,
+            rigid_body_handle,
+        }
+    }
+}
+
+impl Behaviour for BouncingBall {
+    fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+        if let Some(node) = ctx.scene.get_node_mut(self.node_id) {
+            let rigid_body = &ctx.physics_state.rigid_body_set[self.rigid_body_handle];
+            node.transform.apply_isometry(*rigid_body.position());
+        }
+    }
+}
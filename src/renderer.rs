@@ -3,6 +3,7 @@ use crate::camera::*;
 use crate::collisions::*;
 use crate::game::*;
 use crate::game_state::*;
+use crate::handle::*;
 use crate::light::*;
 use crate::logger::*;
 use crate::math::*;
@@ -11,19 +12,19 @@ use crate::sampler_cache::*;
 use crate::scene::*;
 use crate::skinning::*;
 use crate::texture::*;
+use crate::texture_streaming::*;
 use crate::transform::*;
 use crate::ui_overlay::*;
+use crate::vfs::*;
 
-use std::collections::{hash_map::Entry, HashMap};
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::num::NonZeroU32;
 use std::num::NonZeroU64;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
-use glam::f32::{Mat4, Vec3};
+use glam::f32::{Mat4, Vec3, Vec4};
 use image::Pixel;
 use wgpu::util::DeviceExt;
 use wgpu::InstanceDescriptor;
@@ -31,10 +32,15 @@ use wgpu_profiler::wgpu_profiler;
 use winit::window::Window;
 
 pub const MAX_LIGHT_COUNT: usize = 32;
+// TODO: the shadow map texture arrays currently put a hard limit on the number of lights at a time
+pub const MAX_SHADOW_CASTING_POINT_LIGHT_COUNT: u32 = 2;
+pub const MAX_SHADOW_CASTING_DIRECTIONAL_LIGHT_COUNT: u32 = 2;
 pub const NEAR_PLANE_DISTANCE: f32 = 0.001;
 pub const FAR_PLANE_DISTANCE: f32 = 100000.0;
 pub const FOV_Y_DEG: f32 = 45.0;
 pub const DEFAULT_WIREFRAME_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0];
+// the coarsest lod whose simplification error stays below this size on screen gets drawn
+pub const LOD_MAX_SCREEN_ERROR_PIXELS: f32 = 1.0;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -71,7 +77,11 @@ fn make_point_light_uniform_buffer(game_state: &GameState) -> Vec<PointLightUnif
                 .scene
                 .get_node(point_light.node_id)
                 .map(|light_node| {
-                    let position = light_node.transform.position();
+                    // lights imported from gltf files can be nested in the node hierarchy
+                    let position = game_state
+                        .scene
+                        .get_global_transform_for_node_opt(light_node.id())
+                        .position();
                     PointLightUniform {
                         position: [position.x, position.y, position.z, 1.0],
                         color: [
@@ -110,6 +120,7 @@ impl From<&DirectionalLightComponent> for DirectionalLightUniform {
             direction,
             color,
             intensity,
+            ..
         } = light;
         let view_proj_matrices =
             build_directional_light_camera_view(-light.direction, 100.0, 100.0, 1000.0);
@@ -181,8 +192,14 @@ pub enum SkyboxHDREnvironment<'a> {
 pub struct BindedPbrMesh {
     pub geometry_buffers: GeometryBuffers,
     pub textures_bind_group: Arc<wgpu::BindGroup>,
+    // indices into RendererPublicData::textures, used to rebuild the bind group when the textures
+    // are replaced by the texture streamer. None if the textures aren't owned by the renderer
+    pub texture_indices: Option<IndexedPbrMaterial>,
     pub dynamic_pbr_params: DynamicPbrParams,
 
+    // used by the gltf exporter, None if the mesh was loaded without its cpu-side copies
+    pub sources: Option<PbrMeshSources>,
+
     pub alpha_mode: AlphaMode,
     pub primitive_mode: PrimitiveMode,
 }
@@ -193,11 +210,33 @@ pub struct GeometryBuffers {
     pub index_buffer: GpuBuffer,
     pub index_buffer_format: wgpu::IndexFormat,
     pub bounding_box: crate::collisions::Aabb,
+    // simplified versions of the index buffer, most detailed first
+    pub lods: Vec<LodIndexBuffer>,
+}
+
+#[derive(Debug)]
+pub struct LodIndexBuffer {
+    pub index_buffer: GpuBuffer,
+    pub index_buffer_format: wgpu::IndexFormat,
+    // relative to the size of the mesh, see MeshLod
+    pub error: f32,
 }
 
-pub type BindedUnlitMesh = GeometryBuffers;
+#[derive(Debug)]
+pub struct BindedUnlitMesh {
+    pub geometry_buffers: GeometryBuffers,
+    pub textures_bind_group: Arc<wgpu::BindGroup>,
+    // index into RendererPublicData::textures
+    pub base_color_texture_index: Option<usize>,
+    // multiplied with the color of the node that uses the mesh
+    pub base_color_factor: Vec4,
+    pub alpha_cutoff: f32,
+
+    pub alpha_mode: AlphaMode,
+    pub primitive_mode: PrimitiveMode,
+}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MeshType {
     Pbr,
     Unlit,
@@ -226,9 +265,12 @@ pub enum AlphaMode {
     Mask,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PrimitiveMode {
     Triangles,
+    Lines,
+    // each point is expanded into a quad of 4 vertices, see vs_point_main in unlit_mesh.wgsl
+    Points,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -255,6 +297,7 @@ pub struct BaseRenderer {
     pub bones_and_instances_bind_group_layout: wgpu::BindGroupLayout,
     pub pbr_textures_bind_group_layout: wgpu::BindGroupLayout,
     default_texture_cache: Mutex<HashMap<DefaultTextureType, Arc<Texture>>>,
+    mip_pipeline_cache: Mutex<HashMap<wgpu::TextureFormat, Arc<MipPipeline>>>,
     pub sampler_cache: Mutex<SamplerCache>,
 }
 
@@ -505,6 +548,7 @@ impl BaseRenderer {
             bones_and_instances_bind_group_layout,
             pbr_textures_bind_group_layout,
             default_texture_cache: Mutex::new(HashMap::new()),
+            mip_pipeline_cache: Mutex::new(HashMap::new()),
             sampler_cache: Mutex::new(SamplerCache::new()),
         }
     }
@@ -630,6 +674,40 @@ impl BaseRenderer {
         Ok(textures_bind_group)
     }
 
+    pub fn make_unlit_textures_bind_group(
+        &self,
+        base_color: Option<&Texture>,
+    ) -> Result<wgpu::BindGroup> {
+        let auto_generated_base_color_texture;
+        let base_color_texture = match base_color {
+            Some(base_color_texture) => base_color_texture,
+            None => {
+                auto_generated_base_color_texture =
+                    self.get_default_texture(DefaultTextureType::BaseColor)?;
+                &auto_generated_base_color_texture
+            }
+        };
+
+        let sampler_cache_guard = self.sampler_cache.lock().unwrap();
+
+        Ok(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.single_texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&base_color_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(
+                        sampler_cache_guard.get_sampler_by_index(base_color_texture.sampler_index),
+                    ),
+                },
+            ],
+            label: Some("InstancedMeshComponent unlit textures_bind_group"),
+        }))
+    }
+
     pub fn get_default_texture(
         &self,
         default_texture_type: DefaultTextureType,
@@ -655,6 +733,18 @@ impl BaseRenderer {
         }
         Ok(default_texture)
     }
+
+    // mips are generated every frame for the supersampled surface textures, so the pipelines
+    // are only created once per format
+    pub fn get_mip_pipeline(&self, format: wgpu::TextureFormat) -> Result<Arc<MipPipeline>> {
+        let mut mip_pipeline_cache_guard = self.mip_pipeline_cache.lock().unwrap();
+        if let Some(mip_pipeline) = mip_pipeline_cache_guard.get(&format) {
+            return Ok(mip_pipeline.clone());
+        }
+        let mip_pipeline = Arc::new(MipPipeline::new(self, format)?);
+        mip_pipeline_cache_guard.insert(format, mip_pipeline.clone());
+        Ok(mip_pipeline)
+    }
 }
 
 pub struct RendererPrivateData {
@@ -663,6 +753,8 @@ pub struct RendererPrivateData {
     all_pbr_instances: ChunkedBuffer<GpuPbrMeshInstance>,
     all_unlit_instances: ChunkedBuffer<GpuUnlitMeshInstance>,
     all_wireframe_instances: ChunkedBuffer<GpuWireframeMeshInstance>,
+    // binded pbr mesh index -> 0 for the full detail index buffer, n for GeometryBuffers::lods[n - 1]
+    pbr_mesh_lod_indices: HashMap<usize, usize>,
     debug_nodes: Vec<GameNodeId>,
 
     bloom_threshold_cleared: bool,
@@ -700,6 +792,19 @@ pub struct RenderBuffers {
     pub binded_unlit_meshes: Vec<BindedUnlitMesh>,
     pub binded_wireframe_meshes: Vec<BindedWireframeMesh>,
     pub textures: Vec<Texture>,
+    pub streamed_textures: Vec<StreamedTexture>,
+}
+
+// the resources that were added to the renderer by merging a loaded scene
+#[derive(Debug)]
+pub struct GltfScene {
+    pub pbr_meshes: Vec<Handle<BindedPbrMesh>>,
+    pub unlit_meshes: Vec<Handle<BindedUnlitMesh>>,
+    pub textures: Vec<Handle<Texture>>,
+    // (path in the hierarchy, node id), used to find the nodes again when the scene is reloaded
+    pub nodes: Vec<(String, GameNodeId)>,
+    pub skins: Vec<usize>,
+    pub animations: Vec<usize>,
 }
 
 pub struct RendererPublicData {
@@ -707,6 +812,17 @@ pub struct RendererPublicData {
     pub binded_unlit_meshes: Vec<BindedUnlitMesh>,
     pub binded_wireframe_meshes: Vec<BindedWireframeMesh>,
     pub textures: Vec<Texture>,
+    pub gltf_scenes: Vec<GltfScene>,
+    pub texture_streamer: TextureStreamer,
+
+    // one slot per element of the lists above, see free_unused_resources
+    pub pbr_mesh_handles: HandlePool<BindedPbrMesh>,
+    pub unlit_mesh_handles: HandlePool<BindedUnlitMesh>,
+    pub texture_handles: HandlePool<Texture>,
+    pub gltf_scene_handles: HandlePool<GltfScene>,
+
+    // used by the untextured unlit meshes and the wireframes
+    default_unlit_textures_bind_group: Arc<wgpu::BindGroup>,
 
     pub skybox_mesh: GeometryBuffers,
 
@@ -718,10 +834,121 @@ pub struct RendererPublicData {
     pub enable_shadows: bool,
     pub enable_wireframe_mode: bool,
     pub draw_node_bounding_spheres: bool,
+    // in pixels
+    pub point_size: f32,
 
     pub ui_overlay: UiOverlay,
 }
 
+impl RendererPublicData {
+    // removes the meshes and textures that have no handles left and aren't used by any node of the
+    // scene, then compacts the lists and updates the indices that point into them. only does the
+    // work when a last handle was dropped since the last call, so meshes that were only kept
+    // alive by nodes are freed along with the next resources that lose their handles
+    #[profiling::function]
+    pub fn free_unused_resources(&mut self, scene: &mut Scene) {
+        // not short circuited so that every pool is reset
+        let handles_dropped = [
+            self.gltf_scene_handles.take_dropped(),
+            self.pbr_mesh_handles.take_dropped(),
+            self.unlit_mesh_handles.take_dropped(),
+            self.texture_handles.take_dropped(),
+        ];
+        if !handles_dropped.contains(&true) {
+            return;
+        }
+
+        // the gltf scenes hold handles to their meshes and textures so they go first
+        self.gltf_scene_handles
+            .remove_unused(&mut self.gltf_scenes, |_| false);
+
+        let mut used_pbr_meshes = vec![false; self.binded_pbr_meshes.len()];
+        let mut used_unlit_meshes = vec![false; self.binded_unlit_meshes.len()];
+        for mesh in scene.nodes().filter_map(|node| node.mesh.as_ref()) {
+            let used_meshes = match mesh.mesh_type {
+                GameNodeMeshType::Pbr { .. } => &mut used_pbr_meshes,
+                GameNodeMeshType::Unlit { .. } => &mut used_unlit_meshes,
+            };
+            for mesh_index in mesh.mesh_indices.iter().copied() {
+                used_meshes[mesh_index] = true;
+            }
+        }
+        let pbr_mesh_index_map = self
+            .pbr_mesh_handles
+            .remove_unused(&mut self.binded_pbr_meshes, |index| used_pbr_meshes[index]);
+        let unlit_mesh_index_map = self
+            .unlit_mesh_handles
+            .remove_unused(&mut self.binded_unlit_meshes, |index| {
+                used_unlit_meshes[index]
+            });
+
+        if pbr_mesh_index_map.is_some() || unlit_mesh_index_map.is_some() {
+            let remap_mesh_index = |mesh_type: MeshType, mesh_index: usize| {
+                let index_map = match mesh_type {
+                    MeshType::Pbr => &pbr_mesh_index_map,
+                    MeshType::Unlit => &unlit_mesh_index_map,
+                };
+                match index_map {
+                    Some(index_map) => index_map[mesh_index],
+                    None => Some(mesh_index),
+                }
+            };
+            self.binded_wireframe_meshes.retain_mut(|wireframe_mesh| {
+                match remap_mesh_index(
+                    wireframe_mesh.source_mesh_type,
+                    wireframe_mesh.source_mesh_index,
+                ) {
+                    Some(source_mesh_index) => {
+                        wireframe_mesh.source_mesh_index = source_mesh_index;
+                        true
+                    }
+                    None => false,
+                }
+            });
+            for mesh in scene.nodes_mut().filter_map(|node| node.mesh.as_mut()) {
+                let mesh_type = MeshType::from(mesh.mesh_type);
+                for mesh_index in mesh.mesh_indices.iter_mut() {
+                    // meshes that are used by a node are never removed
+                    *mesh_index = remap_mesh_index(mesh_type, *mesh_index).unwrap();
+                }
+            }
+        }
+
+        let mut used_textures = vec![false; self.textures.len()];
+        let pbr_mesh_texture_indices = self
+            .binded_pbr_meshes
+            .iter()
+            .flat_map(|mesh| mesh.texture_indices.iter())
+            .flat_map(|texture_indices| texture_indices.texture_indices());
+        let unlit_mesh_texture_indices = self
+            .binded_unlit_meshes
+            .iter()
+            .filter_map(|mesh| mesh.base_color_texture_index);
+        for texture_index in pbr_mesh_texture_indices.chain(unlit_mesh_texture_indices) {
+            used_textures[texture_index] = true;
+        }
+        if let Some(texture_index_map) = self
+            .texture_handles
+            .remove_unused(&mut self.textures, |index| used_textures[index])
+        {
+            let remap_texture_index = |texture_index: &mut usize| {
+                *texture_index = texture_index_map[*texture_index].unwrap()
+            };
+            for mesh in &mut self.binded_pbr_meshes {
+                if let Some(texture_indices) = mesh.texture_indices.as_mut() {
+                    texture_indices.remap_texture_indices(remap_texture_index);
+                }
+            }
+            for mesh in &mut self.binded_unlit_meshes {
+                if let Some(texture_index) = mesh.base_color_texture_index.as_mut() {
+                    remap_texture_index(texture_index);
+                }
+            }
+            self.texture_streamer.remap_textures(&texture_index_map);
+        }
+    }
+}
+
 pub struct Renderer {
     pub base: Arc<BaseRenderer>,
     pub data: Arc<Mutex<RendererPublicData>>,
@@ -732,6 +959,8 @@ pub struct Renderer {
 
     mesh_pipeline: wgpu::RenderPipeline,
     unlit_mesh_pipeline: wgpu::RenderPipeline,
+    unlit_line_pipeline: wgpu::RenderPipeline,
+    unlit_point_pipeline: wgpu::RenderPipeline,
     wireframe_pipeline: wgpu::RenderPipeline,
     skybox_pipeline: wgpu::RenderPipeline,
     tone_mapping_pipeline: wgpu::RenderPipeline,
@@ -742,10 +971,10 @@ pub struct Renderer {
     bloom_blur_pipeline: wgpu::RenderPipeline,
 
     #[allow(dead_code)]
-    box_mesh_index: i32,
-    sphere_mesh_index: i32,
+    box_mesh: Handle<BindedUnlitMesh>,
+    sphere_mesh: Handle<BindedUnlitMesh>,
     #[allow(dead_code)]
-    plane_mesh_index: i32,
+    plane_mesh: Handle<BindedUnlitMesh>,
 }
 
 impl Renderer {
@@ -777,7 +1006,7 @@ impl Renderer {
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Unlit Mesh Shader"),
                 source: wgpu::ShaderSource::Wgsl(
-                    std::fs::read_to_string("./src/shaders/unlit_mesh.wgsl")?.into(),
+                    vfs_read_to_string("assets/shaders/unlit_mesh.wgsl")?.into(),
                 ),
             });
 
@@ -786,7 +1015,7 @@ impl Renderer {
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Blit Shader"),
                 source: wgpu::ShaderSource::Wgsl(
-                    std::fs::read_to_string("./src/shaders/blit.wgsl")?.into(),
+                    vfs_read_to_string("assets/shaders/blit.wgsl")?.into(),
                 ),
             });
 
@@ -795,7 +1024,7 @@ impl Renderer {
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Textured Mesh Shader"),
                 source: wgpu::ShaderSource::Wgsl(
-                    std::fs::read_to_string("./src/shaders/textured_mesh.wgsl")?.into(),
+                    vfs_read_to_string("assets/shaders/textured_mesh.wgsl")?.into(),
                 ),
             });
 
@@ -804,7 +1033,7 @@ impl Renderer {
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Skybox Shader"),
                 source: wgpu::ShaderSource::Wgsl(
-                    std::fs::read_to_string("./src/shaders/skybox.wgsl")?.into(),
+                    vfs_read_to_string("assets/shaders/skybox.wgsl")?.into(),
                 ),
             });
 
@@ -1057,6 +1286,7 @@ impl Renderer {
                     bind_group_layouts: &[
                         &lights_bind_group_layout,
                         &base.bones_and_instances_bind_group_layout,
+                        &base.single_texture_bind_group_layout,
                     ],
                     push_constant_ranges: &[mesh_camera_push_constant_range.clone()],
                 });
@@ -1078,6 +1308,25 @@ impl Renderer {
             .device
             .create_render_pipeline(&unlit_mesh_pipeline_descriptor);
 
+        let mut unlit_line_pipeline_descriptor = unlit_mesh_pipeline_descriptor.clone();
+        unlit_line_pipeline_descriptor.label = Some("Unlit Line Render Pipeline");
+        unlit_line_pipeline_descriptor.primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        };
+        let unlit_line_pipeline = base
+            .device
+            .create_render_pipeline(&unlit_line_pipeline_descriptor);
+
+        let mut unlit_point_pipeline_descriptor = unlit_mesh_pipeline_descriptor.clone();
+        unlit_point_pipeline_descriptor.label = Some("Unlit Point Render Pipeline");
+        unlit_point_pipeline_descriptor.vertex.entry_point = "vs_point_main";
+        // the quads are always facing the camera
+        unlit_point_pipeline_descriptor.primitive.cull_mode = None;
+        let unlit_point_pipeline = base
+            .device
+            .create_render_pipeline(&unlit_point_pipeline_descriptor);
+
         let mut wireframe_pipeline_descriptor = unlit_mesh_pipeline_descriptor.clone();
         wireframe_pipeline_descriptor.label = Some("Wireframe Render Pipeline");
         let wireframe_mesh_pipeline_v_buffers = &[Vertex::desc()];
@@ -1511,7 +1760,7 @@ impl Renderer {
 
         let initial_render_scale = INITIAL_RENDER_SCALE;
 
-        let cube_mesh = BasicMesh::new("./src/models/cube.obj")?;
+        let cube_mesh = BasicMesh::new("assets/models/cube.obj")?;
 
         let skybox_mesh = Self::bind_geometry_buffers_for_basic_mesh_impl(&base.device, &cube_mesh);
 
@@ -1521,7 +1770,7 @@ impl Renderer {
             Texture::create_scaled_surface_texture(&base, initial_render_scale, "bloom_texture_1"),
             Texture::create_scaled_surface_texture(&base, initial_render_scale, "bloom_texture_2"),
         ];
-        let tone_mapping_texture = Texture::create_scaled_surface_texture(
+        let tone_mapping_texture = Texture::create_scaled_surface_texture_with_mips(
             &base,
             initial_render_scale,
             "tone_mapping_texture",
@@ -1642,7 +1891,7 @@ impl Renderer {
 
         let skybox_texture = match skybox_background {
             SkyboxBackground::Equirectangular { image_path } => {
-                let er_skybox_texture_bytes = std::fs::read(image_path)?;
+                let er_skybox_texture_bytes = vfs_read(image_path)?;
                 let er_skybox_texture = Texture::from_encoded_image(
                     &base,
                     &er_skybox_texture_bytes,
@@ -1667,12 +1916,12 @@ impl Renderer {
                     &equirectangular_to_cubemap_pipeline,
                     &er_skybox_texture,
                     false, // an artifact occurs between the edges of the texture with mipmaps enabled
-                )
+                )?
             }
             SkyboxBackground::Cube { face_image_paths } => {
                 let cubemap_skybox_images = face_image_paths
                     .iter()
-                    .map(|path| image::load_from_memory(&std::fs::read(path)?))
+                    .map(|path| anyhow::Ok(image::load_from_memory(&vfs_read(path)?)?))
                     .collect::<Result<Vec<_>, _>>()?;
 
                 Texture::create_cubemap(
@@ -1688,7 +1937,7 @@ impl Renderer {
                     Some("cubemap_skybox_texture"),
                     wgpu::TextureFormat::Rgba8UnormSrgb,
                     false,
-                )
+                )?
             }
         };
 
@@ -1696,7 +1945,7 @@ impl Renderer {
         let skybox_rad_texture = match skybox_hdr_environment {
             Some(SkyboxHDREnvironment::Equirectangular { image_path }) => {
                 let skybox_rad_texture_decoder = {
-                    let reader = BufReader::new(File::open(image_path)?);
+                    let reader = std::io::Cursor::new(vfs_read(image_path)?);
                     image::codecs::hdr::HdrDecoder::new(reader)?
                 };
                 let skybox_rad_texture_dimensions = {
@@ -1743,7 +1992,7 @@ impl Renderer {
                     &equirectangular_to_cubemap_pipeline,
                     &skybox_rad_texture_er,
                     false,
-                );
+                )?;
 
                 &er_to_cube_texture
             }
@@ -1757,7 +2006,7 @@ impl Renderer {
             &diffuse_env_map_gen_pipeline,
             skybox_rad_texture,
             false,
-        );
+        )?;
 
         let specular_env_map = Texture::create_specular_env_map(
             &base,
@@ -1917,14 +2166,14 @@ impl Renderer {
             &base,
             1024,
             Some("point_shadow_map_texture"),
-            2, // TODO: this currently puts on hard limit on number of point lights at a time
+            MAX_SHADOW_CASTING_POINT_LIGHT_COUNT,
         );
 
         let directional_shadow_map_textures = Texture::create_depth_texture_array(
             &base,
             2048,
             Some("directional_shadow_map_texture"),
-            2, // TODO: this currently puts on hard limit on number of directional lights at a time
+            MAX_SHADOW_CASTING_DIRECTIONAL_LIGHT_COUNT,
         );
 
         let sampler_cache_guard = base.sampler_cache.lock().unwrap();
@@ -2011,11 +2260,23 @@ impl Renderer {
             base.surface_config.lock().unwrap().format,
         );
 
+        let default_unlit_textures_bind_group =
+            Arc::new(base.make_unlit_textures_bind_group(None)?);
+
         let mut data = RendererPublicData {
             binded_pbr_meshes: vec![],
             binded_unlit_meshes: vec![],
             binded_wireframe_meshes: vec![],
             textures: vec![],
+            gltf_scenes: vec![],
+            texture_streamer: TextureStreamer::new(),
+
+            pbr_mesh_handles: HandlePool::new(),
+            unlit_mesh_handles: HandlePool::new(),
+            texture_handles: HandlePool::new(),
+            gltf_scene_handles: HandlePool::new(),
+
+            default_unlit_textures_bind_group,
 
             skybox_mesh,
 
@@ -2027,23 +2288,24 @@ impl Renderer {
             enable_shadows: true,
             enable_wireframe_mode: false,
             draw_node_bounding_spheres: false,
+            point_size: INITIAL_POINT_SIZE,
 
             ui_overlay,
         };
 
-        let box_mesh_index = Self::bind_basic_unlit_mesh(&base, &mut data, &cube_mesh)
-            .try_into()
-            .unwrap();
+        let box_mesh = Self::bind_basic_unlit_mesh(&base, &mut data, &cube_mesh);
 
-        let sphere_mesh = BasicMesh::new("./src/models/sphere.obj").unwrap();
-        let sphere_mesh_index = Self::bind_basic_unlit_mesh(&base, &mut data, &sphere_mesh)
-            .try_into()
-            .unwrap();
+        let sphere_mesh = Self::bind_basic_unlit_mesh(
+            &base,
+            &mut data,
+            &BasicMesh::new("assets/models/sphere.obj").unwrap(),
+        );
 
-        let plane_mesh = BasicMesh::new("./src/models/plane.obj").unwrap();
-        let plane_mesh_index = Self::bind_basic_unlit_mesh(&base, &mut data, &plane_mesh)
-            .try_into()
-            .unwrap();
+        let plane_mesh = Self::bind_basic_unlit_mesh(
+            &base,
+            &mut data,
+            &BasicMesh::new("assets/models/plane.obj").unwrap(),
+        );
 
         // buffer up to 4 frames
         let profiler = wgpu_profiler::GpuProfiler::new(
@@ -2065,6 +2327,7 @@ impl Renderer {
                 all_pbr_instances: ChunkedBuffer::empty(),
                 all_unlit_instances: ChunkedBuffer::empty(),
                 all_wireframe_instances: ChunkedBuffer::empty(),
+                pbr_mesh_lod_indices: HashMap::new(),
                 debug_nodes: vec![],
 
                 bloom_threshold_cleared: true,
@@ -2099,6 +2362,8 @@ impl Renderer {
 
             mesh_pipeline,
             unlit_mesh_pipeline,
+            unlit_line_pipeline,
+            unlit_point_pipeline,
             wireframe_pipeline,
             skybox_pipeline,
             tone_mapping_pipeline,
@@ -2108,9 +2373,9 @@ impl Renderer {
             bloom_threshold_pipeline,
             bloom_blur_pipeline,
 
-            box_mesh_index,
-            sphere_mesh_index,
-            plane_mesh_index,
+            box_mesh,
+            sphere_mesh,
+            plane_mesh,
         };
 
         Ok(renderer)
@@ -2120,11 +2385,20 @@ impl Renderer {
         base: &BaseRenderer,
         data: &mut RendererPublicData,
         mesh: &BasicMesh,
-    ) -> usize {
+    ) -> Handle<BindedUnlitMesh> {
         let geometry_buffers = Self::bind_geometry_buffers_for_basic_mesh(base, mesh);
 
-        data.binded_unlit_meshes.push(geometry_buffers);
-        let unlit_mesh_index = data.binded_unlit_meshes.len() - 1;
+        data.binded_unlit_meshes.push(BindedUnlitMesh {
+            geometry_buffers,
+            textures_bind_group: data.default_unlit_textures_bind_group.clone(),
+            base_color_texture_index: None,
+            base_color_factor: Vec4::ONE,
+            alpha_cutoff: -1.0,
+            alpha_mode: AlphaMode::Opaque,
+            primitive_mode: PrimitiveMode::Triangles,
+        });
+        let unlit_mesh = data.unlit_mesh_handles.push();
+        let unlit_mesh_index = unlit_mesh.index();
 
         let wireframe_index_buffer = Self::make_wireframe_index_buffer_for_basic_mesh(base, mesh);
         data.binded_wireframe_meshes.push(BindedWireframeMesh {
@@ -2134,17 +2408,17 @@ impl Renderer {
             index_buffer_format: wgpu::IndexFormat::Uint16,
         });
 
-        unlit_mesh_index
+        unlit_mesh
     }
 
-    // returns index of mesh in the RenderScene::binded_pbr_meshes list
+    // the mesh is freed once the handle is dropped and no node uses it anymore
     pub fn bind_basic_pbr_mesh(
         base: &BaseRenderer,
         data: &mut RendererPublicData,
         mesh: &BasicMesh,
         material: &PbrMaterial,
         dynamic_pbr_params: DynamicPbrParams,
-    ) -> Result<usize> {
+    ) -> Result<Handle<BindedPbrMesh>> {
         let geometry_buffers = Self::bind_geometry_buffers_for_basic_mesh(base, mesh);
 
         let textures_bind_group = Arc::new(base.make_pbr_textures_bind_group(material, false)?);
@@ -2153,10 +2427,13 @@ impl Renderer {
             geometry_buffers,
             dynamic_pbr_params,
             textures_bind_group,
+            texture_indices: None,
+            sources: None,
             alpha_mode: AlphaMode::Opaque,
             primitive_mode: PrimitiveMode::Triangles,
         });
-        let pbr_mesh_index = data.binded_pbr_meshes.len() - 1;
+        let pbr_mesh = data.pbr_mesh_handles.push();
+        let pbr_mesh_index = pbr_mesh.index();
 
         let wireframe_index_buffer = Self::make_wireframe_index_buffer_for_basic_mesh(base, mesh);
         data.binded_wireframe_meshes.push(BindedWireframeMesh {
@@ -2166,7 +2443,66 @@ impl Renderer {
             index_buffer_format: wgpu::IndexFormat::Uint16,
         });
 
-        Ok(pbr_mesh_index)
+        Ok(pbr_mesh)
+    }
+
+    // recreates the bind groups of the meshes that use any of the given textures
+    fn rebind_textures(
+        base: &BaseRenderer,
+        data: &mut RendererPublicData,
+        texture_indices: &[usize],
+    ) -> Result<()> {
+        let texture_indices: HashSet<usize> = texture_indices.iter().copied().collect();
+
+        let mut textures_bind_group_cache: HashMap<IndexedPbrMaterial, Arc<wgpu::BindGroup>> =
+            HashMap::new();
+        for binded_pbr_mesh in &mut data.binded_pbr_meshes {
+            let Some(mesh_texture_indices) = &binded_pbr_mesh.texture_indices else {
+                continue;
+            };
+            if !mesh_texture_indices
+                .texture_indices()
+                .any(|texture_index| texture_indices.contains(&texture_index))
+            {
+                continue;
+            }
+            binded_pbr_mesh.textures_bind_group =
+                match textures_bind_group_cache.entry(mesh_texture_indices.clone()) {
+                    Entry::Occupied(entry) => entry.get().clone(),
+                    Entry::Vacant(vacant_entry) => {
+                        let pbr_material = vacant_entry.key().get_pbr_material(&data.textures);
+                        let textures_bind_group =
+                            Arc::new(base.make_pbr_textures_bind_group(&pbr_material, true)?);
+                        vacant_entry.insert(textures_bind_group.clone());
+                        textures_bind_group
+                    }
+                };
+        }
+
+        let mut unlit_textures_bind_group_cache: HashMap<usize, Arc<wgpu::BindGroup>> =
+            HashMap::new();
+        for binded_unlit_mesh in &mut data.binded_unlit_meshes {
+            let Some(texture_index) = binded_unlit_mesh.base_color_texture_index else {
+                continue;
+            };
+            if !texture_indices.contains(&texture_index) {
+                continue;
+            }
+            binded_unlit_mesh.textures_bind_group = match unlit_textures_bind_group_cache
+                .entry(texture_index)
+            {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(vacant_entry) => {
+                    let textures_bind_group = Arc::new(
+                        base.make_unlit_textures_bind_group(Some(&data.textures[texture_index]))?,
+                    );
+                    vacant_entry.insert(textures_bind_group.clone());
+                    textures_bind_group
+                }
+            };
+        }
+
+        Ok(())
     }
 
     fn bind_geometry_buffers_for_basic_mesh(
@@ -2220,6 +2556,7 @@ impl Renderer {
             index_buffer,
             index_buffer_format: wgpu::IndexFormat::Uint16,
             bounding_box,
+            lods: vec![],
         }
     }
 
@@ -2295,7 +2632,7 @@ impl Renderer {
                 "bloom_texture_2",
             ),
         ];
-        private_data_guard.tone_mapping_texture = Texture::create_scaled_surface_texture(
+        private_data_guard.tone_mapping_texture = Texture::create_scaled_surface_texture_with_mips(
             &self.base,
             data_guard.render_scale,
             "tone_mapping_texture",
@@ -2441,7 +2778,7 @@ impl Renderer {
         data: &mut RendererPublicData,
         private_data: &mut RendererPrivateData,
         game_state: &mut GameState,
-        sphere_mesh_index: i32,
+        sphere_mesh: &Handle<BindedUnlitMesh>,
     ) {
         if !data.draw_node_bounding_spheres {
             return;
@@ -2470,7 +2807,7 @@ impl Renderer {
                                         mesh_type: GameNodeMeshType::Unlit {
                                             color: Vec3::new(0.0, 1.0, 0.0),
                                         },
-                                        mesh_indices: vec![sphere_mesh_index.try_into().unwrap()],
+                                        mesh_indices: vec![sphere_mesh.index()],
                                         wireframe: true,
                                         cullable: false,
                                     }))
@@ -2526,6 +2863,8 @@ impl Renderer {
 
         Self::clear_debug_nodes(private_data, &mut game_state.scene);
 
+        data.free_unused_resources(&mut game_state.scene);
+
         game_state.scene.recompute_global_node_transforms();
 
         let window_size = *base.window_size.lock().unwrap();
@@ -2534,7 +2873,20 @@ impl Renderer {
             window_size.width as f32 / window_size.height as f32,
         );
 
-        Self::add_debug_nodes(data, private_data, game_state, self.sphere_mesh_index);
+        let camera_position = game_state
+            .player_controller
+            .position(&game_state.physics_state);
+        // world space size at a distance of 1 -> size on screen in pixels
+        let pixels_per_world_unit =
+            window_size.height as f32 / (2.0 * (deg_to_rad(FOV_Y_DEG) / 2.0).tan());
+
+        Self::add_debug_nodes(data, private_data, game_state, &self.sphere_mesh);
+
+        // converted from pixels to clip space units
+        let point_size = [
+            2.0 * data.point_size / window_size.width as f32,
+            2.0 * data.point_size / window_size.height as f32,
+        ];
 
         let mut frustum_culled_node_list: Vec<GameNodeId> = Vec::new();
         for node in game_state.scene.nodes() {
@@ -2592,10 +2944,23 @@ impl Renderer {
             usize,
             Vec<GpuWireframeMeshInstance>,
         > = HashMap::new();
+        private_data.pbr_mesh_lod_indices.clear();
+        // texture index -> size on screen in pixels of the largest mesh that uses it
+        let mut texture_screen_sizes: HashMap<usize, f32> = HashMap::new();
 
         for node_id in frustum_culled_node_list {
             let node = scene.get_node_unchecked(node_id);
             let transform = Mat4::from(scene.get_global_transform_for_node_opt(node.id()));
+            // diameter of the bounding sphere in pixels, None if it's unknown
+            let node_screen_size = match scene.get_node_bounding_sphere_opt(node_id, data) {
+                Some(bounding_sphere) if node.skin_index.is_none() => {
+                    let distance = (bounding_sphere.origin - camera_position)
+                        .length()
+                        .max(NEAR_PLANE_DISTANCE);
+                    Some(bounding_sphere.radius * 2.0 / distance * pixels_per_world_unit)
+                }
+                _ => None,
+            };
             if let Some(GameNodeMesh {
                 mesh_indices,
                 mesh_type,
@@ -2604,7 +2969,38 @@ impl Renderer {
             }) = &node.mesh
             {
                 for mesh_index in mesh_indices.iter().copied() {
-                    match (mesh_type, data.enable_wireframe_mode, *wireframe) {
+                    let mesh_texture_indices: Vec<usize> = match mesh_type {
+                        GameNodeMeshType::Pbr { .. } => data.binded_pbr_meshes[mesh_index]
+                            .texture_indices
+                            .iter()
+                            .flat_map(|texture_indices| texture_indices.texture_indices())
+                            .collect(),
+                        GameNodeMeshType::Unlit { .. } => data.binded_unlit_meshes[mesh_index]
+                            .base_color_texture_index
+                            .into_iter()
+                            .collect(),
+                    };
+                    for texture_index in mesh_texture_indices {
+                        // stream in everything if the size is unknown
+                        let screen_size = node_screen_size.unwrap_or(f32::INFINITY);
+                        texture_screen_sizes
+                            .entry(texture_index)
+                            .and_modify(|current_screen_size| {
+                                *current_screen_size = current_screen_size.max(screen_size)
+                            })
+                            .or_insert(screen_size);
+                    }
+
+                    // points have no edges so they're drawn as usual in wireframe mode
+                    let is_point_mesh = matches!(mesh_type, GameNodeMeshType::Unlit { .. })
+                        && data.binded_unlit_meshes[mesh_index].primitive_mode
+                            == PrimitiveMode::Points;
+                    let (is_wireframe_mode_on, is_node_wireframe) = if is_point_mesh {
+                        (false, false)
+                    } else {
+                        (data.enable_wireframe_mode, *wireframe)
+                    };
+                    match (mesh_type, is_wireframe_mode_on, is_node_wireframe) {
                         (GameNodeMeshType::Pbr { material_override }, false, false) => {
                             let gpu_instance = GpuPbrMeshInstance::new(
                                 transform,
@@ -2620,11 +3016,34 @@ impl Renderer {
                                     entry.insert(vec![gpu_instance]);
                                 }
                             }
+
+                            // all instances are drawn together so the closest one decides the lod
+                            let lods = &data.binded_pbr_meshes[mesh_index].geometry_buffers.lods;
+                            let lod_index = match node_screen_size {
+                                // the error is relative to the bounding sphere diameter of the mesh,
+                                // which is at most the one of the node
+                                Some(node_screen_size) => lods
+                                    .iter()
+                                    .take_while(|lod| {
+                                        lod.error * node_screen_size < LOD_MAX_SCREEN_ERROR_PIXELS
+                                    })
+                                    .count(),
+                                None => 0,
+                            };
+                            private_data
+                                .pbr_mesh_lod_indices
+                                .entry(mesh_index)
+                                .and_modify(|current_lod_index| {
+                                    *current_lod_index = (*current_lod_index).min(lod_index)
+                                })
+                                .or_insert(lod_index);
                         }
                         (mesh_type, is_wireframe_mode_on, is_node_wireframe) => {
                             let color = match mesh_type {
                                 GameNodeMeshType::Unlit { color } => {
-                                    [color.x, color.y, color.z, 1.0]
+                                    let color = Vec4::new(color.x, color.y, color.z, 1.0)
+                                        * data.binded_unlit_meshes[mesh_index].base_color_factor;
+                                    color.to_array()
                                 }
                                 GameNodeMeshType::Pbr { material_override } => {
                                     let fallback_pbr_params =
@@ -2679,10 +3098,8 @@ impl Renderer {
                                     })
                                     .unwrap()
                                     .0;
-                                let gpu_instance = GpuWireframeMeshInstance {
-                                    color,
-                                    model_transform: transform,
-                                };
+                                let gpu_instance =
+                                    GpuWireframeMeshInstance::new(transform, color, -1.0);
                                 match wireframe_mesh_index_to_gpu_instances
                                     .entry(wireframe_mesh_index)
                                 {
@@ -2694,10 +3111,15 @@ impl Renderer {
                                     }
                                 }
                             } else {
-                                let gpu_instance = GpuUnlitMeshInstance {
-                                    color,
-                                    model_transform: transform,
+                                let alpha_cutoff = match mesh_type {
+                                    GameNodeMeshType::Unlit { .. } => {
+                                        data.binded_unlit_meshes[mesh_index].alpha_cutoff
+                                    }
+                                    GameNodeMeshType::Pbr { .. } => -1.0,
                                 };
+                                let gpu_instance =
+                                    GpuUnlitMeshInstance::new(transform, color, alpha_cutoff)
+                                        .with_point_size(point_size);
                                 match unlit_mesh_index_to_gpu_instances.entry(mesh_index) {
                                     Entry::Occupied(mut entry) => {
                                         entry.get_mut().push(gpu_instance);
@@ -2713,6 +3135,15 @@ impl Renderer {
             }
         }
 
+        let changed_texture_indices =
+            data.texture_streamer
+                .update(base, &mut data.textures, &texture_screen_sizes);
+        if !changed_texture_indices.is_empty() {
+            if let Err(err) = Self::rebind_textures(base, data, &changed_texture_indices) {
+                logger_log(&format!("Error rebinding streamed textures: {err:?}"));
+            }
+        }
+
         let min_storage_buffer_offset_alignment = base.limits.min_storage_buffer_offset_alignment;
 
         private_data.all_pbr_instances = ChunkedBuffer::new(
@@ -2885,7 +3316,7 @@ impl Renderer {
             .chain(
                 data.binded_unlit_meshes
                     .iter()
-                    .map(|mesh| mesh.index_buffer.length_bytes()),
+                    .map(|mesh| mesh.geometry_buffers.index_buffer.length_bytes()),
             )
             .reduce(|acc, val| acc + val);
         let _total_vertex_buffer_memory_usage = data
@@ -2895,7 +3326,7 @@ impl Renderer {
             .chain(
                 data.binded_unlit_meshes
                     .iter()
-                    .map(|mesh| mesh.vertex_buffer.length_bytes()),
+                    .map(|mesh| mesh.geometry_buffers.vertex_buffer.length_bytes()),
             )
             .reduce(|acc, val| acc + val);
 
@@ -2978,7 +3409,10 @@ impl Renderer {
                     .get_node(game_state.point_lights[light_index].node_id)
                 {
                     build_cubemap_face_camera_views(
-                        light_node.transform.position(),
+                        game_state
+                            .scene
+                            .get_global_transform_for_node_opt(light_node.id())
+                            .position(),
                         0.1,
                         1000.0,
                         false,
@@ -3059,19 +3493,38 @@ impl Renderer {
             }),
         };
 
-        let player_transform = game_state
-            .scene
-            .get_global_transform_for_node(game_state.player_node_id);
         let window_size = *base.window_size.lock().unwrap();
-
-        let main_camera_data = ShaderCameraData::from_mat4(
-            player_transform.into(),
-            window_size.width as f32 / window_size.height as f32,
-            NEAR_PLANE_DISTANCE,
-            FAR_PLANE_DISTANCE,
-            deg_to_rad(FOV_Y_DEG),
-            true,
-        );
+        let aspect_ratio = window_size.width as f32 / window_size.height as f32;
+        let scene_camera = game_state.scene_camera_node_id.and_then(|node_id| {
+            game_state
+                .scene
+                .cameras
+                .iter()
+                .find(|camera| camera.node_id == node_id)
+        });
+        let main_camera_data = match scene_camera {
+            Some(scene_camera) => scene_camera.to_shader_camera_data(
+                game_state
+                    .scene
+                    .get_global_transform_for_node(scene_camera.node_id)
+                    .into(),
+                aspect_ratio,
+                true,
+            ),
+            None => {
+                let player_transform = game_state
+                    .scene
+                    .get_global_transform_for_node(game_state.player_node_id);
+                ShaderCameraData::from_mat4(
+                    player_transform.into(),
+                    aspect_ratio,
+                    NEAR_PLANE_DISTANCE,
+                    FAR_PLANE_DISTANCE,
+                    deg_to_rad(FOV_Y_DEG),
+                    true,
+                )
+            }
+        };
 
         Self::render_pbr_meshes(
             base,
@@ -3123,13 +3576,31 @@ impl Renderer {
                         - unlit_instance_chunk.start_index)
                         / private_data.all_unlit_instances.stride();
 
-                    let geometry_buffers = &data.binded_unlit_meshes[binded_unlit_mesh_index];
+                    let BindedUnlitMesh {
+                        geometry_buffers,
+                        textures_bind_group,
+                        primitive_mode,
+                        ..
+                    } = &data.binded_unlit_meshes[binded_unlit_mesh_index];
+
+                    render_pass.set_pipeline(match primitive_mode {
+                        PrimitiveMode::Triangles => &self.unlit_mesh_pipeline,
+                        PrimitiveMode::Lines => &self.unlit_line_pipeline,
+                        PrimitiveMode::Points => &self.unlit_point_pipeline,
+                    });
 
+                    let bone_transforms_buffer_start_index = private_data
+                        .all_bone_transforms
+                        .get_start_index(MeshType::Unlit, binded_unlit_mesh_index);
                     render_pass.set_bind_group(
                         1,
                         &private_data.bones_and_unlit_instances_bind_group,
-                        &[0, instances_buffer_start_index],
+                        &[
+                            bone_transforms_buffer_start_index,
+                            instances_buffer_start_index,
+                        ],
                     );
+                    render_pass.set_bind_group(2, textures_bind_group, &[]);
                     render_pass
                         .set_vertex_buffer(0, geometry_buffers.vertex_buffer.src().slice(..));
                     render_pass.set_index_buffer(
@@ -3144,6 +3615,7 @@ impl Renderer {
                 }
 
                 render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(2, &data.default_unlit_textures_bind_group, &[]);
 
                 for wireframe_instance_chunk in private_data.all_wireframe_instances.chunks() {
                     let binded_wireframe_mesh_index = wireframe_instance_chunk.id;
@@ -3160,30 +3632,21 @@ impl Renderer {
                         ..
                     } = &data.binded_wireframe_meshes[binded_wireframe_mesh_index];
 
-                    let (vertex_buffer, bone_transforms_buffer_start_index) = match source_mesh_type
-                    {
+                    let vertex_buffer = match source_mesh_type {
                         MeshType::Pbr => {
-                            let bone_transforms_buffer_start_index = private_data
-                                .all_bone_transforms
-                                .animated_bone_transforms
-                                .iter()
-                                .find(|bone_slice| {
-                                    bone_slice.binded_pbr_mesh_index == *source_mesh_index
-                                })
-                                .map(|bone_slice| bone_slice.start_index.try_into().unwrap())
-                                .unwrap_or(0);
-                            (
-                                &data.binded_pbr_meshes[*source_mesh_index]
-                                    .geometry_buffers
-                                    .vertex_buffer,
-                                bone_transforms_buffer_start_index,
-                            )
+                            &data.binded_pbr_meshes[*source_mesh_index]
+                                .geometry_buffers
+                                .vertex_buffer
+                        }
+                        MeshType::Unlit => {
+                            &data.binded_unlit_meshes[*source_mesh_index]
+                                .geometry_buffers
+                                .vertex_buffer
                         }
-                        MeshType::Unlit => (
-                            &data.binded_unlit_meshes[*source_mesh_index].vertex_buffer,
-                            0,
-                        ),
                     };
+                    let bone_transforms_buffer_start_index = private_data
+                        .all_bone_transforms
+                        .get_start_index(*source_mesh_type, *source_mesh_index);
                     render_pass.set_bind_group(
                         1,
                         &private_data.bones_and_wireframe_instances_bind_group,
@@ -3299,12 +3762,21 @@ impl Renderer {
             private_data.bloom_threshold_cleared = true;
         }
 
+        // the surface blit reads the smaller mips when supersampling
+        let tone_mapping_target_view =
+            private_data
+                .tone_mapping_texture
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor {
+                    mip_level_count: NonZeroU32::new(1),
+                    ..Default::default()
+                });
         {
             let label = "Skybox";
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some(label),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &private_data.tone_mapping_texture.view,
+                    view: &tone_mapping_target_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(black),
@@ -3346,7 +3818,7 @@ impl Renderer {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some(label),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &private_data.tone_mapping_texture.view,
+                    view: &tone_mapping_target_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Load,
@@ -3371,6 +3843,16 @@ impl Renderer {
             });
         }
 
+        if private_data.tone_mapping_texture.texture.mip_level_count() > 1 {
+            if let Err(err) = private_data.tone_mapping_texture.generate_mipmaps(
+                base,
+                &mut encoder,
+                MipGenerationOptions::default(),
+            ) {
+                logger_log(&format!("Error generating the tone mapping mips: {err:?}"));
+            }
+        }
+
         {
             let label = "Surface blit";
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -3458,13 +3940,7 @@ impl Renderer {
                     let binded_pbr_mesh_index = pbr_instance_chunk.id;
                     let bone_transforms_buffer_start_index = private_data
                         .all_bone_transforms
-                        .animated_bone_transforms
-                        .iter()
-                        .find(|bone_slice| {
-                            bone_slice.binded_pbr_mesh_index == binded_pbr_mesh_index
-                        })
-                        .map(|bone_slice| bone_slice.start_index.try_into().unwrap())
-                        .unwrap_or(0);
+                        .get_start_index(MeshType::Pbr, binded_pbr_mesh_index);
                     let instances_buffer_start_index = pbr_instance_chunk.start_index as u32;
                     let instance_count = (pbr_instance_chunk.end_index
                         - pbr_instance_chunk.start_index)
@@ -3487,14 +3963,26 @@ impl Renderer {
                     if !is_shadow {
                         render_pass.set_bind_group(3, textures_bind_group, &[]);
                     }
+                    let (index_buffer, index_buffer_format) = match private_data
+                        .pbr_mesh_lod_indices
+                        .get(&binded_pbr_mesh_index)
+                        .copied()
+                        .unwrap_or(0)
+                    {
+                        0 => (
+                            &geometry_buffers.index_buffer,
+                            geometry_buffers.index_buffer_format,
+                        ),
+                        lod_index => {
+                            let lod = &geometry_buffers.lods[lod_index - 1];
+                            (&lod.index_buffer, lod.index_buffer_format)
+                        }
+                    };
                     render_pass
                         .set_vertex_buffer(0, geometry_buffers.vertex_buffer.src().slice(..));
-                    render_pass.set_index_buffer(
-                        geometry_buffers.index_buffer.src().slice(..),
-                        geometry_buffers.index_buffer_format,
-                    );
+                    render_pass.set_index_buffer(index_buffer.src().slice(..), index_buffer_format);
                     render_pass.draw_indexed(
-                        0..geometry_buffers.index_buffer.length() as u32,
+                        0..index_buffer.length() as u32,
                         0,
                         0..instance_count as u32,
                     );
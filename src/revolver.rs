@@ -1,4 +1,8 @@
+use crate::audio::*;
+use crate::behaviour::*;
+use crate::game::*;
 use crate::math::*;
+use crate::physics::*;
 use crate::player_controller::*;
 use crate::scene::*;
 use crate::transform::*;
@@ -13,11 +17,18 @@ const CAMERA_FOLLOW_LERP_FACTOR: f32 = 0.8;
 const WEAPON_SWAY_RESET_LERP_FACTOR: f32 = 0.3;
 const MAX_SWAY_DEG: f32 = 3.0;
 
+// sent to the behaviours when a shot hits a collider
+#[derive(Debug, Clone, Copy)]
+pub struct ColliderHitMessage {
+    pub collider_handle: ColliderHandle,
+}
+
 #[derive(Debug)]
 pub struct Revolver {
     animation_index: usize,
     cooldown: f32,
     last_fired_instant: Option<Instant>,
+    gunshot_sound_index: Option<usize>,
 
     pub node_id: GameNodeId,
     hand_node_id: GameNodeId,
@@ -34,6 +45,7 @@ impl Revolver {
         camera_node_id: GameNodeId,
         model_node_id: GameNodeId,
         animation_index: usize,
+        gunshot_sound_index: Option<usize>,
         transform: crate::transform::Transform,
     ) -> Self {
         let hand_node = scene.add_node(GameNodeDescBuilder::new().build());
@@ -47,8 +59,10 @@ impl Revolver {
         );
         let node_id = node.id();
 
-        if let Some(model_node) = scene.get_node_mut(model_node_id) {
-            model_node.parent_id = Some(node_id);
+        if scene.get_node(model_node_id).is_some() {
+            scene
+                .set_parent(model_node_id, Some(node_id), false)
+                .unwrap();
         }
 
         // let cooldown = scene.animations[animation_index].length_seconds;
@@ -58,6 +72,7 @@ impl Revolver {
             animation_index,
             cooldown,
             last_fired_instant: None,
+            gunshot_sound_index,
 
             node_id,
             hand_node_id,
@@ -69,7 +84,7 @@ impl Revolver {
         }
     }
 
-    pub fn update(&mut self, player_view_direction: ControlledViewDirection, scene: &mut Scene) {
+    fn follow_camera(&mut self, player_view_direction: ControlledViewDirection, scene: &mut Scene) {
         let camera_transform = scene.get_node(self.camera_node_id).unwrap().transform;
 
         // update
@@ -126,4 +141,50 @@ impl Revolver {
             .current_time_seconds = 0.0;
         true
     }
+
+    // plays the gunshot and tells the behaviours about the collider that was hit, if any
+    fn shoot(&self, ctx: &mut BehaviourContext) {
+        if let Some(gunshot_sound_index) = self.gunshot_sound_index {
+            let mut audio_manager_guard = ctx.audio_manager.lock().unwrap();
+            audio_manager_guard.play_sound(gunshot_sound_index);
+            audio_manager_guard.reload_sound(
+                gunshot_sound_index,
+                SoundParams {
+                    initial_volume: 0.75,
+                    fixed_volume: true,
+                    spacial_params: None,
+                },
+            )
+        }
+
+        let player_position = ctx.player_controller.position(ctx.physics_state);
+        let direction_vec = ctx.player_controller.view_direction.to_direction_vector();
+        let ray = Ray::new(
+            point![player_position.x, player_position.y, player_position.z],
+            vector![direction_vec.x, direction_vec.y, direction_vec.z],
+        );
+        let max_distance = ARENA_SIDE_LENGTH * 10.0;
+        let solid = true;
+        if let Some((collider_handle, _)) = ctx.physics_state.query_pipeline.cast_ray(
+            &ctx.physics_state.rigid_body_set,
+            &ctx.physics_state.collider_set,
+            &ray,
+            max_distance,
+            solid,
+            QueryFilter::from(
+                InteractionGroups::all().with_filter(!COLLISION_GROUP_PLAYER_UNSHOOTABLE),
+            ),
+        ) {
+            ctx.send_message(ColliderHitMessage { collider_handle });
+        }
+    }
+}
+
+impl Behaviour for Revolver {
+    fn update(&mut self, _dt: f32, ctx: &mut BehaviourContext) {
+        self.follow_camera(ctx.player_controller.view_direction, ctx.scene);
+        if ctx.player_controller.mouse_button_pressed && self.fire(ctx.scene) {
+            self.shoot(ctx);
+        }
+    }
 }
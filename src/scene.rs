@@ -1,10 +1,19 @@
 use crate::animation::*;
+use crate::camera::*;
 use crate::collisions::*;
+use crate::component::*;
+use crate::handle::*;
+use crate::light::*;
 use crate::mesh::*;
 use crate::renderer::*;
+use crate::texture::*;
 
-use std::{collections::HashMap, hash::BuildHasherDefault};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::BuildHasherDefault,
+};
 
+use anyhow::{bail, Result};
 use glam::f32::{Mat4, Vec3, Vec4};
 use twox_hash::XxHash64;
 
@@ -15,9 +24,16 @@ pub struct Scene {
     global_node_transforms: Vec<crate::transform::Transform>,
     pub skins: Vec<Skin>,
     pub animations: Vec<Animation>,
+    // lights and cameras that were authored in the scene file, attached to their nodes
+    pub point_lights: Vec<PointLightComponent>,
+    pub directional_lights: Vec<DirectionalLightComponent>,
+    pub cameras: Vec<CameraComponent>,
     // skeleton skin node index -> parent_index_map
     skeleton_parent_index_maps:
         HashMap<u32, HashMap<u32, u32, BuildHasherDefault<XxHash64>>, BuildHasherDefault<XxHash64>>,
+    // name -> nodes with that name, in the order they were added
+    node_name_index: HashMap<String, Vec<GameNodeId>>,
+    pub components: ComponentStore,
 }
 
 #[derive(Debug, Clone)]
@@ -34,8 +50,11 @@ pub struct GameNode {
     pub transform: crate::transform::Transform,
     pub skin_index: Option<usize>,
     pub mesh: Option<GameNodeMesh>,
-    pub name: Option<String>,
-    pub parent_id: Option<GameNodeId>,
+    // kept in sync with the scene's name index, change it through Scene::set_node_name
+    name: Option<String>,
+    // kept in sync with the children of the parent, change it through Scene::set_parent
+    parent_id: Option<GameNodeId>,
+    children: Vec<GameNodeId>,
     id: GameNodeId,
 }
 
@@ -109,6 +128,9 @@ impl Scene {
         nodes_desc: Vec<IndexedGameNodeDesc>,
         indexed_skins: Vec<IndexedSkin>,
         animations: Vec<IndexedAnimation>,
+        point_lights: Vec<IndexedPointLightComponent>,
+        directional_lights: Vec<IndexedDirectionalLightComponent>,
+        cameras: Vec<IndexedCameraComponent>,
     ) -> Self {
         let animations: Vec<_> = animations
             .iter()
@@ -135,26 +157,39 @@ impl Scene {
             global_node_transforms: Vec::new(),
             skins: Vec::new(),
             animations,
+            point_lights: Vec::new(),
+            directional_lights: Vec::new(),
+            cameras: Vec::new(),
             skeleton_parent_index_maps: Default::default(),
+            node_name_index: Default::default(),
+            components: Default::default(),
         };
 
+        // parents can come after their children, so they're attached once all the nodes exist
         nodes_desc.iter().for_each(|node_desc| {
             let IndexedGameNodeDesc {
                 transform,
                 skin_index,
                 mesh,
                 name,
-                parent_index,
+                ..
             } = node_desc.clone();
             scene.add_node(GameNodeDesc {
                 transform,
                 skin_index,
                 mesh,
                 name,
-                parent_id: parent_index
-                    .map(|parent_index| GameNodeId((parent_index).try_into().unwrap(), 0)),
+                parent_id: None,
             });
         });
+        for (node_index, node_desc) in nodes_desc.iter().enumerate() {
+            if let Some(parent_index) = node_desc.parent_index {
+                scene.attach_to_parent(
+                    GameNodeId(node_index.try_into().unwrap(), 0),
+                    Some(GameNodeId(parent_index.try_into().unwrap(), 0)),
+                );
+            }
+        }
 
         scene.skins = (0..indexed_skins.len())
             .map(|skin_index| {
@@ -181,6 +216,59 @@ impl Scene {
 
         scene.recompute_global_node_transforms();
 
+        scene.point_lights = point_lights
+            .into_iter()
+            .map(
+                |IndexedPointLightComponent {
+                     node_index,
+                     color,
+                     intensity,
+                 }| PointLightComponent {
+                    node_id: GameNodeId(node_index.try_into().unwrap(), 0),
+                    color,
+                    intensity,
+                },
+            )
+            .collect();
+
+        scene.directional_lights = directional_lights
+            .into_iter()
+            .map(
+                |IndexedDirectionalLightComponent {
+                     node_index,
+                     color,
+                     intensity,
+                 }| {
+                    let node_id = GameNodeId(node_index.try_into().unwrap(), 0);
+                    let global_transform = scene.get_global_transform_for_node_opt(node_id);
+                    DirectionalLightComponent {
+                        node_id: Some(node_id),
+                        position: global_transform.position(),
+                        // lights point down the -Z axis of their node
+                        direction: (global_transform.rotation() * Vec3::new(0.0, 0.0, -1.0))
+                            .normalize(),
+                        color,
+                        intensity,
+                    }
+                },
+            )
+            .collect();
+
+        scene.cameras = cameras
+            .into_iter()
+            .map(
+                |IndexedCameraComponent {
+                     node_index,
+                     name,
+                     projection,
+                 }| CameraComponent {
+                    node_id: GameNodeId(node_index.try_into().unwrap(), 0),
+                    name,
+                    projection,
+                },
+            )
+            .collect();
+
         scene
     }
 
@@ -224,40 +312,20 @@ impl Scene {
         }
     }
 
+    // the meshes and textures of other_render_buffers stay alive while the returned handle or any
+    // node that uses them is around
     pub fn merge_scene(
         &mut self,
         renderer_data: &mut RendererPublicData,
         mut other_scene: Scene,
-        mut other_render_buffers: RenderBuffers,
-    ) {
-        let pbr_mesh_index_offset = renderer_data.binded_pbr_meshes.len();
-        let unlit_mesh_index_offset = renderer_data.binded_unlit_meshes.len();
+        other_render_buffers: RenderBuffers,
+    ) -> Handle<GltfScene> {
+        let merged_render_buffers = add_render_buffers(renderer_data, other_render_buffers);
+        let node_hierarchy_paths = other_scene.get_node_hierarchy_paths();
 
-        for binded_wireframe_mesh in &mut other_render_buffers.binded_wireframe_meshes {
-            match binded_wireframe_mesh.source_mesh_type {
-                MeshType::Pbr => {
-                    binded_wireframe_mesh.source_mesh_index += pbr_mesh_index_offset;
-                }
-                MeshType::Unlit => {
-                    binded_wireframe_mesh.source_mesh_index += unlit_mesh_index_offset;
-                }
-            }
-        }
-
-        renderer_data
-            .binded_pbr_meshes
-            .append(&mut other_render_buffers.binded_pbr_meshes);
-        renderer_data
-            .binded_unlit_meshes
-            .append(&mut other_render_buffers.binded_unlit_meshes);
-        renderer_data
-            .binded_wireframe_meshes
-            .append(&mut other_render_buffers.binded_wireframe_meshes);
-        renderer_data
-            .textures
-            .append(&mut other_render_buffers.textures);
         let skin_index_offset = self.skins.len();
         let node_index_offset = self.nodes.len();
+        let animation_index_offset = self.animations.len();
         let convert_node_id = |old_node_id| {
             let GameNodeId(old_index, _) = old_node_id;
             let new_index = old_index + node_index_offset as u32;
@@ -265,28 +333,8 @@ impl Scene {
         };
         for (node, _) in &mut other_scene.nodes {
             if let Some(ref mut node) = node {
-                match node.mesh {
-                    Some(GameNodeMesh {
-                        ref mut mesh_indices,
-                        mesh_type: GameNodeMeshType::Pbr { .. },
-                        ..
-                    }) => {
-                        *mesh_indices = mesh_indices
-                            .iter()
-                            .map(|mesh_index| mesh_index + pbr_mesh_index_offset)
-                            .collect();
-                    }
-                    Some(GameNodeMesh {
-                        ref mut mesh_indices,
-                        mesh_type: GameNodeMeshType::Unlit { .. },
-                        ..
-                    }) => {
-                        *mesh_indices = mesh_indices
-                            .iter()
-                            .map(|mesh_index| mesh_index + unlit_mesh_index_offset)
-                            .collect();
-                    }
-                    _ => {}
+                if let Some(ref mut mesh) = node.mesh {
+                    merged_render_buffers.offset_mesh_indices(mesh);
                 }
                 if let Some(ref mut skin_index) = node.skin_index {
                     *skin_index += skin_index_offset;
@@ -294,7 +342,13 @@ impl Scene {
                 if let Some(ref mut parent_id) = node.parent_id {
                     *parent_id = convert_node_id(*parent_id);
                 }
+                for child_id in &mut node.children {
+                    *child_id = convert_node_id(*child_id);
+                }
                 node.id = convert_node_id(node.id);
+                if let Some(name) = node.name.clone() {
+                    self.node_name_index.entry(name).or_default().push(node.id);
+                }
             }
         }
         for mut skin in &mut other_scene.skins {
@@ -311,11 +365,468 @@ impl Scene {
                 channel.node_id = convert_node_id(channel.node_id);
             }
         }
+        for point_light in &mut other_scene.point_lights {
+            point_light.node_id = convert_node_id(point_light.node_id);
+        }
+        for directional_light in &mut other_scene.directional_lights {
+            directional_light.node_id = directional_light.node_id.map(convert_node_id);
+        }
+        for camera in &mut other_scene.cameras {
+            camera.node_id = convert_node_id(camera.node_id);
+        }
+
+        renderer_data.gltf_scenes.push(GltfScene {
+            pbr_meshes: merged_render_buffers.pbr_meshes,
+            unlit_meshes: merged_render_buffers.unlit_meshes,
+            textures: merged_render_buffers.textures,
+            nodes: node_hierarchy_paths
+                .into_iter()
+                .map(|(path, node_id)| (path, convert_node_id(node_id)))
+                .collect(),
+            skins: (skin_index_offset..skin_index_offset + other_scene.skins.len()).collect(),
+            animations: (animation_index_offset
+                ..animation_index_offset + other_scene.animations.len())
+                .collect(),
+        });
+        let gltf_scene_handle = renderer_data.gltf_scene_handles.push();
 
         self.nodes.append(&mut other_scene.nodes);
         self.skins.append(&mut other_scene.skins);
         self.animations.append(&mut other_scene.animations);
+        self.point_lights.append(&mut other_scene.point_lights);
+        self.directional_lights
+            .append(&mut other_scene.directional_lights);
+        self.cameras.append(&mut other_scene.cameras);
+        self.components
+            .append(std::mem::take(&mut other_scene.components), convert_node_id);
         self.rebuild_skeleton_parent_index_maps();
+
+        gltf_scene_handle
+    }
+
+    // swaps in the meshes, textures, skins and animations of a new version of a scene that was
+    // merged before. the nodes that are still at the same place in the hierarchy keep their id,
+    // transform and parent, and the animations keep their state. the lights and cameras of the new
+    // version are ignored
+    pub fn reload_scene(
+        &mut self,
+        renderer_data: &mut RendererPublicData,
+        gltf_scene: &Handle<GltfScene>,
+        other_scene: Scene,
+        other_render_buffers: RenderBuffers,
+    ) {
+        let merged_render_buffers = add_render_buffers(renderer_data, other_render_buffers);
+        let GltfScene {
+            nodes: old_nodes,
+            skins: old_skin_indices,
+            animations: old_animation_indices,
+            ..
+        } = &renderer_data.gltf_scenes[gltf_scene.index()];
+        let (nodes, skins, animations) = self.reload_scene_nodes(
+            old_nodes,
+            old_skin_indices,
+            old_animation_indices,
+            other_scene,
+            |mesh| merged_render_buffers.offset_mesh_indices(mesh),
+        );
+
+        // the old meshes and textures are freed once their handles are dropped here
+        renderer_data.gltf_scenes[gltf_scene.index()] = GltfScene {
+            pbr_meshes: merged_render_buffers.pbr_meshes,
+            unlit_meshes: merged_render_buffers.unlit_meshes,
+            textures: merged_render_buffers.textures,
+            nodes,
+            skins,
+            animations,
+        };
+    }
+
+    // the part of reload_scene that doesn't touch the renderer. returns the hierarchy paths, skin
+    // indices and animation indices of the new version
+    fn reload_scene_nodes(
+        &mut self,
+        old_nodes: &[(String, GameNodeId)],
+        old_skin_indices: &[usize],
+        old_animation_indices: &[usize],
+        mut other_scene: Scene,
+        offset_mesh_indices: impl Fn(&mut GameNodeMesh),
+    ) -> (Vec<(String, GameNodeId)>, Vec<usize>, Vec<usize>) {
+        let old_node_ids: HashMap<&str, GameNodeId> = old_nodes
+            .iter()
+            .filter(|(_, node_id)| self.get_node(*node_id).is_some())
+            .map(|(path, node_id)| (path.as_str(), *node_id))
+            .collect();
+
+        // the new nodes are added empty first so that all the ids are known when filling them in
+        let node_hierarchy_paths = other_scene.get_node_hierarchy_paths();
+        let mut kept_node_ids = HashSet::new();
+        let mut new_node_ids = HashMap::new();
+        for (path, other_node_id) in &node_hierarchy_paths {
+            let node_id = match old_node_ids.get(path.as_str()) {
+                Some(old_node_id) => {
+                    kept_node_ids.insert(*old_node_id);
+                    *old_node_id
+                }
+                None => self.add_node(GameNodeDescBuilder::new().build()).id(),
+            };
+            new_node_ids.insert(*other_node_id, node_id);
+        }
+
+        let mut skin_indices = vec![];
+        for (skin_number, mut skin) in other_scene.skins.drain(..).enumerate() {
+            skin.node_id = new_node_ids[&skin.node_id];
+            for bone_node_id in &mut skin.bone_node_ids {
+                *bone_node_id = new_node_ids[bone_node_id];
+            }
+            match old_skin_indices.get(skin_number) {
+                Some(old_skin_index) => {
+                    self.skins[*old_skin_index] = skin;
+                    skin_indices.push(*old_skin_index);
+                }
+                None => {
+                    self.skins.push(skin);
+                    skin_indices.push(self.skins.len() - 1);
+                }
+            }
+        }
+        // removing skins would shift the indices of the others
+        for old_skin_index in old_skin_indices.iter().skip(skin_indices.len()) {
+            self.skins[*old_skin_index].bone_node_ids.clear();
+            self.skins[*old_skin_index]
+                .bone_inverse_bind_matrices
+                .clear();
+            self.skins[*old_skin_index]
+                .bone_bounding_box_transforms
+                .clear();
+        }
+
+        let mut new_node_links = vec![];
+        for (other_node, _) in other_scene.nodes.drain(..) {
+            let Some(other_node) = other_node else {
+                continue;
+            };
+            let node_id = new_node_ids[&other_node.id];
+            let mut mesh = other_node.mesh;
+            let node = self.get_node_mut(node_id).unwrap();
+            if let Some(ref mut mesh) = mesh {
+                offset_mesh_indices(mesh);
+                if let Some(old_mesh) = node.mesh.as_ref() {
+                    mesh.wireframe = old_mesh.wireframe;
+                }
+            }
+            if !kept_node_ids.contains(&node_id) {
+                node.transform = other_node.transform;
+                new_node_links.push((
+                    node_id,
+                    other_node.name,
+                    other_node
+                        .parent_id
+                        .map(|parent_id| new_node_ids[&parent_id]),
+                ));
+            }
+            node.mesh = mesh;
+            node.skin_index = other_node
+                .skin_index
+                .map(|skin_index| skin_indices[skin_index]);
+        }
+        for (node_id, name, parent_id) in new_node_links {
+            self.set_node_name(node_id, name);
+            self.attach_to_parent(node_id, parent_id);
+        }
+
+        let mut animation_indices = vec![];
+        for (animation_number, mut animation) in other_scene.animations.drain(..).enumerate() {
+            for channel in &mut animation.channels {
+                channel.node_id = new_node_ids[&channel.node_id];
+            }
+            match old_animation_indices.get(animation_number) {
+                Some(old_animation_index) => {
+                    animation.state = self.animations[*old_animation_index].state;
+                    self.animations[*old_animation_index] = animation;
+                    animation_indices.push(*old_animation_index);
+                }
+                None => {
+                    self.animations.push(animation);
+                    animation_indices.push(self.animations.len() - 1);
+                }
+            }
+        }
+        for old_animation_index in old_animation_indices.iter().skip(animation_indices.len()) {
+            self.animations[*old_animation_index].channels.clear();
+            self.animations[*old_animation_index].state.is_playing = false;
+        }
+
+        // removed last so the old animations don't get stopped for losing their channels
+        for (_, old_node_id) in old_nodes {
+            if !kept_node_ids.contains(old_node_id) {
+                self.remove_node(*old_node_id);
+            }
+        }
+        self.rebuild_skeleton_parent_index_maps();
+
+        (
+            node_hierarchy_paths
+                .into_iter()
+                .map(|(path, other_node_id)| (path, new_node_ids[&other_node_id]))
+                .collect(),
+            skin_indices,
+            animation_indices,
+        )
+    }
+
+    // adds a copy of the prefab's nodes, skins and animations under a new instance node that has
+    // the transform. the copies share the prefab's meshes and textures, which stay alive while
+    // any instance uses them even if the prefab is dropped. the directional lights of the prefab
+    // aren't attached to a node and are left out, and so are its components
+    pub fn instantiate(
+        &mut self,
+        renderer_data: &RendererPublicData,
+        prefab: &Prefab,
+        parent_id: Option<GameNodeId>,
+        transform: crate::transform::Transform,
+    ) -> PrefabInstance {
+        let gltf_scene = &renderer_data.gltf_scenes[prefab.gltf_scene.index()];
+        self.add_scene_copy(
+            &prefab.scene,
+            parent_id,
+            transform,
+            |mesh_type, mesh_index| match mesh_type {
+                GameNodeMeshType::Pbr { .. } => gltf_scene.pbr_meshes[mesh_index].index(),
+                GameNodeMeshType::Unlit { .. } => gltf_scene.unlit_meshes[mesh_index].index(),
+            },
+        )
+    }
+
+    // adds a copy of the nodes that were merged into the scene from a gltf scene, like
+    // instantiate does for a prefab, leaving out the ones in skip_node_ids. the skins and
+    // animations of the gltf scene are copied too, in the state that they're currently in
+    pub fn instantiate_merged_scene(
+        &mut self,
+        gltf_scene: &GltfScene,
+        skip_node_ids: &[GameNodeId],
+        parent_id: Option<GameNodeId>,
+        transform: crate::transform::Transform,
+    ) -> PrefabInstance {
+        let node_ids: Vec<_> = gltf_scene
+            .nodes
+            .iter()
+            .map(|(_, node_id)| *node_id)
+            .filter(|node_id| !skip_node_ids.contains(node_id) && self.get_node(*node_id).is_some())
+            .collect();
+        let node_indices: HashMap<_, _> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(node_index, node_id)| (*node_id, node_index))
+            .collect();
+        let skin_numbers: HashMap<_, _> = gltf_scene
+            .skins
+            .iter()
+            .enumerate()
+            .map(|(skin_number, skin_index)| (*skin_index, skin_number))
+            .collect();
+
+        let nodes_desc = node_ids
+            .iter()
+            .map(|node_id| {
+                let node = self.get_node(*node_id).unwrap();
+                IndexedGameNodeDesc {
+                    transform: node.transform,
+                    skin_index: node
+                        .skin_index
+                        .and_then(|skin_index| skin_numbers.get(&skin_index).copied()),
+                    mesh: node.mesh.clone(),
+                    name: node.name.clone(),
+                    parent_index: node
+                        .parent_id
+                        .and_then(|parent_id| node_indices.get(&parent_id).copied()),
+                }
+            })
+            .collect();
+        let skins = gltf_scene
+            .skins
+            .iter()
+            .map(|skin_index| {
+                let skin = &self.skins[*skin_index];
+                IndexedSkin {
+                    bone_node_indices: skin
+                        .bone_node_ids
+                        .iter()
+                        .map(|bone_node_id| node_indices[bone_node_id])
+                        .collect(),
+                    bone_inverse_bind_matrices: skin.bone_inverse_bind_matrices.clone(),
+                    bone_bounding_box_transforms: skin.bone_bounding_box_transforms.clone(),
+                }
+            })
+            .collect();
+        let mut source_scene = Scene::new(nodes_desc, skins, vec![], vec![], vec![], vec![]);
+        for animation_index in &gltf_scene.animations {
+            let mut animation = self.animations[*animation_index].clone();
+            animation.channels.retain_mut(|channel| {
+                let Some(node_index) = node_indices.get(&channel.node_id) else {
+                    return false;
+                };
+                channel.node_id = GameNodeId((*node_index).try_into().unwrap(), 0);
+                true
+            });
+            source_scene.animations.push(animation);
+        }
+
+        // the meshes already index into the renderer's
+        self.add_scene_copy(&source_scene, parent_id, transform, |_, mesh_index| {
+            mesh_index
+        })
+    }
+
+    fn add_scene_copy(
+        &mut self,
+        other_scene: &Scene,
+        parent_id: Option<GameNodeId>,
+        transform: crate::transform::Transform,
+        get_mesh_index: impl Fn(GameNodeMeshType, usize) -> usize,
+    ) -> PrefabInstance {
+        let instance_node_id = self
+            .add_node(
+                GameNodeDescBuilder::new()
+                    .transform(transform)
+                    .parent_id(parent_id)
+                    .build(),
+            )
+            .id();
+
+        let skin_index_offset = self.skins.len();
+        let mut new_node_ids = HashMap::new();
+        for other_node in other_scene.nodes() {
+            let mut mesh = other_node.mesh.clone();
+            if let Some(ref mut mesh) = mesh {
+                for mesh_index in &mut mesh.mesh_indices {
+                    *mesh_index = get_mesh_index(mesh.mesh_type, *mesh_index);
+                }
+            }
+            let node_id = self
+                .add_node(GameNodeDesc {
+                    transform: other_node.transform,
+                    skin_index: other_node
+                        .skin_index
+                        .map(|skin_index| skin_index + skin_index_offset),
+                    mesh,
+                    name: other_node.name.clone(),
+                    parent_id: None,
+                })
+                .id();
+            new_node_ids.insert(other_node.id(), node_id);
+        }
+        let instance_root_node_ids: HashSet<_> = other_scene
+            .get_instance_root_node_ids()
+            .into_iter()
+            .collect();
+        // the root bones go under a holder with the identity transform so that they're removed
+        // along with the instance. the skeleton space transforms stop at the holder, so the
+        // instance transform still only reaches the bones through the mesh node
+        let mut bone_holder_node_id = None;
+        for other_node in other_scene.nodes() {
+            let parent_id = match other_node.parent_id {
+                Some(other_parent_id) => new_node_ids[&other_parent_id],
+                None if instance_root_node_ids.contains(&other_node.id()) => instance_node_id,
+                None => *bone_holder_node_id.get_or_insert_with(|| {
+                    self.add_node(
+                        GameNodeDescBuilder::new()
+                            .parent_id(Some(instance_node_id))
+                            .build(),
+                    )
+                    .id()
+                }),
+            };
+            self.attach_to_parent(new_node_ids[&other_node.id()], Some(parent_id));
+        }
+
+        for other_skin in &other_scene.skins {
+            let mut skin = other_skin.clone();
+            skin.node_id = new_node_ids[&skin.node_id];
+            for bone_node_id in &mut skin.bone_node_ids {
+                *bone_node_id = new_node_ids[bone_node_id];
+            }
+            self.skins.push(skin);
+        }
+        let animation_index_offset = self.animations.len();
+        for other_animation in &other_scene.animations {
+            let mut animation = other_animation.clone();
+            for channel in &mut animation.channels {
+                channel.node_id = new_node_ids[&channel.node_id];
+            }
+            self.animations.push(animation);
+        }
+        for other_point_light in &other_scene.point_lights {
+            let mut point_light = other_point_light.clone();
+            point_light.node_id = new_node_ids[&point_light.node_id];
+            self.point_lights.push(point_light);
+        }
+        for other_directional_light in &other_scene.directional_lights {
+            let mut directional_light = other_directional_light.clone();
+            directional_light.node_id = directional_light
+                .node_id
+                .map(|node_id| new_node_ids[&node_id]);
+            self.directional_lights.push(directional_light);
+        }
+        for other_camera in &other_scene.cameras {
+            let mut camera = other_camera.clone();
+            camera.node_id = new_node_ids[&camera.node_id];
+            self.cameras.push(camera);
+        }
+        self.rebuild_skeleton_parent_index_maps();
+
+        PrefabInstance {
+            node_id: instance_node_id,
+            animations: (animation_index_offset..self.animations.len()).collect(),
+        }
+    }
+
+    // the root nodes that should follow the transform of an instance of the scene. skinned meshes
+    // already get it through the mesh node, parenting a root bone too would apply it a second time
+    // through the skeleton space transforms
+    pub fn get_instance_root_node_ids(&self) -> Vec<GameNodeId> {
+        let bone_node_ids: HashSet<_> = self
+            .skins
+            .iter()
+            .flat_map(|skin| skin.bone_node_ids.iter().copied())
+            .collect();
+        self.nodes()
+            .filter(|node| node.parent_id.is_none() && !bone_node_ids.contains(&node.id()))
+            .map(|node| node.id())
+            .collect()
+    }
+
+    // identifies the nodes by their names and position among their siblings, which survives
+    // changes to the file that reorder the nodes. returned in node order
+    fn get_node_hierarchy_paths(&self) -> Vec<(String, GameNodeId)> {
+        let mut path_segments = HashMap::new();
+        let mut sibling_name_counts: HashMap<(Option<GameNodeId>, Option<&str>), usize> =
+            HashMap::new();
+        for node in self.nodes() {
+            let sibling_name_count = sibling_name_counts
+                .entry((node.parent_id, node.name.as_deref()))
+                .or_default();
+            path_segments.insert(
+                node.id(),
+                format!(
+                    "{}#{}",
+                    node.name.as_deref().unwrap_or_default(),
+                    sibling_name_count
+                ),
+            );
+            *sibling_name_count += 1;
+        }
+
+        self.nodes()
+            .map(|node| {
+                let mut path = path_segments[&node.id()].clone();
+                let mut parent_id = node.parent_id;
+                while let Some(parent) = parent_id.and_then(|parent_id| self.get_node(parent_id)) {
+                    path = format!("{}/{}", path_segments[&parent.id()], path);
+                    parent_id = parent.parent_id;
+                }
+                (path, node.id())
+            })
+            .collect()
     }
 
     pub fn get_node_bounding_sphere(
@@ -462,33 +973,117 @@ impl Scene {
                     None
                 }
             });
-        match empty_node {
-            Some((empty_node_index, empty_node_gen)) => {
-                let new_gen = empty_node_gen + 1;
-                let new_node = GameNode {
-                    transform,
-                    skin_index,
-                    mesh,
-                    name,
-                    id: GameNodeId(empty_node_index.try_into().unwrap(), new_gen),
-                    parent_id,
-                };
-                self.nodes[empty_node_index] = (Some(new_node), new_gen);
-                self.nodes[empty_node_index].0.as_ref().unwrap()
-            }
+        let (node_index, node_gen) = match empty_node {
+            Some((empty_node_index, empty_node_gen)) => (empty_node_index, empty_node_gen + 1),
             None => {
-                let new_node = GameNode {
-                    transform,
-                    skin_index,
-                    mesh,
-                    name,
-                    id: GameNodeId(self.nodes.len().try_into().unwrap(), 0),
-                    parent_id,
-                };
-                self.nodes.push((Some(new_node), 0));
-                self.nodes[self.nodes.len() - 1].0.as_ref().unwrap()
+                self.nodes.push((None, 0));
+                (self.nodes.len() - 1, 0)
+            }
+        };
+        let node_id = GameNodeId(node_index.try_into().unwrap(), node_gen);
+        let new_node = GameNode {
+            transform,
+            skin_index,
+            mesh,
+            name: name.clone(),
+            id: node_id,
+            parent_id: None,
+            children: Vec::new(),
+        };
+        self.nodes[node_index] = (Some(new_node), node_gen);
+        if let Some(name) = name {
+            self.node_name_index.entry(name).or_default().push(node_id);
+        }
+        if parent_id.is_some() {
+            self.attach_to_parent(node_id, parent_id);
+        }
+        self.nodes[node_index].0.as_ref().unwrap()
+    }
+
+    // moves the node to the children of its new parent without touching its transform. a parent
+    // that doesn't exist makes it a root node
+    fn attach_to_parent(&mut self, node_id: GameNodeId, parent_id: Option<GameNodeId>) {
+        let parent_id = parent_id.filter(|parent_id| self.get_node(*parent_id).is_some());
+        let Some(node) = self.get_node_mut(node_id) else {
+            return;
+        };
+        let old_parent_id = std::mem::replace(&mut node.parent_id, parent_id);
+        if let Some(old_parent) =
+            old_parent_id.and_then(|old_parent_id| self.get_node_mut(old_parent_id))
+        {
+            old_parent.children.retain(|child_id| *child_id != node_id);
+        }
+        if let Some(parent) = parent_id.and_then(|parent_id| self.get_node_mut(parent_id)) {
+            parent.children.push(node_id);
+        }
+    }
+
+    fn move_node(
+        &mut self,
+        node_id: GameNodeId,
+        parent_id: Option<GameNodeId>,
+        keep_global_transform: bool,
+    ) {
+        if keep_global_transform {
+            let global_transform = self.get_global_transform_for_node(node_id);
+            let parent_global_transform = parent_id
+                .map(|parent_id| self.get_global_transform_for_node(parent_id))
+                .unwrap_or(crate::transform::Transform::IDENTITY);
+            self.get_node_mut(node_id).unwrap().transform =
+                crate::transform::Transform::from(parent_global_transform.inverse())
+                    * global_transform;
+        }
+        self.attach_to_parent(node_id, parent_id);
+    }
+
+    // makes the node a child of parent_id, or a root node if it's None. with keep_global_transform
+    // the local transform is changed so that the node stays where it is in the world
+    pub fn set_parent(
+        &mut self,
+        node_id: GameNodeId,
+        parent_id: Option<GameNodeId>,
+        keep_global_transform: bool,
+    ) -> Result<()> {
+        if self.get_node(node_id).is_none() {
+            bail!("Node {:?} doesn't exist", node_id);
+        }
+        if let Some(parent_id) = parent_id {
+            if self.get_node(parent_id).is_none() {
+                bail!("Parent node {:?} doesn't exist", parent_id);
+            }
+            if self
+                .get_node_ancestry_list(parent_id)
+                .any(|ancestor_id| ancestor_id == node_id)
+            {
+                bail!(
+                    "Node {:?} can't be parented to itself or one of its descendants",
+                    node_id
+                );
             }
         }
+        self.move_node(node_id, parent_id, keep_global_transform);
+        self.rebuild_skeleton_parent_index_maps();
+        Ok(())
+    }
+
+    pub fn children(&self, node_id: GameNodeId) -> impl Iterator<Item = GameNodeId> + '_ {
+        self.get_children(node_id).iter().copied()
+    }
+
+    // depth first, each node comes before its children
+    pub fn descendants(&self, node_id: GameNodeId) -> impl Iterator<Item = GameNodeId> + '_ {
+        let mut stack: Vec<_> = self.get_children(node_id).iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let node_id = stack.pop()?;
+            stack.extend(self.get_children(node_id).iter().rev());
+            Some(node_id)
+        })
+    }
+
+    fn get_children(&self, node_id: GameNodeId) -> &[GameNodeId] {
+        self.get_node(node_id)
+            .map(|node| node.children.as_slice())
+            .unwrap_or_default()
     }
 
     pub fn get_node(&self, node_id: GameNodeId) -> Option<&GameNode> {
@@ -525,12 +1120,162 @@ impl Scene {
         self.nodes[node_index].0.as_mut()
     }
 
+    // the children of the node are moved up to its parent and keep their global transforms
     pub fn remove_node(&mut self, node_id: GameNodeId) {
         // make sure it still exists
-        if let Some(node) = self.get_node(node_id) {
-            let GameNodeId(node_index, _) = node.id;
-            self.nodes[node_index as usize].0.take();
-            self.rebuild_skeleton_parent_index_maps();
+        let Some(node) = self.get_node(node_id) else {
+            return;
+        };
+        let parent_id = node.parent_id;
+        for child_id in node.children.clone() {
+            self.move_node(child_id, parent_id, true);
+        }
+        self.attach_to_parent(node_id, None);
+        self.take_node(node_id);
+        self.remove_references_to_removed_nodes();
+        self.rebuild_skeleton_parent_index_maps();
+    }
+
+    pub fn remove_node_recursive(&mut self, node_id: GameNodeId) {
+        if self.get_node(node_id).is_none() {
+            return;
+        }
+        let removed_node_ids: Vec<_> = std::iter::once(node_id)
+            .chain(self.descendants(node_id))
+            .collect();
+        self.attach_to_parent(node_id, None);
+        for removed_node_id in removed_node_ids {
+            self.take_node(removed_node_id);
+        }
+        self.remove_references_to_removed_nodes();
+        self.rebuild_skeleton_parent_index_maps();
+    }
+
+    fn take_node(&mut self, node_id: GameNodeId) {
+        self.components.remove_node(node_id);
+        let GameNodeId(node_index, _) = node_id;
+        if let Some(name) = self.nodes[node_index as usize]
+            .0
+            .take()
+            .and_then(|node| node.name)
+        {
+            self.remove_from_node_name_index(&name, node_id);
+        }
+    }
+
+    fn remove_from_node_name_index(&mut self, name: &str, node_id: GameNodeId) {
+        if let Some(node_ids) = self.node_name_index.get_mut(name) {
+            node_ids.retain(|other_node_id| *other_node_id != node_id);
+            if node_ids.is_empty() {
+                self.node_name_index.remove(name);
+            }
+        }
+    }
+
+    pub fn set_node_name(&mut self, node_id: GameNodeId, name: Option<String>) {
+        let Some(node) = self.get_node_mut(node_id) else {
+            return;
+        };
+        let old_name = std::mem::replace(&mut node.name, name.clone());
+        if let Some(old_name) = old_name {
+            self.remove_from_node_name_index(&old_name, node_id);
+        }
+        if let Some(name) = name {
+            self.node_name_index.entry(name).or_default().push(node_id);
+        }
+    }
+
+    // the first node with the name that was added to the scene
+    pub fn find_node_by_name(&self, name: &str) -> Option<GameNodeId> {
+        self.find_nodes_by_name(name).next()
+    }
+
+    pub fn find_nodes_by_name(&self, name: &str) -> impl Iterator<Item = GameNodeId> + '_ {
+        self.node_name_index
+            .get(name)
+            .map(|node_ids| node_ids.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .copied()
+    }
+
+    // finds a node by the names of its ancestors, e.g. "Armature/Hips/Spine/RightHand". the first
+    // name belongs to a child of root_node_id, or to a root node if it's None
+    pub fn find_node_by_path(
+        &self,
+        root_node_id: Option<GameNodeId>,
+        path: &str,
+    ) -> Option<GameNodeId> {
+        let path_names: Vec<_> = path.split('/').filter(|name| !name.is_empty()).collect();
+        self.find_nodes_by_name(path_names.last()?).find(|node_id| {
+            let mut ancestry_list = self.get_node_ancestry_list(*node_id);
+            path_names.iter().rev().all(|path_name| {
+                ancestry_list
+                    .next()
+                    .and_then(|ancestor_id| self.get_node(ancestor_id))
+                    .and_then(|ancestor| ancestor.name())
+                    == Some(*path_name)
+            }) && ancestry_list.next() == root_node_id
+        })
+    }
+
+    // index of the first animation with the name
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations
+            .iter()
+            .position(|animation| animation.name.as_deref() == Some(name))
+    }
+
+    // skins and animations are emptied instead of removed so the indices of the others stay valid.
+    // the meshes of the skins that lost a node are drawn without skinning
+    fn remove_references_to_removed_nodes(&mut self) {
+        let Scene {
+            nodes,
+            skins,
+            animations,
+            point_lights,
+            directional_lights,
+            cameras,
+            ..
+        } = self;
+        let node_exists = |node_id: &GameNodeId| {
+            let GameNodeId(node_index, node_gen) = *node_id;
+            matches!(&nodes[node_index as usize], (Some(_), actual_node_gen) if *actual_node_gen == node_gen)
+        };
+
+        let mut broken_skins = vec![];
+        for (skin_index, skin) in skins.iter_mut().enumerate() {
+            if node_exists(&skin.node_id) && skin.bone_node_ids.iter().all(node_exists) {
+                continue;
+            }
+            skin.bone_node_ids.clear();
+            skin.bone_inverse_bind_matrices.clear();
+            skin.bone_bounding_box_transforms.clear();
+            broken_skins.push((skin.node_id, skin_index));
+        }
+
+        point_lights.retain(|point_light| node_exists(&point_light.node_id));
+        directional_lights.retain(|directional_light| {
+            directional_light.node_id.as_ref().map_or(true, node_exists)
+        });
+        cameras.retain(|camera| node_exists(&camera.node_id));
+
+        for animation in animations.iter_mut() {
+            let channel_count = animation.channels.len();
+            animation
+                .channels
+                .retain(|channel| node_exists(&channel.node_id));
+            if animation.channels.is_empty() && channel_count > 0 {
+                animation.state.is_playing = false;
+            }
+        }
+
+        for (skin_node_id, skin_index) in broken_skins {
+            if let Some(skin_node) = self.get_node_mut(skin_node_id) {
+                if skin_node.skin_index == Some(skin_index) {
+                    skin_node.skin_index = None;
+                }
+            }
         }
     }
 
@@ -547,6 +1292,141 @@ impl Scene {
     }
 }
 
+// a loaded scene that's kept as a template for Scene::instantiate. the meshes of its nodes
+// index into the meshes of its gltf scene instead of the renderer's
+pub struct Prefab {
+    scene: Scene,
+    gltf_scene: Handle<GltfScene>,
+}
+
+impl Prefab {
+    pub fn new(
+        renderer_data: &mut RendererPublicData,
+        scene: Scene,
+        render_buffers: RenderBuffers,
+    ) -> Self {
+        let merged_render_buffers = add_render_buffers(renderer_data, render_buffers);
+        renderer_data.gltf_scenes.push(GltfScene {
+            pbr_meshes: merged_render_buffers.pbr_meshes,
+            unlit_meshes: merged_render_buffers.unlit_meshes,
+            textures: merged_render_buffers.textures,
+            nodes: vec![],
+            skins: vec![],
+            animations: vec![],
+        });
+        Self {
+            scene,
+            gltf_scene: renderer_data.gltf_scene_handles.push(),
+        }
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn gltf_scene(&self) -> &Handle<GltfScene> {
+        &self.gltf_scene
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefabInstance {
+    // holds the transform of the instance, removing it recursively removes the instance
+    pub node_id: GameNodeId,
+    // indices into Scene::animations, in the order of the prefab's animations
+    pub animations: Vec<usize>,
+}
+
+struct MergedRenderBuffers {
+    pbr_mesh_index_offset: usize,
+    unlit_mesh_index_offset: usize,
+    pbr_meshes: Vec<Handle<BindedPbrMesh>>,
+    unlit_meshes: Vec<Handle<BindedUnlitMesh>>,
+    textures: Vec<Handle<Texture>>,
+}
+
+impl MergedRenderBuffers {
+    fn offset_mesh_indices(&self, mesh: &mut GameNodeMesh) {
+        let mesh_index_offset = match mesh.mesh_type {
+            GameNodeMeshType::Pbr { .. } => self.pbr_mesh_index_offset,
+            GameNodeMeshType::Unlit { .. } => self.unlit_mesh_index_offset,
+        };
+        for mesh_index in &mut mesh.mesh_indices {
+            *mesh_index += mesh_index_offset;
+        }
+    }
+}
+
+// moves the meshes and textures over to the renderer, fixing up the indices between them
+fn add_render_buffers(
+    renderer_data: &mut RendererPublicData,
+    mut render_buffers: RenderBuffers,
+) -> MergedRenderBuffers {
+    let pbr_mesh_index_offset = renderer_data.binded_pbr_meshes.len();
+    let unlit_mesh_index_offset = renderer_data.binded_unlit_meshes.len();
+    let texture_index_offset = renderer_data.textures.len();
+
+    for binded_pbr_mesh in &mut render_buffers.binded_pbr_meshes {
+        if let Some(ref mut texture_indices) = binded_pbr_mesh.texture_indices {
+            texture_indices
+                .remap_texture_indices(|texture_index| *texture_index += texture_index_offset);
+        }
+    }
+    for binded_unlit_mesh in &mut render_buffers.binded_unlit_meshes {
+        if let Some(ref mut texture_index) = binded_unlit_mesh.base_color_texture_index {
+            *texture_index += texture_index_offset;
+        }
+    }
+    for mut streamed_texture in render_buffers.streamed_textures.drain(..) {
+        streamed_texture.texture_index += texture_index_offset;
+        renderer_data.texture_streamer.add_texture(streamed_texture);
+    }
+
+    for binded_wireframe_mesh in &mut render_buffers.binded_wireframe_meshes {
+        match binded_wireframe_mesh.source_mesh_type {
+            MeshType::Pbr => {
+                binded_wireframe_mesh.source_mesh_index += pbr_mesh_index_offset;
+            }
+            MeshType::Unlit => {
+                binded_wireframe_mesh.source_mesh_index += unlit_mesh_index_offset;
+            }
+        }
+    }
+
+    let merged_render_buffers = MergedRenderBuffers {
+        pbr_mesh_index_offset,
+        unlit_mesh_index_offset,
+        pbr_meshes: render_buffers
+            .binded_pbr_meshes
+            .iter()
+            .map(|_| renderer_data.pbr_mesh_handles.push())
+            .collect(),
+        unlit_meshes: render_buffers
+            .binded_unlit_meshes
+            .iter()
+            .map(|_| renderer_data.unlit_mesh_handles.push())
+            .collect(),
+        textures: render_buffers
+            .textures
+            .iter()
+            .map(|_| renderer_data.texture_handles.push())
+            .collect(),
+    };
+
+    renderer_data
+        .binded_pbr_meshes
+        .append(&mut render_buffers.binded_pbr_meshes);
+    renderer_data
+        .binded_unlit_meshes
+        .append(&mut render_buffers.binded_unlit_meshes);
+    renderer_data
+        .binded_wireframe_meshes
+        .append(&mut render_buffers.binded_wireframe_meshes);
+    renderer_data.textures.append(&mut render_buffers.textures);
+
+    merged_render_buffers
+}
+
 fn build_node_bounding_sphere(
     mesh: &GameNodeMesh,
     global_transform: &crate::transform::Transform,
@@ -569,7 +1449,9 @@ fn build_node_bounding_sphere(
                     .bounding_box
             }
             GameNodeMeshType::Unlit { .. } => {
-                renderer_data.binded_unlit_meshes[mesh_index].bounding_box
+                renderer_data.binded_unlit_meshes[mesh_index]
+                    .geometry_buffers
+                    .bounding_box
             }
         });
 
@@ -600,6 +1482,14 @@ impl GameNode {
     pub fn id(&self) -> GameNodeId {
         self.id
     }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn parent_id(&self) -> Option<GameNodeId> {
+        self.parent_id
+    }
 }
 
 impl GameNodeId {
@@ -724,7 +1614,7 @@ mod tests {
 
     #[test]
     fn removing_nodes_invalidates_ids() {
-        let mut scene = Scene::new(vec![], vec![], vec![]);
+        let mut scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
 
         let node_1 = scene.add_node(GameNodeDesc::default());
         let node_1_id = node_1.id();
@@ -755,6 +1645,508 @@ mod tests {
         assert_node_exists(&scene, node_3_id);
     }
 
+    #[test]
+    fn hierarchy_paths_tell_same_named_siblings_apart() {
+        let mut scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        let root_id = scene
+            .add_node(GameNodeDescBuilder::new().name(Some("root".into())).build())
+            .id();
+        for _ in 0..2 {
+            scene.add_node(
+                GameNodeDescBuilder::new()
+                    .name(Some("wheel".into()))
+                    .parent_id(Some(root_id))
+                    .build(),
+            );
+        }
+        scene.add_node(GameNodeDescBuilder::new().parent_id(Some(root_id)).build());
+
+        let paths: Vec<_> = scene
+            .get_node_hierarchy_paths()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["root#0", "root#0/wheel#0", "root#0/wheel#1", "root#0/#0"]
+        );
+    }
+
+    #[test]
+    fn reparenting_and_recursive_removal() {
+        let mut scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        let translated = |x| {
+            crate::transform::TransformBuilder::new()
+                .position(Vec3::new(x, 0.0, 0.0))
+                .build()
+        };
+        let root_id = scene.add_node(GameNodeDesc::default()).id();
+        let arm_id = scene
+            .add_node(
+                GameNodeDescBuilder::new()
+                    .transform(translated(1.0))
+                    .parent_id(Some(root_id))
+                    .build(),
+            )
+            .id();
+        let hand_id = scene
+            .add_node(
+                GameNodeDescBuilder::new()
+                    .transform(translated(2.0))
+                    .parent_id(Some(arm_id))
+                    .build(),
+            )
+            .id();
+        let other_root_id = scene
+            .add_node(
+                GameNodeDescBuilder::new()
+                    .transform(translated(10.0))
+                    .build(),
+            )
+            .id();
+
+        assert_eq!(
+            scene.descendants(root_id).collect::<Vec<_>>(),
+            vec![arm_id, hand_id]
+        );
+        assert!(scene.set_parent(root_id, Some(hand_id), false).is_err());
+
+        scene
+            .set_parent(hand_id, Some(other_root_id), true)
+            .unwrap();
+        assert_eq!(scene.children(arm_id).count(), 0);
+        assert_eq!(
+            scene.children(other_root_id).collect::<Vec<_>>(),
+            vec![hand_id]
+        );
+        assert_eq!(
+            scene.get_global_transform_for_node(hand_id).position(),
+            Vec3::new(3.0, 0.0, 0.0)
+        );
+
+        // removing a single node moves its children up
+        scene
+            .set_parent(arm_id, Some(other_root_id), false)
+            .unwrap();
+        scene.remove_node(other_root_id);
+        assert_eq!(scene.get_node(arm_id).unwrap().parent_id(), None);
+        assert_eq!(
+            scene.get_global_transform_for_node(arm_id).position(),
+            Vec3::new(11.0, 0.0, 0.0)
+        );
+
+        scene.set_parent(hand_id, Some(arm_id), true).unwrap();
+        scene.skins.push(Skin {
+            node_id: root_id,
+            bone_node_ids: vec![hand_id],
+            bone_inverse_bind_matrices: vec![Mat4::IDENTITY],
+            bone_bounding_box_transforms: vec![crate::transform::Transform::IDENTITY],
+        });
+        scene.get_node_mut(root_id).unwrap().skin_index = Some(0);
+        scene.animations.push(Animation {
+            name: None,
+            length_seconds: 1.0,
+            speed: 1.0,
+            channels: vec![Channel {
+                node_id: hand_id,
+                property: gltf::animation::Property::Translation,
+                interpolation_type: gltf::animation::Interpolation::Linear,
+                keyframe_timings: vec![],
+                keyframe_values_u8: vec![],
+            }],
+            state: AnimationState {
+                is_playing: true,
+                ..Default::default()
+            },
+        });
+
+        scene.remove_node_recursive(arm_id);
+        assert_node_doesnt_exist(&scene, arm_id);
+        assert_node_doesnt_exist(&scene, hand_id);
+        assert_eq!(scene.node_count(), 1);
+        assert!(scene.skins[0].bone_node_ids.is_empty());
+        assert_eq!(scene.get_node(root_id).unwrap().skin_index, None);
+        assert!(scene.animations[0].channels.is_empty());
+        assert!(!scene.animations[0].state.is_playing);
+    }
+
+    #[test]
+    fn nodes_can_be_found_by_name_and_path() {
+        let mut scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        let mut add_character = |root_node_id| {
+            let mut parent_id = root_node_id;
+            for name in ["Armature", "Hips", "Spine", "RightHand"] {
+                parent_id = Some(
+                    scene
+                        .add_node(
+                            GameNodeDescBuilder::new()
+                                .name(Some(name.into()))
+                                .parent_id(parent_id)
+                                .build(),
+                        )
+                        .id(),
+                );
+            }
+        };
+        add_character(None);
+        add_character(None);
+        let second_character_id = scene
+            .add_node(
+                GameNodeDescBuilder::new()
+                    .name(Some("player".into()))
+                    .build(),
+            )
+            .id();
+        let second_armature_id = scene.find_nodes_by_name("Armature").nth(1).unwrap();
+        scene
+            .set_parent(second_armature_id, Some(second_character_id), false)
+            .unwrap();
+
+        let first_hand_id = scene
+            .find_node_by_path(None, "Armature/Hips/Spine/RightHand")
+            .unwrap();
+        let second_hand_id = scene
+            .find_node_by_path(Some(second_character_id), "Armature/Hips/Spine/RightHand")
+            .unwrap();
+        assert_ne!(first_hand_id, second_hand_id);
+        assert_eq!(scene.find_node_by_name("RightHand"), Some(first_hand_id));
+        assert_eq!(
+            scene.find_node_by_path(Some(second_character_id), "Hips/Spine/RightHand"),
+            None
+        );
+        assert_eq!(
+            scene.find_node_by_path(None, "player/Armature/Hips"),
+            scene
+                .get_node(second_hand_id)
+                .unwrap()
+                .parent_id()
+                .and_then(|spine_id| scene.get_node(spine_id).unwrap().parent_id())
+        );
+
+        scene.remove_node_recursive(scene.find_node_by_name("Armature").unwrap());
+        assert_eq!(scene.find_node_by_name("RightHand"), Some(second_hand_id));
+        scene.set_node_name(second_hand_id, Some("LeftHand".into()));
+        assert_eq!(scene.find_node_by_name("RightHand"), None);
+        assert_eq!(
+            scene.find_node_by_path(Some(second_character_id), "Armature/Hips/Spine/LeftHand"),
+            Some(second_hand_id)
+        );
+    }
+
+    #[test]
+    fn scene_copies_get_their_own_nodes_skins_and_animations() {
+        let mut template = Scene::new(
+            vec![
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: Some(0),
+                    mesh: Some(GameNodeMesh::from_pbr_mesh_index(1)),
+                    name: Some("body".into()),
+                    parent_index: None,
+                },
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: None,
+                    mesh: None,
+                    name: Some("hips".into()),
+                    parent_index: None,
+                },
+            ],
+            vec![IndexedSkin {
+                bone_node_indices: vec![1],
+                bone_inverse_bind_matrices: vec![Mat4::IDENTITY],
+                bone_bounding_box_transforms: vec![crate::transform::Transform::IDENTITY],
+            }],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        template.animations.push(Animation {
+            name: Some("walk".into()),
+            length_seconds: 1.0,
+            speed: 1.0,
+            channels: vec![Channel {
+                node_id: template.find_node_by_name("hips").unwrap(),
+                property: gltf::animation::Property::Rotation,
+                interpolation_type: gltf::animation::Interpolation::Linear,
+                keyframe_timings: vec![],
+                keyframe_values_u8: vec![],
+            }],
+            state: AnimationState::default(),
+        });
+
+        let mut scene = Scene::new(vec![], vec![], vec![], vec![], vec![], vec![]);
+        let parent_id = scene.add_node(GameNodeDesc::default()).id();
+        let get_mesh_index = |_, mesh_index| mesh_index + 10;
+        let instances: Vec<_> = (0..2)
+            .map(|_| {
+                scene.add_scene_copy(
+                    &template,
+                    Some(parent_id),
+                    crate::transform::Transform::IDENTITY,
+                    get_mesh_index,
+                )
+            })
+            .collect();
+
+        assert_eq!(scene.node_count(), 9);
+        assert_eq!(scene.skins.len(), 2);
+        for (instance_number, instance) in instances.iter().enumerate() {
+            assert_eq!(
+                scene.get_node(instance.node_id).unwrap().parent_id(),
+                Some(parent_id)
+            );
+            let body_id = scene
+                .find_node_by_path(Some(instance.node_id), "body")
+                .unwrap();
+            let body = scene.get_node(body_id).unwrap();
+            assert_eq!(body.mesh.as_ref().unwrap().mesh_indices, vec![11]);
+            let skin = &scene.skins[body.skin_index.unwrap()];
+            assert_eq!(skin.node_id, body_id);
+
+            // the root bone's skeleton ends at its holder so the instance transform isn't applied
+            // twice
+            let hips_id = scene
+                .find_nodes_by_name("hips")
+                .nth(instance_number)
+                .unwrap();
+            assert_eq!(skin.bone_node_ids, vec![hips_id]);
+            let bone_holder_id = scene.get_node(hips_id).unwrap().parent_id().unwrap();
+            assert_eq!(
+                scene.get_node(bone_holder_id).unwrap().parent_id(),
+                Some(instance.node_id)
+            );
+            assert_eq!(
+                scene.get_skeleton_node_ancestry_list(hips_id, body_id),
+                vec![hips_id, bone_holder_id]
+            );
+            assert_eq!(instance.animations, vec![instance_number]);
+            assert_eq!(
+                scene.animations[instance.animations[0]].channels[0].node_id,
+                hips_id
+            );
+        }
+
+        // the skeleton goes away with the instance
+        scene.remove_node_recursive(instances[0].node_id);
+        assert_eq!(scene.node_count(), 5);
+        assert_eq!(scene.find_nodes_by_name("hips").count(), 1);
+    }
+
+    #[test]
+    fn merged_scenes_can_be_copied() {
+        let mut scene = Scene::new(
+            vec![
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: None,
+                    mesh: None,
+                    name: Some("instance".into()),
+                    parent_index: None,
+                },
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: Some(0),
+                    mesh: Some(GameNodeMesh::from_pbr_mesh_index(7)),
+                    name: Some("body".into()),
+                    parent_index: Some(0),
+                },
+                IndexedGameNodeDesc {
+                    transform: crate::transform::Transform::IDENTITY,
+                    skin_index: None,
+                    mesh: None,
+                    name: Some("hips".into()),
+                    parent_index: None,
+                },
+            ],
+            vec![IndexedSkin {
+                bone_node_indices: vec![2],
+                bone_inverse_bind_matrices: vec![Mat4::IDENTITY],
+                bone_bounding_box_transforms: vec![crate::transform::Transform::IDENTITY],
+            }],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        let instance_id = scene.find_node_by_name("instance").unwrap();
+        scene.animations.push(Animation {
+            name: Some("walk".into()),
+            length_seconds: 1.0,
+            speed: 1.0,
+            channels: vec![Channel {
+                node_id: scene.find_node_by_name("hips").unwrap(),
+                property: gltf::animation::Property::Rotation,
+                interpolation_type: gltf::animation::Interpolation::Linear,
+                keyframe_timings: vec![],
+                keyframe_values_u8: vec![],
+            }],
+            state: AnimationState::default(),
+        });
+        scene.animations[0].state.current_time_seconds = 0.5;
+        let gltf_scene = GltfScene {
+            pbr_meshes: vec![],
+            unlit_meshes: vec![],
+            textures: vec![],
+            nodes: scene.get_node_hierarchy_paths(),
+            skins: vec![0],
+            animations: vec![0],
+        };
+
+        let instance = scene.instantiate_merged_scene(
+            &gltf_scene,
+            &[instance_id],
+            None,
+            crate::transform::Transform::IDENTITY,
+        );
+
+        // the skipped node's children become roots of the copy
+        assert_eq!(scene.node_count(), 7);
+        assert_eq!(scene.find_nodes_by_name("instance").count(), 1);
+        let body_id = scene
+            .find_node_by_path(Some(instance.node_id), "body")
+            .unwrap();
+        let body = scene.get_node(body_id).unwrap();
+        assert_eq!(body.mesh.as_ref().unwrap().mesh_indices, vec![7]);
+        assert_eq!(body.skin_index, Some(1));
+        let hips_id = scene.find_nodes_by_name("hips").nth(1).unwrap();
+        assert_eq!(scene.skins[1].node_id, body_id);
+        assert_eq!(scene.skins[1].bone_node_ids, vec![hips_id]);
+        assert_eq!(instance.animations, vec![1]);
+        assert_eq!(scene.animations[1].channels[0].node_id, hips_id);
+        assert_eq!(scene.animations[1].state.current_time_seconds, 0.5);
+
+        scene.remove_node_recursive(instance.node_id);
+        assert_eq!(scene.node_count(), 3);
+    }
+
+    #[test]
+    fn reloading_keeps_the_ids_skins_and_animations_of_unchanged_nodes() {
+        let node =
+            |name: &str, parent_index, skin_index, mesh_index: Option<usize>| IndexedGameNodeDesc {
+                transform: crate::transform::Transform::IDENTITY,
+                skin_index,
+                mesh: mesh_index.map(GameNodeMesh::from_pbr_mesh_index),
+                name: Some(name.into()),
+                parent_index,
+            };
+        let skin = |bone_node_index| IndexedSkin {
+            bone_node_indices: vec![bone_node_index],
+            bone_inverse_bind_matrices: vec![Mat4::IDENTITY],
+            bone_bounding_box_transforms: vec![crate::transform::Transform::IDENTITY],
+        };
+        let animation = |node_id| Animation {
+            name: None,
+            length_seconds: 1.0,
+            speed: 1.0,
+            channels: vec![Channel {
+                node_id,
+                property: gltf::animation::Property::Rotation,
+                interpolation_type: gltf::animation::Interpolation::Linear,
+                keyframe_timings: vec![],
+                keyframe_values_u8: vec![],
+            }],
+            state: AnimationState::default(),
+        };
+
+        let mut scene = Scene::new(
+            vec![
+                node("root", None, None, None),
+                node("body", Some(0), Some(0), Some(1)),
+                node("hips", Some(0), None, None),
+                node("arm", Some(0), Some(1), Some(2)),
+                node("old_name", Some(0), None, None),
+                node("tail", Some(0), None, None),
+            ],
+            vec![skin(2), skin(2)],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        let old_id = |scene: &Scene, name| scene.find_node_by_name(name).unwrap();
+        let [root_id, body_id, hips_id, arm_id, old_name_id, tail_id] =
+            ["root", "body", "hips", "arm", "old_name", "tail"].map(|name| old_id(&scene, name));
+        scene.animations.push(animation(hips_id));
+        scene.animations.push(animation(old_name_id));
+        scene.animations[0].state.current_time_seconds = 0.5;
+        let old_nodes = scene.get_node_hierarchy_paths();
+
+        // the body lost its skin, so the arm's skin moves into the first slot
+        let mut new_version = Scene::new(
+            vec![
+                node("root", None, None, None),
+                node("body", Some(0), None, Some(1)),
+                node("hips", Some(0), None, None),
+                node("arm", Some(0), Some(0), Some(2)),
+                node("new_name", Some(0), None, None),
+                node("hand", Some(3), None, None),
+            ],
+            vec![skin(2)],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        for name in ["hips", "new_name", "hand"] {
+            let node_id = new_version.find_node_by_name(name).unwrap();
+            new_version.animations.push(animation(node_id));
+        }
+
+        let (nodes, skins, animations) =
+            scene.reload_scene_nodes(&old_nodes, &[0, 1], &[0, 1], new_version, |mesh| {
+                for mesh_index in &mut mesh.mesh_indices {
+                    *mesh_index += 10;
+                }
+            });
+
+        for (name, node_id) in [
+            ("root", root_id),
+            ("body", body_id),
+            ("hips", hips_id),
+            ("arm", arm_id),
+        ] {
+            assert_eq!(scene.find_node_by_name(name), Some(node_id));
+        }
+        assert_node_doesnt_exist(&scene, old_name_id);
+        assert_node_doesnt_exist(&scene, tail_id);
+        let new_name_id = scene.find_node_by_name("new_name").unwrap();
+        let hand_id = scene.find_node_by_name("hand").unwrap();
+        assert_eq!(
+            scene.get_node(new_name_id).unwrap().parent_id(),
+            Some(root_id)
+        );
+        assert_eq!(scene.get_node(hand_id).unwrap().parent_id(), Some(arm_id));
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(scene.node_count(), 6);
+
+        assert_eq!(skins, vec![0]);
+        assert_eq!(scene.get_node(body_id).unwrap().skin_index, None);
+        let arm = scene.get_node(arm_id).unwrap();
+        assert_eq!(arm.skin_index, Some(0));
+        assert_eq!(arm.mesh.as_ref().unwrap().mesh_indices, vec![12]);
+        assert_eq!(scene.skins[0].node_id, arm_id);
+        assert_eq!(scene.skins[0].bone_node_ids, vec![hips_id]);
+        assert!(scene.skins[1].bone_node_ids.is_empty());
+
+        // the emptied skin still names the arm but must not add a second, empty bone slice
+        let bone_data = crate::skinning::get_all_bone_data(&scene, 256);
+        assert_eq!(bone_data.animated_bone_transforms.len(), 1);
+        let bone_slice = &bone_data.animated_bone_transforms[0];
+        assert_eq!(bone_slice.binded_mesh_index, 12);
+        assert_eq!(
+            bone_slice.end_index - bone_slice.start_index,
+            std::mem::size_of::<Mat4>()
+        );
+
+        assert_eq!(animations, vec![0, 1, 2]);
+        assert_eq!(scene.animations[0].state.current_time_seconds, 0.5);
+        assert_eq!(scene.animations[0].channels[0].node_id, hips_id);
+        assert_eq!(scene.animations[1].channels[0].node_id, new_name_id);
+        assert_eq!(scene.animations[2].channels[0].node_id, hand_id);
+    }
+
     fn assert_node_exists(scene: &Scene, node_id: GameNodeId) {
         assert_eq!(scene.get_node(node_id).map(|node| node.id), Some(node_id));
     }
@@ -13,6 +13,15 @@ var<push_constant> BLOOM_CONFIG: BloomConfig;
 
 var<push_constant> TONE_MAPPING_CONFIG: ToneMappingConfigUniform;
 
+struct MipConfig {
+    // 0 = box, 1 = kaiser
+    filter_type: u32,
+    // 1 if the texels hold srgb colors in a linear format
+    decode_srgb: u32,
+}
+
+var<push_constant> MIP_CONFIG: MipConfig;
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(2) tex_coords: vec2<f32>,
@@ -136,6 +145,107 @@ fn bloom_blur_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(result, 1.0);
 }
 
+// mip generation:
+
+// in destination texels
+const kaiser_radius: f32 = 2.0;
+const kaiser_beta: f32 = 4.0;
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return select(
+        pow((color + 0.055) / 1.055, vec3<f32>(2.4)),
+        color / 12.92,
+        color <= vec3<f32>(0.04045)
+    );
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    return select(
+        1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055,
+        color * 12.92,
+        color <= vec3<f32>(0.0031308)
+    );
+}
+
+// modified bessel function of the first kind, the series converges quickly for small x
+fn bessel_i0(x: f32) -> f32 {
+    let quarter_x_squared = x * x / 4.0;
+    var result = 1.0;
+    var term = 1.0;
+    for (var k = 1; k < 16; k = k + 1) {
+        term = term * quarter_x_squared / f32(k * k);
+        result = result + term;
+    }
+    return result;
+}
+
+// x is the distance from the center of the destination texel in destination texels
+fn kaiser_weight(x: f32) -> f32 {
+    let t = x / kaiser_radius;
+    if abs(t) >= 1.0 {
+        return 0.0;
+    }
+    let window = bessel_i0(kaiser_beta * sqrt(1.0 - t * t)) / bessel_i0(kaiser_beta);
+    var sinc = 1.0;
+    if abs(x) > epsilon {
+        sinc = sin(pi * x) / (pi * x);
+    }
+    return sinc * window;
+}
+
+// how much of the source texel starting at texel_start is covered by the destination texel
+fn box_weight(texel_start: f32, center: f32, radius: f32) -> f32 {
+    return max(min(texel_start + 1.0, center + radius) - max(texel_start, center - radius), 0.0);
+}
+
+fn mip_filter_weight(texel_start: f32, center: f32, scale: f32) -> f32 {
+    if MIP_CONFIG.filter_type == 1u {
+        return kaiser_weight((texel_start + 0.5 - center) / scale);
+    }
+    return box_weight(texel_start, center, 0.5 * scale);
+}
+
+// texture_1 should be the previous mip, the edges are clamped
+@fragment
+fn mip_downsample_fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let src_size = vec2<i32>(textureDimensions(texture_1));
+    let dst_size = max(src_size / 2, vec2<i32>(1));
+    // source texels per destination texel
+    let scale = vec2<f32>(src_size) / vec2<f32>(dst_size);
+    // in.position is at the center of the destination texel
+    let center = in.position.xy * scale;
+
+    var radius = 0.5 * scale;
+    if MIP_CONFIG.filter_type == 1u {
+        radius = kaiser_radius * scale;
+    }
+    let start = vec2<i32>(floor(center - radius));
+    let end = vec2<i32>(ceil(center + radius));
+
+    var result = vec4<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var y = start.y; y < end.y; y = y + 1) {
+        let weight_y = mip_filter_weight(f32(y), center.y, scale.y);
+        for (var x = start.x; x < end.x; x = x + 1) {
+            let weight = weight_y * mip_filter_weight(f32(x), center.x, scale.x);
+            let coords = clamp(vec2<i32>(x, y), vec2<i32>(0), src_size - 1);
+            var texel = textureLoad(texture_1, coords, 0);
+            if MIP_CONFIG.decode_srgb == 1u {
+                texel = vec4<f32>(srgb_to_linear(texel.rgb), texel.a);
+            }
+            result = result + texel * weight;
+            weight_sum = weight_sum + weight;
+        }
+    }
+    result = result / weight_sum;
+
+    if MIP_CONFIG.decode_srgb == 1u {
+        // the kaiser filter can overshoot
+        result = vec4<f32>(linear_to_srgb(max(result.rgb, vec3<f32>(0.0))), result.a);
+    }
+    return result;
+}
+
 // BRDF LUT:
 
 const pi: f32 = 3.141592653589793;
@@ -13,6 +13,9 @@ struct Instance {
     model_transform_2: vec4<f32>,
     model_transform_3: vec4<f32>,
     color: vec4<f32>,
+    alpha_cutoff: f32,
+    padding: f32,
+    point_size: vec2<f32>,
 }
 
 struct BonesUniform {
@@ -27,6 +30,11 @@ var<storage, read> bones_uniform: BonesUniform;
 @group(1) @binding(1)
 var<storage, read> instances_uniform: InstancesUniform;
 
+@group(2) @binding(0)
+var base_color_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var base_color_sampler: sampler;
+
 struct VertexInput {
     @location(0) object_position: vec3<f32>,
     @location(1) object_normal: vec3<f32>,
@@ -42,16 +50,17 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
     @location(1) vertex_color: vec4<f32>,
+    @location(2) tex_coords: vec2<f32>,
+    @location(3) alpha_cutoff: f32,
 }
 
 struct FragmentOutput {
     @location(0) color: vec4<f32>,
 }
 
-@vertex
-fn vs_main(
+fn do_vertex_shade(
     vshader_input: VertexInput,
-    @builtin(instance_index) instance_index: u32,
+    instance_index: u32,
 ) -> VertexOutput {
     let instance = instances_uniform.value[instance_index];
 
@@ -80,6 +89,35 @@ fn vs_main(
     out.clip_position = clip_position;
     out.color = instance.color;
     out.vertex_color = vshader_input.object_color;
+    out.tex_coords = vshader_input.object_tex_coords;
+    out.alpha_cutoff = instance.alpha_cutoff;
+    return out;
+}
+
+@vertex
+fn vs_main(
+    vshader_input: VertexInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    return do_vertex_shade(vshader_input, instance_index);
+}
+
+// every point is stored as 4 copies of the same vertex, one for each corner of a screen-aligned quad
+@vertex
+fn vs_point_main(
+    vshader_input: VertexInput,
+    @builtin(instance_index) instance_index: u32,
+    @builtin(vertex_index) vertex_index: u32,
+) -> VertexOutput {
+    var out = do_vertex_shade(vshader_input, instance_index);
+
+    let point_size = instances_uniform.value[instance_index].point_size;
+    let corner = vec2<f32>(
+        f32(vertex_index & 1u),
+        f32((vertex_index >> 1u) & 1u),
+    ) * 2.0 - 1.0;
+    let clip_offset = corner * 0.5 * point_size * out.clip_position.w;
+    out.clip_position = out.clip_position + vec4<f32>(clip_offset, 0.0, 0.0);
     return out;
 }
 
@@ -87,6 +125,15 @@ fn vs_main(
 fn fs_main(in: VertexOutput) -> FragmentOutput {
     var out: FragmentOutput;
     // out.color = vec4<f32>(0.996078431372549, 0.9725490196078431, 0.6627450980392157, 1.0);
-    out.color = in.color * in.vertex_color;
+    let base_color_t = textureSample(
+        base_color_texture,
+        base_color_sampler,
+        in.tex_coords
+    );
+    let color = in.color * in.vertex_color * base_color_t;
+    if color.a <= in.alpha_cutoff {
+        discard;
+    }
+    out.color = color;
     return out;
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use crate::renderer::*;
 use crate::scene::*;
 
 use std::collections::{hash_map::Entry, HashMap};
@@ -12,11 +13,26 @@ pub struct AllBoneTransforms {
 
 #[derive(Debug)]
 pub struct AllBoneTransformsSlice {
-    pub binded_pbr_mesh_index: usize,
+    pub mesh_type: MeshType,
+    pub binded_mesh_index: usize,
     pub start_index: usize,
     pub end_index: usize,
 }
 
+impl AllBoneTransforms {
+    // offset of the mesh's bones in the buffer, or of the identity bones if it isn't skinned
+    pub fn get_start_index(&self, mesh_type: MeshType, binded_mesh_index: usize) -> u32 {
+        self.animated_bone_transforms
+            .iter()
+            .find(|bone_slice| {
+                bone_slice.mesh_type == mesh_type
+                    && bone_slice.binded_mesh_index == binded_mesh_index
+            })
+            .map(|bone_slice| bone_slice.start_index.try_into().unwrap())
+            .unwrap_or(0)
+    }
+}
+
 pub fn get_all_bone_data(
     scene: &Scene,
     min_storage_buffer_offset_alignment: u32,
@@ -35,29 +51,35 @@ pub fn get_all_bone_data(
     let mut animated_bone_transforms: Vec<AllBoneTransformsSlice> = Vec::new();
     let mut skin_index_to_slice_map: HashMap<usize, (usize, usize)> = HashMap::new();
 
-    for skin in &scene.skins {
+    for (skin_index, skin) in scene.skins.iter().enumerate() {
         if let Some(GameNodeMesh {
             mesh_indices,
-            mesh_type: GameNodeMeshType::Pbr { .. },
+            mesh_type,
             ..
         }) = &scene
             .get_node(skin.node_id)
             .and_then(|skin_node| skin_node.mesh.as_ref())
         {
-            for binded_pbr_mesh_index in mesh_indices.iter().copied() {
-                let skin_index = scene.get_node(skin.node_id).unwrap().skin_index.unwrap();
-
+            // skins that were left behind by a reloaded scene are empty and their node may have
+            // been given another skin, which gets its bones when that skin comes up
+            if skin.bone_node_ids.is_empty()
+                || scene.get_node(skin.node_id).unwrap().skin_index != Some(skin_index)
+            {
+                continue;
+            }
+            let mesh_type = MeshType::from(*mesh_type);
+            for binded_mesh_index in mesh_indices.iter().copied() {
                 match skin_index_to_slice_map.entry(skin_index) {
                     Entry::Occupied(entry) => {
                         let (start_index, end_index) = *entry.get();
                         animated_bone_transforms.push(AllBoneTransformsSlice {
-                            binded_pbr_mesh_index,
+                            mesh_type,
+                            binded_mesh_index,
                             start_index,
                             end_index,
                         });
                     }
                     Entry::Vacant(entry) => {
-                        let skin = &scene.skins[skin_index];
                         let bone_transforms: Vec<_> = skin
                             .bone_node_ids
                             .iter()
@@ -84,7 +106,8 @@ pub fn get_all_bone_data(
                         buffer.append(&mut padding);
 
                         animated_bone_transforms.push(AllBoneTransformsSlice {
-                            binded_pbr_mesh_index,
+                            mesh_type,
+                            binded_mesh_index,
                             start_index,
                             end_index,
                         });
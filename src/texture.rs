@@ -1,6 +1,7 @@
 use crate::camera::*;
 use crate::renderer::*;
 use crate::sampler_cache::*;
+use crate::vfs::*;
 
 use std::num::NonZeroU32;
 
@@ -25,6 +26,102 @@ pub struct CreateCubeMapImagesParam<'a> {
     pub neg_z: &'a image::DynamicImage,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MipFilter {
+    // averages the texels under each destination texel
+    #[default]
+    Box,
+    // windowed sinc, sharper than box but it can ring around hard edges
+    Kaiser,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MipGenerationOptions {
+    pub filter: MipFilter,
+    // filter in linear space for textures that hold srgb colors in a non-srgb format.
+    // srgb formats are always filtered in linear space
+    pub srgb: bool,
+}
+
+// the downsampling pipeline for one texture format, see BaseRenderer::get_mip_pipeline
+pub struct MipPipeline {
+    source_bind_group_layout: wgpu::BindGroupLayout,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl MipPipeline {
+    pub fn new(base_renderer: &BaseRenderer, format: wgpu::TextureFormat) -> Result<Self> {
+        let blit_shader = base_renderer
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(
+                    vfs_read_to_string("assets/shaders/blit.wgsl")?.into(),
+                ),
+            });
+
+        // texels are read with textureLoad so formats that can't be filtered work too
+        let mip_source_bind_group_layout =
+            base_renderer
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    }],
+                    label: Some("mip_source_bind_group_layout"),
+                });
+
+        let mip_pipeline_layout =
+            base_renderer
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Mip Pipeline Layout"),
+                    bind_group_layouts: &[&mip_source_bind_group_layout],
+                    push_constant_ranges: &[wgpu::PushConstantRange {
+                        stages: wgpu::ShaderStages::FRAGMENT,
+                        range: 0..8,
+                    }],
+                });
+
+        let mip_render_pipeline =
+            base_renderer
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("mip_render_pipeline"),
+                    layout: Some(&mip_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &blit_shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &blit_shader,
+                        entry_point: "mip_downsample_fs_main",
+                        targets: &[Some(format.into())],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: Default::default(),
+                    multiview: None,
+                });
+
+        Ok(Self {
+            source_bind_group_layout: mip_source_bind_group_layout,
+            render_pipeline: mip_render_pipeline,
+        })
+    }
+}
+
 // TODO: maybe implement some functions on the BaseRendererState so we have the device and queue for free?
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
@@ -108,7 +205,7 @@ impl Texture {
                 size,
             );
 
-            let mip_encoder =
+            let mut mip_encoder =
                 base_renderer
                     .device
                     .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -117,11 +214,11 @@ impl Texture {
 
             generate_mipmaps_for_texture(
                 base_renderer,
-                mip_encoder,
+                &mut mip_encoder,
                 &texture,
-                mip_level_count,
-                format,
+                MipGenerationOptions::default(),
             )?;
+            base_renderer.queue.submit(Some(mip_encoder.finish()));
 
             texture
         } else {
@@ -134,7 +231,10 @@ impl Texture {
                     sample_count: 1,
                     dimension: wgpu::TextureDimension::D2,
                     format,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    // copy src is needed to move the resident mips over when streaming more in
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::COPY_DST
+                        | wgpu::TextureUsages::COPY_SRC,
                     view_formats: &[],
                 },
                 img_bytes,
@@ -156,6 +256,18 @@ impl Texture {
         })
     }
 
+    // records the passes that fill in every mip after the first one from the first one, for each
+    // layer of 2d, array and cube textures. the texture needs the TEXTURE_BINDING and
+    // RENDER_ATTACHMENT usages
+    pub fn generate_mipmaps(
+        &self,
+        base_renderer: &BaseRenderer,
+        encoder: &mut wgpu::CommandEncoder,
+        options: MipGenerationOptions,
+    ) -> Result<()> {
+        generate_mipmaps_for_texture(base_renderer, encoder, &self.texture, options)
+    }
+
     pub fn _from_color_srgb(base_renderer: &BaseRenderer, color: [u8; 4]) -> Result<Self> {
         let one_pixel_image = {
             let mut img = image::RgbaImage::new(1, 1);
@@ -230,6 +342,31 @@ impl Texture {
         base_renderer: &BaseRenderer,
         render_scale: f32,
         label: &str,
+    ) -> Self {
+        Self::create_scaled_surface_texture_impl(base_renderer, render_scale, 1, label)
+    }
+
+    // has enough mips to be sampled down to the surface size without aliasing when supersampling.
+    // the mips must be regenerated after rendering to the first one, see Texture::generate_mipmaps
+    pub fn create_scaled_surface_texture_with_mips(
+        base_renderer: &BaseRenderer,
+        render_scale: f32,
+        label: &str,
+    ) -> Self {
+        let mip_level_count = render_scale.sqrt().log2().ceil().max(0.0) as u32 + 1;
+        Self::create_scaled_surface_texture_impl(
+            base_renderer,
+            render_scale,
+            mip_level_count,
+            label,
+        )
+    }
+
+    fn create_scaled_surface_texture_impl(
+        base_renderer: &BaseRenderer,
+        render_scale: f32,
+        mip_level_count: u32,
+        label: &str,
     ) -> Self {
         let size = {
             let surface_config_guard = base_renderer.surface_config.lock().unwrap();
@@ -244,7 +381,7 @@ impl Texture {
             .create_texture(&wgpu::TextureDescriptor {
                 label: Some(label),
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Rgba16Float,
@@ -267,7 +404,7 @@ impl Texture {
                     address_mode_w: wgpu::AddressMode::ClampToEdge,
                     mag_filter: wgpu::FilterMode::Linear,
                     min_filter: wgpu::FilterMode::Linear,
-                    mipmap_filter: wgpu::FilterMode::Nearest,
+                    mipmap_filter: wgpu::FilterMode::Linear,
                     ..Default::default()
                 },
             );
@@ -455,7 +592,7 @@ impl Texture {
         er_to_cubemap_pipeline: &wgpu::RenderPipeline,
         er_texture: &Texture,
         generate_mipmaps: bool,
-    ) -> Self {
+    ) -> Result<Self> {
         let size = wgpu::Extent3d {
             width: er_texture.size.width / 3,
             height: er_texture.size.width / 3,
@@ -601,7 +738,19 @@ impl Texture {
         }
 
         if generate_mipmaps {
-            todo!("Call generate_mipmaps_for_texture for each side of the cubemap");
+            let mut mip_encoder =
+                base_renderer
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("mip_encoder"),
+                    });
+            generate_mipmaps_for_texture(
+                base_renderer,
+                &mut mip_encoder,
+                &cubemap_texture,
+                MipGenerationOptions::default(),
+            )?;
+            base_renderer.queue.submit(Some(mip_encoder.finish()));
         }
 
         let view = cubemap_texture.create_view(&wgpu::TextureViewDescriptor {
@@ -626,12 +775,12 @@ impl Texture {
                 },
             );
 
-        Self {
+        Ok(Self {
             texture: cubemap_texture,
             view,
             sampler_index,
             size,
-        }
+        })
     }
 
     /// Each image should have the same dimensions!
@@ -641,7 +790,7 @@ impl Texture {
         label: Option<&str>,
         format: wgpu::TextureFormat,
         generate_mipmaps: bool,
-    ) -> Self {
+    ) -> Result<Self> {
         // order of the images for a cubemap is documented here:
         // https://www.khronos.org/opengl/wiki/Cubemap_Texture
         let images_as_rgba = vec![
@@ -669,27 +818,57 @@ impl Texture {
             1
         };
 
-        let texture = base_renderer.device.create_texture_with_data(
-            &base_renderer.queue,
-            &wgpu::TextureDescriptor {
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if generate_mipmaps {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = base_renderer
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
                 mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                usage,
                 view_formats: &[],
+            });
+        // only the first mip of each face is written, the rest are generated below
+        base_renderer.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
             },
             // pack images into one big byte array
             &images_as_rgba
                 .iter()
                 .flat_map(|image| image.to_vec())
                 .collect::<Vec<_>>(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(format.describe().block_size as u32 * dimensions.0),
+                rows_per_image: NonZeroU32::new(dimensions.1),
+            },
+            size,
         );
 
         if generate_mipmaps {
-            todo!("Call generate_mipmaps_for_texture for each side of the cubemap");
+            let mut mip_encoder =
+                base_renderer
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("mip_encoder"),
+                    });
+            generate_mipmaps_for_texture(
+                base_renderer,
+                &mut mip_encoder,
+                &texture,
+                MipGenerationOptions::default(),
+            )?;
+            base_renderer.queue.submit(Some(mip_encoder.finish()));
         }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
@@ -714,12 +893,12 @@ impl Texture {
                 },
             );
 
-        Self {
+        Ok(Self {
             texture,
             view,
             sampler_index,
             size,
-        }
+        })
     }
 
     pub fn create_diffuse_env_map(
@@ -729,7 +908,7 @@ impl Texture {
         env_map_gen_pipeline: &wgpu::RenderPipeline,
         skybox_rad_texture: &Texture,
         generate_mipmaps: bool,
-    ) -> Self {
+    ) -> Result<Self> {
         let size = wgpu::Extent3d {
             width: 128,
             height: 128,
@@ -867,7 +1046,19 @@ impl Texture {
         }
 
         if generate_mipmaps {
-            todo!("Call generate_mipmaps_for_texture for each side of the cubemap");
+            let mut mip_encoder =
+                base_renderer
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("mip_encoder"),
+                    });
+            generate_mipmaps_for_texture(
+                base_renderer,
+                &mut mip_encoder,
+                &env_map,
+                MipGenerationOptions::default(),
+            )?;
+            base_renderer.queue.submit(Some(mip_encoder.finish()));
         }
 
         let view = env_map.create_view(&wgpu::TextureViewDescriptor {
@@ -892,12 +1083,12 @@ impl Texture {
                 },
             );
 
-        Self {
+        Ok(Self {
             texture: env_map,
             view,
             sampler_index,
             size,
-        }
+        })
     }
 
     pub fn create_specular_env_map(
@@ -1206,151 +1397,114 @@ impl Texture {
     }
 }
 
+// the tests don't have a gpu, so cube and array layers are checked by hand after changing this:
+// pass true for generate_mipmaps to Texture::create_cubemap in Renderer::new and swap in the
+// commented out textureSampleLevel line in skybox.wgsl with a level of 4.0. each face should be a
+// blurred copy of its own image, not of the first face. array layers use the same per layer views
 fn generate_mipmaps_for_texture(
     base_renderer: &BaseRenderer,
-    mut mip_encoder: wgpu::CommandEncoder,
+    mip_encoder: &mut wgpu::CommandEncoder,
     texture: &wgpu::Texture,
-    mip_level_count: u32,
-    format: wgpu::TextureFormat,
+    options: MipGenerationOptions,
 ) -> Result<()> {
-    let blit_shader = base_renderer
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(
-                std::fs::read_to_string("./src/shaders/blit.wgsl")?.into(),
-            ),
-        });
-
-    let single_texture_bind_group_layout =
-        base_renderer
-            .device
-            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("single_texture_bind_group_layout"),
-            });
-
-    let mip_pipeline_layout =
-        base_renderer
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Mesh Pipeline Layout"),
-                bind_group_layouts: &[&single_texture_bind_group_layout],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::VERTEX,
-                    range: 0..std::mem::size_of::<MeshShaderCameraRaw>() as u32,
-                }],
-            });
-
-    let mip_render_pipeline =
-        base_renderer
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("mip_render_pipeline"),
-                layout: Some(&mip_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &blit_shader,
-                    entry_point: "vs_main",
-                    buffers: &[],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &blit_shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(format.into())],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    ..Default::default()
-                },
-                depth_stencil: None,
-                multisample: Default::default(),
-                multiview: None,
-            });
+    let format = texture.format();
+    if texture.dimension() != wgpu::TextureDimension::D2 {
+        bail!(
+            "Mip generation is only supported for 2d textures, got {:?}",
+            texture.dimension()
+        );
+    }
+    if !matches!(
+        format.describe().sample_type,
+        wgpu::TextureSampleType::Float { .. }
+    ) {
+        bail!(
+            "Mip generation needs a float texture format, got {:?}",
+            format
+        );
+    }
+    let required_usages =
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT;
+    if !texture.usage().contains(required_usages) {
+        bail!(
+            "Mip generation needs the texture usages {:?}, got {:?}",
+            required_usages,
+            texture.usage()
+        );
+    }
+    if !base_renderer
+        .adapter
+        .get_texture_format_features(format)
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+    {
+        bail!("Texture format {:?} can't be rendered to", format);
+    }
 
-    let mip_texure_views = (0..mip_level_count)
-        .map(|mip| {
-            texture.create_view(&wgpu::TextureViewDescriptor {
-                label: Some("mip"),
-                format: None,
-                dimension: None,
-                aspect: wgpu::TextureAspect::All,
-                base_mip_level: mip,
-                mip_level_count: NonZeroU32::new(1),
-                base_array_layer: 0,
-                array_layer_count: None,
+    let mip_pipeline = base_renderer.get_mip_pipeline(format)?;
+
+    // srgb formats are already decoded when they're read and encoded when they're written
+    let decode_srgb = options.srgb && !format.describe().srgb;
+    let mip_config = [
+        match options.filter {
+            MipFilter::Box => 0u32,
+            MipFilter::Kaiser => 1u32,
+        },
+        decode_srgb as u32,
+    ];
+
+    let mip_level_count = texture.mip_level_count();
+    for layer in 0..texture.size().depth_or_array_layers {
+        let mip_texure_views = (0..mip_level_count)
+            .map(|mip| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mip"),
+                    format: None,
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    aspect: wgpu::TextureAspect::All,
+                    base_mip_level: mip,
+                    mip_level_count: NonZeroU32::new(1),
+                    base_array_layer: layer,
+                    array_layer_count: NonZeroU32::new(1),
+                })
             })
-        })
-        .collect::<Vec<_>>();
+            .collect::<Vec<_>>();
 
-    for target_mip in 1..mip_level_count as usize {
-        let bind_group;
-        {
-            let mut sampler_cache_guard = base_renderer.sampler_cache.lock().unwrap();
-            let mip_sampler = sampler_cache_guard.get_sampler(
-                &base_renderer.device,
-                &SamplerDescriptor {
-                    address_mode_u: wgpu::AddressMode::ClampToEdge,
-                    address_mode_v: wgpu::AddressMode::ClampToEdge,
-                    address_mode_w: wgpu::AddressMode::ClampToEdge,
-                    mag_filter: wgpu::FilterMode::Linear,
-                    min_filter: wgpu::FilterMode::Linear,
-                    mipmap_filter: wgpu::FilterMode::Nearest,
-                    ..Default::default()
-                },
-            );
-            bind_group = base_renderer
+        for target_mip in 1..mip_level_count as usize {
+            let bind_group = base_renderer
                 .device
                 .create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &single_texture_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(
-                                &mip_texure_views[target_mip - 1],
-                            ),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Sampler(mip_sampler),
-                        },
-                    ],
+                    layout: &mip_pipeline.source_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(
+                            &mip_texure_views[target_mip - 1],
+                        ),
+                    }],
                     label: None,
                 });
-        }
 
-        let mut rpass = mip_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &mip_texure_views[target_mip],
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                    store: true,
-                },
-            })],
-            depth_stencil_attachment: None,
-        });
-        rpass.set_pipeline(&mip_render_pipeline);
-        rpass.set_bind_group(0, &bind_group, &[]);
-        rpass.draw(0..3, 0..1);
+            let mut rpass = mip_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &mip_texure_views[target_mip],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&mip_pipeline.render_pipeline);
+            rpass.set_push_constants(
+                wgpu::ShaderStages::FRAGMENT,
+                0,
+                bytemuck::cast_slice(&mip_config),
+            );
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
     }
-    base_renderer.queue.submit(Some(mip_encoder.finish()));
     Ok(())
 }
@@ -1,7 +1,16 @@
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
+use crate::ktx2::{
+    get_level_block_counts, get_level_dimensions, is_ktx2, UastcChannels, UastcKtx2,
+};
+use crate::vfs::*;
+
 const BASISU_COMPRESSION_FORMAT: basis_universal::BasisTextureFormat =
     basis_universal::BasisTextureFormat::UASTC4x4;
+// level 3 takes longer to compress but is higher quality
+pub const DEFAULT_UASTC_QUALITY_LEVEL: u32 = 3;
+pub const MAX_UASTC_QUALITY_LEVEL: u32 = 4;
 
 pub struct TextureCompressor(());
 
@@ -13,13 +22,139 @@ pub struct TextureCompressionArgs<'a> {
     pub is_normal_map: bool,
     pub is_srgb: bool,
     pub thread_count: u32,
+    // 0 is the fastest, MAX_UASTC_QUALITY_LEVEL is the slowest and highest quality
+    pub quality_level: u32,
+}
+
+// gpu texture compression families that basisu can transcode to, in order of preference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodeTarget {
+    Bc,
+    Astc,
+    Etc2,
+    // decoded on the cpu for adapters that don't support any of the compressed formats
+    Uncompressed,
+}
+
+impl TranscodeTarget {
+    pub fn from_features(features: wgpu::Features) -> Self {
+        if features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC) {
+            Self::Bc
+        } else if features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR) {
+            Self::Astc
+        } else if features.contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2) {
+            Self::Etc2
+        } else {
+            Self::Uncompressed
+        }
+    }
+}
+
+// the pixel format of the transcoded mip chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodedFormat {
+    Bc7Rgba,
+    Bc5Rg,
+    Astc4x4Rgba,
+    Etc2Rgba,
+    EacRg11,
+    Rgba8,
+    Rg8,
+}
+
+impl TranscodedFormat {
+    pub fn new(target: TranscodeTarget, is_normal_map: bool) -> Self {
+        // normal maps are stored as (x, x, x, y) so they need a two channel format that
+        // reads from red and alpha. astc has none so those fall back to rg8
+        match (target, is_normal_map) {
+            (TranscodeTarget::Bc, false) => Self::Bc7Rgba,
+            (TranscodeTarget::Bc, true) => Self::Bc5Rg,
+            (TranscodeTarget::Astc, false) => Self::Astc4x4Rgba,
+            (TranscodeTarget::Etc2, false) => Self::Etc2Rgba,
+            (TranscodeTarget::Etc2, true) => Self::EacRg11,
+            (TranscodeTarget::Uncompressed, false) => Self::Rgba8,
+            (TranscodeTarget::Astc | TranscodeTarget::Uncompressed, true) => Self::Rg8,
+        }
+    }
+
+    fn get_transcoder_format(self) -> basis_universal::transcoding::TranscoderTextureFormat {
+        use basis_universal::transcoding::TranscoderTextureFormat;
+        match self {
+            Self::Bc7Rgba => TranscoderTextureFormat::BC7_RGBA,
+            Self::Bc5Rg => TranscoderTextureFormat::BC5_RG,
+            Self::Astc4x4Rgba => TranscoderTextureFormat::ASTC_4x4_RGBA,
+            Self::Etc2Rgba => TranscoderTextureFormat::ETC2_RGBA,
+            Self::EacRg11 => TranscoderTextureFormat::ETC2_EAC_RG11,
+            // rg8 is repacked from rgba8 after transcoding
+            Self::Rgba8 | Self::Rg8 => TranscoderTextureFormat::RGBA32,
+        }
+    }
+
+    fn get_transcoder_block_format(self) -> basis_universal::transcoding::TranscoderBlockFormat {
+        use basis_universal::transcoding::TranscoderBlockFormat;
+        match self {
+            Self::Bc7Rgba => TranscoderBlockFormat::BC7,
+            Self::Bc5Rg => TranscoderBlockFormat::BC5,
+            Self::Astc4x4Rgba => TranscoderBlockFormat::ASTC_4x4,
+            Self::Etc2Rgba => TranscoderBlockFormat::ETC2_RGBA,
+            Self::EacRg11 => TranscoderBlockFormat::ETC2_EAC_RG11,
+            Self::Rgba8 | Self::Rg8 => TranscoderBlockFormat::RGBA32,
+        }
+    }
+
+    // the two channel formats have no srgb variant, but they're only used for normal maps
+    pub fn get_wgpu_format(self, is_srgb: bool) -> wgpu::TextureFormat {
+        match (self, is_srgb) {
+            (Self::Bc7Rgba, false) => wgpu::TextureFormat::Bc7RgbaUnorm,
+            (Self::Bc7Rgba, true) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            (Self::Bc5Rg, _) => wgpu::TextureFormat::Bc5RgUnorm,
+            (Self::Astc4x4Rgba, is_srgb) => wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B4x4,
+                channel: if is_srgb {
+                    wgpu::AstcChannel::UnormSrgb
+                } else {
+                    wgpu::AstcChannel::Unorm
+                },
+            },
+            (Self::Etc2Rgba, false) => wgpu::TextureFormat::Etc2Rgba8Unorm,
+            (Self::Etc2Rgba, true) => wgpu::TextureFormat::Etc2Rgba8UnormSrgb,
+            (Self::EacRg11, _) => wgpu::TextureFormat::EacRg11Unorm,
+            (Self::Rgba8, false) => wgpu::TextureFormat::Rgba8Unorm,
+            (Self::Rgba8, true) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            (Self::Rg8, _) => wgpu::TextureFormat::Rg8Unorm,
+        }
+    }
 }
 
 pub struct CompressedTexture {
-    pub format: basis_universal::transcoding::TranscoderTextureFormat,
+    pub format: TranscodedFormat,
+    // size of the largest mip level in raw
     pub width: u32,
     pub height: u32,
     pub raw: Vec<u8>,
+    // number of mip levels in raw
+    pub mip_count: u32,
+}
+
+// a compressed image whose container was only parsed once, so that its levels can be transcoded
+// again later, e.g. by the texture streamer, without decoding the whole file each time
+#[derive(Debug, Clone)]
+pub struct ParsedCompressedImage {
+    pub info: CompressedTextureInfo,
+    container: ParsedContainer,
+}
+
+#[derive(Debug, Clone)]
+enum ParsedContainer {
+    Ktx2(UastcKtx2),
+    // the zstd decoded .basis file
+    Basis(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedTextureInfo {
+    pub width: u32,
+    pub height: u32,
     pub mip_count: u32,
 }
 
@@ -47,11 +182,12 @@ impl TextureCompressor {
             is_normal_map,
             is_srgb,
             thread_count,
+            quality_level,
         } = args;
 
         let mut params = basis_universal::CompressorParams::new();
         params.set_basis_format(BASISU_COMPRESSION_FORMAT);
-        params.set_uastc_quality_level(3); // level 3 takes longer to compress but is higher quality
+        params.set_uastc_quality_level(quality_level.min(MAX_UASTC_QUALITY_LEVEL));
         params.set_rdo_uastc(Some(1.0)); // default
         params.set_generate_mipmaps(true);
         params.set_mipmap_smallest_dimension(1); // default
@@ -87,93 +223,392 @@ impl TextureCompressor {
             anyhow::bail!("Error compressing img to basisu {:?}", error_code);
         }
 
-        // 0 = default compression level
-        let zstd_encoded_data = zstd::stream::encode_all(basisu_compressor.basis_file(), 0)?;
+        let (levels, has_alpha) = get_uastc_levels_from_basis_file(basisu_compressor.basis_file())?;
+
+        let ktx2 = UastcKtx2 {
+            width: img_width,
+            height: img_height,
+            is_srgb,
+            channels: if is_normal_map {
+                UastcChannels::Rrrg
+            } else if has_alpha {
+                UastcChannels::Rgba
+            } else {
+                UastcChannels::Rgb
+            },
+            levels,
+        };
 
-        Ok(zstd_encoded_data)
+        // 0 = default compression level
+        ktx2.to_bytes(Some(0))
     }
 
+    // accepts ktx2 files with uastc payloads as well as the older zstd compressed .basis files
     pub fn transcode_image(
         &self,
         img_bytes: &[u8],
         is_normal_map: bool,
+        target: TranscodeTarget,
     ) -> anyhow::Result<CompressedTexture> {
-        basis_universal::transcoder_init();
-
-        let zstd_decoded_data = zstd::stream::decode_all(img_bytes)?;
+        self.transcode_image_levels(
+            &self.parse_image(img_bytes)?,
+            is_normal_map,
+            target,
+            0..u32::MAX,
+        )
+    }
 
-        let mut basisu_transcoder = basis_universal::Transcoder::new();
+    pub fn parse_image(&self, img_bytes: &[u8]) -> anyhow::Result<ParsedCompressedImage> {
+        basis_universal::transcoder_init();
 
-        if !basisu_transcoder.validate_header(&zstd_decoded_data) {
-            anyhow::bail!("Image data failed basisu validation");
+        if is_ktx2(img_bytes) {
+            let ktx2 = UastcKtx2::from_bytes(img_bytes)?;
+            return Ok(ParsedCompressedImage {
+                info: CompressedTextureInfo {
+                    width: ktx2.width,
+                    height: ktx2.height,
+                    mip_count: ktx2.levels.len() as u32,
+                },
+                container: ParsedContainer::Ktx2(ktx2),
+            });
         }
 
-        if let Err(prep_err) = basisu_transcoder.prepare_transcoding(&zstd_decoded_data) {
-            anyhow::bail!("Error calling prepare_transcoding: {:?}", prep_err);
-        }
+        let zstd_decoded_data = zstd::stream::decode_all(img_bytes)?;
+        let (_, info) = prepare_basis_transcoder(&zstd_decoded_data)?;
+        Ok(ParsedCompressedImage {
+            info,
+            container: ParsedContainer::Basis(zstd_decoded_data),
+        })
+    }
 
-        let mip_levels = basisu_transcoder.image_level_count(&zstd_decoded_data, 0);
+    // levels is clamped to the mip levels of the image, e.g. 2..u32::MAX skips the two largest
+    pub fn transcode_image_levels(
+        &self,
+        image: &ParsedCompressedImage,
+        is_normal_map: bool,
+        target: TranscodeTarget,
+        levels: Range<u32>,
+    ) -> anyhow::Result<CompressedTexture> {
+        basis_universal::transcoder_init();
 
-        let basis_universal::ImageLevelDescription {
-            original_width: img_width,
-            original_height: img_height,
-            ..
-        } = basisu_transcoder
-            .image_level_description(&zstd_decoded_data, 0, 0)
-            .unwrap();
+        let info = image.info;
+        let format = TranscodedFormat::new(target, is_normal_map);
+        let level_range = levels.start..levels.end.min(info.mip_count);
+        if level_range.is_empty() {
+            anyhow::bail!(
+                "Mip levels {:?} are out of range, the image has {:?}",
+                levels,
+                info.mip_count
+            );
+        }
 
-        let gpu_texture_format = if is_normal_map {
-            basis_universal::transcoding::TranscoderTextureFormat::BC5_RG
-        } else {
-            basis_universal::transcoding::TranscoderTextureFormat::BC7_RGBA
+        let mut mip_chain_bytes = Vec::new();
+        let mut add_level = |mip_level: Vec<u8>| {
+            if format == TranscodedFormat::Rg8 {
+                mip_chain_bytes.extend(rgba8_normal_map_to_rg8(&mip_level));
+            } else {
+                mip_chain_bytes.extend(mip_level);
+            }
         };
 
-        // full mip chain uses 33% more memory
-        // https://en.wikipedia.org/wiki/1/4_%2B_1/16_%2B_1/64_%2B_1/256_%2B_%E2%8B%AF
-        let mut full_mip_chain_bytes =
-            Vec::with_capacity((img_width as f32 * img_height as f32 * 1.34).ceil() as usize);
-        for mip_level in 0..mip_levels {
-            match basisu_transcoder.transcode_image_level(
-                &zstd_decoded_data,
-                gpu_texture_format,
-                basis_universal::transcoding::TranscodeParameters {
-                    image_index: 0,
-                    level_index: mip_level,
-                    decode_flags: Some(basis_universal::transcoding::DecodeFlags::HIGH_QUALITY),
-                    output_row_pitch_in_blocks_or_pixels: None,
-                    output_rows_in_pixels: None,
-                },
-            ) {
-                Ok(mip_level) => {
-                    full_mip_chain_bytes.extend(mip_level);
+        match &image.container {
+            ParsedContainer::Ktx2(ktx2) => {
+                let transcoder = basis_universal::transcoding::LowLevelUastcTranscoder::new();
+                for level_index in level_range.clone() {
+                    let (num_blocks_x, num_blocks_y) =
+                        get_level_block_counts(ktx2.width, ktx2.height, level_index as usize);
+                    let (original_width, original_height) =
+                        get_level_dimensions(ktx2.width, ktx2.height, level_index as usize);
+                    let mip_level = transcoder
+                        .transcode_slice(
+                            &ktx2.levels[level_index as usize],
+                            basis_universal::transcoding::SliceParametersUastc {
+                                num_blocks_x,
+                                num_blocks_y,
+                                has_alpha: ktx2.channels.has_alpha(),
+                                original_width,
+                                original_height,
+                            },
+                            basis_universal::transcoding::DecodeFlags::HIGH_QUALITY,
+                            format.get_transcoder_block_format(),
+                        )
+                        .map_err(|transcode_error| {
+                            anyhow::anyhow!("Error transcoding ktx2 level {:?}", transcode_error)
+                        })?;
+                    add_level(mip_level);
                 }
-                Err(transcode_error) => {
-                    anyhow::bail!("Error transcoding img from basisu {:?}", transcode_error);
+            }
+            ParsedContainer::Basis(zstd_decoded_data) => {
+                let (mut basisu_transcoder, _) = prepare_basis_transcoder(zstd_decoded_data)?;
+                for level_index in level_range.clone() {
+                    match basisu_transcoder.transcode_image_level(
+                        zstd_decoded_data,
+                        format.get_transcoder_format(),
+                        basis_universal::transcoding::TranscodeParameters {
+                            image_index: 0,
+                            level_index,
+                            decode_flags: Some(
+                                basis_universal::transcoding::DecodeFlags::HIGH_QUALITY,
+                            ),
+                            output_row_pitch_in_blocks_or_pixels: None,
+                            output_rows_in_pixels: None,
+                        },
+                    ) {
+                        Ok(mip_level) => add_level(mip_level),
+                        Err(transcode_error) => {
+                            anyhow::bail!(
+                                "Error transcoding img from basisu {:?}",
+                                transcode_error
+                            );
+                        }
+                    };
                 }
-            };
+            }
         }
 
+        let (width, height) =
+            get_level_dimensions(info.width, info.height, level_range.start as usize);
         Ok(CompressedTexture {
-            format: gpu_texture_format,
-            width: img_width,
-            height: img_height,
-            raw: full_mip_chain_bytes,
-            mip_count: mip_levels,
+            format,
+            width,
+            height,
+            raw: mip_chain_bytes,
+            mip_count: level_range.len() as u32,
         })
     }
 }
 
+// returns a transcoder that's ready to use on the zstd decoded .basis file
+fn prepare_basis_transcoder(
+    zstd_decoded_data: &[u8],
+) -> anyhow::Result<(basis_universal::Transcoder, CompressedTextureInfo)> {
+    let mut basisu_transcoder = basis_universal::Transcoder::new();
+
+    if !basisu_transcoder.validate_header(zstd_decoded_data) {
+        anyhow::bail!("Image data failed basisu validation");
+    }
+
+    if let Err(prep_err) = basisu_transcoder.prepare_transcoding(zstd_decoded_data) {
+        anyhow::bail!("Error calling prepare_transcoding: {:?}", prep_err);
+    }
+
+    let mip_count = basisu_transcoder.image_level_count(zstd_decoded_data, 0);
+
+    let basis_universal::ImageLevelDescription {
+        original_width: width,
+        original_height: height,
+        ..
+    } = basisu_transcoder
+        .image_level_description(zstd_decoded_data, 0, 0)
+        .ok_or_else(|| anyhow::anyhow!("Basis file has no mip levels"))?;
+
+    Ok((
+        basisu_transcoder,
+        CompressedTextureInfo {
+            width,
+            height,
+            mip_count,
+        },
+    ))
+}
+
 impl Default for TextureCompressor {
     fn default() -> Self {
         Self::new()
     }
 }
 
+// the uastc blocks of each mip level are stored as-is in the slices of the .basis file
+// see basis_file_header and basis_slice_desc in basisu_file_headers.h
+fn get_uastc_levels_from_basis_file(basis_file: &[u8]) -> anyhow::Result<(Vec<Vec<u8>>, bool)> {
+    const HEADER_SIZE: usize = 77;
+    const SLICE_DESC_SIZE: usize = 23;
+    const TEX_FORMAT_UASTC_4X4: usize = 1;
+    const HEADER_FLAG_HAS_ALPHA_SLICES: usize = 4;
+
+    let read = |offset: usize, size: usize| -> anyhow::Result<usize> {
+        let bytes = basis_file
+            .get(offset..offset + size)
+            .ok_or_else(|| anyhow::anyhow!("Basis file is truncated"))?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, byte| (acc << 8) | *byte as usize))
+    };
+
+    if !basis_file.starts_with(b"sB") || read(4, 2)? != HEADER_SIZE {
+        anyhow::bail!("Invalid basis file header");
+    }
+    let slice_count = read(14, 3)?;
+    let image_count = read(17, 3)?;
+    if read(20, 1)? != TEX_FORMAT_UASTC_4X4 || image_count != 1 {
+        anyhow::bail!("Expected a basis file with a single UASTC image");
+    }
+    let has_alpha = read(21, 2)? & HEADER_FLAG_HAS_ALPHA_SLICES != 0;
+    let slice_descs_offset = read(65, 4)?;
+
+    let mut levels = Vec::with_capacity(slice_count);
+    for slice_index in 0..slice_count {
+        let slice_desc_offset = slice_descs_offset + slice_index * SLICE_DESC_SIZE;
+        let level_index = read(slice_desc_offset + 3, 1)?;
+        let file_offset = read(slice_desc_offset + 13, 4)?;
+        let file_size = read(slice_desc_offset + 17, 4)?;
+        if level_index != levels.len() {
+            anyhow::bail!("Basis file slices are out of order");
+        }
+        levels.push(
+            basis_file
+                .get(file_offset..file_offset + file_size)
+                .ok_or_else(|| anyhow::anyhow!("Basis file slice is out of bounds"))?
+                .to_vec(),
+        );
+    }
+    Ok((levels, has_alpha))
+}
+
+// keeps red and alpha, which is where the compressor puts the normal map's x and y
+fn rgba8_normal_map_to_rg8(rgba8_bytes: &[u8]) -> Vec<u8> {
+    rgba8_bytes
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[0], pixel[3]])
+        .collect()
+}
+
 pub fn texture_path_to_compressed_path(path: &Path) -> PathBuf {
     let mut out_path = path.to_path_buf();
     out_path.set_file_name(format!(
-        "{:}_compressed.bin",
+        "{:}_compressed.ktx2",
         out_path.file_stem().unwrap().to_str().unwrap()
     ));
     out_path
 }
+
+// for images stored in a buffer view of the gltf file instead of a separate file
+pub fn embedded_texture_path_to_compressed_path(gltf_path: &Path, image_index: usize) -> PathBuf {
+    let mut out_path = gltf_path.to_path_buf();
+    out_path.set_file_name(format!(
+        "{:}_image_{:}_compressed.ktx2",
+        out_path.file_stem().unwrap().to_str().unwrap(),
+        image_index
+    ));
+    out_path
+}
+
+// textures compressed before the switch to ktx2 are zstd compressed .basis files
+pub fn find_compressed_texture_path(compressed_path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let legacy_path = compressed_path.with_extension("bin");
+    if vfs_exists(compressed_path)? {
+        Ok(Some(compressed_path.to_path_buf()))
+    } else if vfs_exists(&legacy_path)? {
+        Ok(Some(legacy_path))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcode_target_fallbacks() {
+        let bc = wgpu::Features::TEXTURE_COMPRESSION_BC;
+        let astc = wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR;
+        let etc2 = wgpu::Features::TEXTURE_COMPRESSION_ETC2;
+
+        assert_eq!(
+            TranscodeTarget::from_features(bc | astc | etc2),
+            TranscodeTarget::Bc
+        );
+        assert_eq!(
+            TranscodeTarget::from_features(astc | etc2),
+            TranscodeTarget::Astc
+        );
+        assert_eq!(TranscodeTarget::from_features(etc2), TranscodeTarget::Etc2);
+        assert_eq!(
+            TranscodeTarget::from_features(wgpu::Features::empty()),
+            TranscodeTarget::Uncompressed
+        );
+
+        let get_formats = |target: TranscodeTarget| {
+            (
+                TranscodedFormat::new(target, false).get_wgpu_format(true),
+                TranscodedFormat::new(target, true).get_wgpu_format(false),
+            )
+        };
+        assert_eq!(
+            get_formats(TranscodeTarget::Bc),
+            (
+                wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+                wgpu::TextureFormat::Bc5RgUnorm
+            )
+        );
+        assert_eq!(
+            get_formats(TranscodeTarget::Astc),
+            (
+                wgpu::TextureFormat::Astc {
+                    block: wgpu::AstcBlock::B4x4,
+                    channel: wgpu::AstcChannel::UnormSrgb,
+                },
+                wgpu::TextureFormat::Rg8Unorm
+            )
+        );
+        assert_eq!(
+            get_formats(TranscodeTarget::Etc2),
+            (
+                wgpu::TextureFormat::Etc2Rgba8UnormSrgb,
+                wgpu::TextureFormat::EacRg11Unorm
+            )
+        );
+        assert_eq!(
+            get_formats(TranscodeTarget::Uncompressed),
+            (
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                wgpu::TextureFormat::Rg8Unorm
+            )
+        );
+
+        // every format the target picks must be usable with the features that picked it
+        for features in [bc, astc, etc2, wgpu::Features::empty()] {
+            let target = TranscodeTarget::from_features(features);
+            for is_normal_map in [false, true] {
+                for is_srgb in [false, true] {
+                    let format =
+                        TranscodedFormat::new(target, is_normal_map).get_wgpu_format(is_srgb);
+                    assert!(features.contains(format.describe().required_features));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn basis_file_levels_convert_to_ktx2() {
+        let basis_file = zstd::stream::decode_all(
+            &std::fs::read("./src/textures/rainbow_gradient_vertical_compressed.bin").unwrap()[..],
+        )
+        .unwrap();
+        let (levels, _) = get_uastc_levels_from_basis_file(&basis_file).unwrap();
+        assert_eq!(levels.len(), 10);
+
+        let ktx2 = UastcKtx2 {
+            width: 640,
+            height: 480,
+            is_srgb: true,
+            channels: UastcChannels::Rgb,
+            levels,
+        };
+        let ktx2_bytes = ktx2.to_bytes(Some(0)).unwrap();
+        assert_eq!(UastcKtx2::from_bytes(&ktx2_bytes).unwrap(), ktx2);
+
+        assert!(get_uastc_levels_from_basis_file(&basis_file[..100]).is_err());
+        assert!(get_uastc_levels_from_basis_file(&ktx2_bytes).is_err());
+    }
+
+    #[test]
+    fn normal_map_rg8_repack() {
+        assert_eq!(
+            rgba8_normal_map_to_rg8(&[10, 10, 10, 20, 30, 30, 30, 40]),
+            vec![10, 20, 30, 40]
+        );
+    }
+}
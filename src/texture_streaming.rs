@@ -0,0 +1,691 @@
+use crate::logger::*;
+use crate::math::*;
+use crate::renderer::*;
+use crate::texture::*;
+use crate::texture_compression::*;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::ops::Range;
+use std::sync::{mpsc, Arc};
+
+// mips up to this size are uploaded when the texture is loaded, the larger ones are streamed in
+// once the texture is seen up close
+pub const INITIAL_RESIDENT_MIP_SIZE: u32 = 128;
+pub const DEFAULT_TEXTURE_VRAM_BUDGET_BYTES: u64 = 512 * 1024 * 1024;
+
+// where the mips that aren't resident come from
+#[derive(Debug, Clone)]
+pub enum StreamedTextureSource {
+    Compressed {
+        image: Arc<ParsedCompressedImage>,
+        is_normal_map: bool,
+        target: TranscodeTarget,
+    },
+    // mip 0 of a png or jpg, the other mips are downsampled from it
+    Rgba8 {
+        pixels: Arc<Vec<u8>>,
+        is_srgb: bool,
+    },
+}
+
+// a texture whose largest mips are transcoded or downsampled and uploaded on demand
+#[derive(Debug, Clone)]
+pub struct StreamedTexture {
+    // index into RendererPublicData::textures
+    pub texture_index: usize,
+    pub source: StreamedTextureSource,
+    pub format: wgpu::TextureFormat,
+    pub label: Option<String>,
+    // size of mip 0
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    // the largest mip that's on the gpu
+    pub resident_base_mip: u32,
+}
+
+impl StreamedTexture {
+    fn get_byte_count(&self, base_mip: u32) -> u64 {
+        get_texture_byte_count(
+            self.format,
+            self.width,
+            self.height,
+            self.mip_count,
+            base_mip,
+        )
+    }
+}
+
+struct StreamedTextureState {
+    // stays the same when textures are removed, unlike the index
+    id: u64,
+    texture: StreamedTexture,
+    // mips that the texture can be created from, ascending. the last one is never evicted
+    base_mips: Vec<u32>,
+    requested_mips: Option<Range<u32>>,
+}
+
+struct MipRequest {
+    streamed_texture_id: u64,
+    source: StreamedTextureSource,
+    width: u32,
+    height: u32,
+    mips: Range<u32>,
+}
+
+struct MipResult {
+    streamed_texture_id: u64,
+    mips: Range<u32>,
+    // the raw mips, largest first
+    result: anyhow::Result<Vec<u8>>,
+}
+
+// keeps the textures within the vram budget, giving the most mips to the textures that cover
+// the most pixels per texel. transcoding and downsampling happen on a background thread
+pub struct TextureStreamer {
+    pub vram_budget_bytes: u64,
+    textures: Vec<StreamedTextureState>,
+    next_id: u64,
+    request_sender: mpsc::Sender<MipRequest>,
+    result_receiver: mpsc::Receiver<MipResult>,
+}
+
+impl TextureStreamer {
+    pub fn new() -> Self {
+        let (request_sender, request_receiver) = mpsc::channel::<MipRequest>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        // exits once the streamer is dropped
+        std::thread::spawn(move || {
+            let texture_compressor = TextureCompressor::new();
+            for request in request_receiver {
+                let result = match &request.source {
+                    StreamedTextureSource::Compressed {
+                        image,
+                        is_normal_map,
+                        target,
+                    } => texture_compressor
+                        .transcode_image_levels(
+                            image,
+                            *is_normal_map,
+                            *target,
+                            request.mips.clone(),
+                        )
+                        .map(|compressed_texture| compressed_texture.raw),
+                    StreamedTextureSource::Rgba8 { pixels, is_srgb } => Ok(get_rgba8_mips(
+                        pixels,
+                        request.width,
+                        request.height,
+                        *is_srgb,
+                        request.mips.clone(),
+                    )),
+                };
+                let mip_result = MipResult {
+                    streamed_texture_id: request.streamed_texture_id,
+                    mips: request.mips,
+                    result,
+                };
+                if result_sender.send(mip_result).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            vram_budget_bytes: DEFAULT_TEXTURE_VRAM_BUDGET_BYTES,
+            textures: vec![],
+            next_id: 0,
+            request_sender,
+            result_receiver,
+        }
+    }
+
+    pub fn add_texture(&mut self, texture: StreamedTexture) {
+        let base_mips = get_base_mips(
+            texture.format,
+            texture.width,
+            texture.height,
+            texture.resident_base_mip,
+        );
+        self.textures.push(StreamedTextureState {
+            id: self.next_id,
+            texture,
+            base_mips,
+            requested_mips: None,
+        });
+        self.next_id += 1;
+    }
+
+    // texture_index_map maps the old texture indices to the new ones, None if it was removed
+    pub fn remap_textures(&mut self, texture_index_map: &[Option<usize>]) {
+        self.textures.retain_mut(
+            |state| match texture_index_map[state.texture.texture_index] {
+                Some(texture_index) => {
+                    state.texture.texture_index = texture_index;
+                    true
+                }
+                None => false,
+            },
+        );
+    }
+
+    pub fn get_resident_byte_count(&self) -> u64 {
+        self.textures
+            .iter()
+            .map(|state| {
+                state
+                    .texture
+                    .get_byte_count(state.texture.resident_base_mip)
+            })
+            .sum()
+    }
+
+    // texture_screen_sizes: texture index -> size in pixels of the largest mesh that uses it
+    // returns the indices of the textures that were recreated, their bind groups must be rebuilt
+    #[profiling::function]
+    pub fn update(
+        &mut self,
+        base: &BaseRenderer,
+        textures: &mut [Texture],
+        texture_screen_sizes: &HashMap<usize, f32>,
+    ) -> Vec<usize> {
+        let mut changed_texture_indices = vec![];
+
+        for mip_result in self.result_receiver.try_iter().collect::<Vec<_>>() {
+            // the texture might have been removed in the meantime
+            let Some(state) = self
+                .textures
+                .iter_mut()
+                .find(|state| state.id == mip_result.streamed_texture_id)
+            else {
+                continue;
+            };
+            state.requested_mips = None;
+            match mip_result.result {
+                // ignore results that no longer line up because mips were evicted meanwhile
+                Ok(mips_raw) if mip_result.mips.end == state.texture.resident_base_mip => {
+                    let texture_index = state.texture.texture_index;
+                    textures[texture_index] = replace_texture(
+                        base,
+                        &textures[texture_index],
+                        &state.texture,
+                        mip_result.mips.start,
+                        Some(&mips_raw),
+                    );
+                    state.texture.resident_base_mip = mip_result.mips.start;
+                    changed_texture_indices.push(texture_index);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    logger_log(&format!(
+                        "Error streaming mips {:?} of texture {:?}: {}",
+                        mip_result.mips, state.texture.label, err
+                    ));
+                }
+            }
+        }
+
+        let budget_inputs: Vec<_> = self
+            .textures
+            .iter()
+            .map(|state| {
+                let texture = &state.texture;
+                let screen_size = texture_screen_sizes
+                    .get(&texture.texture_index)
+                    .copied()
+                    .unwrap_or(0.0);
+                // textures that aren't visible keep what they have until the memory is needed
+                let wanted_base_mip = if screen_size > 0.0 {
+                    get_wanted_base_mip(texture.width, texture.height, screen_size)
+                } else {
+                    texture.resident_base_mip
+                };
+                BudgetInput {
+                    format: texture.format,
+                    width: texture.width,
+                    height: texture.height,
+                    mip_count: texture.mip_count,
+                    base_mips: &state.base_mips,
+                    wanted_base_mip,
+                    screen_size,
+                }
+            })
+            .collect();
+        let target_base_mips = fit_base_mips_to_budget(&budget_inputs, self.vram_budget_bytes);
+
+        for (state, target_base_mip) in self.textures.iter_mut().zip(target_base_mips) {
+            let resident_base_mip = state.texture.resident_base_mip;
+            if target_base_mip > resident_base_mip {
+                let texture_index = state.texture.texture_index;
+                textures[texture_index] = replace_texture(
+                    base,
+                    &textures[texture_index],
+                    &state.texture,
+                    target_base_mip,
+                    None,
+                );
+                state.texture.resident_base_mip = target_base_mip;
+                changed_texture_indices.push(texture_index);
+            } else if target_base_mip < resident_base_mip && state.requested_mips.is_none() {
+                let mips = target_base_mip..resident_base_mip;
+                let request = MipRequest {
+                    streamed_texture_id: state.id,
+                    source: state.texture.source.clone(),
+                    width: state.texture.width,
+                    height: state.texture.height,
+                    mips: mips.clone(),
+                };
+                if self.request_sender.send(request).is_ok() {
+                    state.requested_mips = Some(mips);
+                }
+            }
+        }
+
+        changed_texture_indices
+    }
+}
+
+impl Default for TextureStreamer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// creates a texture that holds the mips from new_base_mip onwards, copying over the ones that
+// are already on the gpu. new_mips_raw has the transcoded or downsampled mips that aren't resident yet
+fn replace_texture(
+    base: &BaseRenderer,
+    old_texture: &Texture,
+    streamed_texture: &StreamedTexture,
+    new_base_mip: u32,
+    new_mips_raw: Option<&[u8]>,
+) -> Texture {
+    let StreamedTexture {
+        format,
+        width,
+        height,
+        mip_count,
+        resident_base_mip: old_base_mip,
+        ..
+    } = *streamed_texture;
+    let full_size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let get_mip_size = |mip: u32| full_size.mip_level_size(mip, wgpu::TextureDimension::D2);
+
+    let size = get_mip_size(new_base_mip);
+    let texture = base.device.create_texture(&wgpu::TextureDescriptor {
+        label: streamed_texture.label.as_deref(),
+        size,
+        mip_level_count: mip_count - new_base_mip,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+
+    let mut encoder = base
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture_streaming_encoder"),
+        });
+    for mip in new_base_mip.max(old_base_mip)..mip_count {
+        encoder.copy_texture_to_texture(
+            wgpu::ImageCopyTexture {
+                texture: &old_texture.texture,
+                mip_level: mip - old_base_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: mip - new_base_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            get_mip_size(mip).physical_size(format),
+        );
+    }
+
+    if let Some(new_mips_raw) = new_mips_raw {
+        let format_info = format.describe();
+        let mut offset = 0;
+        for mip in new_base_mip..old_base_mip {
+            let mip_physical_size = get_mip_size(mip).physical_size(format);
+            let blocks_x = mip_physical_size.width / format_info.block_dimensions.0 as u32;
+            let blocks_y = mip_physical_size.height / format_info.block_dimensions.1 as u32;
+            let bytes_per_row = blocks_x * format_info.block_size as u32;
+            let byte_count = (bytes_per_row * blocks_y) as usize;
+            base.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip - new_base_mip,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &new_mips_raw[offset..offset + byte_count],
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(bytes_per_row),
+                    rows_per_image: NonZeroU32::new(blocks_y),
+                },
+                mip_physical_size,
+            );
+            offset += byte_count;
+        }
+    }
+
+    base.queue.submit(std::iter::once(encoder.finish()));
+
+    Texture {
+        view: texture.create_view(&Default::default()),
+        texture,
+        sampler_index: old_texture.sampler_index,
+        size,
+    }
+}
+
+// box filters mip 0 down to the requested mips, in linear space for srgb images. returns the
+// mips one after the other, largest first
+pub fn get_rgba8_mips(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    is_srgb: bool,
+    mips: Range<u32>,
+) -> Vec<u8> {
+    let to_linear: Vec<f32> = (0..=255)
+        .map(|value| {
+            let value = value as f32 / 255.0;
+            if is_srgb {
+                srgb_to_linear(value)
+            } else {
+                value
+            }
+        })
+        .collect();
+    let from_linear = |value: f32| {
+        let value = if is_srgb {
+            linear_to_srgb(value)
+        } else {
+            value
+        };
+        (value * 255.0).round() as u8
+    };
+
+    let mut mip_chain = vec![];
+    let mut mip_pixels = Cow::Borrowed(pixels);
+    let (mut mip_width, mut mip_height) = (width as usize, height as usize);
+    for mip in 0..mips.end {
+        if mip > 0 {
+            let (next_width, next_height) = ((mip_width / 2).max(1), (mip_height / 2).max(1));
+            let mut next_pixels = vec![0u8; next_width * next_height * 4];
+            for y in 0..next_height {
+                for x in 0..next_width {
+                    // odd sizes drop the last row or column
+                    let sample_xs = [2 * x, (2 * x + 1).min(mip_width - 1)];
+                    let sample_ys = [2 * y, (2 * y + 1).min(mip_height - 1)];
+                    for channel in 0..4 {
+                        let mut sum = 0.0;
+                        for sample_y in sample_ys {
+                            for sample_x in sample_xs {
+                                let value =
+                                    mip_pixels[(sample_y * mip_width + sample_x) * 4 + channel];
+                                // alpha is always linear
+                                sum += if channel == 3 {
+                                    value as f32 / 255.0
+                                } else {
+                                    to_linear[value as usize]
+                                };
+                            }
+                        }
+                        let average = sum / 4.0;
+                        next_pixels[(y * next_width + x) * 4 + channel] = if channel == 3 {
+                            (average * 255.0).round() as u8
+                        } else {
+                            from_linear(average)
+                        };
+                    }
+                }
+            }
+            mip_pixels = Cow::Owned(next_pixels);
+            (mip_width, mip_height) = (next_width, next_height);
+        }
+        if mips.contains(&mip) {
+            mip_chain.extend_from_slice(&mip_pixels);
+        }
+    }
+    mip_chain
+}
+
+pub fn get_mip_byte_count(format: wgpu::TextureFormat, width: u32, height: u32, mip: u32) -> u64 {
+    let format_info = format.describe();
+    let (block_width, block_height) = (
+        format_info.block_dimensions.0 as u32,
+        format_info.block_dimensions.1 as u32,
+    );
+    let mip_width = (width >> mip).max(1);
+    let mip_height = (height >> mip).max(1);
+    let blocks_x = (mip_width + block_width - 1) / block_width;
+    let blocks_y = (mip_height + block_height - 1) / block_height;
+    blocks_x as u64 * blocks_y as u64 * format_info.block_size as u64
+}
+
+pub fn get_texture_byte_count(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    mip_count: u32,
+    base_mip: u32,
+) -> u64 {
+    (base_mip..mip_count)
+        .map(|mip| get_mip_byte_count(format, width, height, mip))
+        .sum()
+}
+
+// compressed textures must be a whole number of blocks in size
+fn is_valid_base_mip(format: wgpu::TextureFormat, width: u32, height: u32, mip: u32) -> bool {
+    let (block_width, block_height) = format.describe().block_dimensions;
+    (width >> mip).max(1) % block_width as u32 == 0
+        && (height >> mip).max(1) % block_height as u32 == 0
+}
+
+// the largest valid mip that's at most INITIAL_RESIDENT_MIP_SIZE, or mip 0 if there's none
+pub fn get_initial_base_mip(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    mip_count: u32,
+) -> u32 {
+    (0..mip_count)
+        .filter(|mip| is_valid_base_mip(format, width, height, *mip))
+        .find(|mip| (width >> mip).max(height >> mip) <= INITIAL_RESIDENT_MIP_SIZE)
+        .unwrap_or(0)
+}
+
+fn get_base_mips(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    lowest_base_mip: u32,
+) -> Vec<u32> {
+    (0..lowest_base_mip)
+        .filter(|mip| is_valid_base_mip(format, width, height, *mip))
+        .chain(std::iter::once(lowest_base_mip))
+        .collect()
+}
+
+// one texel per pixel for a mesh that covers screen_size pixels, assuming the texture is
+// stretched over the whole mesh
+fn get_wanted_base_mip(width: u32, height: u32, screen_size: f32) -> u32 {
+    let texels_per_pixel = width.max(height) as f32 / screen_size.max(1.0);
+    texels_per_pixel.log2().floor().max(0.0) as u32
+}
+
+struct BudgetInput<'a> {
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    mip_count: u32,
+    base_mips: &'a [u32],
+    wanted_base_mip: u32,
+    // 0 if the texture isn't visible
+    screen_size: f32,
+}
+
+// starts from the mips that each texture wants and evicts the largest mips of the textures that
+// need them the least until everything fits
+fn fit_base_mips_to_budget(textures: &[BudgetInput], budget_bytes: u64) -> Vec<u32> {
+    // index into BudgetInput::base_mips
+    let mut base_mip_indices: Vec<usize> = textures
+        .iter()
+        .map(|texture| {
+            texture
+                .base_mips
+                .iter()
+                .rposition(|base_mip| *base_mip <= texture.wanted_base_mip)
+                .unwrap_or(0)
+        })
+        .collect();
+    let get_byte_count = |texture: &BudgetInput, base_mip_index: usize| {
+        get_texture_byte_count(
+            texture.format,
+            texture.width,
+            texture.height,
+            texture.mip_count,
+            texture.base_mips[base_mip_index],
+        )
+    };
+    let mut total_bytes: u64 = textures
+        .iter()
+        .zip(&base_mip_indices)
+        .map(|(texture, base_mip_index)| get_byte_count(texture, *base_mip_index))
+        .sum();
+
+    while total_bytes > budget_bytes {
+        // pixels per texel of the largest mip, lower means that the mip is less noticeable
+        let least_needed = textures
+            .iter()
+            .zip(&base_mip_indices)
+            .enumerate()
+            .filter(|(_, (texture, base_mip_index))| **base_mip_index + 1 < texture.base_mips.len())
+            .map(|(texture_index, (texture, base_mip_index))| {
+                let base_mip = texture.base_mips[*base_mip_index];
+                let mip_size = (texture.width >> base_mip).max(texture.height >> base_mip);
+                (texture_index, texture.screen_size / mip_size as f32)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((texture_index, _)) = least_needed else {
+            break;
+        };
+        let texture = &textures[texture_index];
+        let base_mip_index = &mut base_mip_indices[texture_index];
+        total_bytes -= get_byte_count(texture, *base_mip_index);
+        *base_mip_index += 1;
+        total_bytes += get_byte_count(texture, *base_mip_index);
+    }
+
+    textures
+        .iter()
+        .zip(base_mip_indices)
+        .map(|(texture, base_mip_index)| texture.base_mips[base_mip_index])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BC7: wgpu::TextureFormat = wgpu::TextureFormat::Bc7RgbaUnormSrgb;
+
+    #[test]
+    fn initial_mips_are_block_aligned() {
+        // 1024 / 8 = 128
+        assert_eq!(get_initial_base_mip(BC7, 1024, 1024, 11), 3);
+        // 480 >> 3 = 60 is a multiple of 4, 480 >> 4 = 30 isn't
+        assert_eq!(get_initial_base_mip(BC7, 640, 480, 10), 3);
+        assert_eq!(get_initial_base_mip(BC7, 100, 64, 7), 0);
+        assert_eq!(get_base_mips(BC7, 640, 480, 3), vec![0, 1, 2, 3]);
+        assert_eq!(get_base_mips(BC7, 640, 24, 3), vec![0, 1, 3]);
+
+        assert_eq!(get_mip_byte_count(BC7, 1024, 1024, 0), 1024 * 1024);
+        // partial blocks take up a whole block
+        assert_eq!(get_mip_byte_count(BC7, 1024, 1024, 10), 16);
+        assert_eq!(
+            get_mip_byte_count(wgpu::TextureFormat::Rgba8Unorm, 3, 5, 0),
+            3 * 5 * 4
+        );
+        assert_eq!(
+            get_texture_byte_count(BC7, 16, 16, 5, 2),
+            3 * 16 // 4x4, 2x2 and 1x1 are one block each
+        );
+    }
+
+    #[test]
+    fn rgba8_mips_are_box_filtered() {
+        #[rustfmt::skip]
+        let pixels = [
+            0, 0, 0, 255,       255, 255, 255, 255,   10, 0, 0, 0,   30, 0, 0, 0,
+            255, 255, 255, 255, 0, 0, 0, 255,         50, 0, 0, 0,   70, 0, 0, 0,
+        ];
+
+        let linear_mips = get_rgba8_mips(&pixels, 4, 2, false, 1..3);
+        assert_eq!(
+            linear_mips,
+            vec![128, 128, 128, 255, 40, 0, 0, 0, 84, 64, 64, 128]
+        );
+        assert_eq!(get_rgba8_mips(&pixels, 4, 2, false, 0..1), pixels.to_vec());
+
+        // black and white average out to a brighter grey in srgb, alpha stays linear
+        let srgb_mips = get_rgba8_mips(&pixels, 4, 2, true, 1..2);
+        assert!(srgb_mips[0] > 180);
+        assert_eq!(srgb_mips[3], 255);
+        assert_eq!(srgb_mips.len(), 2 * 4);
+    }
+
+    #[test]
+    fn wanted_mips_follow_screen_size() {
+        assert_eq!(get_wanted_base_mip(1024, 1024, 2000.0), 0);
+        assert_eq!(get_wanted_base_mip(1024, 1024, 1024.0), 0);
+        assert_eq!(get_wanted_base_mip(1024, 1024, 500.0), 1);
+        assert_eq!(get_wanted_base_mip(1024, 512, 16.0), 6);
+        assert_eq!(get_wanted_base_mip(1024, 1024, 0.0), 10);
+    }
+
+    #[test]
+    fn budget_evicts_least_needed_mips() {
+        let base_mips = get_base_mips(BC7, 1024, 1024, 3);
+        let make_input = |wanted_base_mip: u32, screen_size: f32| BudgetInput {
+            format: BC7,
+            width: 1024,
+            height: 1024,
+            mip_count: 11,
+            base_mips: &base_mips,
+            wanted_base_mip,
+            screen_size,
+        };
+        let textures = [
+            make_input(0, 1024.0),
+            make_input(0, 512.0),
+            make_input(3, 0.0),
+        ];
+
+        // everything fits
+        assert_eq!(fit_base_mips_to_budget(&textures, u64::MAX), vec![0, 0, 3]);
+
+        // the smaller one on screen loses its largest mip first
+        let full_bytes = get_texture_byte_count(BC7, 1024, 1024, 11, 0);
+        let budget = full_bytes * 2 - 1;
+        assert_eq!(fit_base_mips_to_budget(&textures, budget), vec![0, 1, 3]);
+
+        // the initial mips are never evicted
+        assert_eq!(fit_base_mips_to_budget(&textures, 0), vec![3, 3, 3]);
+    }
+}
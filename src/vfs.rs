@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+
+// bump this whenever the layout of the pack archive changes
+pub const PACK_ARCHIVE_VERSION: u32 = 2;
+const PACK_ARCHIVE_MAGIC: &[u8; 8] = b"IKARIPAK";
+// magic, version, index offset
+const PACK_ARCHIVE_HEADER_SIZE: u64 = 8 + 4 + 8;
+
+lazy_static! {
+    // the working directory is mounted at the root by default, so relative paths behave like
+    // they do with std::fs until something else is mounted
+    pub static ref VFS: RwLock<Vfs> = RwLock::new(Vfs::default());
+}
+
+pub fn vfs_read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    VFS.read().unwrap().read(path.as_ref())
+}
+
+pub fn vfs_read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
+    Ok(String::from_utf8(vfs_read(path)?)?)
+}
+
+pub fn vfs_exists<P: AsRef<Path>>(path: P) -> Result<bool> {
+    VFS.read().unwrap().exists(path.as_ref())
+}
+
+pub fn vfs_modified<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
+    VFS.read().unwrap().modified(path.as_ref())
+}
+
+pub fn vfs_directory_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    VFS.read().unwrap().directory_path(path.as_ref())
+}
+
+pub enum VfsSource {
+    Directory(PathBuf),
+    Pack(PackArchive),
+}
+
+struct VfsMount {
+    mount_point: PathBuf,
+    source: VfsSource,
+}
+
+pub struct Vfs {
+    mounts: Vec<VfsMount>,
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        let mut vfs = Self::new();
+        vfs.mount("", VfsSource::Directory(PathBuf::from(".")));
+        vfs
+    }
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self { mounts: vec![] }
+    }
+
+    // the mounts are searched from the last one to the first one, so e.g. a pack archive that's
+    // mounted over a directory overrides the files that it contains
+    pub fn mount<P: AsRef<Path>>(&mut self, mount_point: P, source: VfsSource) {
+        self.mounts.push(VfsMount {
+            mount_point: normalize_path(mount_point.as_ref()),
+            source,
+        });
+    }
+
+    pub fn unmount<P: AsRef<Path>>(&mut self, mount_point: P) {
+        let mount_point = normalize_path(mount_point.as_ref());
+        self.mounts.retain(|mount| mount.mount_point != mount_point);
+    }
+
+    // absolute paths skip the mounts and go straight to the file system
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        if path.is_absolute() {
+            return Ok(std::fs::read(path)?);
+        }
+        for (source, relative_path) in self.get_candidates(path) {
+            match source {
+                VfsSource::Directory(directory) => {
+                    match std::fs::read(directory.join(&relative_path)) {
+                        Ok(bytes) => return Ok(bytes),
+                        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                VfsSource::Pack(pack_archive) => {
+                    if let Some(bytes) = pack_archive.read(&relative_path)? {
+                        return Ok(bytes);
+                    }
+                }
+            }
+        }
+        bail!("File not found: {}", path.display())
+    }
+
+    pub fn exists(&self, path: &Path) -> Result<bool> {
+        if path.is_absolute() {
+            return Ok(path.try_exists()?);
+        }
+        for (source, relative_path) in self.get_candidates(path) {
+            let exists = match source {
+                VfsSource::Directory(directory) => directory.join(&relative_path).try_exists()?,
+                VfsSource::Pack(pack_archive) => pack_archive.contains(&relative_path),
+            };
+            if exists {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // files in a pack archive keep the modification time they had when they were packed
+    pub fn modified(&self, path: &Path) -> Option<SystemTime> {
+        if path.is_absolute() {
+            return get_file_modified_time(path);
+        }
+        for (source, relative_path) in self.get_candidates(path) {
+            match source {
+                VfsSource::Directory(directory) => {
+                    if let Some(modified) = get_file_modified_time(&directory.join(relative_path)) {
+                        return Some(modified);
+                    }
+                }
+                // the loose file's time would be wrong since the packed one is what gets read
+                VfsSource::Pack(pack_archive) => {
+                    if pack_archive.contains(&relative_path) {
+                        return pack_archive.modified(&relative_path);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // the file system path behind a mounted path, for writing to it. that's the file that would be
+    // read if it exists and otherwise the path in the last directory mounted above it. files that
+    // are read from a pack archive can't be written to
+    pub fn directory_path(&self, path: &Path) -> Result<PathBuf> {
+        if path.is_absolute() {
+            return Ok(path.to_path_buf());
+        }
+        let mut new_file_path = None;
+        for (source, relative_path) in self.get_candidates(path) {
+            match source {
+                VfsSource::Directory(directory) => {
+                    let file_path = directory.join(relative_path);
+                    if file_path.try_exists()? {
+                        return Ok(file_path);
+                    }
+                    new_file_path.get_or_insert(file_path);
+                }
+                VfsSource::Pack(pack_archive) => {
+                    if pack_archive.contains(&relative_path) {
+                        bail!(
+                            "{} is read from a pack archive and can't be written to",
+                            path.display()
+                        );
+                    }
+                }
+            }
+        }
+        new_file_path
+            .ok_or_else(|| anyhow::anyhow!("No directory is mounted at {}", path.display()))
+    }
+
+    // the mounts that the path is under along with the path relative to the mount point
+    fn get_candidates<'a>(
+        &'a self,
+        path: &Path,
+    ) -> impl Iterator<Item = (&'a VfsSource, PathBuf)> + 'a {
+        let path = normalize_path(path);
+        self.mounts.iter().rev().filter_map(move |mount| {
+            path.strip_prefix(&mount.mount_point)
+                .ok()
+                .map(|relative_path| (&mount.source, relative_path.to_path_buf()))
+        })
+    }
+}
+
+fn get_file_modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// removes the . and .. components, e.g. ./src/models/../sounds/bgm.mp3 -> src/sounds/bgm.mp3
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized_path = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized_path.pop();
+            }
+            component => normalized_path.push(component),
+        }
+    }
+    normalized_path
+}
+
+// the paths in pack archives always use forward slashes
+fn get_pack_path(path: &Path) -> String {
+    normalize_path(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    compressed_size: u64,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+// stored as nanoseconds since the unix epoch, 0 if the time isn't known
+fn modified_to_nanos(modified: Option<SystemTime>) -> u64 {
+    modified
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .and_then(|duration| u64::try_from(duration.as_nanos()).ok())
+        .unwrap_or(0)
+}
+
+fn nanos_to_modified(nanos: u64) -> Option<SystemTime> {
+    (nanos != 0).then(|| SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos))
+}
+
+// a single file that holds many zstd compressed files, laid out as:
+// header | compressed files | index of (path, offset, compressed size, size, modified)
+// the files are compressed separately so one of them can be read without touching the others
+pub struct PackArchive {
+    file: Mutex<File>,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl PackArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path)?;
+
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != PACK_ARCHIVE_MAGIC {
+            bail!("{} is not a pack archive", path.display());
+        }
+        let version = read_u32(&mut file)?;
+        if version != PACK_ARCHIVE_VERSION {
+            bail!(
+                "Pack archive version {:?} doesn't match the expected version {:?}",
+                version,
+                PACK_ARCHIVE_VERSION
+            );
+        }
+        let index_offset = read_u64(&mut file)?;
+
+        file.seek(SeekFrom::Start(index_offset))?;
+        let entry_count = read_u32(&mut file)?;
+        let mut entries = HashMap::new();
+        for _ in 0..entry_count {
+            let mut path_bytes = vec![0u8; read_u32(&mut file)? as usize];
+            file.read_exact(&mut path_bytes)?;
+            let entry = PackEntry {
+                offset: read_u64(&mut file)?,
+                compressed_size: read_u64(&mut file)?,
+                size: read_u64(&mut file)?,
+                modified: nanos_to_modified(read_u64(&mut file)?),
+            };
+            entries.insert(String::from_utf8(path_bytes)?, entry);
+        }
+
+        Ok(Self {
+            file: Mutex::new(file),
+            entries,
+        })
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(&get_pack_path(path))
+    }
+
+    // None if the archive doesn't contain the file or it was packed without a modification time
+    pub fn modified(&self, path: &Path) -> Option<SystemTime> {
+        self.entries.get(&get_pack_path(path))?.modified
+    }
+
+    // None if the archive doesn't contain the file
+    pub fn read(&self, path: &Path) -> Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.get(&get_pack_path(path)) else {
+            return Ok(None);
+        };
+        let mut compressed_bytes = vec![0u8; entry.compressed_size as usize];
+        {
+            let mut file_guard = self.file.lock().unwrap();
+            file_guard.seek(SeekFrom::Start(entry.offset))?;
+            file_guard.read_exact(&mut compressed_bytes)?;
+        }
+        let bytes = zstd::stream::decode_all(compressed_bytes.as_slice())?;
+        if bytes.len() as u64 != entry.size {
+            bail!(
+                "File {} in pack archive is {:?} bytes long, expected {:?}",
+                path.display(),
+                bytes.len(),
+                entry.size
+            );
+        }
+        Ok(Some(bytes))
+    }
+}
+
+pub struct PackArchiveWriter {
+    writer: BufWriter<File>,
+    entries: Vec<(String, PackEntry)>,
+    position: u64,
+    compression_level: i32,
+}
+
+impl PackArchiveWriter {
+    // 0 = default zstd compression level
+    pub fn create<P: AsRef<Path>>(path: P, compression_level: i32) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(PACK_ARCHIVE_MAGIC)?;
+        writer.write_all(&PACK_ARCHIVE_VERSION.to_le_bytes())?;
+        // index offset, filled in by finish
+        writer.write_all(&0u64.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            entries: vec![],
+            position: PACK_ARCHIVE_HEADER_SIZE,
+            compression_level,
+        })
+    }
+
+    // the modification time is what the vfs reports for the file, so caches that are packed along
+    // with their sources stay fresh
+    pub fn add_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        bytes: &[u8],
+        modified: Option<SystemTime>,
+    ) -> Result<()> {
+        let pack_path = get_pack_path(path.as_ref());
+        if self
+            .entries
+            .iter()
+            .any(|(other_path, _)| *other_path == pack_path)
+        {
+            bail!("File {} was already added to the pack archive", pack_path);
+        }
+        let compressed_bytes = zstd::stream::encode_all(bytes, self.compression_level)?;
+        self.writer.write_all(&compressed_bytes)?;
+        self.entries.push((
+            pack_path,
+            PackEntry {
+                offset: self.position,
+                compressed_size: compressed_bytes.len() as u64,
+                size: bytes.len() as u64,
+                modified,
+            },
+        ));
+        self.position += compressed_bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        let index_offset = self.position;
+        self.writer
+            .write_all(&u32::try_from(self.entries.len())?.to_le_bytes())?;
+        for (path, entry) in &self.entries {
+            self.writer
+                .write_all(&u32::try_from(path.len())?.to_le_bytes())?;
+            self.writer.write_all(path.as_bytes())?;
+            self.writer.write_all(&entry.offset.to_le_bytes())?;
+            self.writer
+                .write_all(&entry.compressed_size.to_le_bytes())?;
+            self.writer.write_all(&entry.size.to_le_bytes())?;
+            self.writer
+                .write_all(&modified_to_nanos(entry.modified).to_le_bytes())?;
+        }
+        self.writer
+            .seek(SeekFrom::Start(PACK_ARCHIVE_HEADER_SIZE - 8))?;
+        self.writer.write_all(&index_offset.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_archive_overrides_loose_files() {
+        let folder = std::env::temp_dir().join(format!("ikari_vfs_test_{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("sounds")).unwrap();
+        std::fs::write(folder.join("sounds/bgm.mp3"), b"loose bgm").unwrap();
+        std::fs::write(folder.join("sounds/gunshot.wav"), b"loose gunshot").unwrap();
+
+        let pack_path = folder.join("assets.pak");
+        let cube_modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut pack_writer = PackArchiveWriter::create(&pack_path, 0).unwrap();
+        pack_writer
+            .add_file("./sounds/bgm.mp3", b"packed bgm", None)
+            .unwrap();
+        pack_writer
+            .add_file("models/cube.obj", &[7u8; 1000], Some(cube_modified))
+            .unwrap();
+        assert!(pack_writer
+            .add_file("sounds/bgm.mp3", b"again", None)
+            .is_err());
+        pack_writer.finish().unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount("./assets", VfsSource::Directory(folder.clone()));
+        vfs.mount(
+            "assets",
+            VfsSource::Pack(PackArchive::open(&pack_path).unwrap()),
+        );
+
+        assert_eq!(
+            vfs.read(Path::new("./assets/sounds/bgm.mp3")).unwrap(),
+            b"packed bgm"
+        );
+        assert_eq!(
+            vfs.read(Path::new("assets/models/../sounds/gunshot.wav"))
+                .unwrap(),
+            b"loose gunshot"
+        );
+        assert_eq!(
+            vfs.read(Path::new("assets/models/cube.obj")).unwrap(),
+            vec![7u8; 1000]
+        );
+        assert!(vfs.exists(Path::new("assets/models/cube.obj")).unwrap());
+        assert_eq!(
+            vfs.modified(Path::new("assets/models/cube.obj")),
+            Some(cube_modified)
+        );
+        assert_eq!(vfs.modified(Path::new("assets/sounds/bgm.mp3")), None);
+        assert!(!vfs.exists(Path::new("models/cube.obj")).unwrap());
+        assert!(vfs.read(Path::new("assets/missing.png")).is_err());
+
+        assert_eq!(
+            vfs.directory_path(Path::new("assets/sounds/gunshot.wav"))
+                .unwrap(),
+            folder.join("sounds/gunshot.wav")
+        );
+        assert_eq!(
+            vfs.directory_path(Path::new("assets/sounds/new.wav"))
+                .unwrap(),
+            folder.join("sounds/new.wav")
+        );
+        assert!(vfs
+            .directory_path(Path::new("assets/sounds/bgm.mp3"))
+            .is_err());
+        assert!(vfs.directory_path(Path::new("models/cube.obj")).is_err());
+
+        vfs.unmount("assets");
+        assert!(vfs.read(Path::new("assets/sounds/bgm.mp3")).is_err());
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+use crate::logger::*;
+
+use lazy_static::lazy_static;
+use std::cell::Cell;
+use std::collections::BinaryHeap;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+lazy_static! {
+    // shared by the asset loader and the loaders it calls into
+    pub static ref WORKER_POOL: WorkerPool = WorkerPool::new(num_cpus::get().max(2));
+}
+
+thread_local! {
+    // priority of the job running on this thread, used for the jobs it spawns through map
+    static CURRENT_JOB_PRIORITY: Cell<Option<JobPriority>> = const { Cell::new(None) };
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobPriority {
+    Background,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type Job = Box<dyn FnOnce(&CancellationToken) + Send>;
+
+struct QueuedJob {
+    priority: JobPriority,
+    sequence_number: u64,
+    cancellation_token: CancellationToken,
+    job: Job,
+}
+
+// highest priority first, then oldest first
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence_number.cmp(&self.sequence_number))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for QueuedJob {}
+
+#[derive(Default)]
+struct JobQueue {
+    jobs: BinaryHeap<QueuedJob>,
+    next_sequence_number: u64,
+    is_shutting_down: bool,
+}
+
+#[derive(Default)]
+struct WorkerPoolShared {
+    queue: Mutex<JobQueue>,
+    job_available: Condvar,
+}
+
+pub struct WorkerPool {
+    shared: Arc<WorkerPoolShared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(worker_count: usize) -> Self {
+        let shared = Arc::new(WorkerPoolShared::default());
+        let workers = (0..worker_count.max(1))
+            .map(|worker_index| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("worker {}", worker_index))
+                    .spawn(move || run_worker(&shared))
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn queued_job_count(&self) -> usize {
+        self.shared.queue.lock().unwrap().jobs.len()
+    }
+
+    // jobs are still run if they're cancelled before starting, so they should check the token
+    // before doing any work and between the expensive steps
+    pub fn spawn(
+        &self,
+        priority: JobPriority,
+        job: impl FnOnce(&CancellationToken) + Send + 'static,
+    ) -> CancellationToken {
+        let cancellation_token = CancellationToken::new();
+        {
+            let mut queue_guard = self.shared.queue.lock().unwrap();
+            let sequence_number = queue_guard.next_sequence_number;
+            queue_guard.next_sequence_number += 1;
+            queue_guard.jobs.push(QueuedJob {
+                priority,
+                sequence_number,
+                cancellation_token: cancellation_token.clone(),
+                job: Box::new(job),
+            });
+        }
+        self.shared.job_available.notify_one();
+        cancellation_token
+    }
+
+    // calls f on all the items in parallel with the priority of the calling job and returns the
+    // results in the same order. the calling thread processes items too, which makes it safe to
+    // call from inside a job even if all the workers are busy
+    pub fn map<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let item_count = items.len();
+        if item_count < 2 {
+            return items.into_iter().map(f).collect();
+        }
+
+        let state = Arc::new(MapState {
+            items: Mutex::new(items.into_iter().map(Some).collect()),
+            results: Mutex::new((0..item_count).map(|_| None).collect()),
+            item_count,
+            next_item_index: AtomicUsize::new(0),
+            finished_count: Mutex::new(0),
+            all_finished: Condvar::new(),
+            f,
+        });
+
+        let priority = CURRENT_JOB_PRIORITY.with(|priority| priority.get().unwrap_or_default());
+        for _ in 0..(item_count - 1).min(self.worker_count()) {
+            let state = state.clone();
+            self.spawn(priority, move |_| state.process_items());
+        }
+        state.process_items();
+
+        let mut finished_count_guard = state.finished_count.lock().unwrap();
+        while *finished_count_guard < item_count {
+            finished_count_guard = state.all_finished.wait(finished_count_guard).unwrap();
+        }
+        drop(finished_count_guard);
+
+        let results = std::mem::take(&mut *state.results.lock().unwrap());
+        results
+            .into_iter()
+            .map(|result| match result.unwrap() {
+                Ok(result) => result,
+                Err(panic) => resume_unwind(panic),
+            })
+            .collect()
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        {
+            let mut queue_guard = self.shared.queue.lock().unwrap();
+            for queued_job in queue_guard.jobs.iter() {
+                queued_job.cancellation_token.cancel();
+            }
+            queue_guard.jobs.clear();
+            queue_guard.is_shutting_down = true;
+        }
+        self.shared.job_available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_worker(shared: &WorkerPoolShared) {
+    loop {
+        let queued_job = {
+            let mut queue_guard = shared.queue.lock().unwrap();
+            loop {
+                if let Some(queued_job) = queue_guard.jobs.pop() {
+                    break queued_job;
+                }
+                if queue_guard.is_shutting_down {
+                    return;
+                }
+                queue_guard = shared.job_available.wait(queue_guard).unwrap();
+            }
+        };
+
+        let QueuedJob {
+            priority,
+            cancellation_token,
+            job,
+            ..
+        } = queued_job;
+        CURRENT_JOB_PRIORITY.with(|current_priority| current_priority.set(Some(priority)));
+        if catch_unwind(AssertUnwindSafe(|| job(&cancellation_token))).is_err() {
+            logger_log("Error: worker pool job panicked");
+        }
+        CURRENT_JOB_PRIORITY.with(|current_priority| current_priority.set(None));
+    }
+}
+
+type ItemResult<R> = std::thread::Result<R>;
+
+struct MapState<T, R, F> {
+    items: Mutex<Vec<Option<T>>>,
+    results: Mutex<Vec<Option<ItemResult<R>>>>,
+    item_count: usize,
+    next_item_index: AtomicUsize,
+    finished_count: Mutex<usize>,
+    all_finished: Condvar,
+    f: F,
+}
+
+impl<T, R, F: Fn(T) -> R> MapState<T, R, F> {
+    // claims items until there are none left. an item is only claimed by one thread and the
+    // caller of map waits for all the claimed items, so none of them can get lost
+    fn process_items(&self) {
+        loop {
+            let item_index = self.next_item_index.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = self
+                .items
+                .lock()
+                .unwrap()
+                .get_mut(item_index)
+                .and_then(Option::take)
+            else {
+                return;
+            };
+
+            let result = catch_unwind(AssertUnwindSafe(|| (self.f)(item)));
+            self.results.lock().unwrap()[item_index] = Some(result);
+
+            let mut finished_count_guard = self.finished_count.lock().unwrap();
+            *finished_count_guard += 1;
+            if *finished_count_guard == self.item_count {
+                self.all_finished.notify_all();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    #[test]
+    fn jobs_run_by_priority() {
+        let pool = WorkerPool::new(1);
+        let order = Arc::new(Mutex::new(vec![]));
+
+        // keep the only worker busy until everything is queued
+        let (unblock_sender, unblock_receiver) = mpsc::channel::<()>();
+        pool.spawn(JobPriority::High, move |_| {
+            unblock_receiver.recv().unwrap();
+        });
+
+        for (name, priority) in [
+            ("background", JobPriority::Background),
+            ("normal 1", JobPriority::Normal),
+            ("high", JobPriority::High),
+            ("normal 2", JobPriority::Normal),
+        ] {
+            let order = order.clone();
+            pool.spawn(priority, move |_| order.lock().unwrap().push(name));
+        }
+        let order_clone = order.clone();
+        let cancelled_job = pool.spawn(JobPriority::High, move |cancellation_token| {
+            if !cancellation_token.is_cancelled() {
+                order_clone.lock().unwrap().push("cancelled");
+            }
+        });
+        cancelled_job.cancel();
+
+        let (done_sender, done_receiver) = mpsc::channel();
+        pool.spawn(JobPriority::Background, move |_| {
+            done_sender.send(()).unwrap()
+        });
+        unblock_sender.send(()).unwrap();
+        done_receiver.recv().unwrap();
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["high", "normal 1", "normal 2", "background"]
+        );
+        assert_eq!(pool.queued_job_count(), 0);
+    }
+
+    #[test]
+    fn map_keeps_order_and_works_inside_jobs() {
+        assert_eq!(WORKER_POOL.map((0..10).collect(), |x: u32| x * 2)[9], 18);
+
+        // every worker calls map, so the items can only finish if the callers help out
+        let (result_sender, result_receiver) = mpsc::channel();
+        for _ in 0..WORKER_POOL.worker_count() {
+            let result_sender = result_sender.clone();
+            WORKER_POOL.spawn(JobPriority::Normal, move |_| {
+                let squares = WORKER_POOL.map((0..100).collect(), |x: u64| x * x);
+                result_sender.send(squares).unwrap();
+            });
+        }
+        for _ in 0..WORKER_POOL.worker_count() {
+            let squares = result_receiver.recv().unwrap();
+            assert_eq!(squares, (0..100).map(|x| x * x).collect::<Vec<u64>>());
+        }
+    }
+}